@@ -0,0 +1,511 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use anyhow::{bail, Context, Result};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use merkle_toolkit::{
+    codegen, hex, serde_hex, DirectoryHasher, DirectoryTree, EntryChange, FileEntry, FileProof,
+    HashAlgorithm, MerkleTree, NodeHasher, Proof, RuntimeHasher,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Parser)]
+#[command(
+    name = "merkle",
+    about = "Build merkle trees, and create and check proofs"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Build a tree and print its root
+    Build {
+        #[command(flatten)]
+        tree: TreeArgs,
+    },
+    /// Print an inclusion proof for a leaf as JSON
+    Prove {
+        #[command(flatten)]
+        tree: TreeArgs,
+        /// Index of the leaf to prove
+        index: usize,
+    },
+    /// Check a proof produced by `merkle prove` against a trusted root
+    Verify {
+        #[command(flatten)]
+        hash: HashArgs,
+        /// JSON proof file, `-` for stdin
+        proof: PathBuf,
+        /// Hex root the proof must lead to; the document's own root is only
+        /// checked against it
+        #[arg(long, value_parser = parse_hash)]
+        root: [u8; 32],
+    },
+    /// Print the tree as a Graphviz DOT graph, e.g. for `dot -Tsvg`
+    Viz {
+        #[command(flatten)]
+        tree: TreeArgs,
+        /// Highlight the proof path of the leaf at this index
+        #[arg(long)]
+        proof: Option<usize>,
+    },
+    /// Record the files below a directory in a manifest for `merkle audit`
+    Manifest {
+        #[command(flatten)]
+        hash: HashArgs,
+        dir: PathBuf,
+        /// Where to write the manifest
+        #[arg(long)]
+        output: PathBuf,
+        /// Bytes per leaf of each file's tree
+        #[arg(long, default_value_t = 4096)]
+        chunk_size: usize,
+    },
+    /// Compare a directory against a manifest, printing changed, added and
+    /// removed files with proofs as JSON, and failing if there are any
+    Audit {
+        #[command(flatten)]
+        hash: HashArgs,
+        dir: PathBuf,
+        /// Manifest written by `merkle manifest`
+        #[arg(long)]
+        manifest: PathBuf,
+    },
+    /// Print a Solidity library that verifies proofs with the same hashing
+    Solidity {
+        #[command(flatten)]
+        hash: HashArgs,
+    },
+}
+
+#[derive(Args)]
+struct TreeArgs {
+    #[command(flatten)]
+    hash: HashArgs,
+    /// File with one leaf per line, `-` for stdin
+    leaves: PathBuf,
+    /// Treat each line as raw leaf data to hash instead of a hex leaf
+    #[arg(long)]
+    raw: bool,
+    /// Tree depth, defaults to the smallest depth that fits every leaf
+    #[arg(long)]
+    depth: Option<usize>,
+}
+
+#[derive(Args)]
+struct HashArgs {
+    #[arg(long, value_enum, default_value_t = HashFunction::Sha256)]
+    hash: HashFunction,
+    /// Hash leaves and nodes without the RFC 6962 prefixes
+    #[arg(long)]
+    no_domain_separation: bool,
+    /// Hash each pair of children in ascending order, as OpenZeppelin does
+    #[arg(long)]
+    sorted_pairs: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum HashFunction {
+    Sha256,
+    Keccak256,
+    /// Double SHA-256, as in Bitcoin
+    Sha256d,
+}
+
+impl HashArgs {
+    fn runtime_hasher(&self) -> RuntimeHasher {
+        RuntimeHasher {
+            algorithm: match self.hash {
+                HashFunction::Sha256 => HashAlgorithm::Sha256,
+                HashFunction::Keccak256 => HashAlgorithm::Keccak256,
+                HashFunction::Sha256d => HashAlgorithm::DoubleSha256,
+            },
+            domain_separation: !self.no_domain_separation,
+            sorted_pairs: self.sorted_pairs,
+        }
+    }
+}
+
+/// Output of `merkle prove`, and input of `merkle verify`.
+#[derive(Debug, Serialize, Deserialize)]
+struct ProofDocument {
+    #[serde(with = "serde_hex")]
+    root: [u8; 32],
+    #[serde(with = "serde_hex")]
+    leaf: [u8; 32],
+    proof: Proof,
+}
+
+/// Magic bytes opening a manifest, versioning its format.
+const MANIFEST_MAGIC: &[u8; 8] = b"MRKLMAN1";
+
+/// A directory's state as saved by `merkle manifest`.
+///
+/// The binary format is the magic, the chunk size and entry count (big-endian
+/// `u64`), the directory root, then per entry the path length (big-endian
+/// `u32`), path, file length (big-endian `u64`) and file root.
+#[derive(Debug, PartialEq)]
+struct Manifest {
+    chunk_size: usize,
+    root: [u8; 32],
+    entries: Vec<FileEntry>,
+}
+
+impl Manifest {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MANIFEST_MAGIC.to_vec();
+        bytes.extend_from_slice(&(self.chunk_size as u64).to_be_bytes());
+        bytes.extend_from_slice(&(self.entries.len() as u64).to_be_bytes());
+        bytes.extend_from_slice(&self.root);
+        for entry in &self.entries {
+            bytes.extend_from_slice(&(entry.path.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(entry.path.as_bytes());
+            bytes.extend_from_slice(&entry.file_len.to_be_bytes());
+            bytes.extend_from_slice(&entry.root);
+        }
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut rest = bytes
+            .strip_prefix(MANIFEST_MAGIC)
+            .context("not a merkle manifest")?;
+        let mut take = |n: usize| {
+            let (field, tail) = rest.split_at_checked(n).context("manifest is truncated")?;
+            rest = tail;
+            anyhow::Ok(field)
+        };
+        let chunk_size = u64::from_be_bytes(take(8)?.try_into()?).try_into()?;
+        let count = u64::from_be_bytes(take(8)?.try_into()?);
+        let root = take(32)?.try_into()?;
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            let path_len = u32::from_be_bytes(take(4)?.try_into()?) as usize;
+            entries.push(FileEntry {
+                path: String::from_utf8(take(path_len)?.to_vec())?,
+                file_len: u64::from_be_bytes(take(8)?.try_into()?),
+                root: take(32)?.try_into()?,
+            });
+        }
+        if !rest.is_empty() {
+            bail!("trailing bytes after manifest entries");
+        }
+        Ok(Self {
+            chunk_size,
+            root,
+            entries,
+        })
+    }
+}
+
+/// Output of `merkle audit`.
+#[derive(Debug, Serialize)]
+struct AuditReport {
+    #[serde(with = "serde_hex")]
+    manifest_root: [u8; 32],
+    #[serde(with = "serde_hex")]
+    current_root: [u8; 32],
+    changes: Vec<AuditChange>,
+}
+
+/// A file that differs from the manifest, with proofs of its recorded and
+/// current state where it has one.
+#[derive(Debug, Serialize)]
+struct AuditChange {
+    path: String,
+    change: EntryChange,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recorded: Option<FileProof>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    current: Option<FileProof>,
+}
+
+fn main() -> Result<ExitCode> {
+    let cli = Cli::parse();
+    let hash = match &cli.command {
+        Command::Build { tree } | Command::Prove { tree, .. } | Command::Viz { tree, .. } => {
+            &tree.hash
+        }
+        Command::Verify { hash, .. }
+        | Command::Manifest { hash, .. }
+        | Command::Audit { hash, .. }
+        | Command::Solidity { hash } => hash,
+    };
+    run(&cli.command, hash.runtime_hasher())
+}
+
+fn run(command: &Command, hasher: RuntimeHasher) -> Result<ExitCode> {
+    match command {
+        Command::Build { tree } => {
+            let tree = build_tree(tree, hasher)?;
+            println!("{}", hex::encode(&tree.root()));
+        }
+        Command::Prove { tree, index } => {
+            let tree = build_tree(tree, hasher)?;
+            let document = ProofDocument {
+                root: tree.root(),
+                leaf: *tree
+                    .leaves()
+                    .get(*index)
+                    .with_context(|| format!("no leaf at index {index}"))?,
+                proof: tree.try_get_proof(*index)?,
+            };
+            println!("{}", serde_json::to_string_pretty(&document)?);
+        }
+        Command::Verify { proof, root, .. } => {
+            let document: ProofDocument = serde_json::from_str(&read_input(proof)?)
+                .context("failed to parse proof document")?;
+            if verify_document(&document, *root, &hasher)? {
+                println!("valid");
+            } else {
+                println!("invalid");
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Command::Viz { tree, proof } => {
+            let tree = build_tree(tree, hasher)?;
+            let proof = proof.map(|index| tree.try_get_proof(index)).transpose()?;
+            print!("{}", tree.to_dot(proof.as_ref()));
+        }
+        Command::Manifest {
+            dir,
+            output,
+            chunk_size,
+            ..
+        } => {
+            if *chunk_size == 0 {
+                bail!("chunk size must be non-zero");
+            }
+            let tree = hash_dir(dir, *chunk_size, hasher)?;
+            let manifest = Manifest {
+                chunk_size: *chunk_size,
+                root: tree.root(),
+                entries: tree.entries().to_vec(),
+            };
+            fs::write(output, manifest.to_bytes())
+                .with_context(|| format!("failed to write {}", output.display()))?;
+            println!("{}", hex::encode(&manifest.root));
+        }
+        Command::Audit { dir, manifest, .. } => {
+            let bytes = fs::read(manifest)
+                .with_context(|| format!("failed to read {}", manifest.display()))?;
+            let manifest = Manifest::from_bytes(&bytes)
+                .with_context(|| format!("failed to parse {}", manifest.display()))?;
+            let report = audit(dir, manifest, hasher)?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            if !report.changes.is_empty() {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Command::Solidity { .. } => print!("{}", codegen::solidity_verifier(&hasher)),
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Compares `dir` against `manifest`, which must have been made with the
+/// same hashing options.
+fn audit(dir: &Path, manifest: Manifest, hasher: RuntimeHasher) -> Result<AuditReport> {
+    if manifest.chunk_size == 0 {
+        bail!("manifest has a chunk size of zero");
+    }
+    let recorded = DirectoryTree::from_entries(manifest.entries, hasher);
+    if recorded.root() != manifest.root {
+        bail!("manifest entries do not match its root; check the hashing options");
+    }
+    let current = hash_dir(dir, manifest.chunk_size, hasher)?;
+    let changes = recorded
+        .diff(&current)
+        .into_iter()
+        .map(|(path, change)| AuditChange {
+            path: path.to_owned(),
+            change,
+            recorded: recorded.prove_file(path),
+            current: current.prove_file(path),
+        })
+        .collect();
+    Ok(AuditReport {
+        manifest_root: manifest.root,
+        current_root: current.root(),
+        changes,
+    })
+}
+
+fn hash_dir(
+    dir: &Path,
+    chunk_size: usize,
+    hasher: RuntimeHasher,
+) -> Result<DirectoryTree<RuntimeHasher>> {
+    DirectoryHasher::with_hasher(chunk_size, hasher)
+        .hash_dir(dir)
+        .with_context(|| format!("failed to hash {}", dir.display()))
+}
+
+fn build_tree<H: NodeHasher>(args: &TreeArgs, hasher: H) -> Result<MerkleTree<H>> {
+    let input = read_input(&args.leaves)?;
+    let leaves = parse_leaves(&input, args.raw, &hasher)?;
+    let depth = match args.depth {
+        Some(depth) => depth,
+        None => leaves.len().next_power_of_two().trailing_zeros() as usize,
+    };
+    let mut tree = MerkleTree::try_with_hasher(depth, hasher)?;
+    for leaf in leaves {
+        tree.try_append_leaf(leaf)?;
+    }
+    Ok(tree)
+}
+
+/// Parses one leaf per non-empty line, either as hex or as raw leaf data.
+fn parse_hash(hash: &str) -> Result<[u8; 32]> {
+    Ok(hex::decode_hash(hash)?)
+}
+
+/// Checks the proof in `document` against the trusted `root`. A document is
+/// self-consistent by construction, so its own root proves nothing and is
+/// only compared with `root`.
+fn verify_document<H: NodeHasher>(
+    document: &ProofDocument,
+    root: [u8; 32],
+    hasher: &H,
+) -> Result<bool> {
+    if document.root != root {
+        bail!(
+            "proof document is for root {}, not {}",
+            hex::encode(&document.root),
+            hex::encode(&root)
+        );
+    }
+    Ok(document.proof.verify_with(hasher, document.leaf, root))
+}
+
+fn parse_leaves<H: NodeHasher>(input: &str, raw: bool, hasher: &H) -> Result<Vec<[u8; 32]>> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(n, line)| {
+            if raw {
+                Ok(hasher.hash_leaf(line.as_bytes()))
+            } else {
+                hex::decode_hash(line.trim()).with_context(|| format!("line {}", n + 1))
+            }
+        })
+        .collect()
+}
+
+fn read_input(path: &Path) -> Result<String> {
+    if path.as_os_str() == "-" {
+        return std::io::read_to_string(std::io::stdin()).context("failed to read stdin");
+    }
+    if !path.exists() {
+        bail!("{} does not exist", path.display());
+    }
+    fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_leaves() {
+        let hasher = RuntimeHasher::default();
+        let hex_input = format!("{}\n\n0x{}\n", "00".repeat(32), "ff".repeat(32));
+        assert_eq!(
+            parse_leaves(&hex_input, false, &hasher).unwrap(),
+            vec![[0u8; 32], [0xff; 32]]
+        );
+        assert_eq!(
+            parse_leaves("a\nb\n", true, &hasher).unwrap(),
+            vec![hasher.hash_leaf(b"a"), hasher.hash_leaf(b"b")]
+        );
+        assert!(parse_leaves("zz\n", false, &hasher).is_err());
+    }
+
+    #[test]
+    fn test_proof_document_roundtrip() {
+        let mut tree = MerkleTree::new(2);
+        for data in [b"a", b"b", b"c"] {
+            tree.append_data(data);
+        }
+        let document = ProofDocument {
+            root: tree.root(),
+            leaf: tree.leaves()[2],
+            proof: tree.get_proof(2),
+        };
+        let json = serde_json::to_string(&document).unwrap();
+        let parsed: ProofDocument = serde_json::from_str(&json).unwrap();
+        assert!(parsed.proof.verify(parsed.leaf, parsed.root));
+
+        // A forged document proving its own leaf against its own root.
+        let hasher = *tree.hasher();
+        assert!(verify_document(&parsed, tree.root(), &hasher).unwrap());
+        let mut forged_tree = MerkleTree::new(2);
+        forged_tree.append_data(b"forged");
+        let forged = ProofDocument {
+            root: forged_tree.root(),
+            leaf: forged_tree.leaves()[0],
+            proof: forged_tree.get_proof(0),
+        };
+        assert!(forged.proof.verify(forged.leaf, forged.root));
+        assert!(verify_document(&forged, tree.root(), &hasher).is_err());
+        let lying = ProofDocument {
+            root: tree.root(),
+            ..forged
+        };
+        assert!(!verify_document(&lying, tree.root(), &hasher).unwrap());
+        assert_eq!(parse_hash(&hex::encode(&tree.root())).unwrap(), tree.root());
+    }
+
+    #[test]
+    fn test_audit_reports_changes_against_manifest() {
+        let dir = std::env::temp_dir().join(format!("merkle-audit-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("kept"), b"same").unwrap();
+        fs::write(dir.join("edited"), b"before").unwrap();
+        fs::write(dir.join("deleted"), b"gone").unwrap();
+
+        let hasher = RuntimeHasher::default();
+        let tree = hash_dir(&dir, 4, hasher).unwrap();
+        let bytes = Manifest {
+            chunk_size: 4,
+            root: tree.root(),
+            entries: tree.entries().to_vec(),
+        }
+        .to_bytes();
+        let manifest = Manifest::from_bytes(&bytes).unwrap();
+        assert_eq!(audit(&dir, manifest, hasher).unwrap().changes.len(), 0);
+
+        fs::write(dir.join("edited"), b"after").unwrap();
+        fs::remove_file(dir.join("deleted")).unwrap();
+        fs::write(dir.join("added"), b"new").unwrap();
+        let report = audit(&dir, Manifest::from_bytes(&bytes).unwrap(), hasher).unwrap();
+        let changes: Vec<_> = report
+            .changes
+            .iter()
+            .map(|change| (change.path.as_str(), change.change))
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                ("added", EntryChange::Added),
+                ("deleted", EntryChange::Removed),
+                ("edited", EntryChange::Changed)
+            ]
+        );
+        let edited = &report.changes[2];
+        assert!(edited
+            .recorded
+            .as_ref()
+            .unwrap()
+            .verify(report.manifest_root));
+        assert!(edited.current.as_ref().unwrap().verify(report.current_root));
+        assert!(report.changes[0].recorded.is_none());
+        assert!(Manifest::from_bytes(b"MRKLMAN1\0").is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
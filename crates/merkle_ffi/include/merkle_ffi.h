@@ -0,0 +1,173 @@
+#ifndef MERKLE_FFI_H
+#define MERKLE_FFI_H
+
+/* Generated by cbindgen from crates/merkle_ffi/src/lib.rs; do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef enum MerkleHashAlgorithm {
+  MERKLE_HASH_ALGORITHM_SHA256 = 0,
+  MERKLE_HASH_ALGORITHM_KECCAK256 = 1,
+} MerkleHashAlgorithm;
+
+/**
+ * Result of every fallible call.
+ */
+typedef enum MerkleStatus {
+  MERKLE_STATUS_OK = 0,
+  /**
+   * A required pointer argument was null.
+   */
+  MERKLE_STATUS_NULL_POINTER,
+  MERKLE_STATUS_DEPTH_TOO_LARGE,
+  MERKLE_STATUS_INDEX_OUT_OF_BOUNDS,
+  MERKLE_STATUS_TREE_FULL,
+  MERKLE_STATUS_INVALID_ENCODING,
+  MERKLE_STATUS_INVALID_PROOF,
+  /**
+   * The node store backing the tree failed.
+   */
+  MERKLE_STATUS_STORAGE,
+} MerkleStatus;
+
+/**
+ * Opaque tree handle.
+ */
+typedef struct MerkleTreeHandle MerkleTreeHandle;
+
+/**
+ * Hashing rules used by a tree or by [`merkle_verify_proof`].
+ */
+typedef struct MerkleHasherConfig {
+  enum MerkleHashAlgorithm algorithm;
+  /**
+   * Apply the RFC 6962 leaf and node prefixes.
+   */
+  bool domain_separation;
+  /**
+   * Hash children in ascending order, as OpenZeppelin's `MerkleProof` does.
+   */
+  bool sorted_pairs;
+} MerkleHasherConfig;
+
+/**
+ * A byte buffer allocated by this library.
+ */
+typedef struct MerkleBuffer {
+  uint8_t *data;
+  size_t len;
+} MerkleBuffer;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Creates an empty tree and stores its handle in `*out`.
+ *
+ * # Safety
+ *
+ * `out` must be null or valid for writes.
+ */
+enum MerkleStatus merkle_tree_new(size_t depth,
+                                  struct MerkleHasherConfig config,
+                                  struct MerkleTreeHandle **out);
+
+/**
+ * Releases a tree. Passing null is a no-op.
+ *
+ * # Safety
+ *
+ * `tree` must be null or a handle from [`merkle_tree_new`] that has not
+ * already been freed.
+ */
+void merkle_tree_free(struct MerkleTreeHandle *tree);
+
+/**
+ * Number of leaves in the tree, or 0 for a null handle.
+ *
+ * # Safety
+ *
+ * `tree` must be null or a live handle.
+ */
+size_t merkle_tree_len(const struct MerkleTreeHandle *tree);
+
+/**
+ * Appends the 32 byte leaf at `leaf`.
+ *
+ * # Safety
+ *
+ * `tree` must be null or a live handle, and `leaf` null or valid for 32
+ * byte reads.
+ */
+enum MerkleStatus merkle_tree_append_leaf(struct MerkleTreeHandle *tree, const uint8_t *leaf);
+
+/**
+ * Hashes `len` bytes at `data` into a leaf and appends it.
+ *
+ * # Safety
+ *
+ * `tree` must be null or a live handle, and `data` valid for `len` byte
+ * reads; it may be null only when `len` is 0.
+ */
+enum MerkleStatus merkle_tree_append_data(struct MerkleTreeHandle *tree,
+                                          const uint8_t *data,
+                                          size_t len);
+
+/**
+ * Writes the 32 byte root to `out`.
+ *
+ * # Safety
+ *
+ * `tree` must be null or a live handle, and `out` null or valid for 32
+ * byte writes.
+ */
+enum MerkleStatus merkle_tree_root(const struct MerkleTreeHandle *tree, uint8_t *out);
+
+/**
+ * Encodes the proof for the leaf at `index` into a new buffer stored in
+ * `*out`, which the caller releases with [`merkle_buffer_free`].
+ *
+ * # Safety
+ *
+ * `tree` must be null or a live handle, and `out` null or valid for writes.
+ */
+enum MerkleStatus merkle_tree_get_proof(const struct MerkleTreeHandle *tree,
+                                        size_t index,
+                                        struct MerkleBuffer *out);
+
+/**
+ * Releases a buffer filled in by this library and resets it to empty.
+ *
+ * # Safety
+ *
+ * `buffer` must be null or point to a buffer produced by this library that
+ * has not already been freed.
+ */
+void merkle_buffer_free(struct MerkleBuffer *buffer);
+
+/**
+ * Checks an encoded proof for the 32 byte `leaf` against the 32 byte
+ * `root`, storing the outcome in `*valid`.
+ *
+ * # Safety
+ *
+ * `leaf` and `root` must be valid for 32 byte reads, `proof` for
+ * `proof_len` byte reads, and `valid` for writes.
+ */
+enum MerkleStatus merkle_verify_proof(struct MerkleHasherConfig config,
+                                      const uint8_t *leaf,
+                                      const uint8_t *proof,
+                                      size_t proof_len,
+                                      const uint8_t *root,
+                                      bool *valid);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* MERKLE_FFI_H */
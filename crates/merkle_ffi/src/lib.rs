@@ -0,0 +1,385 @@
+//! C ABI for `merkle_toolkit`. The header is generated into
+//! `include/merkle_ffi.h` on every build.
+//!
+//! Ownership rules:
+//!
+//! - A tree returned by [`merkle_tree_new`] is owned by the caller and must be
+//!   released with exactly one call to [`merkle_tree_free`].
+//! - A [`MerkleBuffer`] filled in by this library is owned by the caller and
+//!   must be released with [`merkle_buffer_free`]; never pass it to `free`.
+//! - Every other pointer is borrowed for the duration of the call only.
+//!   Hashes are 32 byte arrays supplied or filled in by the caller.
+//!
+//! A tree handle is not synchronised; callers sharing one across threads
+//! must serialise access themselves.
+
+use std::ptr;
+use std::slice;
+
+use merkle_toolkit::{HashAlgorithm, MerkleError, MerkleTree, NodeHasher, Proof, RuntimeHasher};
+
+/// Result of every fallible call.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MerkleStatus {
+    Ok = 0,
+    /// A required pointer argument was null.
+    NullPointer,
+    DepthTooLarge,
+    IndexOutOfBounds,
+    TreeFull,
+    InvalidEncoding,
+    InvalidProof,
+    /// The node store backing the tree failed.
+    Storage,
+}
+
+impl From<MerkleError> for MerkleStatus {
+    fn from(error: MerkleError) -> Self {
+        match error {
+            MerkleError::DepthTooLarge { .. } => Self::DepthTooLarge,
+            MerkleError::IndexOutOfBounds { .. }
+            | MerkleError::SizeOutOfBounds { .. }
+            | MerkleError::UnknownCheckpoint { .. } => Self::IndexOutOfBounds,
+            MerkleError::TreeFull { .. } => Self::TreeFull,
+            MerkleError::InvalidConsistencyRange { .. } | MerkleError::InvalidEncoding(_) => {
+                Self::InvalidEncoding
+            }
+            MerkleError::InvalidProof
+            | MerkleError::InvalidProofLength { .. }
+            | MerkleError::LeafModified { .. }
+            | MerkleError::InvalidSignature => Self::InvalidProof,
+            MerkleError::CorruptNode { .. } | MerkleError::Storage(_) => Self::Storage,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MerkleHashAlgorithm {
+    Sha256 = 0,
+    Keccak256 = 1,
+}
+
+/// Hashing rules used by a tree or by [`merkle_verify_proof`].
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct MerkleHasherConfig {
+    pub algorithm: MerkleHashAlgorithm,
+    /// Apply the RFC 6962 leaf and node prefixes.
+    pub domain_separation: bool,
+    /// Hash children in ascending order, as OpenZeppelin's `MerkleProof` does.
+    pub sorted_pairs: bool,
+}
+
+impl From<MerkleHasherConfig> for RuntimeHasher {
+    fn from(config: MerkleHasherConfig) -> Self {
+        Self {
+            algorithm: match config.algorithm {
+                MerkleHashAlgorithm::Sha256 => HashAlgorithm::Sha256,
+                MerkleHashAlgorithm::Keccak256 => HashAlgorithm::Keccak256,
+            },
+            domain_separation: config.domain_separation,
+            sorted_pairs: config.sorted_pairs,
+        }
+    }
+}
+
+/// Opaque tree handle.
+pub struct MerkleTreeHandle(MerkleTree<RuntimeHasher>);
+
+/// A byte buffer allocated by this library.
+#[repr(C)]
+#[derive(Debug)]
+pub struct MerkleBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl MerkleBuffer {
+    fn from_vec(bytes: Vec<u8>) -> Self {
+        let bytes = Box::into_raw(bytes.into_boxed_slice());
+        Self {
+            data: bytes.cast(),
+            len: bytes.len(),
+        }
+    }
+}
+
+/// Creates an empty tree and stores its handle in `*out`.
+///
+/// # Safety
+///
+/// `out` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_tree_new(
+    depth: usize,
+    config: MerkleHasherConfig,
+    out: *mut *mut MerkleTreeHandle,
+) -> MerkleStatus {
+    if out.is_null() {
+        return MerkleStatus::NullPointer;
+    }
+    match MerkleTree::try_with_hasher(depth, config.into()) {
+        Ok(tree) => {
+            *out = Box::into_raw(Box::new(MerkleTreeHandle(tree)));
+            MerkleStatus::Ok
+        }
+        Err(error) => error.into(),
+    }
+}
+
+/// Releases a tree. Passing null is a no-op.
+///
+/// # Safety
+///
+/// `tree` must be null or a handle from [`merkle_tree_new`] that has not
+/// already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_tree_free(tree: *mut MerkleTreeHandle) {
+    if !tree.is_null() {
+        drop(Box::from_raw(tree));
+    }
+}
+
+/// Number of leaves in the tree, or 0 for a null handle.
+///
+/// # Safety
+///
+/// `tree` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_tree_len(tree: *const MerkleTreeHandle) -> usize {
+    tree.as_ref().map_or(0, |tree| tree.0.len())
+}
+
+/// Appends the 32 byte leaf at `leaf`.
+///
+/// # Safety
+///
+/// `tree` must be null or a live handle, and `leaf` null or valid for 32
+/// byte reads.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_tree_append_leaf(
+    tree: *mut MerkleTreeHandle,
+    leaf: *const u8,
+) -> MerkleStatus {
+    let (Some(tree), Some(leaf)) = (tree.as_mut(), read_hash(leaf)) else {
+        return MerkleStatus::NullPointer;
+    };
+    status(tree.0.try_append_leaf(leaf))
+}
+
+/// Hashes `len` bytes at `data` into a leaf and appends it.
+///
+/// # Safety
+///
+/// `tree` must be null or a live handle, and `data` valid for `len` byte
+/// reads; it may be null only when `len` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_tree_append_data(
+    tree: *mut MerkleTreeHandle,
+    data: *const u8,
+    len: usize,
+) -> MerkleStatus {
+    let (Some(tree), Some(data)) = (tree.as_mut(), read_bytes(data, len)) else {
+        return MerkleStatus::NullPointer;
+    };
+    let leaf = tree.0.hasher().hash_leaf(data);
+    status(tree.0.try_append_leaf(leaf))
+}
+
+/// Writes the 32 byte root to `out`.
+///
+/// # Safety
+///
+/// `tree` must be null or a live handle, and `out` null or valid for 32
+/// byte writes.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_tree_root(
+    tree: *const MerkleTreeHandle,
+    out: *mut u8,
+) -> MerkleStatus {
+    let Some(tree) = tree.as_ref() else {
+        return MerkleStatus::NullPointer;
+    };
+    if out.is_null() {
+        return MerkleStatus::NullPointer;
+    }
+    ptr::copy_nonoverlapping(tree.0.root().as_ptr(), out, 32);
+    MerkleStatus::Ok
+}
+
+/// Encodes the proof for the leaf at `index` into a new buffer stored in
+/// `*out`, which the caller releases with [`merkle_buffer_free`].
+///
+/// # Safety
+///
+/// `tree` must be null or a live handle, and `out` null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_tree_get_proof(
+    tree: *const MerkleTreeHandle,
+    index: usize,
+    out: *mut MerkleBuffer,
+) -> MerkleStatus {
+    let Some(tree) = tree.as_ref() else {
+        return MerkleStatus::NullPointer;
+    };
+    if out.is_null() {
+        return MerkleStatus::NullPointer;
+    }
+    match tree.0.try_get_proof(index) {
+        Ok(proof) => {
+            *out = MerkleBuffer::from_vec(proof.to_bytes());
+            MerkleStatus::Ok
+        }
+        Err(error) => error.into(),
+    }
+}
+
+/// Releases a buffer filled in by this library and resets it to empty.
+///
+/// # Safety
+///
+/// `buffer` must be null or point to a buffer produced by this library that
+/// has not already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_buffer_free(buffer: *mut MerkleBuffer) {
+    let Some(buffer) = buffer.as_mut() else {
+        return;
+    };
+    if !buffer.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+            buffer.data,
+            buffer.len,
+        )));
+    }
+    buffer.data = ptr::null_mut();
+    buffer.len = 0;
+}
+
+/// Checks an encoded proof for the 32 byte `leaf` against the 32 byte
+/// `root`, storing the outcome in `*valid`.
+///
+/// # Safety
+///
+/// `leaf` and `root` must be valid for 32 byte reads, `proof` for
+/// `proof_len` byte reads, and `valid` for writes.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_verify_proof(
+    config: MerkleHasherConfig,
+    leaf: *const u8,
+    proof: *const u8,
+    proof_len: usize,
+    root: *const u8,
+    valid: *mut bool,
+) -> MerkleStatus {
+    let (Some(leaf), Some(proof), Some(root)) = (
+        read_hash(leaf),
+        read_bytes(proof, proof_len),
+        read_hash(root),
+    ) else {
+        return MerkleStatus::NullPointer;
+    };
+    if valid.is_null() {
+        return MerkleStatus::NullPointer;
+    }
+    match Proof::from_bytes(proof) {
+        Ok(proof) => {
+            *valid = proof.verify_with(&RuntimeHasher::from(config), leaf, root);
+            MerkleStatus::Ok
+        }
+        Err(error) => error.into(),
+    }
+}
+
+unsafe fn read_hash(hash: *const u8) -> Option<[u8; 32]> {
+    (!hash.is_null()).then(|| ptr::read_unaligned(hash.cast()))
+}
+
+unsafe fn read_bytes<'a>(data: *const u8, len: usize) -> Option<&'a [u8]> {
+    match (data.is_null(), len) {
+        (true, 0) => Some(&[]),
+        (true, _) => None,
+        (false, _) => Some(slice::from_raw_parts(data, len)),
+    }
+}
+
+fn status(result: Result<(), MerkleError>) -> MerkleStatus {
+    result.map_or_else(MerkleStatus::from, |()| MerkleStatus::Ok)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: MerkleHasherConfig = MerkleHasherConfig {
+        algorithm: MerkleHashAlgorithm::Sha256,
+        domain_separation: true,
+        sorted_pairs: false,
+    };
+
+    #[test]
+    fn test_build_prove_verify() {
+        unsafe {
+            let mut tree = ptr::null_mut();
+            assert_eq!(merkle_tree_new(2, CONFIG, &mut tree), MerkleStatus::Ok);
+            for data in [&b"a"[..], b"b", b"c"] {
+                let status = merkle_tree_append_data(tree, data.as_ptr(), data.len());
+                assert_eq!(status, MerkleStatus::Ok);
+            }
+            assert_eq!(merkle_tree_len(tree), 3);
+
+            let mut root = [0u8; 32];
+            assert_eq!(merkle_tree_root(tree, root.as_mut_ptr()), MerkleStatus::Ok);
+            let mut proof = MerkleBuffer {
+                data: ptr::null_mut(),
+                len: 0,
+            };
+            assert_eq!(merkle_tree_get_proof(tree, 1, &mut proof), MerkleStatus::Ok);
+
+            let leaf = RuntimeHasher::from(CONFIG).hash_leaf(b"b");
+            let mut valid = false;
+            let status = merkle_verify_proof(
+                CONFIG,
+                leaf.as_ptr(),
+                proof.data,
+                proof.len,
+                root.as_ptr(),
+                &mut valid,
+            );
+            assert_eq!(status, MerkleStatus::Ok);
+            assert!(valid);
+
+            merkle_buffer_free(&mut proof);
+            assert!(proof.data.is_null());
+            merkle_tree_free(tree);
+        }
+    }
+
+    #[test]
+    fn test_errors_map_to_status() {
+        unsafe {
+            let mut tree = ptr::null_mut();
+            assert_eq!(
+                merkle_tree_new(usize::MAX, CONFIG, &mut tree),
+                MerkleStatus::DepthTooLarge
+            );
+            assert!(tree.is_null());
+            assert_eq!(merkle_tree_new(0, CONFIG, &mut tree), MerkleStatus::Ok);
+            let leaf = [7u8; 32];
+            assert_eq!(
+                merkle_tree_append_leaf(tree, leaf.as_ptr()),
+                MerkleStatus::Ok
+            );
+            assert_eq!(
+                merkle_tree_append_leaf(tree, leaf.as_ptr()),
+                MerkleStatus::TreeFull
+            );
+            assert_eq!(
+                merkle_tree_append_leaf(tree, ptr::null()),
+                MerkleStatus::NullPointer
+            );
+            merkle_tree_free(tree);
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! Node.js bindings for `merkle_toolkit`, built with `@napi-rs/cli`
+//! (`npm run build`), which also emits `index.d.ts`.
+//!
+//! ```js
+//! const { MerkleTree, hashLeaf, verifyProof } = require('merkle-toolkit')
+//!
+//! const tree = MerkleTree.fromData(21, leaves.map(Buffer.from))
+//! const proof = tree.getProof(1)
+//! verifyProof(hashLeaf(leaves[1]), proof, tree.root())
+//! ```
+//!
+//! Hashes are 32 byte `Buffer`s and proofs use the [`Proof::to_bytes`]
+//! encoding.
+
+use merkle_toolkit::{MerkleError, NodeHasher, Proof, RuntimeHasher};
+use napi::bindgen_prelude::Buffer;
+use napi::{Error, Result};
+use napi_derive::napi;
+
+/// Hasher options shared by tree construction and verification. Omitted
+/// fields default to domain-separated SHA-256.
+#[napi(object)]
+pub struct HasherOptions {
+    /// `"sha256"` or `"keccak256"`.
+    pub hash: Option<String>,
+    pub domain_separation: Option<bool>,
+    /// Hash children in ascending order, as OpenZeppelin's `MerkleProof` does.
+    pub sorted_pairs: Option<bool>,
+}
+
+#[napi(js_name = "MerkleTree")]
+pub struct NodeMerkleTree {
+    inner: merkle_toolkit::MerkleTree<RuntimeHasher>,
+}
+
+#[napi]
+impl NodeMerkleTree {
+    #[napi(constructor)]
+    pub fn new(depth: u32, options: Option<HasherOptions>) -> Result<Self> {
+        let hasher = runtime_hasher(options)?;
+        let inner = merkle_toolkit::MerkleTree::try_with_hasher(depth as usize, hasher)
+            .map_err(napi_error)?;
+        Ok(Self { inner })
+    }
+
+    /// Builds a tree over already hashed leaves in one pass.
+    #[napi(factory)]
+    pub fn from_leaves(
+        depth: u32,
+        leaves: Vec<Buffer>,
+        options: Option<HasherOptions>,
+    ) -> Result<Self> {
+        let hasher = runtime_hasher(options)?;
+        let leaves = leaves
+            .iter()
+            .map(|leaf| hash_from_slice(leaf))
+            .collect::<Result<_>>()?;
+        let inner = merkle_toolkit::MerkleTree::from_leaves(depth as usize, hasher, leaves)
+            .map_err(napi_error)?;
+        Ok(Self { inner })
+    }
+
+    /// Hashes every item of `data` into a leaf and builds a tree over them.
+    #[napi(factory)]
+    pub fn from_data(
+        depth: u32,
+        data: Vec<Buffer>,
+        options: Option<HasherOptions>,
+    ) -> Result<Self> {
+        let hasher = runtime_hasher(options)?;
+        let leaves = data.iter().map(|item| hasher.hash_leaf(item)).collect();
+        let inner = merkle_toolkit::MerkleTree::from_leaves(depth as usize, hasher, leaves)
+            .map_err(napi_error)?;
+        Ok(Self { inner })
+    }
+
+    #[napi(getter)]
+    pub fn length(&self) -> u32 {
+        self.inner.len() as u32
+    }
+
+    #[napi(getter)]
+    pub fn depth(&self) -> u32 {
+        self.inner.depth() as u32
+    }
+
+    #[napi]
+    pub fn append_leaf(&mut self, leaf: Buffer) -> Result<()> {
+        let leaf = hash_from_slice(&leaf)?;
+        self.inner.try_append_leaf(leaf).map_err(napi_error)
+    }
+
+    #[napi]
+    pub fn append_data(&mut self, data: Buffer) -> Result<()> {
+        let leaf = self.inner.hasher().hash_leaf(&data);
+        self.inner.try_append_leaf(leaf).map_err(napi_error)
+    }
+
+    #[napi]
+    pub fn root(&self) -> Buffer {
+        self.inner.root().to_vec().into()
+    }
+
+    #[napi]
+    pub fn get_proof(&self, index: u32) -> Result<Buffer> {
+        let proof = self
+            .inner
+            .try_get_proof(index as usize)
+            .map_err(napi_error)?;
+        Ok(proof.to_bytes().into())
+    }
+}
+
+/// Hashes `data` into a leaf the way `MerkleTree.appendData` does.
+#[napi]
+pub fn hash_leaf(data: Buffer, options: Option<HasherOptions>) -> Result<Buffer> {
+    let hasher = runtime_hasher(options)?;
+    Ok(hasher.hash_leaf(&data).to_vec().into())
+}
+
+/// Checks an encoded proof for a 32 byte `leaf` against `root`.
+#[napi]
+pub fn verify_proof(
+    leaf: Buffer,
+    proof: Buffer,
+    root: Buffer,
+    options: Option<HasherOptions>,
+) -> Result<bool> {
+    let hasher = runtime_hasher(options)?;
+    let proof = Proof::from_bytes(&proof).map_err(napi_error)?;
+    Ok(proof.verify_with(&hasher, hash_from_slice(&leaf)?, hash_from_slice(&root)?))
+}
+
+fn runtime_hasher(options: Option<HasherOptions>) -> Result<RuntimeHasher> {
+    let mut hasher = RuntimeHasher::default();
+    if let Some(options) = options {
+        if let Some(hash) = options.hash {
+            hasher.algorithm = hash.parse().map_err(napi_error)?;
+        }
+        if let Some(domain_separation) = options.domain_separation {
+            hasher.domain_separation = domain_separation;
+        }
+        if let Some(sorted_pairs) = options.sorted_pairs {
+            hasher.sorted_pairs = sorted_pairs;
+        }
+    }
+    Ok(hasher)
+}
+
+fn hash_from_slice(bytes: &[u8]) -> Result<[u8; 32]> {
+    bytes
+        .try_into()
+        .map_err(|_| Error::from_reason("expected a 32 byte Buffer"))
+}
+
+fn napi_error(error: MerkleError) -> Error {
+    Error::from_reason(error.to_string())
+}
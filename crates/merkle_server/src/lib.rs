@@ -0,0 +1,215 @@
+//! gRPC access to a shared merkle tree.
+//!
+//! [`MerkleService`] serves the `merkle.v1.Merkle` service from
+//! `proto/merkle.proto` over a tree that any number of requests read
+//! concurrently; appends take the write lock briefly. With the `rest`
+//! feature, [`rest::router`] serves the same tree as HTTP/JSON.
+
+use merkle_toolkit::{MerkleError, MerkleTree, Proof, SharedMerkleTree};
+use tonic::{Request, Response, Status};
+
+#[cfg(feature = "rest")]
+pub mod rest;
+
+pub mod pb {
+    tonic::include_proto!("merkle.v1");
+}
+
+pub use pb::merkle_server::MerkleServer;
+
+/// Handle to the tree the service answers from. Clones share the tree.
+#[derive(Debug, Clone)]
+pub struct MerkleService {
+    tree: SharedMerkleTree,
+}
+
+impl MerkleService {
+    pub fn new(tree: MerkleTree) -> Self {
+        Self {
+            tree: SharedMerkleTree::new(tree),
+        }
+    }
+
+    /// The shared tree, for callers that also update it outside the service.
+    pub fn tree(&self) -> &SharedMerkleTree {
+        &self.tree
+    }
+
+    pub fn into_server(self) -> MerkleServer<Self> {
+        MerkleServer::new(self)
+    }
+}
+
+fn status(error: MerkleError) -> Status {
+    match error {
+        MerkleError::IndexOutOfBounds { .. } => Status::not_found(error.to_string()),
+        MerkleError::TreeFull { .. } => Status::resource_exhausted(error.to_string()),
+        MerkleError::Storage(_) => Status::internal(error.to_string()),
+        _ => Status::invalid_argument(error.to_string()),
+    }
+}
+
+fn hash(bytes: &[u8], field: &str) -> Result<[u8; 32], Status> {
+    bytes
+        .try_into()
+        .map_err(|_| Status::invalid_argument(format!("{field} must be 32 bytes")))
+}
+
+fn proof_from_pb(proof: pb::Proof) -> Result<Proof, Status> {
+    let size = |value: u64| {
+        usize::try_from(value).map_err(|_| Status::invalid_argument("proof size out of range"))
+    };
+    Ok(Proof {
+        index: size(proof.index)?,
+        size: size(proof.size)?,
+        siblings: proof
+            .siblings
+            .iter()
+            .map(|sibling| hash(sibling, "sibling"))
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+fn proof_to_pb(proof: Proof) -> pb::Proof {
+    pb::Proof {
+        index: proof.index as u64,
+        size: proof.size as u64,
+        siblings: proof
+            .siblings
+            .iter()
+            .map(|sibling| sibling.to_vec())
+            .collect(),
+    }
+}
+
+#[tonic::async_trait]
+impl pb::merkle_server::Merkle for MerkleService {
+    async fn append_leaf(
+        &self,
+        request: Request<pb::AppendLeafRequest>,
+    ) -> Result<Response<pb::AppendLeafResponse>, Status> {
+        let index = match request.into_inner().entry {
+            Some(pb::append_leaf_request::Entry::Leaf(leaf)) => {
+                self.tree.append_leaf(hash(&leaf, "leaf")?)
+            }
+            Some(pb::append_leaf_request::Entry::Data(data)) => self.tree.append_data(&data),
+            None => return Err(Status::invalid_argument("a leaf or data is required")),
+        }
+        .map_err(status)?;
+        // The root after this append, which later appends may already
+        // have moved past.
+        let root = self.tree.read().root_at_size(index + 1).map_err(status)?;
+        Ok(Response::new(pb::AppendLeafResponse {
+            index: index as u64,
+            root: root.to_vec(),
+        }))
+    }
+
+    async fn get_root(
+        &self,
+        _: Request<pb::GetRootRequest>,
+    ) -> Result<Response<pb::GetRootResponse>, Status> {
+        let snapshot = self.tree.snapshot().map_err(status)?;
+        Ok(Response::new(pb::GetRootResponse {
+            root: snapshot.root.to_vec(),
+            size: snapshot.size as u64,
+        }))
+    }
+
+    async fn get_proof(
+        &self,
+        request: Request<pb::GetProofRequest>,
+    ) -> Result<Response<pb::GetProofResponse>, Status> {
+        let index = usize::try_from(request.into_inner().index)
+            .map_err(|_| Status::invalid_argument("index out of range"))?;
+        let tree = self.tree.read();
+        let proof = tree.try_get_proof(index).map_err(status)?;
+        Ok(Response::new(pb::GetProofResponse {
+            leaf: tree.leaves()[index].to_vec(),
+            proof: Some(proof_to_pb(proof)),
+        }))
+    }
+
+    async fn verify_proof(
+        &self,
+        request: Request<pb::VerifyProofRequest>,
+    ) -> Result<Response<pb::VerifyProofResponse>, Status> {
+        let request = request.into_inner();
+        let leaf = hash(&request.leaf, "leaf")?;
+        let proof = proof_from_pb(
+            request
+                .proof
+                .ok_or_else(|| Status::invalid_argument("a proof is required"))?,
+        )?;
+        let tree = self.tree.read();
+        let root = match request.root.is_empty() {
+            true => tree.try_root().map_err(status)?,
+            false => hash(&request.root, "root")?,
+        };
+        Ok(Response::new(pb::VerifyProofResponse {
+            valid: proof.verify_with(tree.hasher(), leaf, root),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::pb::merkle_server::Merkle;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_append_prove_verify() {
+        let service = MerkleService::new(MerkleTree::new(4));
+        for i in 0u8..5 {
+            let appended = service
+                .append_leaf(Request::new(pb::AppendLeafRequest {
+                    entry: Some(pb::append_leaf_request::Entry::Data(vec![i])),
+                }))
+                .await
+                .unwrap()
+                .into_inner();
+            assert_eq!(appended.index, i as u64);
+        }
+        let root = service
+            .get_root(Request::new(pb::GetRootRequest {}))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(root.size, 5);
+        assert_eq!(root.root, service.tree().root().unwrap());
+
+        let proof = service
+            .get_proof(Request::new(pb::GetProofRequest { index: 3 }))
+            .await
+            .unwrap()
+            .into_inner();
+        let verify = |leaf: Vec<u8>, root: Vec<u8>| {
+            service.verify_proof(Request::new(pb::VerifyProofRequest {
+                leaf,
+                proof: proof.proof.clone(),
+                root,
+            }))
+        };
+        assert!(
+            verify(proof.leaf.clone(), Vec::new())
+                .await
+                .unwrap()
+                .into_inner()
+                .valid
+        );
+        assert!(
+            !verify(proof.leaf.clone(), vec![0; 32])
+                .await
+                .unwrap()
+                .into_inner()
+                .valid
+        );
+        assert!(verify(vec![1; 31], Vec::new()).await.is_err());
+
+        let missing = service
+            .get_proof(Request::new(pb::GetProofRequest { index: 5 }))
+            .await
+            .unwrap_err();
+        assert_eq!(missing.code(), tonic::Code::NotFound);
+    }
+}
@@ -0,0 +1,50 @@
+use std::net::SocketAddr;
+
+use anyhow::Result;
+use clap::Parser;
+use merkle_server::MerkleService;
+use merkle_toolkit::MerkleTree;
+
+#[derive(Parser)]
+#[command(name = "merkle-server", about = "Serve a merkle tree over gRPC")]
+struct Cli {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:50051")]
+    addr: SocketAddr,
+    /// Also serve the HTTP/JSON API on this address
+    #[cfg(feature = "rest")]
+    #[arg(long)]
+    http_addr: Option<SocketAddr>,
+    /// Tree depth; the tree holds up to 2^depth leaves
+    #[arg(long, default_value_t = 20)]
+    depth: usize,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let service = MerkleService::new(MerkleTree::try_new(cli.depth)?);
+    #[cfg(feature = "rest")]
+    let rest = {
+        let service = service.clone();
+        async move {
+            if let Some(http_addr) = cli.http_addr {
+                let listener = tokio::net::TcpListener::bind(http_addr).await?;
+                axum::serve(listener, merkle_server::rest::router(service)).await?;
+            }
+            Ok::<_, anyhow::Error>(())
+        }
+    };
+    #[cfg(not(feature = "rest"))]
+    let rest = async { Ok::<_, anyhow::Error>(()) };
+    let grpc = async {
+        tonic::transport::Server::builder()
+            .add_service(service.into_server())
+            .serve(cli.addr)
+            .await?;
+        Ok::<_, anyhow::Error>(())
+    };
+    // Either server failing stops the process with its error.
+    tokio::try_join!(grpc, rest)?;
+    Ok(())
+}
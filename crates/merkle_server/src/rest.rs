@@ -0,0 +1,228 @@
+//! HTTP/JSON routes over the same tree as the gRPC service.
+//!
+//! | Route               | Body                                     | Response                     |
+//! |---------------------|------------------------------------------|------------------------------|
+//! | `GET /root`         |                                          | `{"root", "size"}`           |
+//! | `GET /proof/{index}`|                                          | `{"leaf", "proof"}`          |
+//! | `POST /verify`      | `{"leaf", "proof", "root"?}`             | `{"valid"}`                  |
+//! | `POST /append`      | `{"leaf"}` or `{"data"}`                 | `{"index", "root"}`          |
+//!
+//! Hashes and data are lowercase hex; proofs use the `merkle_toolkit`
+//! serde representation. Errors come back as `{"error": message}`.
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use merkle_toolkit::{hex, serde_hex, MerkleError, Proof};
+use serde::{Deserialize, Serialize};
+
+use crate::MerkleService;
+
+pub struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(serde_json::json!({ "error": self.1 }))).into_response()
+    }
+}
+
+impl From<MerkleError> for ApiError {
+    fn from(error: MerkleError) -> Self {
+        let code = match error {
+            MerkleError::IndexOutOfBounds { .. } => StatusCode::NOT_FOUND,
+            MerkleError::TreeFull { .. } => StatusCode::INSUFFICIENT_STORAGE,
+            MerkleError::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        };
+        Self(code, error.to_string())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RootResponse {
+    #[serde(with = "serde_hex")]
+    pub root: [u8; 32],
+    pub size: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProofResponse {
+    #[serde(with = "serde_hex")]
+    pub leaf: [u8; 32],
+    pub proof: Proof,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VerifyRequest {
+    #[serde(with = "serde_hex")]
+    pub leaf: [u8; 32],
+    pub proof: Proof,
+    /// Checked against the current root when absent.
+    #[serde(default, with = "optional_hash")]
+    pub root: Option<[u8; 32]>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VerifyResponse {
+    pub valid: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AppendRequest {
+    Leaf(#[serde(with = "serde_hex")] [u8; 32]),
+    /// Hex-encoded data, hashed into a leaf by the tree's hasher.
+    Data(String),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AppendResponse {
+    pub index: usize,
+    #[serde(with = "serde_hex")]
+    pub root: [u8; 32],
+}
+
+mod optional_hash {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        hash: &Option<[u8; 32]>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match hash {
+            Some(hash) => merkle_toolkit::serde_hex::serialize(hash, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<[u8; 32]>, D::Error> {
+        #[derive(Deserialize)]
+        struct Hash(#[serde(with = "merkle_toolkit::serde_hex")] [u8; 32]);
+        Ok(Option::<Hash>::deserialize(deserializer)?.map(|hash| hash.0))
+    }
+}
+
+/// The REST routes, answering from `service`'s tree.
+pub fn router(service: MerkleService) -> Router {
+    Router::new()
+        .route("/root", get(root))
+        .route("/proof/{index}", get(proof))
+        .route("/verify", post(verify))
+        .route("/append", post(append))
+        .with_state(service)
+}
+
+async fn root(State(service): State<MerkleService>) -> Result<Json<RootResponse>, ApiError> {
+    let snapshot = service.tree().snapshot()?;
+    Ok(Json(RootResponse {
+        root: snapshot.root,
+        size: snapshot.size,
+    }))
+}
+
+async fn proof(
+    State(service): State<MerkleService>,
+    Path(index): Path<usize>,
+) -> Result<Json<ProofResponse>, ApiError> {
+    let tree = service.tree().read();
+    let proof = tree.try_get_proof(index)?;
+    Ok(Json(ProofResponse {
+        leaf: tree.leaves()[index],
+        proof,
+    }))
+}
+
+async fn verify(
+    State(service): State<MerkleService>,
+    Json(request): Json<VerifyRequest>,
+) -> Result<Json<VerifyResponse>, ApiError> {
+    let tree = service.tree().read();
+    let root = match request.root {
+        Some(root) => root,
+        None => tree.try_root()?,
+    };
+    Ok(Json(VerifyResponse {
+        valid: request.proof.verify_with(tree.hasher(), request.leaf, root),
+    }))
+}
+
+async fn append(
+    State(service): State<MerkleService>,
+    Json(request): Json<AppendRequest>,
+) -> Result<Json<AppendResponse>, ApiError> {
+    let tree = service.tree();
+    let index = match request {
+        AppendRequest::Leaf(leaf) => tree.append_leaf(leaf)?,
+        AppendRequest::Data(data) => tree.append_data(&hex::decode(&data)?)?,
+    };
+    Ok(Json(AppendResponse {
+        index,
+        root: tree.read().root_at_size(index + 1)?,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use http_body_util::BodyExt;
+    use merkle_toolkit::MerkleTree;
+    use tower::ServiceExt;
+
+    async fn call(
+        app: &Router,
+        method: &str,
+        uri: &str,
+        body: serde_json::Value,
+    ) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(match body.is_null() {
+                true => Body::empty(),
+                false => Body::from(body.to_string()),
+            })
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_routes() {
+        let service = MerkleService::new(MerkleTree::new(4));
+        let app = router(service.clone());
+        let (status, appended) =
+            call(&app, "POST", "/append", serde_json::json!({ "data": "00" })).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(appended["index"], 0);
+        let leaf = hex::encode(&[7; 32]);
+        let (_, appended) =
+            call(&app, "POST", "/append", serde_json::json!({ "leaf": leaf })).await;
+        assert_eq!(appended["index"], 1);
+
+        let (_, root) = call(&app, "GET", "/root", serde_json::Value::Null).await;
+        assert_eq!(root["size"], 2);
+        assert_eq!(root["root"], hex::encode(&service.tree().root().unwrap()));
+
+        let (_, proof) = call(&app, "GET", "/proof/1", serde_json::Value::Null).await;
+        assert_eq!(proof["leaf"], leaf);
+        let (_, verified) = call(&app, "POST", "/verify", proof.clone()).await;
+        assert_eq!(verified["valid"], true);
+        let mut wrong_root = proof.clone();
+        wrong_root["root"] = hex::encode(&[0; 32]).into();
+        let (_, verified) = call(&app, "POST", "/verify", wrong_root).await;
+        assert_eq!(verified["valid"], false);
+
+        let (status, error) = call(&app, "GET", "/proof/9", serde_json::Value::Null).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(error["error"].is_string());
+    }
+}
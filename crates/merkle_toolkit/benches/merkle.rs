@@ -1,5 +1,5 @@
 use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
-use merkle_toolkit::MerkleTree;
+use merkle_toolkit::{DomainSeparated, MerkleTree, Sha256Hasher};
 use sha2::{Digest, Sha256};
 use std::time::Duration;
 
@@ -40,5 +40,37 @@ fn bench_get_proof(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, bench_get_proof);
+/// Benchmark building a tree leaf by leaf against hashing whole levels at once
+fn bench_build(c: &mut Criterion) {
+    let mut group = c.benchmark_group("merkle_build");
+    group.sample_size(10);
+
+    let depth = 16;
+    let leaves: Vec<[u8; 32]> = (0u32..(1u32 << depth))
+        .map(|i| Sha256::digest(i.to_le_bytes()).into())
+        .collect();
+
+    group.bench_function(BenchmarkId::new("append_leaf", leaves.len()), |b| {
+        b.iter(|| {
+            let mut tree = MerkleTree::new(depth);
+            for leaf in &leaves {
+                tree.append_leaf(*leaf);
+            }
+            black_box(tree.root());
+        })
+    });
+
+    group.bench_function(BenchmarkId::new("from_leaves", leaves.len()), |b| {
+        b.iter(|| {
+            let tree =
+                MerkleTree::from_leaves(depth, DomainSeparated(Sha256Hasher), leaves.clone())
+                    .unwrap();
+            black_box(tree.root());
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_get_proof, bench_build);
 criterion_main!(benches);
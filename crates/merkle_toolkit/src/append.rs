@@ -0,0 +1,112 @@
+use crate::{MerkleError, MerkleTree, NodeHasher, NodeStore};
+
+/// Number of parent nodes each rayon task hashes.
+#[cfg(feature = "rayon")]
+const PAR_CHUNK_SIZE: usize = 4096;
+
+impl<H: NodeHasher, S: NodeStore> MerkleTree<H, S> {
+    /// Appends `leaves`, then rehashes each node above them once, a level at
+    /// a time with [`NodeHasher::hash_pairs`], instead of walking a path per
+    /// leaf. Nothing is appended if the leaves don't fit.
+    pub fn append_leaves(&mut self, leaves: &[[u8; 32]]) -> Result<(), MerkleError> {
+        self.append_leaves_by(leaves, |hasher, children, parents| {
+            hasher.hash_pairs(children, parents)
+        })
+    }
+
+    /// Parallel [`MerkleTree::append_leaves`], hashing each level in chunks
+    /// on the rayon thread pool.
+    #[cfg(feature = "rayon")]
+    pub fn par_append_leaves(&mut self, leaves: &[[u8; 32]]) -> Result<(), MerkleError>
+    where
+        H: Sync,
+    {
+        use rayon::prelude::*;
+
+        self.append_leaves_by(leaves, |hasher, children, parents| {
+            children
+                .par_chunks(2 * PAR_CHUNK_SIZE)
+                .zip(parents.par_chunks_mut(PAR_CHUNK_SIZE))
+                .for_each(|(children, parents)| hasher.hash_pairs(children, parents))
+        })
+    }
+
+    fn append_leaves_by(
+        &mut self,
+        leaves: &[[u8; 32]],
+        hash_level: impl Fn(&H, &[[u8; 32]], &mut [[u8; 32]]),
+    ) -> Result<(), MerkleError> {
+        span!(DEBUG, "merkle.append", leaves = leaves.len());
+        let start = self.len();
+        if leaves.len() > self.capacity() - start {
+            return Err(MerkleError::TreeFull {
+                capacity: self.capacity(),
+            });
+        }
+        for (index, &leaf) in (start..).zip(leaves) {
+            self.levels.put(0, index, leaf)?;
+            self.index_leaf(index, leaf);
+        }
+        crate::telemetry::leaves_appended(leaves.len());
+        if leaves.is_empty() {
+            return Ok(());
+        }
+
+        // Each level is rehashed from the pair holding its first new node.
+        let (mut level, mut first) = (0, start & !1);
+        while self.levels.level_len(level) > 1 {
+            let mut children = (first..self.levels.level_len(level))
+                .map(|index| Ok(self.levels.get(level, index)?.unwrap_or(self.zeros[level])))
+                .collect::<Result<Vec<_>, MerkleError>>()?;
+            if children.len() % 2 == 1 {
+                children.push(self.zeros[level]);
+            }
+            let mut parents = vec![[0u8; 32]; children.len() / 2];
+            hash_level(&self.hasher, &children, &mut parents);
+
+            level += 1;
+            first /= 2;
+            for (index, parent) in (first..).zip(parents) {
+                self.levels.put(level, index, parent)?;
+            }
+            first &= !1;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append_leaves_matches_one_at_a_time() {
+        let leaves: Vec<[u8; 32]> = (0u8..13).map(|i| [i; 32]).collect();
+        let mut single = MerkleTree::new(5);
+        let mut batched = MerkleTree::new(5);
+        for batch in [
+            &leaves[..1],
+            &leaves[1..2],
+            &[],
+            &leaves[2..7],
+            &leaves[7..],
+        ] {
+            batch.iter().for_each(|&leaf| single.append_leaf(leaf));
+            batched.append_leaves(batch).unwrap();
+            assert_eq!(batched.root(), single.root());
+            assert_eq!(batched.store(), single.store());
+        }
+        assert!(MerkleTree::new(2).append_leaves(&leaves[..5]).is_err());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_par_append_leaves() {
+        let leaves: Vec<[u8; 32]> = (0u32..10_000).map(|i| [i as u8; 32]).collect();
+        let mut tree = MerkleTree::new(14);
+        tree.par_append_leaves(&leaves[..3]).unwrap();
+        tree.par_append_leaves(&leaves[3..]).unwrap();
+        let rebuilt = MerkleTree::from_leaves(14, *tree.hasher(), leaves).unwrap();
+        assert_eq!(tree.root(), rebuilt.root());
+    }
+}
@@ -0,0 +1,286 @@
+use std::future::{ready, Future};
+
+use crate::checkpoint::Checkpoints;
+use crate::{
+    capacity, pad_root, zero_hashes_with, MerkleError, MerkleTree, NodeHasher, NodeStore, Proof,
+    MAX_DEPTH,
+};
+
+/// Node storage whose reads and writes may wait on I/O, such as a remote
+/// database or an object store.
+///
+/// It mirrors [`NodeStore`], with every call returning a future. Every
+/// `NodeStore` that is `Sync` is an `AsyncNodeStore` whose futures are
+/// immediately ready; blocking stores such as sled should be wrapped in
+/// [`BlockingStore`] (feature `tokio`) instead so their I/O runs off the
+/// executor.
+pub trait AsyncNodeStore {
+    fn levels(&self) -> impl Future<Output = Result<usize, MerkleError>> + Send;
+
+    fn level_len(&self, level: usize) -> impl Future<Output = Result<usize, MerkleError>> + Send;
+
+    fn get(
+        &self,
+        level: usize,
+        index: usize,
+    ) -> impl Future<Output = Result<Option<[u8; 32]>, MerkleError>> + Send;
+
+    fn put(
+        &mut self,
+        level: usize,
+        index: usize,
+        node: [u8; 32],
+    ) -> impl Future<Output = Result<(), MerkleError>> + Send;
+}
+
+impl<S: NodeStore + Sync> AsyncNodeStore for S {
+    fn levels(&self) -> impl Future<Output = Result<usize, MerkleError>> + Send {
+        ready(Ok(NodeStore::levels(self)))
+    }
+
+    fn level_len(&self, level: usize) -> impl Future<Output = Result<usize, MerkleError>> + Send {
+        ready(Ok(NodeStore::level_len(self, level)))
+    }
+
+    fn get(
+        &self,
+        level: usize,
+        index: usize,
+    ) -> impl Future<Output = Result<Option<[u8; 32]>, MerkleError>> + Send {
+        ready(NodeStore::get(self, level, index))
+    }
+
+    fn put(
+        &mut self,
+        level: usize,
+        index: usize,
+        node: [u8; 32],
+    ) -> impl Future<Output = Result<(), MerkleError>> + Send {
+        ready(NodeStore::put(self, level, index, node))
+    }
+}
+
+impl<H: NodeHasher, S: AsyncNodeStore> MerkleTree<H, S> {
+    /// Creates a tree over an asynchronous `store`, which may already hold
+    /// the nodes of a tree built with the same depth and hasher.
+    pub fn with_async_store(depth: usize, hasher: H, store: S) -> Result<Self, MerkleError> {
+        if depth > MAX_DEPTH {
+            return Err(MerkleError::DepthTooLarge {
+                depth,
+                max: MAX_DEPTH,
+            });
+        }
+        Ok(Self {
+            depth,
+            levels: store,
+            zeros: zero_hashes_with(&hasher, depth),
+            leaf_index: None,
+            checkpoints: Checkpoints::default(),
+            hasher,
+        })
+    }
+
+    pub async fn len_async(&self) -> Result<usize, MerkleError> {
+        AsyncNodeStore::level_len(&self.levels, 0).await
+    }
+
+    pub async fn root_async(&self) -> Result<[u8; 32], MerkleError> {
+        if self.len_async().await? == 0 {
+            return Ok(self.zeros[self.depth]);
+        }
+        let top = AsyncNodeStore::levels(&self.levels).await? - 1;
+        let node = AsyncNodeStore::get(&self.levels, top, 0).await?;
+        Ok(pad_root(
+            &self.hasher,
+            &self.zeros,
+            node.unwrap_or(self.zeros[top]),
+            top,
+            self.depth,
+        ))
+    }
+
+    /// [`MerkleTree::try_get_proof`], awaiting each node read.
+    pub async fn get_proof_async(&self, index: usize) -> Result<Proof, MerkleError> {
+        let len = self.len_async().await?;
+        if index >= len {
+            return Err(MerkleError::IndexOutOfBounds { index, len });
+        }
+        let levels = AsyncNodeStore::levels(&self.levels).await?;
+        let mut siblings = Vec::with_capacity(self.depth);
+        for level in 0..levels - 1 {
+            let sibling = AsyncNodeStore::get(&self.levels, level, (index >> level) ^ 1).await?;
+            siblings.push(sibling.unwrap_or(self.zeros[level]));
+        }
+        siblings.extend_from_slice(&self.zeros[siblings.len()..self.depth]);
+        Ok(Proof {
+            index,
+            size: len,
+            siblings,
+        })
+    }
+
+    /// [`MerkleTree::try_append_leaf`], awaiting each node write.
+    pub async fn append_leaf_async(&mut self, leaf: [u8; 32]) -> Result<(), MerkleError> {
+        let index = self.len_async().await?;
+        if index >= capacity(self.depth) {
+            return Err(MerkleError::TreeFull {
+                capacity: capacity(self.depth),
+            });
+        }
+        AsyncNodeStore::put(&mut self.levels, 0, index, leaf).await?;
+        self.index_leaf(index, leaf);
+        let (mut level, mut index) = (0, index);
+        while AsyncNodeStore::level_len(&self.levels, level).await? > 1 {
+            let left = AsyncNodeStore::get(&self.levels, level, index & !1).await?;
+            let right = AsyncNodeStore::get(&self.levels, level, index | 1).await?;
+            let zero = self.zeros[level];
+            let parent = self
+                .hasher
+                .hash_nodes(left.unwrap_or(zero), right.unwrap_or(zero));
+            index /= 2;
+            level += 1;
+            AsyncNodeStore::put(&mut self.levels, level, index, parent).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs a blocking [`NodeStore`] on tokio's blocking thread pool, so a
+/// slow disk read stalls only the request waiting for it.
+#[cfg(feature = "tokio")]
+#[derive(Debug)]
+pub struct BlockingStore<S> {
+    store: std::sync::Arc<std::sync::RwLock<S>>,
+}
+
+#[cfg(feature = "tokio")]
+impl<S> BlockingStore<S> {
+    pub fn new(store: S) -> Self {
+        Self {
+            store: std::sync::Arc::new(std::sync::RwLock::new(store)),
+        }
+    }
+
+    fn run<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&std::sync::RwLock<S>) -> Result<T, MerkleError> + Send + 'static,
+    ) -> impl Future<Output = Result<T, MerkleError>> + Send
+    where
+        S: Send + Sync + 'static,
+    {
+        let store = self.store.clone();
+        async move {
+            tokio::task::spawn_blocking(move || f(&store))
+                .await
+                .map_err(|e| MerkleError::Storage(e.to_string()))?
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<S: NodeStore + Send + Sync + 'static> AsyncNodeStore for BlockingStore<S> {
+    fn levels(&self) -> impl Future<Output = Result<usize, MerkleError>> + Send {
+        self.run(|store| Ok(store.read().unwrap_or_else(|e| e.into_inner()).levels()))
+    }
+
+    fn level_len(&self, level: usize) -> impl Future<Output = Result<usize, MerkleError>> + Send {
+        self.run(move |store| {
+            Ok(store
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .level_len(level))
+        })
+    }
+
+    fn get(
+        &self,
+        level: usize,
+        index: usize,
+    ) -> impl Future<Output = Result<Option<[u8; 32]>, MerkleError>> + Send {
+        self.run(move |store| {
+            store
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .get(level, index)
+        })
+    }
+
+    fn put(
+        &mut self,
+        level: usize,
+        index: usize,
+        node: [u8; 32],
+    ) -> impl Future<Output = Result<(), MerkleError>> + Send {
+        self.run(move |store| {
+            store
+                .write()
+                .unwrap_or_else(|e| e.into_inner())
+                .put(level, index, node)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DomainSeparated, MemoryStore, Sha256Hasher};
+
+    #[tokio::test]
+    async fn test_async_matches_sync() {
+        let mut sync = MerkleTree::new(6);
+        let mut tree =
+            MerkleTree::with_async_store(6, DomainSeparated(Sha256Hasher), MemoryStore::new())
+                .unwrap();
+        assert_eq!(tree.root_async().await.unwrap(), sync.root());
+        for i in 0u8..11 {
+            sync.append_data(&[i]);
+            tree.append_leaf_async(sync.leaves()[i as usize])
+                .await
+                .unwrap();
+        }
+        assert_eq!(tree.root_async().await.unwrap(), sync.root());
+        for index in 0..11 {
+            assert_eq!(
+                tree.get_proof_async(index).await.unwrap(),
+                sync.get_proof(index)
+            );
+        }
+        assert!(tree.get_proof_async(11).await.is_err());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_blocking_store() {
+        let mut tree = MerkleTree::with_async_store(
+            3,
+            DomainSeparated(Sha256Hasher),
+            BlockingStore::new(MemoryStore::new()),
+        )
+        .unwrap();
+        let mut sync = MerkleTree::new(3);
+        for i in 0u8..8 {
+            sync.append_data(&[i]);
+            tree.append_leaf_async(sync.leaves()[i as usize])
+                .await
+                .unwrap();
+        }
+        assert_eq!(tree.get_proof_async(5).await.unwrap(), sync.get_proof(5));
+        assert_eq!(
+            tree.append_leaf_async([0; 32]).await,
+            Err(MerkleError::TreeFull { capacity: 8 })
+        );
+
+        // A panic while the lock is held poisons it but leaves the store
+        // usable.
+        let store = BlockingStore::new(MemoryStore::new());
+        let lock = store.store.clone();
+        std::thread::spawn(move || {
+            let _guard = lock.write().unwrap();
+            panic!("poison");
+        })
+        .join()
+        .unwrap_err();
+        assert!(store.store.is_poisoned());
+        assert_eq!(store.levels().await, Ok(0));
+    }
+}
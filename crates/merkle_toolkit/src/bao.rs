@@ -0,0 +1,295 @@
+use crate::{
+    height, zero_hashes_with, DomainSeparated, FileTree, MerkleError, NodeHasher, Sha256Hasher,
+};
+
+// Encodings follow Bao's layout over a `FileTree`: the file length as a
+// big-endian `u64`, then the tree in pre-order. Each parent is written as
+// its two children (64 bytes, the empty-subtree hash standing in for a
+// missing right child) and each leaf as its chunk. The outboard form leaves
+// the chunks out so it can be kept next to a file that is already on disk.
+//
+// Walking in pre-order means every parent arrives before the data below
+// it, so a receiver holding only the root can check each chunk the moment
+// it is complete.
+
+impl<H: NodeHasher> FileTree<H> {
+    /// The combined encoding of `data`, which must be the file this tree
+    /// was built from.
+    pub fn encode(&self, data: &[u8]) -> Result<Vec<u8>, MerkleError> {
+        if data.len() as u64 != self.file_len() {
+            return Err(MerkleError::InvalidEncoding(
+                "data does not match the file length",
+            ));
+        }
+        let mut out = Vec::with_capacity(8 + data.len() + 64 * self.chunk_count());
+        self.write_pre_order(&mut out, Some(data));
+        Ok(out)
+    }
+
+    /// The outboard encoding: header and parents only.
+    pub fn outboard(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(8 + 64 * self.chunk_count());
+        self.write_pre_order(&mut out, None);
+        out
+    }
+
+    fn write_pre_order(&self, out: &mut Vec<u8>, data: Option<&[u8]>) {
+        out.extend_from_slice(&self.file_len().to_be_bytes());
+        if self.chunk_count() == 0 {
+            return;
+        }
+        let (levels, zeros) = (&self.tree().levels, &self.tree().zeros);
+        let mut stack = vec![(height(self.chunk_count()), 0)];
+        while let Some((level, index)) = stack.pop() {
+            if level == 0 {
+                if let Some(data) = data {
+                    let start = index * self.chunk_size();
+                    out.extend_from_slice(&data[start..data.len().min(start + self.chunk_size())]);
+                }
+                continue;
+            }
+            let children = &levels[level - 1];
+            out.extend_from_slice(&children[2 * index]);
+            out.extend_from_slice(children.get(2 * index + 1).unwrap_or(&zeros[level - 1]));
+            if 2 * index + 1 < children.len() {
+                stack.push((level - 1, 2 * index + 1));
+            }
+            stack.push((level - 1, 2 * index));
+        }
+    }
+}
+
+/// Checks a Bao-style stream against a trusted root as it arrives, handing
+/// back each chunk once it is verified.
+///
+/// Feed it the combined encoding in pieces of any size; bytes are only
+/// released after the chunk they belong to hashed to the leaf its verified
+/// parents expect. With [`StreamVerifier::with_outboard`] it is fed the raw
+/// file instead and takes the parents from the outboard encoding.
+#[derive(Debug, Clone)]
+pub struct StreamVerifier<H = DomainSeparated> {
+    hasher: H,
+    chunk_size: usize,
+    root: [u8; 32],
+    file_len: Option<u64>,
+    /// Nodes still to be checked, next on top: `(level, index, hash)`.
+    pending: Vec<(usize, usize, [u8; 32])>,
+    /// Empty-subtree hashes, filled in once the file length is known.
+    zeros: Vec<[u8; 32]>,
+    buffer: Vec<u8>,
+    outboard: Option<(Vec<u8>, usize)>,
+}
+
+impl StreamVerifier {
+    pub fn new(root: [u8; 32], chunk_size: usize) -> Self {
+        Self::with_hasher(DomainSeparated(Sha256Hasher), root, chunk_size)
+    }
+}
+
+impl<H: NodeHasher> StreamVerifier<H> {
+    pub fn with_hasher(hasher: H, root: [u8; 32], chunk_size: usize) -> Self {
+        assert!(chunk_size > 0, "chunk size must be non-zero");
+        Self {
+            hasher,
+            chunk_size,
+            root,
+            file_len: None,
+            pending: Vec::new(),
+            zeros: Vec::new(),
+            buffer: Vec::new(),
+            outboard: None,
+        }
+    }
+
+    /// A verifier for raw file bytes, checked against the parents in
+    /// `outboard`.
+    pub fn with_outboard(
+        hasher: H,
+        root: [u8; 32],
+        chunk_size: usize,
+        outboard: Vec<u8>,
+    ) -> Result<Self, MerkleError> {
+        let mut verifier = Self::with_hasher(hasher, root, chunk_size);
+        let header = outboard.get(..8).ok_or(MerkleError::InvalidEncoding(
+            "outboard is missing its header",
+        ))?;
+        verifier.start(u64::from_be_bytes(header.try_into().unwrap()))?;
+        verifier.outboard = Some((outboard, 8));
+        Ok(verifier)
+    }
+
+    /// The file length from the header, once it has arrived.
+    pub fn file_len(&self) -> Option<u64> {
+        self.file_len
+    }
+
+    /// Whether every chunk has been verified.
+    pub fn is_complete(&self) -> bool {
+        self.file_len.is_some() && self.pending.is_empty()
+    }
+
+    /// Takes the next part of the stream and returns the file bytes it
+    /// completed and verified. Fails on the first byte that doesn't match.
+    pub fn feed(&mut self, bytes: &[u8]) -> Result<Vec<u8>, MerkleError> {
+        self.buffer.extend_from_slice(bytes);
+        if self.file_len.is_none() {
+            if self.buffer.len() < 8 {
+                return Ok(Vec::new());
+            }
+            let header: Vec<u8> = self.buffer.drain(..8).collect();
+            self.start(u64::from_be_bytes(header.try_into().unwrap()))?;
+        }
+
+        let mut verified = Vec::new();
+        let mut consumed = 0;
+        while let Some(&(level, index, hash)) = self.pending.last() {
+            if level == 0 {
+                let len = self.chunk_len(index);
+                let Some(chunk) = self.buffer.get(consumed..consumed + len) else {
+                    break;
+                };
+                if self.hasher.hash_leaf(chunk) != hash {
+                    return Err(MerkleError::InvalidProof);
+                }
+                verified.extend_from_slice(chunk);
+                consumed += len;
+                self.pending.pop();
+                continue;
+            }
+            let parent = match &mut self.outboard {
+                Some((outboard, position)) => {
+                    let parent = outboard
+                        .get(*position..*position + 64)
+                        .ok_or(MerkleError::InvalidEncoding("outboard is truncated"))?;
+                    *position += 64;
+                    parent
+                }
+                None => match self.buffer.get(consumed..consumed + 64) {
+                    Some(parent) => {
+                        consumed += 64;
+                        parent
+                    }
+                    None => break,
+                },
+            };
+            let left: [u8; 32] = parent[..32].try_into().unwrap();
+            let right: [u8; 32] = parent[32..].try_into().unwrap();
+            if self.hasher.hash_nodes(left, right) != hash {
+                return Err(MerkleError::InvalidProof);
+            }
+            self.pending.pop();
+            let chunks = self.chunk_count();
+            if (2 * index + 1) << (level - 1) < chunks {
+                self.pending.push((level - 1, 2 * index + 1, right));
+            } else if right != self.zeros[level - 1] {
+                return Err(MerkleError::InvalidProof);
+            }
+            self.pending.push((level - 1, 2 * index, left));
+        }
+        self.buffer.drain(..consumed);
+        Ok(verified)
+    }
+
+    /// Ends the stream, failing if it stopped early or ran on.
+    pub fn finish(self) -> Result<(), MerkleError> {
+        if !self.is_complete() || !self.buffer.is_empty() {
+            return Err(MerkleError::InvalidEncoding(
+                "stream is incomplete or too long",
+            ));
+        }
+        Ok(())
+    }
+
+    fn start(&mut self, file_len: u64) -> Result<(), MerkleError> {
+        self.file_len = Some(file_len);
+        let chunks = self.chunk_count();
+        if chunks == 0 {
+            return match self.root == [0u8; 32] {
+                true => Ok(()),
+                false => Err(MerkleError::InvalidProof),
+            };
+        }
+        self.zeros = zero_hashes_with(&self.hasher, height(chunks));
+        self.pending.push((height(chunks), 0, self.root));
+        Ok(())
+    }
+
+    fn chunk_count(&self) -> usize {
+        self.file_len.unwrap_or(0).div_ceil(self.chunk_size as u64) as usize
+    }
+
+    fn chunk_len(&self, index: usize) -> usize {
+        let start = index as u64 * self.chunk_size as u64;
+        (self.file_len.unwrap_or(0) - start).min(self.chunk_size as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FileHasher;
+
+    #[test]
+    fn test_streams_verify_incrementally() {
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i % 253) as u8).collect();
+        let file = FileHasher::new(1024).hash_reader(&data[..]).unwrap();
+        let encoded = file.encode(&data).unwrap();
+        // Ten chunks under eleven parents.
+        assert_eq!(encoded.len(), 8 + data.len() + 64 * 11);
+        assert_eq!(file.outboard().len(), 8 + 64 * 11);
+
+        // Small pieces release verified bytes well before the end.
+        let mut verifier = StreamVerifier::new(file.root(), 1024);
+        let mut received = Vec::new();
+        for piece in encoded.chunks(100) {
+            received.extend(verifier.feed(piece).unwrap());
+            if received.len() == 1024 {
+                assert!(!verifier.is_complete());
+            }
+        }
+        assert_eq!(received, data);
+        verifier.finish().unwrap();
+
+        let mut outboard = StreamVerifier::with_outboard(
+            DomainSeparated(Sha256Hasher),
+            file.root(),
+            1024,
+            file.outboard(),
+        )
+        .unwrap();
+        assert_eq!(outboard.feed(&data[..3000]).unwrap(), &data[..2048]);
+        assert_eq!(outboard.feed(&data[3000..]).unwrap(), &data[2048..]);
+        outboard.finish().unwrap();
+    }
+
+    #[test]
+    fn test_rejects_corruption() {
+        let data = vec![7u8; 5000];
+        let file = FileHasher::new(1024).hash_reader(&data[..]).unwrap();
+        let encoded = file.encode(&data).unwrap();
+
+        // Flipping a byte of the last chunk still releases the chunks before it.
+        let mut corrupt = encoded.clone();
+        *corrupt.last_mut().unwrap() ^= 1;
+        let mut verifier = StreamVerifier::new(file.root(), 1024);
+        assert_eq!(
+            verifier.feed(&corrupt[..encoded.len() - 1]).unwrap().len(),
+            4096
+        );
+        assert_eq!(
+            verifier.feed(&corrupt[encoded.len() - 1..]),
+            Err(MerkleError::InvalidProof)
+        );
+
+        // A wrong length changes the tree shape the root commits to.
+        let mut longer = encoded.clone();
+        longer[6] += 0x10;
+        assert!(StreamVerifier::new(file.root(), 1024)
+            .feed(&longer)
+            .is_err());
+
+        let mut truncated = StreamVerifier::new(file.root(), 1024);
+        truncated.feed(&encoded[..encoded.len() - 1]).unwrap();
+        assert!(truncated.finish().is_err());
+    }
+}
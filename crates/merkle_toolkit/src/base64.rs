@@ -0,0 +1,104 @@
+//! Standard, padded base64 (RFC 4648), as used by the Certificate
+//! Transparency JSON API.
+
+use crate::MerkleError;
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+            group | u32::from(byte) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => out.push(ALPHABET[((group >> (18 - 6 * i)) & 0x3f) as usize] as char),
+                false => out.push('='),
+            }
+        }
+    }
+    out
+}
+
+/// Decodes padded base64, rejecting stray bits after the last byte so every
+/// input has a single encoding.
+pub fn decode(base64: &str) -> Result<Vec<u8>, MerkleError> {
+    let base64 = base64.as_bytes();
+    if base64.len() % 4 != 0 {
+        return Err(MerkleError::InvalidEncoding(
+            "base64 length is not a multiple of 4",
+        ));
+    }
+    let mut out = Vec::with_capacity(base64.len() / 4 * 3);
+    let groups = base64.len() / 4;
+    for (n, quad) in base64.chunks_exact(4).enumerate() {
+        let padding = match (quad[2], quad[3]) {
+            (b'=', b'=') => 2,
+            (_, b'=') => 1,
+            _ => 0,
+        };
+        if padding > 0 && n + 1 != groups {
+            return Err(MerkleError::InvalidEncoding(
+                "base64 padding before the end",
+            ));
+        }
+        let group = quad[..4 - padding]
+            .iter()
+            .enumerate()
+            .try_fold(0u32, |group, (i, &c)| {
+                Ok::<_, MerkleError>(group | digit(c)? << (18 - 6 * i))
+            })?;
+        if group & (0xffff >> (16 - 8 * padding)) != 0 {
+            return Err(MerkleError::InvalidEncoding("non-canonical base64"));
+        }
+        out.extend_from_slice(&group.to_be_bytes()[1..4 - padding]);
+    }
+    Ok(out)
+}
+
+/// Decodes exactly 32 bytes of base64.
+pub fn decode_hash(base64: &str) -> Result<[u8; 32], MerkleError> {
+    decode(base64)?
+        .try_into()
+        .map_err(|_| MerkleError::InvalidEncoding("expected 32 bytes of base64"))
+}
+
+fn digit(c: u8) -> Result<u32, MerkleError> {
+    match c {
+        b'A'..=b'Z' => Ok(u32::from(c - b'A')),
+        b'a'..=b'z' => Ok(u32::from(c - b'a') + 26),
+        b'0'..=b'9' => Ok(u32::from(c - b'0') + 52),
+        b'+' => Ok(62),
+        b'/' => Ok(63),
+        _ => Err(MerkleError::InvalidEncoding("invalid base64 digit")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64_vectors() {
+        let vectors = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (bytes, base64) in vectors {
+            assert_eq!(encode(bytes.as_bytes()), base64);
+            assert_eq!(decode(base64).unwrap(), bytes.as_bytes());
+        }
+        assert_eq!(decode("+/+/").unwrap(), [0xfb, 0xff, 0xbf]);
+        assert!(decode("Zg=").is_err());
+        assert!(decode("Zh==").is_err());
+        assert!(decode("Zg==Zg==").is_err());
+        assert!(decode("Z!==").is_err());
+        assert!(decode_hash("Zg==").is_err());
+    }
+}
@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+
+use crate::{MerkleTree, NodeHasher};
+
+/// A leaf, its proof and its index, as passed to [`MerkleTree::verify_proofs`].
+pub type ProofItem<'a> = ([u8; 32], &'a [[u8; 32]], usize);
+
+/// Number of items each rayon task verifies with its own node cache.
+#[cfg(feature = "rayon")]
+const PAR_CHUNK_SIZE: usize = 1024;
+
+impl MerkleTree {
+    pub fn verify_proofs(items: &[ProofItem<'_>], root: [u8; 32]) -> Vec<bool> {
+        Self::verify_proofs_with(&crate::DomainSeparated(crate::Sha256Hasher), items, root)
+    }
+}
+
+impl<H: NodeHasher> MerkleTree<H> {
+    /// Verifies many proofs against the same root, returning one result per
+    /// item.
+    ///
+    /// Nodes on the path of an already verified proof are remembered, so a
+    /// later proof stops hashing as soon as it reaches one of them.
+    pub fn verify_proofs_with(hasher: &H, items: &[ProofItem<'_>], root: [u8; 32]) -> Vec<bool> {
+        span!(DEBUG, "merkle.verify_batch", proofs = items.len());
+        let mut verified: HashMap<(usize, usize, usize), [u8; 32]> = HashMap::new();
+        let mut path = Vec::new();
+        items
+            .iter()
+            .map(|&(leaf, proof, index)| {
+                path.clear();
+                let mut node = leaf;
+                let mut idx = index;
+                for (level, sibling) in proof.iter().enumerate() {
+                    let key = (proof.len(), level, idx);
+                    if let Some(known) = verified.get(&key) {
+                        return *known == node;
+                    }
+                    path.push((key, node));
+                    node = if idx % 2 == 0 {
+                        hasher.hash_nodes(node, *sibling)
+                    } else {
+                        hasher.hash_nodes(*sibling, node)
+                    };
+                    idx /= 2;
+                }
+                let valid = node == root;
+                if valid {
+                    verified.extend(path.drain(..));
+                }
+                valid
+            })
+            .collect()
+    }
+
+    /// Parallel [`MerkleTree::verify_proofs_with`], splitting `items` into
+    /// chunks verified on the rayon thread pool.
+    #[cfg(feature = "rayon")]
+    pub fn verify_proofs_par_with(hasher: &H, items: &[ProofItem<'_>], root: [u8; 32]) -> Vec<bool>
+    where
+        H: Sync,
+    {
+        use rayon::prelude::*;
+
+        items
+            .par_chunks(PAR_CHUNK_SIZE)
+            .flat_map_iter(|chunk| Self::verify_proofs_with(hasher, chunk, root))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_proofs_matches_individual_verification() {
+        let mut tree = MerkleTree::new(4);
+        for i in 0u8..13 {
+            tree.append_data(&[i]);
+        }
+        let root = tree.root();
+        let proofs: Vec<_> = (0..13).map(|i| tree.get_proof(i)).collect();
+
+        let mut items: Vec<ProofItem<'_>> = (0..13)
+            .map(|i| (tree.leaves()[i], proofs[i].siblings.as_slice(), i))
+            .collect();
+        // A wrong leaf, a wrong index, and a repeat of a valid item.
+        items.push(([9u8; 32], proofs[2].siblings.as_slice(), 2));
+        items.push((tree.leaves()[3], proofs[3].siblings.as_slice(), 4));
+        items.push(items[5]);
+
+        let results = MerkleTree::verify_proofs(&items, root);
+        let expected: Vec<_> = items
+            .iter()
+            .map(|&(leaf, proof, index)| MerkleTree::verify_proof(leaf, proof, index, root))
+            .collect();
+        assert_eq!(results, expected);
+        assert_eq!(results.iter().filter(|valid| !**valid).count(), 2);
+
+        #[cfg(feature = "rayon")]
+        assert_eq!(
+            MerkleTree::verify_proofs_par_with(tree.hasher(), &items, root),
+            results
+        );
+    }
+}
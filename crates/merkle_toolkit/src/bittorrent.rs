@@ -0,0 +1,215 @@
+//! BitTorrent v2 file hashing as specified in BEP 52.
+//!
+//! A file is split into 16 KiB blocks whose SHA-256 hashes are the leaves of
+//! a plain SHA-256 tree. The leaves are padded with zero hashes up to the
+//! next power of two, so the tree is always complete, and its root is the
+//! file's `pieces root`. The layer whose nodes each cover one piece is the
+//! file's entry in the torrent's `piece layers`.
+
+use std::io::{self, Read};
+
+use crate::{MerkleError, MerkleTree, NodeHasher, Proof, Sha256Hasher, MAX_DEPTH};
+
+/// Size of the blocks hashed into leaves.
+pub const BLOCK_SIZE: usize = 16 * 1024;
+
+/// The merkle tree of one file.
+#[derive(Debug, Clone)]
+pub struct FileTree {
+    tree: MerkleTree<Sha256Hasher>,
+    /// Number of real blocks, before padding.
+    blocks: usize,
+}
+
+impl FileTree {
+    pub fn from_bytes(data: &[u8]) -> Result<Self, MerkleError> {
+        Self::from_block_hashes(data.chunks(BLOCK_SIZE).map(hash_block).collect())
+    }
+
+    /// Hashes the file read from `reader` one block at a time.
+    pub fn from_reader(mut reader: impl Read) -> io::Result<Self> {
+        let mut hashes = Vec::new();
+        let mut block = vec![0u8; BLOCK_SIZE];
+        loop {
+            let mut filled = 0;
+            while filled < BLOCK_SIZE {
+                match reader.read(&mut block[filled..])? {
+                    0 => break,
+                    read => filled += read,
+                }
+            }
+            if filled == 0 {
+                break;
+            }
+            hashes.push(hash_block(&block[..filled]));
+            if filled < BLOCK_SIZE {
+                break;
+            }
+        }
+        Self::from_block_hashes(hashes).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+    }
+
+    /// Builds the tree from the hashes of the file's blocks.
+    pub fn from_block_hashes(mut hashes: Vec<[u8; 32]>) -> Result<Self, MerkleError> {
+        let blocks = hashes.len();
+        let depth = blocks.next_power_of_two().trailing_zeros() as usize;
+        if depth > MAX_DEPTH {
+            return Err(MerkleError::DepthTooLarge {
+                depth,
+                max: MAX_DEPTH,
+            });
+        }
+        hashes.resize(blocks.next_power_of_two(), [0u8; 32]);
+        Ok(Self {
+            tree: MerkleTree::from_leaves(depth, Sha256Hasher, hashes)?,
+            blocks,
+        })
+    }
+
+    /// The `pieces root`, or `None` for an empty file, which has none.
+    pub fn pieces_root(&self) -> Option<[u8; 32]> {
+        (self.blocks > 0).then(|| self.tree.root())
+    }
+
+    /// Number of 16 KiB blocks in the file.
+    pub fn blocks(&self) -> usize {
+        self.blocks
+    }
+
+    /// Hashes of each `piece_length` piece, the last padded like the tree.
+    /// BEP 52 only stores this layer for files larger than one piece; for
+    /// smaller files it is empty.
+    pub fn piece_layer(&self, piece_length: usize) -> Result<Vec<[u8; 32]>, MerkleError> {
+        let level = piece_level(piece_length)?;
+        let Some(nodes) = self
+            .tree
+            .levels
+            .get(level)
+            .filter(|_| self.blocks > 1 << level)
+        else {
+            return Ok(Vec::new());
+        };
+        Ok(nodes[..self.blocks.div_ceil(1 << level)].to_vec())
+    }
+
+    /// Proof for the hash of piece `index` up to the pieces root.
+    pub fn piece_proof(&self, index: usize, piece_length: usize) -> Result<Proof, MerkleError> {
+        let layer = self.piece_layer(piece_length)?;
+        if index >= layer.len() {
+            return Err(MerkleError::IndexOutOfBounds {
+                index,
+                len: layer.len(),
+            });
+        }
+        let level = piece_level(piece_length)?;
+        let siblings = (level..self.tree.levels.len() - 1)
+            .map(|at| self.tree.levels[at][(index >> (at - level)) ^ 1])
+            .collect();
+        Ok(Proof {
+            index,
+            size: layer.len(),
+            siblings,
+        })
+    }
+}
+
+/// Hash of one piece of `piece_length` bytes, with missing blocks of a short
+/// last piece padded with zero hashes.
+pub fn piece_hash(piece: &[u8], piece_length: usize) -> Result<[u8; 32], MerkleError> {
+    let level = piece_level(piece_length)?;
+    if piece.len() > piece_length {
+        return Err(MerkleError::InvalidEncoding(
+            "piece is longer than the piece length",
+        ));
+    }
+    let mut hashes: Vec<_> = piece.chunks(BLOCK_SIZE).map(hash_block).collect();
+    hashes.resize(1 << level, [0u8; 32]);
+    Ok(MerkleTree::from_leaves(level, Sha256Hasher, hashes)?.root())
+}
+
+/// Checks piece data against a file's pieces root with a proof from
+/// [`FileTree::piece_proof`].
+pub fn verify_piece(
+    piece: &[u8],
+    piece_length: usize,
+    proof: &Proof,
+    pieces_root: [u8; 32],
+) -> bool {
+    piece_hash(piece, piece_length)
+        .is_ok_and(|hash| proof.verify_with(&Sha256Hasher, hash, pieces_root))
+}
+
+fn hash_block(block: &[u8]) -> [u8; 32] {
+    Sha256Hasher.hash(&[block])
+}
+
+/// Tree level whose nodes cover one piece. Piece lengths are powers of two
+/// of at least one block.
+fn piece_level(piece_length: usize) -> Result<usize, MerkleError> {
+    if piece_length < BLOCK_SIZE || !piece_length.is_power_of_two() {
+        return Err(MerkleError::InvalidEncoding(
+            "piece length must be a power of two of at least 16 KiB",
+        ));
+    }
+    Ok((piece_length / BLOCK_SIZE).trailing_zeros() as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hex;
+
+    #[test]
+    fn test_pieces_root_and_layer() {
+        let data: Vec<u8> = (0..5 * BLOCK_SIZE + 1000)
+            .map(|i| ((i * 7 + 3) % 251) as u8)
+            .collect();
+        let file = FileTree::from_bytes(&data).unwrap();
+        assert_eq!(file.blocks(), 6);
+        assert_eq!(
+            hex::encode(&file.pieces_root().unwrap()),
+            "d27d7006d3259a98016c6af74865e759bfbb97f788ee2d7d3072a44b8986ccf6"
+        );
+        assert_eq!(
+            file.piece_layer(2 * BLOCK_SIZE)
+                .unwrap()
+                .iter()
+                .map(|node| hex::encode(node))
+                .collect::<Vec<_>>(),
+            [
+                "6b7702ec93fd7bde4fccf7123b679a0703843fe9d838e7bc9ac4eee2c26e3923",
+                "aa998194bca61ba064c9766803e08a07ae1db27d8dec9a3026c5fb683276a939",
+                "ab6cf01d1ef192629eb4c967c5bc5b9d8ae26b4cf52429ecdd32d6e5d7e9fe8d",
+            ]
+        );
+        assert_eq!(
+            FileTree::from_reader(&data[..]).unwrap().pieces_root(),
+            file.pieces_root()
+        );
+
+        // A file within one block is its own root and has no piece layer.
+        let small = FileTree::from_bytes(b"hello").unwrap();
+        assert_eq!(
+            hex::encode(&small.pieces_root().unwrap()),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        assert!(small.piece_layer(BLOCK_SIZE).unwrap().is_empty());
+        assert_eq!(FileTree::from_bytes(&[]).unwrap().pieces_root(), None);
+        assert!(file.piece_layer(3 * BLOCK_SIZE).is_err());
+    }
+
+    #[test]
+    fn test_piece_proofs() {
+        let data: Vec<u8> = (0..11 * BLOCK_SIZE + 5).map(|i| (i % 256) as u8).collect();
+        let file = FileTree::from_bytes(&data).unwrap();
+        let root = file.pieces_root().unwrap();
+        let piece_length = 4 * BLOCK_SIZE;
+        for (index, piece) in data.chunks(piece_length).enumerate() {
+            let proof = file.piece_proof(index, piece_length).unwrap();
+            assert_eq!(proof.len(), 2);
+            assert!(verify_piece(piece, piece_length, &proof, root));
+            assert!(!verify_piece(&piece[1..], piece_length, &proof, root));
+        }
+        assert!(file.piece_proof(3, piece_length).is_err());
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use crate::{MerkleError, NodeStore};
+
+/// Hit and miss counts of a [`CachedStore`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Nodes currently cached.
+    pub entries: usize,
+    pub capacity: usize,
+}
+
+impl CacheStats {
+    /// Fraction of reads served from the cache, zero before any read.
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            reads => self.hits as f64 / reads as f64,
+        }
+    }
+}
+
+/// Wraps a [`NodeStore`] with a least-recently-used cache of up to
+/// `capacity` nodes.
+///
+/// Proofs for recent leaves, and appends, keep touching the same paths, so
+/// with a disk-backed store most of their reads are served from memory
+/// instead of costing one read per level. Writes go through to the inner
+/// store and refresh the cached copy.
+pub struct CachedStore<S> {
+    inner: S,
+    cache: Mutex<Lru>,
+}
+
+#[derive(Default)]
+struct Lru {
+    capacity: usize,
+    /// Node and last use of each cached position.
+    nodes: HashMap<(usize, usize), ([u8; 32], u64)>,
+    /// Cached positions by last use, oldest first.
+    order: BTreeMap<u64, (usize, usize)>,
+    tick: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl Lru {
+    fn get(&mut self, key: (usize, usize)) -> Option<[u8; 32]> {
+        let tick = self.tick + 1;
+        let (node, used) = self.nodes.get_mut(&key)?;
+        self.order.remove(used);
+        self.order.insert(tick, key);
+        *used = tick;
+        self.tick = tick;
+        Some(*node)
+    }
+
+    fn insert(&mut self, key: (usize, usize), node: [u8; 32]) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.tick += 1;
+        if let Some((_, used)) = self.nodes.insert(key, (node, self.tick)) {
+            self.order.remove(&used);
+        }
+        self.order.insert(self.tick, key);
+        if self.nodes.len() > self.capacity {
+            let (_, oldest) = self.order.pop_first().unwrap();
+            self.nodes.remove(&oldest);
+        }
+    }
+}
+
+impl<S: NodeStore> CachedStore<S> {
+    pub fn new(inner: S, capacity: usize) -> Self {
+        Self {
+            inner,
+            cache: Mutex::new(Lru {
+                capacity,
+                ..Lru::default()
+            }),
+        }
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    pub fn cache_stats(&self) -> CacheStats {
+        let cache = self.cache.lock().unwrap();
+        CacheStats {
+            hits: cache.hits,
+            misses: cache.misses,
+            entries: cache.nodes.len(),
+            capacity: cache.capacity,
+        }
+    }
+
+    /// Resets the hit and miss counts, keeping the cached nodes.
+    pub fn reset_stats(&self) {
+        let mut cache = self.cache.lock().unwrap();
+        cache.hits = 0;
+        cache.misses = 0;
+    }
+}
+
+impl<S: NodeStore> NodeStore for CachedStore<S> {
+    fn levels(&self) -> usize {
+        self.inner.levels()
+    }
+
+    fn level_len(&self, level: usize) -> usize {
+        self.inner.level_len(level)
+    }
+
+    fn get(&self, level: usize, index: usize) -> Result<Option<[u8; 32]>, MerkleError> {
+        // Levels are dense, so there is nothing to look up past their end.
+        if index >= self.inner.level_len(level) {
+            return Ok(None);
+        }
+        let mut cache = self.cache.lock().unwrap();
+        if let Some(node) = cache.get((level, index)) {
+            cache.hits += 1;
+            return Ok(Some(node));
+        }
+        cache.misses += 1;
+        let node = self.inner.get(level, index)?;
+        if let Some(node) = node {
+            cache.insert((level, index), node);
+        }
+        Ok(node)
+    }
+
+    fn put(&mut self, level: usize, index: usize, node: [u8; 32]) -> Result<(), MerkleError> {
+        self.inner.put(level, index, node)?;
+        self.cache.get_mut().unwrap().insert((level, index), node);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DomainSeparated, MemoryStore, MerkleTree, Sha256Hasher};
+
+    #[test]
+    fn test_cached_store_serves_hot_paths() {
+        let store = CachedStore::new(MemoryStore::new(), 64);
+        let mut cached = MerkleTree::with_store(6, DomainSeparated(Sha256Hasher), store).unwrap();
+        let mut memory = MerkleTree::new(6);
+        for i in 0u8..20 {
+            cached.append_data(&[i]);
+            memory.append_data(&[i]);
+        }
+        assert_eq!(cached.root(), memory.root());
+        cached.store().reset_stats();
+        assert_eq!(cached.get_proof(19), memory.get_proof(19));
+        let stats = cached.store().cache_stats();
+        assert_eq!((stats.hits, stats.misses), (3, 0));
+        assert_eq!(stats.hit_rate(), 1.0);
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let mut store = CachedStore::new(MemoryStore::new(), 2);
+        for index in 0..3 {
+            store.put(0, index, [index as u8; 32]).unwrap();
+        }
+        assert_eq!(store.cache_stats().entries, 2);
+        assert_eq!(store.get(0, 0).unwrap(), Some([0; 32]));
+        assert_eq!(store.get(0, 2).unwrap(), Some([2; 32]));
+        assert_eq!(store.get(0, 0).unwrap(), Some([0; 32]));
+        let stats = store.cache_stats();
+        assert_eq!((stats.hits, stats.misses), (2, 1));
+        // Reading 0 back in evicted 1, the least recently used.
+        store.get(0, 1).unwrap();
+        assert_eq!(store.cache_stats().misses, 2);
+    }
+}
@@ -0,0 +1,146 @@
+use crate::{DomainSeparated, MerkleError, MerkleTree, NodeHasher, Proof, Sha256Hasher};
+
+/// The top levels of a tree below the root, held by a verifier so proofs
+/// can stop short of them.
+///
+/// `nodes` are ordered level by level from just below the root down, as in
+/// spl-account-compression: the node at generalized index `g` (the root is
+/// 1, the children of `g` are `2g` and `2g + 1`) is `nodes[g - 2]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+pub struct Canopy {
+    /// Number of leaves in the tree the canopy was taken from.
+    pub size: usize,
+    /// Depth of that tree.
+    pub depth: usize,
+    /// Number of levels held.
+    pub levels: usize,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::vec"))]
+    pub nodes: Vec<[u8; 32]>,
+}
+
+impl Canopy {
+    /// Verifies a truncated proof with the default [`MerkleTree`] hasher.
+    pub fn verify(&self, leaf: [u8; 32], proof: &Proof, root: [u8; 32]) -> bool {
+        self.verify_with(&DomainSeparated(Sha256Hasher), leaf, proof, root)
+    }
+
+    /// Verifies a proof from [`MerkleTree::get_truncated_proof`], taking the
+    /// missing top siblings from the canopy.
+    pub fn verify_with<H: NodeHasher>(
+        &self,
+        hasher: &H,
+        leaf: [u8; 32],
+        proof: &Proof,
+        root: [u8; 32],
+    ) -> bool {
+        let height = self.depth;
+        if proof.size != self.size
+            || proof.index >= proof.size
+            || proof.len() + self.levels != height
+            || self.nodes.len() != canopy_len(self.levels)
+        {
+            return false;
+        }
+        let mut index = proof.index;
+        let mut computed_hash = leaf;
+        for level in 0..height {
+            let sibling = match proof.get(level) {
+                Some(sibling) => *sibling,
+                None => self.nodes[(1 << (height - level)) + (index ^ 1) - 2],
+            };
+            computed_hash = if index % 2 == 0 {
+                hasher.hash_nodes(computed_hash, sibling)
+            } else {
+                hasher.hash_nodes(sibling, computed_hash)
+            };
+            index /= 2;
+        }
+        computed_hash == root
+    }
+}
+
+impl<H: NodeHasher> MerkleTree<H> {
+    /// The top `levels` levels below the root.
+    pub fn canopy(&self, levels: usize) -> Result<Canopy, MerkleError> {
+        if levels > self.depth {
+            return Err(MerkleError::DepthTooLarge {
+                depth: levels,
+                max: self.depth,
+            });
+        }
+        let nodes = (1..=levels)
+            .flat_map(|depth| (0..1 << depth).map(move |index| (self.depth - depth, index)))
+            .map(|(level, index)| self.node(level, index))
+            .collect::<Result<_, _>>()?;
+        Ok(Canopy {
+            size: self.len(),
+            depth: self.depth,
+            levels,
+            nodes,
+        })
+    }
+
+    /// The proof for the leaf at `index` without the siblings covered by a
+    /// canopy of `canopy_levels` levels.
+    pub fn get_truncated_proof(
+        &self,
+        index: usize,
+        canopy_levels: usize,
+    ) -> Result<Proof, MerkleError> {
+        let mut proof = self.try_get_proof(index)?;
+        if canopy_levels > proof.len() {
+            return Err(MerkleError::DepthTooLarge {
+                depth: canopy_levels,
+                max: proof.len(),
+            });
+        }
+        proof.siblings.truncate(proof.len() - canopy_levels);
+        Ok(proof)
+    }
+}
+
+fn canopy_len(levels: usize) -> usize {
+    (2 << levels) - 2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncated_proofs_verify_with_canopy() {
+        let mut tree = MerkleTree::new(4);
+        for i in 0u8..11 {
+            tree.append_data(&[i]);
+        }
+        for levels in 0..=4 {
+            let canopy = tree.canopy(levels).unwrap();
+            assert_eq!(canopy.nodes.len(), canopy_len(levels));
+            for index in 0..tree.len() {
+                let proof = tree.get_truncated_proof(index, levels).unwrap();
+                assert_eq!(proof.len(), 4 - levels);
+                assert!(canopy.verify(tree.leaves()[index], &proof, tree.root()));
+                assert!(!canopy.verify([0xff; 32], &proof, tree.root()));
+            }
+        }
+        assert!(tree.canopy(5).is_err());
+
+        let full = tree.get_proof(3);
+        let canopy = tree.canopy(2).unwrap();
+        assert!(!canopy.verify(tree.leaves()[3], &full, tree.root()));
+
+        // The canopy of a sparse tree spans the padding above its leaves.
+        let mut deep = MerkleTree::new(6);
+        for i in 0u8..3 {
+            deep.append_data(&[i]);
+        }
+        let canopy = deep.canopy(5).unwrap();
+        let proof = deep.get_truncated_proof(2, 5).unwrap();
+        assert!(canopy.verify(deep.leaves()[2], &proof, deep.root()));
+    }
+}
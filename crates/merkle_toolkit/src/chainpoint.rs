@@ -0,0 +1,246 @@
+//! Export of inclusion proofs as Chainpoint v4 JSON.
+//!
+//! A Chainpoint proof starts from the proven hash and lists operations that
+//! turn it into the anchored value: `{"l": hex}` prepends bytes, `{"r": hex}`
+//! appends them and `{"op": "sha-256"}` hashes everything so far. Each tree
+//! level becomes a sibling concatenation plus a hash, with the node prefix
+//! prepended for [`DomainSeparated`] hashers, so any Chainpoint verifier can
+//! replay the path to the root.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    hex, DomainSeparated, DoubleSha256Hasher, NodeHasher, Proof, Sha256Hasher, NODE_PREFIX,
+};
+
+/// JSON-LD context of Chainpoint v4 proofs.
+pub const CONTEXT: &str = "https://w3id.org/chainpoint/v4";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainpointProof {
+    #[serde(rename = "@context")]
+    pub context: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// The proven leaf, hex encoded.
+    pub hash: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proof_id: Option<String>,
+    /// When the hash was submitted, as an ISO 8601 timestamp.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash_received: Option<String>,
+    pub branches: Vec<Branch>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Branch {
+    pub label: String,
+    pub ops: Vec<Op>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub branches: Vec<Branch>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Op {
+    /// Prepend these hex-encoded bytes.
+    Left {
+        l: String,
+    },
+    /// Append these hex-encoded bytes.
+    Right {
+        r: String,
+    },
+    /// Hash the value so far, e.g. with `sha-256` or `sha-256-x2`.
+    Hash {
+        op: String,
+    },
+    Anchors {
+        anchors: Vec<Anchor>,
+    },
+}
+
+/// Where the value at the end of a branch was published.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Anchor {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub anchor_id: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub uris: Vec<String>,
+}
+
+/// Hashers whose node hash can be written as Chainpoint operations.
+pub trait ChainpointHasher: NodeHasher {
+    /// Operations taking a node to its parent, given its sibling.
+    fn node_ops(&self, sibling: [u8; 32], sibling_is_left: bool) -> Vec<Op>;
+}
+
+fn concat(sibling: [u8; 32], sibling_is_left: bool) -> Op {
+    match sibling_is_left {
+        true => Op::Left {
+            l: hex::encode(&sibling),
+        },
+        false => Op::Right {
+            r: hex::encode(&sibling),
+        },
+    }
+}
+
+fn hash_op(name: &str) -> Op {
+    Op::Hash { op: name.into() }
+}
+
+impl ChainpointHasher for Sha256Hasher {
+    fn node_ops(&self, sibling: [u8; 32], sibling_is_left: bool) -> Vec<Op> {
+        vec![concat(sibling, sibling_is_left), hash_op("sha-256")]
+    }
+}
+
+impl ChainpointHasher for DoubleSha256Hasher {
+    fn node_ops(&self, sibling: [u8; 32], sibling_is_left: bool) -> Vec<Op> {
+        vec![concat(sibling, sibling_is_left), hash_op("sha-256-x2")]
+    }
+}
+
+impl<H: ChainpointHasher> ChainpointHasher for DomainSeparated<H> {
+    fn node_ops(&self, sibling: [u8; 32], sibling_is_left: bool) -> Vec<Op> {
+        let mut ops = self.0.node_ops(sibling, sibling_is_left);
+        ops.insert(
+            1,
+            Op::Left {
+                l: hex::encode(&[NODE_PREFIX]),
+            },
+        );
+        ops
+    }
+}
+
+impl ChainpointProof {
+    /// A proof of `leaf` whose single branch, labelled `label`, ends at the
+    /// tree root.
+    pub fn from_proof<H: ChainpointHasher>(
+        hasher: &H,
+        leaf: [u8; 32],
+        proof: &Proof,
+        label: &str,
+    ) -> Self {
+        let ops = proof
+            .iter()
+            .enumerate()
+            .flat_map(|(level, &sibling)| hasher.node_ops(sibling, (proof.index >> level) & 1 == 1))
+            .collect();
+        Self {
+            context: CONTEXT.into(),
+            kind: "Chainpoint".into(),
+            hash: hex::encode(&leaf),
+            proof_id: None,
+            hash_received: None,
+            branches: vec![Branch {
+                label: label.into(),
+                ops,
+                branches: Vec::new(),
+            }],
+        }
+    }
+
+    /// Ends the first branch with an anchor for the root.
+    pub fn with_anchor(mut self, anchor: Anchor) -> Self {
+        if let Some(branch) = self.branches.first_mut() {
+            branch.ops.push(Op::Anchors {
+                anchors: vec![anchor],
+            });
+        }
+        self
+    }
+
+    /// Replays the first branch, returning the value it ends at. `None` if
+    /// the proof is malformed or uses a hash this crate doesn't implement.
+    pub fn evaluate(&self) -> Option<[u8; 32]> {
+        let mut value = hex::decode(&self.hash).ok()?;
+        for op in &self.branches.first()?.ops {
+            match op {
+                Op::Left { l } => value.splice(..0, hex::decode(l).ok()?),
+                Op::Right { r } => value.splice(value.len().., hex::decode(r).ok()?),
+                Op::Hash { op } => {
+                    let hashed = match op.as_str() {
+                        "sha-256" => Sha256Hasher.hash(&[&value]),
+                        "sha-256-x2" => DoubleSha256Hasher.hash(&[&value]),
+                        _ => return None,
+                    };
+                    value = hashed.to_vec();
+                    continue;
+                }
+                Op::Anchors { .. } => continue,
+            };
+        }
+        value.try_into().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MerkleTree;
+
+    #[test]
+    fn test_ops_replay_to_root() {
+        let mut tree = MerkleTree::new(3);
+        for i in 0u8..5 {
+            tree.append_data(&[i]);
+        }
+        for index in 0..5 {
+            let proof = ChainpointProof::from_proof(
+                tree.hasher(),
+                tree.leaves()[index],
+                &tree.get_proof(index),
+                "merkle_toolkit",
+            );
+            assert_eq!(proof.evaluate(), Some(tree.root()));
+        }
+
+        let mut plain = MerkleTree::legacy(2);
+        plain.append_leaf([1; 32]);
+        plain.append_leaf([2; 32]);
+        let proof =
+            ChainpointProof::from_proof(&Sha256Hasher, [2; 32], &plain.get_proof(1), "legacy");
+        assert_eq!(proof.evaluate(), Some(plain.root()));
+    }
+
+    #[test]
+    fn test_json_schema() {
+        let proof = Proof {
+            index: 1,
+            size: 2,
+            siblings: vec![[0xaa; 32]],
+        };
+        let proof =
+            ChainpointProof::from_proof(&Sha256Hasher, [0xbb; 32], &proof, "cal_anchor_branch")
+                .with_anchor(Anchor {
+                    kind: "cal".into(),
+                    anchor_id: "1234".into(),
+                    uris: Vec::new(),
+                });
+        let json = serde_json::to_value(&proof).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "@context": "https://w3id.org/chainpoint/v4",
+                "type": "Chainpoint",
+                "hash": "bb".repeat(32),
+                "branches": [{
+                    "label": "cal_anchor_branch",
+                    "ops": [
+                        { "l": "aa".repeat(32) },
+                        { "op": "sha-256" },
+                        { "anchors": [{ "type": "cal", "anchor_id": "1234" }] },
+                    ],
+                }],
+            })
+        );
+        assert_eq!(
+            serde_json::from_value::<ChainpointProof>(json).unwrap(),
+            proof
+        );
+    }
+}
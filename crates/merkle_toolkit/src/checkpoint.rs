@@ -0,0 +1,165 @@
+use crate::{pad_root, MerkleError, MerkleTree, NodeHasher};
+
+/// Identifies a checkpoint saved by [`MerkleTree::checkpoint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CheckpointId(pub u64);
+
+/// The checkpoints of a tree, oldest first.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Checkpoints {
+    next: u64,
+    saved: Vec<(CheckpointId, Checkpoint)>,
+}
+
+/// A tree's size and root, with the last node of each cached level: the
+/// right edge of the tree, which is all that appends change.
+#[derive(Debug, Clone)]
+struct Checkpoint {
+    size: usize,
+    root: [u8; 32],
+    frontier: Vec<[u8; 32]>,
+}
+
+impl Checkpoints {
+    fn position(&self, id: CheckpointId) -> Result<usize, MerkleError> {
+        self.saved
+            .binary_search_by_key(&id, |&(id, _)| id)
+            .map_err(|_| MerkleError::UnknownCheckpoint { id: id.0 })
+    }
+}
+
+impl<H: NodeHasher> MerkleTree<H> {
+    /// Saves the tree's current state, `O(log n)` nodes, for
+    /// [`MerkleTree::restore`].
+    pub fn checkpoint(&mut self) -> CheckpointId {
+        let checkpoint = Checkpoint {
+            size: self.len(),
+            root: self.root(),
+            frontier: self
+                .levels
+                .iter()
+                .filter_map(|level| level.last().copied())
+                .collect(),
+        };
+        let id = CheckpointId(self.checkpoints.next);
+        self.checkpoints.next += 1;
+        self.checkpoints.saved.push((id, checkpoint));
+        id
+    }
+
+    /// Rolls the tree back to checkpoint `id`, dropping the leaves appended
+    /// since and restoring the right edge they changed, and returns the
+    /// restored root.
+    ///
+    /// Checkpoints taken after `id` are discarded; `id` itself is kept, so
+    /// the tree can be restored to it again. Restoring fails with
+    /// [`MerkleError::LeafModified`], naming the first leaf of the changed
+    /// subtree, if a leaf the checkpoint covers was since replaced other
+    /// than the last one, and leaves the tree untouched.
+    pub fn restore(&mut self, id: CheckpointId) -> Result<[u8; 32], MerkleError> {
+        let position = self.checkpoints.position(id)?;
+        let checkpoint = self.checkpoints.saved[position].1.clone();
+        if checkpoint.size > self.len() {
+            return Err(MerkleError::SizeOutOfBounds {
+                size: checkpoint.size,
+                len: self.len(),
+            });
+        }
+        // Level lengths at the checkpoint; the nodes left of each level's
+        // last one must still be the ones its right edge was hashed from.
+        let mut lens = vec![checkpoint.size];
+        while lens[lens.len() - 1] > 1 {
+            lens.push(lens[lens.len() - 1].div_ceil(2));
+        }
+        let frontier = &checkpoint.frontier;
+        for level in 0..frontier.len().saturating_sub(1) {
+            let last = lens[level] - 1;
+            let parent = if last % 2 == 0 {
+                self.hasher.hash_nodes(frontier[level], self.zeros[level])
+            } else {
+                self.hasher
+                    .hash_nodes(self.levels[level][last - 1], frontier[level])
+            };
+            if parent != frontier[level + 1] {
+                return Err(MerkleError::LeafModified {
+                    index: (last - 1) << level,
+                });
+            }
+        }
+        let top = frontier.len().saturating_sub(1);
+        if let Some(&node) = frontier.last() {
+            if pad_root(&self.hasher, &self.zeros, node, top, self.depth) != checkpoint.root {
+                return Err(MerkleError::LeafModified { index: 0 });
+            }
+        }
+
+        if let Some(&leaf) = frontier.first() {
+            let last = checkpoint.size - 1;
+            let old_leaf = self.levels[0][last];
+            self.reindex_leaf(last, Some(old_leaf), leaf)?;
+        }
+        if let Some(map) = &mut self.leaf_index {
+            map.retain(|_, index| *index < checkpoint.size);
+        }
+        self.levels.truncate(frontier.len().max(1));
+        self.levels[0].truncate(checkpoint.size);
+        for (level, &node) in frontier.iter().enumerate() {
+            self.levels[level].truncate(lens[level]);
+            self.levels[level][lens[level] - 1] = node;
+        }
+        self.checkpoints.saved.truncate(position + 1);
+        Ok(checkpoint.root)
+    }
+
+    /// Forgets checkpoint `id`, for once its speculation has been accepted.
+    pub fn release_checkpoint(&mut self, id: CheckpointId) -> Result<(), MerkleError> {
+        let position = self.checkpoints.position(id)?;
+        self.checkpoints.saved.remove(position);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restore_rolls_back_appends() {
+        let mut tree = MerkleTree::new(4).with_leaf_index();
+        let empty = tree.checkpoint();
+        for i in 0u8..5 {
+            tree.append_data(&[i]);
+        }
+        let expected = tree.clone();
+        let five = tree.checkpoint();
+        for i in 5u8..11 {
+            tree.append_data(&[i]);
+        }
+        let eleven = tree.checkpoint();
+        tree.append_data(&[11]);
+
+        assert_eq!(tree.restore(five).unwrap(), expected.root());
+        assert_eq!(tree.levels, expected.levels);
+        assert_eq!(tree.index_of(&expected.leaves()[4]), Some(4));
+        assert!(tree.index_of(&tree.hasher().hash_leaf(&[6])).is_none());
+        assert_eq!(
+            tree.restore(eleven),
+            Err(MerkleError::UnknownCheckpoint { id: eleven.0 })
+        );
+
+        tree.append_data(&[9]);
+        tree.restore(five).unwrap();
+        assert_eq!(tree.get_proof(4), expected.get_proof(4));
+        tree.set_leaf(1, [7; 32]).unwrap();
+        assert!(matches!(
+            tree.restore(five),
+            Err(MerkleError::LeafModified { .. })
+        ));
+        assert_eq!(tree.leaves()[1], [7; 32]);
+
+        assert_eq!(tree.restore(empty).unwrap(), MerkleTree::new(4).root());
+        assert!(tree.is_empty());
+        tree.release_checkpoint(empty).unwrap();
+        assert!(tree.restore(empty).is_err());
+    }
+}
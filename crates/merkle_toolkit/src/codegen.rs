@@ -0,0 +1,99 @@
+//! Source generation for verifying proofs outside Rust.
+
+use crate::{HashAlgorithm, RuntimeHasher};
+
+/// Emits a Solidity `MerkleVerifier` library that checks proofs exactly as
+/// [`Proof::verify_with`](crate::Proof::verify_with) does with `hasher`:
+/// the same hash function, the same leaf and node prefixes, and left/right
+/// order taken from the bits of the leaf index, or from sorting each pair
+/// when [`RuntimeHasher::sorted_pairs`] is set.
+pub fn solidity_verifier(hasher: &RuntimeHasher) -> String {
+    let name = match hasher.algorithm {
+        HashAlgorithm::Sha256 => "SHA-256",
+        HashAlgorithm::Keccak256 => "Keccak-256",
+        HashAlgorithm::DoubleSha256 => "double SHA-256",
+    };
+    let digest = |input: &str| match hasher.algorithm {
+        HashAlgorithm::Sha256 => format!("sha256({input})"),
+        HashAlgorithm::Keccak256 => format!("keccak256({input})"),
+        HashAlgorithm::DoubleSha256 => format!("sha256(abi.encodePacked(sha256({input})))"),
+    };
+    let (rules, leaf, node) = if hasher.domain_separation {
+        (
+            "with RFC 6962 domain separation",
+            digest("abi.encodePacked(bytes1(0x00), data)"),
+            digest("abi.encodePacked(bytes1(0x01), left, right)"),
+        )
+    } else {
+        (
+            "without domain separation",
+            digest("data"),
+            digest("abi.encodePacked(left, right)"),
+        )
+    };
+    let (rules, sort) = if hasher.sorted_pairs {
+        (
+            format!("{rules}, sorted pairs"),
+            "if (left > right) {\n            (left, right) = (right, left);\n        }\n        ",
+        )
+    } else {
+        (rules.to_string(), "")
+    };
+    format!(
+        r#"// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+/// Merkle proof verification generated by merkle_toolkit: {name} {rules}.
+library MerkleVerifier {{
+    function hashLeaf(bytes memory data) internal pure returns (bytes32) {{
+        return {leaf};
+    }}
+
+    function hashNodes(bytes32 left, bytes32 right) internal pure returns (bytes32) {{
+        {sort}return {node};
+    }}
+
+    /// Checks that `proof` (siblings from the leaf level up) leads from
+    /// `leaf` at `index` to `root`.
+    function verify(bytes32[] memory proof, uint256 index, bytes32 leaf, bytes32 root)
+        internal
+        pure
+        returns (bool)
+    {{
+        bytes32 computed = leaf;
+        for (uint256 i = 0; i < proof.length; i++) {{
+            if (index % 2 == 0) {{
+                computed = hashNodes(computed, proof[i]);
+            }} else {{
+                computed = hashNodes(proof[i], computed);
+            }}
+            index /= 2;
+        }}
+        return computed == root;
+    }}
+}}
+"#
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solidity_verifier_follows_hasher() {
+        let source = solidity_verifier(&RuntimeHasher::default());
+        assert!(source.contains("return sha256(abi.encodePacked(bytes1(0x01), left, right));"));
+        assert!(source.contains("return sha256(abi.encodePacked(bytes1(0x00), data));"));
+
+        let source = solidity_verifier(&RuntimeHasher {
+            algorithm: HashAlgorithm::Keccak256,
+            domain_separation: false,
+            sorted_pairs: true,
+        });
+        assert!(source.contains("return keccak256(abi.encodePacked(left, right));"));
+        assert!(source.contains("return keccak256(data);"));
+        assert!(source.contains("(left, right) = (right, left);"));
+        assert!(!source.contains("bytes1(0x00)"));
+    }
+}
@@ -0,0 +1,308 @@
+use crate::{HashAlgorithm, MerkleError, NodeHasher, Proof, RuntimeHasher};
+
+/// Tree construction rules of other merkle libraries, for reproducing their
+/// roots and proofs byte for byte.
+///
+/// Unlike [`crate::MerkleTree`] these trees have no fixed depth and never
+/// pad: an odd node at the end of a level is either carried up unchanged or,
+/// with `duplicate_odd`, hashed with itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TreeConfig {
+    pub algorithm: HashAlgorithm,
+    /// Sort leaves before building (merkletreejs `sortLeaves`).
+    pub sort_leaves: bool,
+    /// Hash each pair of children in ascending order (merkletreejs
+    /// `sortPairs`).
+    pub sort_pairs: bool,
+    /// Hash an odd last node with itself instead of carrying it up
+    /// (merkletreejs `duplicateOdd`).
+    ///
+    /// This makes the root ambiguous: appending a copy of an odd last node
+    /// gives the same root. [`TreeConfig::verify`] rejects any level where a
+    /// node is paired with an equal sibling.
+    pub duplicate_odd: bool,
+}
+
+impl TreeConfig {
+    /// merkletreejs with its default options.
+    pub fn merkletreejs(algorithm: HashAlgorithm) -> Self {
+        Self {
+            algorithm,
+            sort_leaves: false,
+            sort_pairs: false,
+            duplicate_odd: false,
+        }
+    }
+
+    /// merkletreejs over keccak256 with `{ sortPairs: true }`, the setup
+    /// used to feed OpenZeppelin's `MerkleProof.sol`.
+    pub fn merkletreejs_keccak() -> Self {
+        Self {
+            sort_pairs: true,
+            ..Self::merkletreejs(HashAlgorithm::Keccak256)
+        }
+    }
+
+    /// merkletreejs with `{ sort: true }`, sorting both leaves and pairs.
+    pub fn merkletreejs_sorted(algorithm: HashAlgorithm) -> Self {
+        Self {
+            sort_leaves: true,
+            sort_pairs: true,
+            ..Self::merkletreejs(algorithm)
+        }
+    }
+
+    /// Bitcoin block trees: double SHA-256, with an odd last node paired
+    /// with itself. Leaves are txids in internal byte order, the reverse of
+    /// how explorers display them.
+    ///
+    /// Duplicating the odd node is the flaw behind CVE-2012-2459: the txid
+    /// lists `[a, b, c]` and `[a, b, c, c]` share a root, so a proof for a
+    /// fourth transaction `c` verifies against a block with three. Proofs
+    /// that pair two equal nodes are rejected by [`TreeConfig::verify`], but
+    /// callers building trees from untrusted lists must still reject
+    /// duplicate leaves themselves, as Bitcoin Core does for blocks.
+    pub fn bitcoin() -> Self {
+        Self {
+            duplicate_odd: true,
+            ..Self::merkletreejs(HashAlgorithm::DoubleSha256)
+        }
+    }
+
+    /// The hasher applied to leaf data and node pairs.
+    pub fn hasher(&self) -> RuntimeHasher {
+        RuntimeHasher {
+            algorithm: self.algorithm,
+            domain_separation: false,
+            sorted_pairs: self.sort_pairs,
+        }
+    }
+
+    /// Builds a tree over already hashed leaves.
+    pub fn build(&self, mut leaves: Vec<[u8; 32]>) -> CompatTree {
+        if self.sort_leaves {
+            leaves.sort_unstable();
+        }
+        let hasher = self.hasher();
+        let mut levels = vec![leaves];
+        while levels.last().unwrap().len() > 1 {
+            let level = levels.last().unwrap();
+            let parents = level
+                .chunks(2)
+                .map(|pair| match *pair {
+                    [left, right] => hasher.hash_nodes(left, right),
+                    [odd] if self.duplicate_odd => hasher.hash_nodes(odd, odd),
+                    [odd] => odd,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(parents);
+        }
+        CompatTree {
+            config: *self,
+            levels,
+        }
+    }
+
+    /// Hashes each item of `data` into a leaf and builds a tree over them
+    /// (merkletreejs `hashLeaves`).
+    pub fn build_from_data<T: AsRef<[u8]>>(&self, data: &[T]) -> CompatTree {
+        let hasher = self.hasher();
+        self.build(
+            data.iter()
+                .map(|item| hasher.hash_leaf(item.as_ref()))
+                .collect(),
+        )
+    }
+
+    /// Checks a proof from [`CompatTree::get_proof`] built under the same
+    /// config. [`Proof::verify`] does not understand carried-up nodes and
+    /// must not be used for these proofs.
+    ///
+    /// With `duplicate_odd`, a node paired with a sibling equal to itself is
+    /// rejected: it is indistinguishable from a duplicated odd node, and
+    /// accepting it would prove a leaf at an index past the end of the tree.
+    pub fn verify(&self, proof: &Proof, leaf: [u8; 32], root: [u8; 32]) -> bool {
+        if proof.index >= proof.size {
+            return false;
+        }
+        let hasher = self.hasher();
+        let mut siblings = proof.siblings.iter();
+        let (mut index, mut count, mut computed_hash) = (proof.index, proof.size, leaf);
+        while count > 1 {
+            if index % 2 == 1 || index + 1 < count {
+                let Some(&sibling) = siblings.next() else {
+                    return false;
+                };
+                if self.duplicate_odd && sibling == computed_hash {
+                    return false;
+                }
+                computed_hash = match index % 2 {
+                    1 => hasher.hash_nodes(sibling, computed_hash),
+                    _ => hasher.hash_nodes(computed_hash, sibling),
+                };
+            } else if self.duplicate_odd {
+                computed_hash = hasher.hash_nodes(computed_hash, computed_hash);
+            }
+            index /= 2;
+            count = count.div_ceil(2);
+        }
+        siblings.next().is_none() && computed_hash == root
+    }
+}
+
+/// A tree built by [`TreeConfig`].
+#[derive(Debug, Clone)]
+pub struct CompatTree {
+    config: TreeConfig,
+    levels: Vec<Vec<[u8; 32]>>,
+}
+
+impl CompatTree {
+    pub fn config(&self) -> &TreeConfig {
+        &self.config
+    }
+
+    /// Leaves in tree order, which is sorted when `sort_leaves` is set.
+    pub fn leaves(&self) -> &[[u8; 32]] {
+        &self.levels[0]
+    }
+
+    pub fn len(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels[0].is_empty()
+    }
+
+    /// Position of `leaf` in tree order.
+    pub fn position(&self, leaf: &[u8; 32]) -> Option<usize> {
+        self.levels[0]
+            .iter()
+            .position(|candidate| candidate == leaf)
+    }
+
+    /// The root, or all zeros for an empty tree.
+    pub fn root(&self) -> [u8; 32] {
+        self.levels
+            .last()
+            .unwrap()
+            .first()
+            .copied()
+            .unwrap_or([0u8; 32])
+    }
+
+    /// Siblings for the leaf at `index`, skipping levels where its node is
+    /// carried up or duplicated, as merkletreejs `getProof` does.
+    pub fn get_proof(&self, index: usize) -> Result<Proof, MerkleError> {
+        let len = self.len();
+        if index >= len {
+            return Err(MerkleError::IndexOutOfBounds { index, len });
+        }
+        let mut siblings = Vec::new();
+        let mut idx = index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            if let Some(sibling) = level.get(idx ^ 1) {
+                siblings.push(*sibling);
+            }
+            idx /= 2;
+        }
+        Ok(Proof {
+            index,
+            size: len,
+            siblings,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hex, verify_sorted_proof, Keccak256Hasher, MerkleTree, SortedPair};
+
+    #[test]
+    fn test_merkletreejs_readme_vector() {
+        // `new MerkleTree(['a', 'b', 'c'].map(x => SHA256(x)), SHA256)` from
+        // the merkletreejs README; `c` is carried up to the root level.
+        let config = TreeConfig::merkletreejs(HashAlgorithm::Sha256);
+        let tree = config.build_from_data(&["a", "b", "c"]);
+        assert_eq!(
+            hex::encode(&tree.root()),
+            "7075152d03a5cd92104887b476862778ec0c87be5c2fa1c0a90f87c49fad6eff"
+        );
+        for (index, leaf) in tree.leaves().iter().enumerate() {
+            let proof = tree.get_proof(index).unwrap();
+            assert!(config.verify(&proof, *leaf, tree.root()));
+        }
+        let proof = tree.get_proof(2).unwrap();
+        assert_eq!(proof.len(), 1);
+        assert!(!config.verify(&proof, tree.leaves()[0], tree.root()));
+
+        let duplicated = TreeConfig {
+            duplicate_odd: true,
+            ..config
+        };
+        let tree = duplicated.build_from_data(&["a", "b", "c"]);
+        assert_eq!(
+            hex::encode(&tree.root()),
+            "d31a37ef6ac14a2db1470c4316beb5592e6afd4465022339adafda76a18ffabe"
+        );
+        let proof = tree.get_proof(2).unwrap();
+        assert!(duplicated.verify(&proof, tree.leaves()[2], tree.root()));
+    }
+
+    #[test]
+    fn test_bitcoin_block_100000() {
+        let display_order = |hash: &str| {
+            let mut bytes = hex::decode_hash(hash).unwrap();
+            bytes.reverse();
+            bytes
+        };
+        let txids = [
+            "8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87",
+            "fff2525b8931402dd09222c50775608f75787bd2b87e56995a7bdd30f79702c4",
+            "6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4",
+            "e9a66845e05d5abc0ad04ec80f774a7e585c6e8db975962d069a522137b80c1d",
+        ];
+        let root =
+            display_order("f3e94742aca4b5ef85488dc37c06c3282295ffec960994b2c0d5ac2a25a95766");
+        let config = TreeConfig::bitcoin();
+        let tree = config.build(txids.iter().map(|txid| display_order(txid)).collect());
+        assert_eq!(tree.root(), root);
+
+        // Three transactions: the last is hashed with itself.
+        let tree = config.build(tree.leaves()[..3].to_vec());
+        let (hasher, leaves) = (config.hasher(), tree.leaves());
+        assert_eq!(
+            tree.root(),
+            hasher.hash_nodes(
+                hasher.hash_nodes(leaves[0], leaves[1]),
+                hasher.hash_nodes(leaves[2], leaves[2])
+            )
+        );
+        let proof = tree.get_proof(2).unwrap();
+        assert!(config.verify(&proof, tree.leaves()[2], tree.root()));
+
+        // CVE-2012-2459: the same root with the last transaction repeated,
+        // and a proof for that fourth transaction.
+        let mutated = config.build([leaves, &leaves[2..]].concat());
+        assert_eq!(mutated.root(), tree.root());
+        let forged = mutated.get_proof(3).unwrap();
+        assert!(!config.verify(&forged, leaves[2], tree.root()));
+        let forged = mutated.get_proof(2).unwrap();
+        assert!(!config.verify(&forged, leaves[2], tree.root()));
+    }
+
+    #[test]
+    fn test_keccak_preset_matches_openzeppelin() {
+        let leaves: Vec<[u8; 32]> = (0u8..4).map(|i| Keccak256Hasher.hash(&[&[i]])).collect();
+        let tree = TreeConfig::merkletreejs_keccak().build(leaves.clone());
+        let reference = MerkleTree::from_leaves(2, SortedPair(Keccak256Hasher), leaves).unwrap();
+        assert_eq!(tree.root(), reference.root());
+        for (index, leaf) in tree.leaves().iter().enumerate() {
+            let proof = tree.get_proof(index).unwrap();
+            assert!(verify_sorted_proof(*leaf, &proof, tree.root()));
+        }
+    }
+}
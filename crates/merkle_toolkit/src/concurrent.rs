@@ -0,0 +1,531 @@
+use std::cmp::Ordering;
+
+use crate::{zero_hashes_with, Keccak256Hasher, MerkleError, NodeHasher, Proof};
+
+/// Deepest tree spl-account-compression supports; leaf indices are `u32`.
+pub const CONCURRENT_MAX_DEPTH: usize = 30;
+
+/// Off-chain replica of spl-account-compression's `ConcurrentMerkleTree`.
+///
+/// The tree keeps no leaves, only a ring buffer of the last `buffer_size`
+/// changes and the proof of the rightmost leaf. That is enough to append,
+/// and to replace leaves with proofs made against any root still in the
+/// buffer: later changes are replayed onto the proof before it is checked.
+///
+/// Nodes are `keccak256(left || right)` and empty subtrees hash up from an
+/// all-zero leaf, as on chain. [`ConcurrentMerkleTree::to_bytes`] produces
+/// the account's tree section, which follows the header and precedes the
+/// canopy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConcurrentMerkleTree {
+    depth: usize,
+    sequence_number: u64,
+    active_index: u64,
+    buffer_size: u64,
+    change_logs: Vec<ChangeLog>,
+    rightmost_proof: Path,
+}
+
+/// One change: the new root and the nodes from the changed leaf upwards.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ChangeLog {
+    root: [u8; 32],
+    path: Vec<[u8; 32]>,
+    index: u32,
+}
+
+/// The proof of the most recently appended leaf.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Path {
+    proof: Vec<[u8; 32]>,
+    leaf: [u8; 32],
+    /// Number of appended leaves.
+    index: u32,
+}
+
+impl ConcurrentMerkleTree {
+    /// Creates an empty tree of `depth` keeping the last `max_buffer_size`
+    /// changes. As on chain, the depth must be positive and the buffer size
+    /// a power of two.
+    pub fn new(depth: usize, max_buffer_size: usize) -> Result<Self, MerkleError> {
+        if depth == 0 {
+            return Err(MerkleError::InvalidEncoding("depth must be positive"));
+        }
+        if depth > CONCURRENT_MAX_DEPTH {
+            return Err(MerkleError::DepthTooLarge {
+                depth,
+                max: CONCURRENT_MAX_DEPTH,
+            });
+        }
+        if !max_buffer_size.is_power_of_two() {
+            return Err(MerkleError::InvalidEncoding(
+                "buffer size must be a power of two",
+            ));
+        }
+        let empty = empty_nodes(depth);
+        let path = empty[..depth].to_vec();
+        let mut change_logs = vec![
+            ChangeLog {
+                root: [0u8; 32],
+                path: vec![[0u8; 32]; depth],
+                index: 0,
+            };
+            max_buffer_size
+        ];
+        change_logs[0] = ChangeLog {
+            root: empty[depth],
+            path: path.clone(),
+            index: 0,
+        };
+        Ok(Self {
+            depth,
+            sequence_number: 0,
+            active_index: 0,
+            buffer_size: 1,
+            change_logs,
+            rightmost_proof: Path {
+                proof: path,
+                leaf: [0u8; 32],
+                index: 0,
+            },
+        })
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn max_buffer_size(&self) -> usize {
+        self.change_logs.len()
+    }
+
+    /// Number of changes applied since creation.
+    pub fn sequence_number(&self) -> u64 {
+        self.sequence_number
+    }
+
+    /// Number of appended leaves.
+    pub fn len(&self) -> usize {
+        self.rightmost_proof.index as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        1 << self.depth
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        self.change_logs[self.active_index as usize].root
+    }
+
+    /// Roots still in the change buffer, oldest first.
+    pub fn recent_roots(&self) -> impl Iterator<Item = [u8; 32]> + '_ {
+        self.buffered_positions()
+            .map(|position| self.change_logs[position].root)
+    }
+
+    /// Appends `leaf` using only the rightmost proof.
+    pub fn append(&mut self, leaf: [u8; 32]) -> Result<(), MerkleError> {
+        let index = self.rightmost_proof.index;
+        if index as usize >= self.capacity() {
+            return Err(MerkleError::TreeFull {
+                capacity: self.capacity(),
+            });
+        }
+        if index == 0 {
+            let proof = self.rightmost_proof.proof.clone();
+            self.update_buffers_from_proof(leaf, &proof, 0);
+            self.rightmost_proof.leaf = leaf;
+            self.rightmost_proof.index = 1;
+            return Ok(());
+        }
+        let empty = empty_nodes(self.depth);
+        let previous = index - 1;
+        // Below the level where the new leaf's path joins the previous
+        // leaf's, the new leaf only has empty siblings.
+        let intersection = index.trailing_zeros() as usize;
+        let mut path = Vec::with_capacity(self.depth);
+        let mut node = leaf;
+        let mut intersection_node = self.rightmost_proof.leaf;
+        for (level, empty) in empty[..self.depth].iter().enumerate() {
+            path.push(node);
+            let sibling = self.rightmost_proof.proof[level];
+            let is_left = (previous >> level) & 1 == 0;
+            match level.cmp(&intersection) {
+                Ordering::Less => {
+                    intersection_node = hash_to_parent(intersection_node, sibling, is_left);
+                    node = hash_to_parent(node, *empty, true);
+                    self.rightmost_proof.proof[level] = *empty;
+                }
+                Ordering::Equal => {
+                    node = hash_to_parent(node, intersection_node, false);
+                    self.rightmost_proof.proof[level] = intersection_node;
+                }
+                Ordering::Greater => node = hash_to_parent(node, sibling, is_left),
+            }
+        }
+        self.advance();
+        self.change_logs[self.active_index as usize] = ChangeLog {
+            root: node,
+            path,
+            index,
+        };
+        self.rightmost_proof.index = index + 1;
+        self.rightmost_proof.leaf = leaf;
+        Ok(())
+    }
+
+    /// Replaces `previous_leaf` at `index` with `new_leaf`, given a proof
+    /// made against `root`, which may be any root still in the buffer.
+    ///
+    /// When `root` has already left the buffer, the proof is replayed over
+    /// the whole buffer, which succeeds as long as the changes that left
+    /// did not touch the proof's path.
+    pub fn set_leaf(
+        &mut self,
+        root: [u8; 32],
+        previous_leaf: [u8; 32],
+        new_leaf: [u8; 32],
+        proof: &[[u8; 32]],
+        index: usize,
+    ) -> Result<(), MerkleError> {
+        let proof = self.fast_forward(root, previous_leaf, proof, index)?;
+        self.update_buffers_from_proof(new_leaf, &proof, index as u32);
+        Ok(())
+    }
+
+    /// Checks that `leaf` is at `index`, given a proof made against `root`.
+    pub fn prove_leaf(
+        &self,
+        root: [u8; 32],
+        leaf: [u8; 32],
+        proof: &[[u8; 32]],
+        index: usize,
+    ) -> Result<(), MerkleError> {
+        self.fast_forward(root, leaf, proof, index).map(drop)
+    }
+
+    /// Encodes the tree as laid out in the account: sequence number, active
+    /// index and buffer size as little-endian `u64`, the change logs (root,
+    /// path, `u32` index, 4 bytes of padding), then the rightmost proof
+    /// (proof, leaf, `u32` index, padding).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::encoded_len(self.depth, self.change_logs.len()));
+        bytes.extend_from_slice(&self.sequence_number.to_le_bytes());
+        bytes.extend_from_slice(&self.active_index.to_le_bytes());
+        bytes.extend_from_slice(&self.buffer_size.to_le_bytes());
+        for log in &self.change_logs {
+            bytes.extend_from_slice(&log.root);
+            log.path
+                .iter()
+                .for_each(|node| bytes.extend_from_slice(node));
+            bytes.extend_from_slice(&log.index.to_le_bytes());
+            bytes.extend_from_slice(&[0u8; 4]);
+        }
+        let rightmost = &self.rightmost_proof;
+        rightmost
+            .proof
+            .iter()
+            .for_each(|node| bytes.extend_from_slice(node));
+        bytes.extend_from_slice(&rightmost.leaf);
+        bytes.extend_from_slice(&rightmost.index.to_le_bytes());
+        bytes.extend_from_slice(&[0u8; 4]);
+        bytes
+    }
+
+    pub fn from_bytes(
+        depth: usize,
+        max_buffer_size: usize,
+        bytes: &[u8],
+    ) -> Result<Self, MerkleError> {
+        let mut tree = Self::new(depth, max_buffer_size)?;
+        if bytes.len() != Self::encoded_len(depth, max_buffer_size) {
+            return Err(MerkleError::InvalidEncoding(
+                "length does not match the tree dimensions",
+            ));
+        }
+        let mut reader = Reader(bytes);
+        tree.sequence_number = reader.u64();
+        tree.active_index = reader.u64();
+        tree.buffer_size = reader.u64();
+        if tree.active_index as usize >= max_buffer_size
+            || tree.buffer_size == 0
+            || tree.buffer_size as usize > max_buffer_size
+        {
+            return Err(MerkleError::InvalidEncoding(
+                "change buffer counters out of range",
+            ));
+        }
+        for log in &mut tree.change_logs {
+            log.root = reader.node();
+            log.path.iter_mut().for_each(|node| *node = reader.node());
+            log.index = reader.u32();
+            reader.u32();
+        }
+        let rightmost = &mut tree.rightmost_proof;
+        rightmost
+            .proof
+            .iter_mut()
+            .for_each(|node| *node = reader.node());
+        rightmost.leaf = reader.node();
+        rightmost.index = reader.u32();
+        if rightmost.index as usize > 1 << depth {
+            return Err(MerkleError::InvalidEncoding(
+                "rightmost index exceeds the tree's capacity",
+            ));
+        }
+        Ok(tree)
+    }
+
+    fn encoded_len(depth: usize, max_buffer_size: usize) -> usize {
+        24 + max_buffer_size * (32 * (depth + 1) + 8) + 32 * (depth + 1) + 8
+    }
+
+    /// Positions of the buffered change logs, oldest first.
+    fn buffered_positions(&self) -> impl Iterator<Item = usize> {
+        let len = self.change_logs.len();
+        let oldest = self.active_index as usize + len + 1 - self.buffer_size as usize;
+        (0..self.buffer_size as usize).map(move |offset| (oldest + offset) % len)
+    }
+
+    /// Replays the changes made since `root` onto `proof` and checks the
+    /// result against the current root.
+    fn fast_forward(
+        &self,
+        root: [u8; 32],
+        leaf: [u8; 32],
+        proof: &[[u8; 32]],
+        index: usize,
+    ) -> Result<Vec<[u8; 32]>, MerkleError> {
+        if index >= self.len() {
+            return Err(MerkleError::IndexOutOfBounds {
+                index,
+                len: self.len(),
+            });
+        }
+        if proof.len() != self.depth {
+            return Err(MerkleError::InvalidProof);
+        }
+        let positions: Vec<usize> = self.buffered_positions().collect();
+        let replay = match positions
+            .iter()
+            .rposition(|&position| self.change_logs[position].root == root)
+        {
+            Some(found) => &positions[found + 1..],
+            None => &positions[..],
+        };
+        let mut proof = proof.to_vec();
+        let mut updated_leaf = leaf;
+        for &position in replay {
+            let log = &self.change_logs[position];
+            if log.index as usize == index {
+                updated_leaf = log.path[0];
+            } else {
+                let critbit = (log.index ^ index as u32).ilog2() as usize;
+                proof[critbit] = log.path[critbit];
+            }
+        }
+        if updated_leaf != leaf {
+            return Err(MerkleError::LeafModified { index });
+        }
+        if !crate::verify_proof(&Keccak256Hasher, leaf, &proof, index, self.root()) {
+            return Err(MerkleError::InvalidProof);
+        }
+        Ok(proof)
+    }
+
+    /// Records the path from `leaf` at `index` as the newest change, and
+    /// patches the rightmost proof if the path crosses it.
+    fn update_buffers_from_proof(&mut self, leaf: [u8; 32], proof: &[[u8; 32]], index: u32) {
+        self.advance();
+        let mut path = Vec::with_capacity(self.depth);
+        let mut node = leaf;
+        for (level, sibling) in proof.iter().enumerate() {
+            path.push(node);
+            node = hash_to_parent(node, *sibling, (index >> level) & 1 == 0);
+        }
+        let rightmost = &mut self.rightmost_proof;
+        if index < rightmost.index {
+            if index == rightmost.index - 1 {
+                rightmost.leaf = leaf;
+            } else {
+                let critbit = (index ^ (rightmost.index - 1)).ilog2() as usize;
+                rightmost.proof[critbit] = path[critbit];
+            }
+        }
+        self.change_logs[self.active_index as usize] = ChangeLog {
+            root: node,
+            path,
+            index,
+        };
+    }
+
+    fn advance(&mut self) {
+        self.active_index = (self.active_index + 1) % self.change_logs.len() as u64;
+        if (self.buffer_size as usize) < self.change_logs.len() {
+            self.buffer_size += 1;
+        }
+        self.sequence_number += 1;
+    }
+}
+
+/// Proof for the leaf at `index` in the full tree of `depth` over `leaves`,
+/// as an indexer of a [`ConcurrentMerkleTree`] would serve it. Check it with
+/// [`Proof::verify_with`] and [`Keccak256Hasher`].
+pub fn prove_from_leaves(
+    depth: usize,
+    leaves: &[[u8; 32]],
+    index: usize,
+) -> Result<Proof, MerkleError> {
+    if depth > CONCURRENT_MAX_DEPTH {
+        return Err(MerkleError::DepthTooLarge {
+            depth,
+            max: CONCURRENT_MAX_DEPTH,
+        });
+    }
+    if index >= leaves.len() {
+        return Err(MerkleError::IndexOutOfBounds {
+            index,
+            len: leaves.len(),
+        });
+    }
+    let empty = empty_nodes(depth);
+    let mut level = leaves.to_vec();
+    let mut siblings = Vec::with_capacity(depth);
+    let mut idx = index;
+    for empty in &empty[..depth] {
+        siblings.push(level.get(idx ^ 1).copied().unwrap_or(*empty));
+        if level.len() % 2 == 1 {
+            level.push(*empty);
+        }
+        let mut parents = vec![[0u8; 32]; level.len() / 2];
+        Keccak256Hasher.hash_pairs(&level, &mut parents);
+        level = parents;
+        idx /= 2;
+    }
+    Ok(Proof {
+        index,
+        size: leaves.len(),
+        siblings,
+    })
+}
+
+/// Roots of empty subtrees of each height up to `depth`.
+fn empty_nodes(depth: usize) -> Vec<[u8; 32]> {
+    zero_hashes_with(&Keccak256Hasher, depth)
+}
+
+fn hash_to_parent(node: [u8; 32], sibling: [u8; 32], is_left: bool) -> [u8; 32] {
+    if is_left {
+        Keccak256Hasher.hash_nodes(node, sibling)
+    } else {
+        Keccak256Hasher.hash_nodes(sibling, node)
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> [u8; N] {
+        let (head, rest) = self.0.split_at(N);
+        self.0 = rest;
+        head.try_into().unwrap()
+    }
+
+    fn node(&mut self) -> [u8; 32] {
+        self.take()
+    }
+
+    fn u32(&mut self) -> u32 {
+        u32::from_le_bytes(self.take())
+    }
+
+    fn u64(&mut self) -> u64 {
+        u64::from_le_bytes(self.take())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaf(i: u8) -> [u8; 32] {
+        Keccak256Hasher.hash(&[&[i]])
+    }
+
+    #[test]
+    fn test_appends_match_full_tree() {
+        let mut tree = ConcurrentMerkleTree::new(3, 4).unwrap();
+        let mut leaves = Vec::new();
+        for i in 0..8 {
+            leaves.push(leaf(i));
+            tree.append(leaf(i)).unwrap();
+            let proof = prove_from_leaves(3, &leaves, 0).unwrap();
+            assert!(proof.verify_with(&Keccak256Hasher, leaves[0], tree.root()));
+        }
+        assert!(tree.append(leaf(8)).is_err());
+        assert_eq!(tree.recent_roots().count(), 4);
+
+        let mut bytes = tree.to_bytes();
+        let decoded = ConcurrentMerkleTree::from_bytes(3, 4, &bytes).unwrap();
+        assert_eq!(decoded, tree);
+
+        // The rightmost index closes the encoding; the active index follows
+        // the sequence number.
+        let len = bytes.len();
+        bytes[len - 8..len - 4].copy_from_slice(&9u32.to_le_bytes());
+        assert!(ConcurrentMerkleTree::from_bytes(3, 4, &bytes).is_err());
+        bytes[len - 8..len - 4].copy_from_slice(&8u32.to_le_bytes());
+        assert!(ConcurrentMerkleTree::from_bytes(3, 4, &bytes).is_ok());
+        bytes[8..16].copy_from_slice(&4u64.to_le_bytes());
+        assert!(ConcurrentMerkleTree::from_bytes(3, 4, &bytes).is_err());
+
+        assert!(ConcurrentMerkleTree::new(0, 4).is_err());
+        assert!(ConcurrentMerkleTree::new(3, 0).is_err());
+        assert!(ConcurrentMerkleTree::new(3, 6).is_err());
+    }
+
+    #[test]
+    fn test_set_leaf_with_stale_proofs() {
+        let mut tree = ConcurrentMerkleTree::new(3, 8).unwrap();
+        let mut leaves: Vec<_> = (0..5).map(leaf).collect();
+        for leaf in &leaves {
+            tree.append(*leaf).unwrap();
+        }
+        let stale_root = tree.root();
+        let stale_proofs: Vec<_> = (0..5)
+            .map(|index| prove_from_leaves(3, &leaves, index).unwrap())
+            .collect();
+
+        for index in [1, 4, 2] {
+            let new_leaf = leaf(100 + index as u8);
+            tree.set_leaf(
+                stale_root,
+                leaves[index],
+                new_leaf,
+                &stale_proofs[index],
+                index,
+            )
+            .unwrap();
+            leaves[index] = new_leaf;
+        }
+        tree.append(leaf(5)).unwrap();
+        leaves.push(leaf(5));
+        let proof = prove_from_leaves(3, &leaves, 0).unwrap();
+        assert!(proof.verify_with(&Keccak256Hasher, leaves[0], tree.root()));
+        tree.prove_leaf(stale_root, leaves[3], &stale_proofs[3], 3)
+            .unwrap();
+
+        assert_eq!(
+            tree.set_leaf(stale_root, leaf(1), leaf(200), &stale_proofs[1], 1),
+            Err(MerkleError::LeafModified { index: 1 })
+        );
+        assert_eq!(
+            tree.prove_leaf(stale_root, leaf(9), &stale_proofs[3], 3),
+            Err(MerkleError::InvalidProof)
+        );
+    }
+}
@@ -0,0 +1,351 @@
+use crate::{
+    height, pad_root, zero_hashes_with, MerkleError, MerkleTree, NodeHasher, NodeStore, Proof,
+    MAX_DEPTH,
+};
+
+/// Proof that a tree of `new_size` leaves is an append-only extension of the
+/// tree formed by its first `old_size` leaves, both rooted at `depth`.
+///
+/// `nodes` are ordered bottom-up, following RFC 6962: the starting node
+/// shared by both trees, followed by the siblings met while walking up from
+/// it. Unlike RFC 6962 the starting node is sent even when it is the old
+/// subtree root, since `old_root` is padded to `depth` and can't stand in
+/// for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+pub struct ConsistencyProof {
+    pub old_size: usize,
+    pub new_size: usize,
+    pub depth: usize,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::vec"))]
+    pub nodes: Vec<[u8; 32]>,
+}
+
+impl ConsistencyProof {
+    /// Encodes the proof as both sizes (big-endian `u64`) and the depth (one
+    /// byte) followed by the nodes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(17 + self.nodes.len() * 32);
+        bytes.extend_from_slice(&(self.old_size as u64).to_be_bytes());
+        bytes.extend_from_slice(&(self.new_size as u64).to_be_bytes());
+        bytes.push(self.depth as u8);
+        for node in &self.nodes {
+            bytes.extend_from_slice(node);
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MerkleError> {
+        if bytes.len() < 17 || (bytes.len() - 17) % 32 != 0 {
+            return Err(MerkleError::InvalidEncoding(
+                "consistency proof length is not 17 + 32n bytes",
+            ));
+        }
+        let size = |range: std::ops::Range<usize>| {
+            usize::try_from(u64::from_be_bytes(bytes[range].try_into().unwrap()))
+                .map_err(|_| MerkleError::InvalidEncoding("tree size does not fit in usize"))
+        };
+        Ok(Self {
+            old_size: size(0..8)?,
+            new_size: size(8..16)?,
+            depth: bytes[16] as usize,
+            nodes: bytes[17..]
+                .chunks_exact(32)
+                .map(|node| node.try_into().unwrap())
+                .collect(),
+        })
+    }
+}
+
+impl MerkleTree {
+    /// Verifies `proof` with the default [`MerkleTree`] hasher; see
+    /// [`MerkleTree::verify_consistency_with`].
+    pub fn verify_consistency(
+        proof: &ConsistencyProof,
+        depth: usize,
+        old_size: usize,
+        new_size: usize,
+        old_root: [u8; 32],
+        new_root: [u8; 32],
+    ) -> bool {
+        Self::verify_consistency_with(
+            &crate::DomainSeparated(crate::Sha256Hasher),
+            proof,
+            depth,
+            old_size,
+            new_size,
+            old_root,
+            new_root,
+        )
+    }
+}
+
+impl<H: NodeHasher> MerkleTree<H> {
+    /// Checks that `proof` links `old_root`, of a tree of `depth` holding
+    /// `old_size` leaves, to `new_root` at `new_size`.
+    ///
+    /// The sizes and depth are the ones the roots were committed to, not
+    /// the proof's: a padded root doesn't bind its size, since trailing zero
+    /// leaves leave it unchanged, so a proof whose fields differ is
+    /// rejected.
+    pub fn verify_consistency_with(
+        hasher: &H,
+        proof: &ConsistencyProof,
+        depth: usize,
+        old_size: usize,
+        new_size: usize,
+        old_root: [u8; 32],
+        new_root: [u8; 32],
+    ) -> bool {
+        span!(DEBUG, "merkle.verify_consistency", old_size, new_size);
+        if (proof.old_size, proof.new_size, proof.depth) != (old_size, new_size, depth) {
+            return false;
+        }
+        if old_size > new_size || height(new_size) > proof.depth || proof.depth > MAX_DEPTH {
+            return false;
+        }
+        let zeros = zero_hashes_with(hasher, proof.depth);
+        if old_size == 0 {
+            return proof.nodes.is_empty() && old_root == zeros[proof.depth];
+        }
+        if old_size == new_size {
+            return proof.nodes.is_empty() && old_root == new_root;
+        }
+
+        let mut nodes = proof.nodes.iter().copied();
+        let mut level = old_size.trailing_zeros() as usize;
+        let mut index = (old_size - 1) >> level;
+        let Some(start) = nodes.next() else {
+            return false;
+        };
+        let (mut old, mut new) = (start, start);
+        let old_height = height(old_size);
+
+        while level < height(new_size) {
+            if index % 2 == 1 {
+                let Some(left) = nodes.next() else {
+                    return false;
+                };
+                old = hasher.hash_nodes(left, old);
+                new = hasher.hash_nodes(left, new);
+            } else {
+                if level < old_height {
+                    old = hasher.hash_nodes(old, zeros[level]);
+                }
+                let right = if (index + 1) << level < new_size {
+                    match nodes.next() {
+                        Some(node) => node,
+                        None => return false,
+                    }
+                } else {
+                    zeros[level]
+                };
+                new = hasher.hash_nodes(new, right);
+            }
+            index /= 2;
+            level += 1;
+        }
+        let old = pad_root(hasher, &zeros, old, old_height, proof.depth);
+        let new = pad_root(hasher, &zeros, new, height(new_size), proof.depth);
+        nodes.next().is_none() && old == old_root && new == new_root
+    }
+}
+
+impl<H: NodeHasher, S: NodeStore> MerkleTree<H, S> {
+    /// Root of the tree formed by the first `size` leaves, at the tree's
+    /// depth.
+    pub fn root_at_size(&self, size: usize) -> Result<[u8; 32], MerkleError> {
+        self.check_size(size)?;
+        if size == 0 {
+            return Ok(self.zeros[self.depth]);
+        }
+        let top = self.node_at_size(height(size), 0, size)?;
+        Ok(pad_root(
+            &self.hasher,
+            &self.zeros,
+            top,
+            height(size),
+            self.depth,
+        ))
+    }
+
+    /// Proof for the leaf at `index` in the tree formed by the first `size`
+    /// leaves, checked against [`MerkleTree::root_at_size`].
+    pub fn get_proof_at_size(&self, index: usize, size: usize) -> Result<Proof, MerkleError> {
+        self.check_size(size)?;
+        if index >= size {
+            return Err(MerkleError::IndexOutOfBounds { index, len: size });
+        }
+        let mut siblings = (0..height(size))
+            .map(|level| {
+                let sibling = (index >> level) ^ 1;
+                if sibling << level < size {
+                    self.node_at_size(level, sibling, size)
+                } else {
+                    Ok(self.zeros[level])
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        siblings.extend_from_slice(&self.zeros[height(size)..self.depth]);
+        Ok(Proof {
+            index,
+            size,
+            siblings,
+        })
+    }
+
+    /// Proves that the first `new_size` leaves extend the first `old_size`.
+    pub fn consistency_proof(
+        &self,
+        old_size: usize,
+        new_size: usize,
+    ) -> Result<ConsistencyProof, MerkleError> {
+        span!(DEBUG, "merkle.prove_consistency", old_size, new_size);
+        self.check_size(new_size)?;
+        if old_size > new_size {
+            return Err(MerkleError::InvalidConsistencyRange { old_size, new_size });
+        }
+        let mut nodes = Vec::new();
+        if old_size > 0 && old_size < new_size {
+            let mut level = old_size.trailing_zeros() as usize;
+            let mut index = (old_size - 1) >> level;
+            nodes.push(self.node_at_size(level, index, old_size)?);
+            while level < height(new_size) {
+                if index % 2 == 1 {
+                    nodes.push(self.node_at_size(level, index - 1, new_size)?);
+                } else if (index + 1) << level < new_size {
+                    nodes.push(self.node_at_size(level, index + 1, new_size)?);
+                }
+                index /= 2;
+                level += 1;
+            }
+        }
+        Ok(ConsistencyProof {
+            old_size,
+            new_size,
+            depth: self.depth,
+            nodes,
+        })
+    }
+
+    pub(crate) fn check_size(&self, size: usize) -> Result<(), MerkleError> {
+        if size > self.len() {
+            return Err(MerkleError::SizeOutOfBounds {
+                size,
+                len: self.len(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Node at `(level, index)` in the tree formed by the first `size` leaves.
+    ///
+    /// Nodes whose leaves all precede `size` are read from the level cache;
+    /// only the right edge of the smaller tree is recomputed.
+    pub(crate) fn node_at_size(
+        &self,
+        level: usize,
+        index: usize,
+        size: usize,
+    ) -> Result<[u8; 32], MerkleError> {
+        if (index + 1) << level <= size {
+            return Ok(self.levels.get(level, index)?.unwrap_or(self.zeros[level]));
+        }
+        let left = self.node_at_size(level - 1, 2 * index, size)?;
+        let right = if (2 * index + 1) << (level - 1) < size {
+            self.node_at_size(level - 1, 2 * index + 1, size)?
+        } else {
+            self.zeros[level - 1]
+        };
+        Ok(self.hasher.hash_nodes(left, right))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_consistency_proofs_between_all_sizes() {
+        let mut tree = MerkleTree::new(4);
+        for i in 0u8..16 {
+            tree.append_data(&[i]);
+        }
+        for new_size in 0..=16 {
+            let new_root = tree.root_at_size(new_size).unwrap();
+            for old_size in 0..=new_size {
+                let old_root = tree.root_at_size(old_size).unwrap();
+                let proof = tree.consistency_proof(old_size, new_size).unwrap();
+                assert!(MerkleTree::verify_consistency(
+                    &proof, 4, old_size, new_size, old_root, new_root
+                ));
+                assert_eq!(
+                    ConsistencyProof::from_bytes(&proof.to_bytes()),
+                    Ok(proof.clone())
+                );
+
+                if old_size == 0 && new_size > 0 {
+                    // Only the empty root is the root of the empty tree.
+                    assert!(!MerkleTree::verify_consistency(
+                        &proof, 4, old_size, new_size, new_root, new_root
+                    ));
+                }
+                if old_size > 0 && old_size < new_size {
+                    assert!(!MerkleTree::verify_consistency(
+                        &proof, 4, old_size, new_size, new_root, new_root
+                    ));
+                    let mut bad = proof.clone();
+                    bad.nodes[0][0] ^= 1;
+                    assert!(!MerkleTree::verify_consistency(
+                        &bad, 4, old_size, new_size, old_root, new_root
+                    ));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_root_at_size_matches_smaller_tree() {
+        let mut tree = MerkleTree::new(3);
+        let mut prefix = MerkleTree::new(3);
+        for i in 0u8..8 {
+            tree.append_data(&[i]);
+        }
+        for i in 0u8..5 {
+            prefix.append_data(&[i]);
+        }
+        assert_eq!(tree.root_at_size(5).unwrap(), prefix.root());
+
+        // A trailing zero leaf leaves the padded root unchanged, so only the
+        // verifier's sizes tell the heads apart.
+        let mut padded = prefix.clone();
+        padded.append_leaf([0u8; 32]);
+        assert_eq!(padded.root(), prefix.root());
+        let proof = padded.consistency_proof(2, 6).unwrap();
+        let (old_root, new_root) = (padded.root_at_size(2).unwrap(), padded.root());
+        assert!(MerkleTree::verify_consistency(
+            &proof, 3, 2, 6, old_root, new_root
+        ));
+        assert!(!MerkleTree::verify_consistency(
+            &proof, 3, 2, 5, old_root, new_root
+        ));
+        assert!(!MerkleTree::verify_consistency(
+            &proof, 4, 2, 6, old_root, new_root
+        ));
+        assert_eq!(
+            tree.consistency_proof(6, 9).unwrap_err(),
+            MerkleError::SizeOutOfBounds { size: 9, len: 8 }
+        );
+        assert_eq!(
+            tree.consistency_proof(6, 5).unwrap_err(),
+            MerkleError::InvalidConsistencyRange {
+                old_size: 6,
+                new_size: 5
+            }
+        );
+    }
+}
@@ -0,0 +1,170 @@
+use crate::Proof;
+
+/// Gas charged per zero byte of transaction calldata (EIP-2028).
+pub const CALLDATA_ZERO_BYTE_GAS: u64 = 4;
+
+/// Gas charged per non-zero byte of transaction calldata (EIP-2028).
+pub const CALLDATA_NONZERO_BYTE_GAS: u64 = 16;
+
+/// The ways a [`Proof`] can be put on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProofEncoding {
+    /// [`Proof::to_bytes`].
+    Binary,
+    /// [`Proof::to_hex`].
+    Hex,
+    /// The `serde` JSON form, siblings as hex strings.
+    Json,
+    /// The `borsh` form.
+    Borsh,
+    /// ABI-encoded calldata for a call taking the arguments of the
+    /// [`solidity_verifier`](crate::codegen::solidity_verifier)'s `verify`,
+    /// `(bytes32[] proof, uint256 index, bytes32 leaf, bytes32 root)`,
+    /// selector included.
+    Abi,
+}
+
+impl Proof {
+    /// Length of the proof in `encoding`, without encoding it.
+    pub fn byte_len(&self, encoding: ProofEncoding) -> usize {
+        encoded_len(self.siblings.len(), self.index, self.size, encoding)
+    }
+
+    /// Calldata gas of passing the proof, `leaf` and `root` to a contract as
+    /// [`ProofEncoding::Abi`], counting the selector as non-zero bytes.
+    pub fn calldata_gas(&self, leaf: [u8; 32], root: [u8; 32]) -> u64 {
+        let index = (self.index as u64).to_be_bytes();
+        let count = (self.siblings.len() as u64).to_be_bytes();
+        let words = [index.as_slice(), &leaf, &root, &count];
+        let fixed = 4 * CALLDATA_NONZERO_BYTE_GAS + word_gas(&[0x80]);
+        fixed
+            + words.iter().map(|word| word_gas(word)).sum::<u64>()
+            + self
+                .siblings
+                .iter()
+                .map(|sibling| word_gas(sibling))
+                .sum::<u64>()
+    }
+}
+
+/// Largest length in `encoding` of a proof in a tree of `depth` levels whose
+/// nodes have `arity` children, which carries `depth * (arity - 1)`
+/// siblings. Sizes for `arity` above 2 are for those siblings laid out as a
+/// flat [`Proof`].
+pub fn estimate_proof_size(depth: usize, arity: usize, encoding: ProofEncoding) -> usize {
+    let capacity = capacity(depth, arity);
+    encoded_len(
+        depth * arity.saturating_sub(1),
+        capacity.saturating_sub(1),
+        capacity,
+        encoding,
+    )
+}
+
+/// Calldata gas of [`Proof::calldata_gas`] for the largest proof of
+/// [`estimate_proof_size`], taking every hash byte to be non-zero.
+pub fn estimate_calldata_gas(depth: usize, arity: usize) -> u64 {
+    let siblings = depth * arity.saturating_sub(1);
+    let index = (capacity(depth, arity).saturating_sub(1) as u64).to_be_bytes();
+    let count = (siblings as u64).to_be_bytes();
+    let hash = 32 * CALLDATA_NONZERO_BYTE_GAS;
+    4 * CALLDATA_NONZERO_BYTE_GAS
+        + word_gas(&[0x80])
+        + word_gas(&index)
+        + word_gas(&count)
+        + (2 + siblings as u64) * hash
+}
+
+fn encoded_len(siblings: usize, index: usize, size: usize, encoding: ProofEncoding) -> usize {
+    match encoding {
+        ProofEncoding::Binary => 16 + 32 * siblings,
+        ProofEncoding::Hex => 2 * (16 + 32 * siblings),
+        // {"index":I,"size":S,"siblings":["<64 hex>",...]}
+        ProofEncoding::Json => {
+            r#"{"index":,"size":,"siblings":[]}"#.len()
+                + digits(index)
+                + digits(size)
+                + 66 * siblings
+                + siblings.saturating_sub(1)
+        }
+        ProofEncoding::Borsh => 20 + 32 * siblings,
+        ProofEncoding::Abi => 4 + 5 * 32 + 32 * siblings,
+    }
+}
+
+/// Gas of a 32-byte calldata word holding `value`, right-aligned.
+fn word_gas(value: &[u8]) -> u64 {
+    let nonzero = value.iter().filter(|&&byte| byte != 0).count() as u64;
+    nonzero * CALLDATA_NONZERO_BYTE_GAS + (32 - nonzero) * CALLDATA_ZERO_BYTE_GAS
+}
+
+/// `arity^depth`, saturating at `usize::MAX`.
+fn capacity(depth: usize, arity: usize) -> usize {
+    u32::try_from(depth)
+        .ok()
+        .and_then(|depth| arity.checked_pow(depth))
+        .unwrap_or(usize::MAX)
+}
+
+fn digits(n: usize) -> usize {
+    n.checked_ilog10().map_or(1, |log| log as usize + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MerkleTree;
+
+    #[test]
+    fn test_byte_len_matches_encodings() {
+        let mut tree = MerkleTree::new(4);
+        for i in 0u8..11 {
+            tree.append_data(&[i]);
+        }
+        let proof = tree.get_proof(10);
+        assert_eq!(
+            proof.byte_len(ProofEncoding::Binary),
+            proof.to_bytes().len()
+        );
+        assert_eq!(proof.byte_len(ProofEncoding::Hex), proof.to_hex().len());
+        #[cfg(feature = "serde")]
+        assert_eq!(
+            proof.byte_len(ProofEncoding::Json),
+            serde_json::to_string(&proof).unwrap().len()
+        );
+        #[cfg(feature = "borsh")]
+        assert_eq!(
+            proof.byte_len(ProofEncoding::Borsh),
+            borsh::to_vec(&proof).unwrap().len()
+        );
+        // The worst case over a depth-4 tree is a proof of leaf 15 of 16.
+        for encoding in [
+            ProofEncoding::Binary,
+            ProofEncoding::Json,
+            ProofEncoding::Abi,
+        ] {
+            assert!(estimate_proof_size(4, 2, encoding) >= proof.byte_len(encoding));
+        }
+        assert_eq!(
+            estimate_proof_size(3, 4, ProofEncoding::Binary),
+            16 + 9 * 32
+        );
+    }
+
+    #[test]
+    fn test_calldata_gas() {
+        let proof = Proof {
+            index: 1,
+            size: 2,
+            siblings: vec![[0; 32]],
+        };
+        // Selector, then offset, index, leaf, root, length and one sibling.
+        let expected = 4 * 16 + (16 + 31 * 4) * 3 + 32 * 16 * 2 + 32 * 4;
+        assert_eq!(proof.calldata_gas([1; 32], [2; 32]), expected);
+        assert!(estimate_calldata_gas(1, 2) > expected);
+        assert_eq!(
+            estimate_calldata_gas(20, 2) - estimate_calldata_gas(19, 2),
+            32 * 16
+        );
+    }
+}
@@ -0,0 +1,868 @@
+//! Certificate Transparency v2 (RFC 9162) tree hashes, proofs and
+//! structures.
+//!
+//! A CT log's tree isn't padded to a fixed depth like [`MerkleTree`]'s: a
+//! tree of `n` leaves splits into the largest power of two below `n` and
+//! the rest, so its root only matches [`MerkleTree::root_at_size`] when `n`
+//! is a power of two. Every subtree the split produces on the left is
+//! complete, though, so [`MerkleTree::ct_root`] and the `ct_*` proofs read
+//! them from the tree's cached levels, and a [`crate::TransparencyLog`] can
+//! serve a CT log's tree heads and proofs in `O(log n)` reads.
+//!
+//! The `*DataV2` structures and [`TransItem`] use the TLS presentation
+//! language encoding of RFC 9162 section 4, so tree heads and proofs from a
+//! log can be fed to [`TransItem::from_bytes`] and checked as they are.
+//! With the `serde` feature, `AuditPath` reads and writes the inclusion
+//! proofs of the JSON API RFC 6962 logs serve.
+
+use crate::{
+    DomainSeparated, MerkleError, MerkleTree, NodeHasher, NodeStore, Sha256Hasher, TreeHeadSigner,
+    TreeHeadVerifier,
+};
+
+/// `VersionedTransType` of a [`TransItem::SignedTreeHead`].
+pub const SIGNED_TREE_HEAD_V2: u16 = 0x0005;
+/// `VersionedTransType` of a [`TransItem::ConsistencyProof`].
+pub const CONSISTENCY_PROOF_V2: u16 = 0x0006;
+/// `VersionedTransType` of a [`TransItem::InclusionProof`].
+pub const INCLUSION_PROOF_V2: u16 = 0x0007;
+
+/// The state of a log at `tree_size` entries, as signed in a
+/// [`SignedTreeHeadDataV2`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeHeadDataV2 {
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    pub tree_size: u64,
+    pub root_hash: [u8; 32],
+    /// The encoded `sth_extensions` list, empty unless the log defines
+    /// extensions.
+    pub extensions: Vec<u8>,
+}
+
+impl TreeHeadDataV2 {
+    /// The TLS encoding, which is what the log signs.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, MerkleError> {
+        let mut bytes = Vec::with_capacity(51 + self.extensions.len());
+        bytes.extend_from_slice(&self.timestamp.to_be_bytes());
+        bytes.extend_from_slice(&self.tree_size.to_be_bytes());
+        put_hash(&mut bytes, &self.root_hash);
+        put_vec16(&mut bytes, &self.extensions, "STH extensions are too long")?;
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MerkleError> {
+        let mut reader = Reader::new(bytes);
+        let head = Self::read(&mut reader)?;
+        reader.finish()?;
+        Ok(head)
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, MerkleError> {
+        Ok(Self {
+            timestamp: reader.u64()?,
+            tree_size: reader.u64()?,
+            root_hash: reader.hash()?,
+            extensions: reader.vec16()?.to_vec(),
+        })
+    }
+}
+
+/// A tree head signed by the log identified by `log_id`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedTreeHeadDataV2 {
+    /// The log's OID, DER encoded without its tag and length.
+    pub log_id: Vec<u8>,
+    pub tree_head: TreeHeadDataV2,
+    pub signature: Vec<u8>,
+}
+
+impl SignedTreeHeadDataV2 {
+    pub fn sign(
+        log_id: Vec<u8>,
+        tree_head: TreeHeadDataV2,
+        signer: &impl TreeHeadSigner,
+    ) -> Result<Self, MerkleError> {
+        let signature = signer.sign(&tree_head.to_bytes()?)?;
+        Ok(Self {
+            log_id,
+            tree_head,
+            signature,
+        })
+    }
+
+    /// Checks the signature over the encoded [`TreeHeadDataV2`].
+    pub fn verify(&self, verifier: &impl TreeHeadVerifier) -> Result<(), MerkleError> {
+        verifier.verify(&self.tree_head.to_bytes()?, &self.signature)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, MerkleError> {
+        let mut bytes = Vec::new();
+        put_log_id(&mut bytes, &self.log_id)?;
+        bytes.extend_from_slice(&self.tree_head.to_bytes()?);
+        put_vec16(&mut bytes, &self.signature, "signature is too long")?;
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MerkleError> {
+        let mut reader = Reader::new(bytes);
+        let head = Self::read(&mut reader)?;
+        reader.finish()?;
+        Ok(head)
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, MerkleError> {
+        Ok(Self {
+            log_id: reader.log_id()?,
+            tree_head: TreeHeadDataV2::read(reader)?,
+            signature: reader.vec16()?.to_vec(),
+        })
+    }
+}
+
+/// Proof that the leaf at `leaf_index` is in the log at `tree_size`
+/// entries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InclusionProofDataV2 {
+    pub log_id: Vec<u8>,
+    pub tree_size: u64,
+    pub leaf_index: u64,
+    /// Leaf level first, as produced by [`MerkleTree::ct_inclusion_path`].
+    pub inclusion_path: Vec<[u8; 32]>,
+}
+
+impl InclusionProofDataV2 {
+    /// Verifies the proof with SHA-256, as RFC 9162 logs hash.
+    pub fn verify(&self, leaf_hash: [u8; 32], root: [u8; 32]) -> bool {
+        self.verify_with(&DomainSeparated(Sha256Hasher), leaf_hash, root)
+    }
+
+    pub fn verify_with<H: NodeHasher>(
+        &self,
+        hasher: &H,
+        leaf_hash: [u8; 32],
+        root: [u8; 32],
+    ) -> bool {
+        verify_inclusion(
+            hasher,
+            leaf_hash,
+            self.leaf_index,
+            self.tree_size,
+            &self.inclusion_path,
+            root,
+        )
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, MerkleError> {
+        let mut bytes = Vec::new();
+        put_log_id(&mut bytes, &self.log_id)?;
+        bytes.extend_from_slice(&self.tree_size.to_be_bytes());
+        bytes.extend_from_slice(&self.leaf_index.to_be_bytes());
+        put_path(&mut bytes, &self.inclusion_path)?;
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MerkleError> {
+        let mut reader = Reader::new(bytes);
+        let proof = Self::read(&mut reader)?;
+        reader.finish()?;
+        Ok(proof)
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, MerkleError> {
+        Ok(Self {
+            log_id: reader.log_id()?,
+            tree_size: reader.u64()?,
+            leaf_index: reader.u64()?,
+            inclusion_path: reader.path()?,
+        })
+    }
+}
+
+/// Proof that the log at `tree_size_2` entries extends the log at
+/// `tree_size_1`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsistencyProofDataV2 {
+    pub log_id: Vec<u8>,
+    pub tree_size_1: u64,
+    pub tree_size_2: u64,
+    pub consistency_path: Vec<[u8; 32]>,
+}
+
+impl ConsistencyProofDataV2 {
+    /// Verifies the proof with SHA-256, as RFC 9162 logs hash.
+    pub fn verify(&self, old_root: [u8; 32], new_root: [u8; 32]) -> bool {
+        self.verify_with(&DomainSeparated(Sha256Hasher), old_root, new_root)
+    }
+
+    pub fn verify_with<H: NodeHasher>(
+        &self,
+        hasher: &H,
+        old_root: [u8; 32],
+        new_root: [u8; 32],
+    ) -> bool {
+        verify_consistency(
+            hasher,
+            self.tree_size_1,
+            self.tree_size_2,
+            &self.consistency_path,
+            old_root,
+            new_root,
+        )
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, MerkleError> {
+        let mut bytes = Vec::new();
+        put_log_id(&mut bytes, &self.log_id)?;
+        bytes.extend_from_slice(&self.tree_size_1.to_be_bytes());
+        bytes.extend_from_slice(&self.tree_size_2.to_be_bytes());
+        put_path(&mut bytes, &self.consistency_path)?;
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MerkleError> {
+        let mut reader = Reader::new(bytes);
+        let proof = Self::read(&mut reader)?;
+        reader.finish()?;
+        Ok(proof)
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, MerkleError> {
+        Ok(Self {
+            log_id: reader.log_id()?,
+            tree_size_1: reader.u64()?,
+            tree_size_2: reader.u64()?,
+            consistency_path: reader.path()?,
+        })
+    }
+}
+
+/// The `TransItem` envelope a log hands out its tree heads and proofs in.
+///
+/// Only the tree-level types are supported; entries and SCTs fail to decode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransItem {
+    SignedTreeHead(SignedTreeHeadDataV2),
+    ConsistencyProof(ConsistencyProofDataV2),
+    InclusionProof(InclusionProofDataV2),
+}
+
+impl TransItem {
+    /// The `VersionedTransType`, followed by the item.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, MerkleError> {
+        let (versioned_type, data) = match self {
+            Self::SignedTreeHead(head) => (SIGNED_TREE_HEAD_V2, head.to_bytes()?),
+            Self::ConsistencyProof(proof) => (CONSISTENCY_PROOF_V2, proof.to_bytes()?),
+            Self::InclusionProof(proof) => (INCLUSION_PROOF_V2, proof.to_bytes()?),
+        };
+        let mut bytes = versioned_type.to_be_bytes().to_vec();
+        bytes.extend_from_slice(&data);
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MerkleError> {
+        let mut reader = Reader::new(bytes);
+        let item = match reader.u16()? {
+            SIGNED_TREE_HEAD_V2 => Self::SignedTreeHead(SignedTreeHeadDataV2::read(&mut reader)?),
+            CONSISTENCY_PROOF_V2 => {
+                Self::ConsistencyProof(ConsistencyProofDataV2::read(&mut reader)?)
+            }
+            INCLUSION_PROOF_V2 => Self::InclusionProof(InclusionProofDataV2::read(&mut reader)?),
+            _ => return Err(MerkleError::InvalidEncoding("unsupported TransItem type")),
+        };
+        reader.finish()?;
+        Ok(item)
+    }
+}
+
+/// An inclusion proof as a CT log's `get-proof-by-hash` endpoint returns
+/// it, `{"leaf_index": 3, "audit_path": ["<base64>", ...]}`.
+///
+/// The response leaves out the tree size, which the monitor asked for, so
+/// [`AuditPath::verify`] and [`AuditPath::into_inclusion_proof`] take it
+/// back.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct AuditPath {
+    pub leaf_index: u64,
+    #[serde(with = "base64_hashes")]
+    pub audit_path: Vec<[u8; 32]>,
+}
+
+#[cfg(feature = "serde")]
+impl AuditPath {
+    /// Verifies the path with SHA-256 against the root of a tree of
+    /// `tree_size` leaves.
+    pub fn verify(&self, leaf_hash: [u8; 32], tree_size: u64, root: [u8; 32]) -> bool {
+        verify_inclusion(
+            &DomainSeparated(Sha256Hasher),
+            leaf_hash,
+            self.leaf_index,
+            tree_size,
+            &self.audit_path,
+            root,
+        )
+    }
+
+    pub fn into_inclusion_proof(self, log_id: Vec<u8>, tree_size: u64) -> InclusionProofDataV2 {
+        InclusionProofDataV2 {
+            log_id,
+            tree_size,
+            leaf_index: self.leaf_index,
+            inclusion_path: self.audit_path,
+        }
+    }
+
+    /// The JSON body a log server responds with.
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("audit paths serialize to JSON")
+    }
+
+    /// Parses a log server's response body, ignoring fields other than the
+    /// index and path.
+    #[cfg(feature = "json")]
+    pub fn from_json(json: &str) -> Result<Self, MerkleError> {
+        serde_json::from_str(json)
+            .map_err(|_| MerkleError::InvalidEncoding("invalid audit path JSON"))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<&InclusionProofDataV2> for AuditPath {
+    fn from(proof: &InclusionProofDataV2) -> Self {
+        Self {
+            leaf_index: proof.leaf_index,
+            audit_path: proof.inclusion_path.clone(),
+        }
+    }
+}
+
+/// Checks an RFC 9162 inclusion path (section 2.1.3.2) from `leaf_hash` at
+/// `leaf_index` to the `root` of a tree of `tree_size` leaves.
+pub fn verify_inclusion<H: NodeHasher>(
+    hasher: &H,
+    leaf_hash: [u8; 32],
+    leaf_index: u64,
+    tree_size: u64,
+    path: &[[u8; 32]],
+    root: [u8; 32],
+) -> bool {
+    if leaf_index >= tree_size {
+        return false;
+    }
+    let (mut index, mut last) = (leaf_index, tree_size - 1);
+    let mut node = leaf_hash;
+    for &sibling in path {
+        if last == 0 {
+            return false;
+        }
+        if index & 1 == 1 || index == last {
+            node = hasher.hash_nodes(sibling, node);
+            while index & 1 == 0 && index != 0 {
+                index >>= 1;
+                last >>= 1;
+            }
+        } else {
+            node = hasher.hash_nodes(node, sibling);
+        }
+        index >>= 1;
+        last >>= 1;
+    }
+    last == 0 && node == root
+}
+
+/// Checks an RFC 9162 consistency path (section 2.1.4.2) between the roots
+/// of trees of `old_size` and `new_size` leaves.
+///
+/// Any tree extends the empty one, so an empty path proves `old_size == 0`
+/// against the empty root.
+pub fn verify_consistency<H: NodeHasher>(
+    hasher: &H,
+    old_size: u64,
+    new_size: u64,
+    path: &[[u8; 32]],
+    old_root: [u8; 32],
+    new_root: [u8; 32],
+) -> bool {
+    if old_size > new_size {
+        return false;
+    }
+    if old_size == new_size {
+        return path.is_empty() && old_root == new_root;
+    }
+    if old_size == 0 {
+        return path.is_empty() && old_root == hasher.hash(&[]);
+    }
+
+    let mut path = path.iter().copied();
+    // The old root is a complete subtree of the new tree, so it starts the
+    // path implicitly.
+    let start = match old_size.is_power_of_two() {
+        true => old_root,
+        false => match path.next() {
+            Some(node) => node,
+            None => return false,
+        },
+    };
+    let (mut index, mut last) = (old_size - 1, new_size - 1);
+    while index & 1 == 1 {
+        index >>= 1;
+        last >>= 1;
+    }
+    let (mut old, mut new) = (start, start);
+    for node in path {
+        if last == 0 {
+            return false;
+        }
+        if index & 1 == 1 || index == last {
+            old = hasher.hash_nodes(node, old);
+            new = hasher.hash_nodes(node, new);
+            while index & 1 == 0 && index != 0 {
+                index >>= 1;
+                last >>= 1;
+            }
+        } else {
+            new = hasher.hash_nodes(new, node);
+        }
+        index >>= 1;
+        last >>= 1;
+    }
+    last == 0 && old == old_root && new == new_root
+}
+
+impl<H: NodeHasher, S: NodeStore> MerkleTree<H, S> {
+    /// The RFC 9162 Merkle Tree Hash of the first `size` leaves, unpadded;
+    /// the empty tree hashes to `hasher.hash(&[])`.
+    pub fn ct_root(&self, size: usize) -> Result<[u8; 32], MerkleError> {
+        self.check_size(size)?;
+        match size {
+            0 => Ok(self.hasher.hash(&[])),
+            _ => self.ct_subtree(0, size),
+        }
+    }
+
+    /// The RFC 9162 inclusion path of the leaf at `index` in the tree of the
+    /// first `size` leaves, checked against [`MerkleTree::ct_root`].
+    pub fn ct_inclusion_path(
+        &self,
+        index: usize,
+        size: usize,
+    ) -> Result<Vec<[u8; 32]>, MerkleError> {
+        self.check_size(size)?;
+        if index >= size {
+            return Err(MerkleError::IndexOutOfBounds { index, len: size });
+        }
+        let mut path = Vec::new();
+        self.ct_path(index, 0, size, &mut path)?;
+        Ok(path)
+    }
+
+    /// The RFC 9162 consistency path from the tree of the first `old_size`
+    /// leaves to the first `new_size`.
+    pub fn ct_consistency_path(
+        &self,
+        old_size: usize,
+        new_size: usize,
+    ) -> Result<Vec<[u8; 32]>, MerkleError> {
+        self.check_size(new_size)?;
+        if old_size > new_size {
+            return Err(MerkleError::InvalidConsistencyRange { old_size, new_size });
+        }
+        let mut path = Vec::new();
+        if old_size > 0 && old_size < new_size {
+            self.ct_subproof(old_size, 0, new_size, true, &mut path)?;
+        }
+        Ok(path)
+    }
+
+    /// MTH of the leaves at `start..end`. Power of two ranges are always
+    /// aligned, so they are complete subtrees read from the cache.
+    fn ct_subtree(&self, start: usize, end: usize) -> Result<[u8; 32], MerkleError> {
+        let len = end - start;
+        if len.is_power_of_two() {
+            let level = len.trailing_zeros() as usize;
+            return self.node(level, start >> level);
+        }
+        let mid = start + split(len);
+        Ok(self
+            .hasher
+            .hash_nodes(self.ct_subtree(start, mid)?, self.ct_subtree(mid, end)?))
+    }
+
+    /// `PATH(index, D[start:end])` of RFC 9162 section 2.1.3.1.
+    fn ct_path(
+        &self,
+        index: usize,
+        start: usize,
+        end: usize,
+        path: &mut Vec<[u8; 32]>,
+    ) -> Result<(), MerkleError> {
+        if end - start <= 1 {
+            return Ok(());
+        }
+        let mid = start + split(end - start);
+        if index < mid {
+            self.ct_path(index, start, mid, path)?;
+            path.push(self.ct_subtree(mid, end)?);
+        } else {
+            self.ct_path(index, mid, end, path)?;
+            path.push(self.ct_subtree(start, mid)?);
+        }
+        Ok(())
+    }
+
+    /// `SUBPROOF(size, D[start:end], complete)` of RFC 9162 section 2.1.4.1,
+    /// `size` counting from `start`.
+    fn ct_subproof(
+        &self,
+        size: usize,
+        start: usize,
+        end: usize,
+        complete: bool,
+        path: &mut Vec<[u8; 32]>,
+    ) -> Result<(), MerkleError> {
+        if size == end - start {
+            if !complete {
+                path.push(self.ct_subtree(start, end)?);
+            }
+            return Ok(());
+        }
+        let split = split(end - start);
+        if size <= split {
+            self.ct_subproof(size, start, start + split, complete, path)?;
+            path.push(self.ct_subtree(start + split, end)?);
+        } else {
+            self.ct_subproof(size - split, start + split, end, false, path)?;
+            path.push(self.ct_subtree(start, start + split)?);
+        }
+        Ok(())
+    }
+}
+
+/// Largest power of two smaller than `len`, for `len > 1`.
+fn split(len: usize) -> usize {
+    1 << (usize::BITS - 1 - (len - 1).leading_zeros())
+}
+
+/// A `NodeHash`, one length byte then the hash.
+fn put_hash(bytes: &mut Vec<u8>, hash: &[u8; 32]) {
+    bytes.push(32);
+    bytes.extend_from_slice(hash);
+}
+
+/// A `NodeHash` list, behind a two byte length.
+fn put_path(bytes: &mut Vec<u8>, path: &[[u8; 32]]) -> Result<(), MerkleError> {
+    let len = u16::try_from(path.len() * 33)
+        .map_err(|_| MerkleError::InvalidEncoding("path is too long"))?;
+    bytes.extend_from_slice(&len.to_be_bytes());
+    for hash in path {
+        put_hash(bytes, hash);
+    }
+    Ok(())
+}
+
+fn put_log_id(bytes: &mut Vec<u8>, log_id: &[u8]) -> Result<(), MerkleError> {
+    if !(2..=127).contains(&log_id.len()) {
+        return Err(MerkleError::InvalidEncoding("log ID is not 2 to 127 bytes"));
+    }
+    bytes.push(log_id.len() as u8);
+    bytes.extend_from_slice(log_id);
+    Ok(())
+}
+
+fn put_vec16(bytes: &mut Vec<u8>, data: &[u8], reason: &'static str) -> Result<(), MerkleError> {
+    let len = u16::try_from(data.len()).map_err(|_| MerkleError::InvalidEncoding(reason))?;
+    bytes.extend_from_slice(&len.to_be_bytes());
+    bytes.extend_from_slice(data);
+    Ok(())
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], MerkleError> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + len)
+            .ok_or(MerkleError::InvalidEncoding("CT structure is truncated"))?;
+        self.position += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, MerkleError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, MerkleError> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, MerkleError> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn vec16(&mut self) -> Result<&'a [u8], MerkleError> {
+        let len = self.u16()? as usize;
+        self.take(len)
+    }
+
+    fn log_id(&mut self) -> Result<Vec<u8>, MerkleError> {
+        let len = self.u8()? as usize;
+        if !(2..=127).contains(&len) {
+            return Err(MerkleError::InvalidEncoding("log ID is not 2 to 127 bytes"));
+        }
+        Ok(self.take(len)?.to_vec())
+    }
+
+    /// Only SHA-256 logs are supported, so every hash is 32 bytes.
+    fn hash(&mut self) -> Result<[u8; 32], MerkleError> {
+        if self.u8()? != 32 {
+            return Err(MerkleError::InvalidEncoding("node hash is not 32 bytes"));
+        }
+        Ok(self.take(32)?.try_into().unwrap())
+    }
+
+    fn path(&mut self) -> Result<Vec<[u8; 32]>, MerkleError> {
+        let mut path = Reader::new(self.vec16()?);
+        let mut hashes = Vec::new();
+        while path.position < path.bytes.len() {
+            hashes.push(path.hash()?);
+        }
+        Ok(hashes)
+    }
+
+    fn finish(&self) -> Result<(), MerkleError> {
+        match self.position == self.bytes.len() {
+            true => Ok(()),
+            false => Err(MerkleError::InvalidEncoding(
+                "trailing bytes after CT structure",
+            )),
+        }
+    }
+}
+
+/// Serde helper for the JSON API's hash lists, base64 strings even in
+/// binary formats since the API only speaks JSON.
+#[cfg(feature = "serde")]
+mod base64_hashes {
+    use serde::de::Error as _;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::base64;
+
+    pub fn serialize<S: Serializer>(hashes: &[[u8; 32]], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(hashes.iter().map(|hash| base64::encode(hash)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<[u8; 32]>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|hash| base64::decode_hash(hash).map_err(D::Error::custom))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hex, tree::height};
+
+    const INPUTS: [&[u8]; 8] = [
+        b"",
+        b"\x00",
+        b"\x10",
+        b"\x20\x21",
+        b"\x30\x31",
+        b"\x40\x41\x42\x43",
+        b"\x50\x51\x52\x53\x54\x55\x56\x57",
+        b"\x60\x61\x62\x63\x64\x65\x66\x67\x68\x69\x6a\x6b\x6c\x6d\x6e\x6f",
+    ];
+
+    #[test]
+    fn test_roots_and_proofs() {
+        let mut tree = MerkleTree::new(8);
+        for input in INPUTS {
+            tree.append_data(input);
+        }
+        // Roots of every prefix, from the certificate-transparency suite.
+        let roots = [
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d",
+            "fac54203e7cc696cf0dfcb42c92a1d9dbaf70ad9e621f4bd8d98662f00e3c125",
+            "aeb6bcfe274b70a14fb067a5e5578264db0fa9b51af5e0ba159158f329e06e77",
+            "d37ee418976dd95753c1c73862b9398fa2a2cf9b4ff0fdfe8b30cd95209614b7",
+            "4e3bbb1f7b478dcfe71fb631631519a3bca12c9aefca1612bfce4c13a86264d4",
+            "76e67dadbcdf1e10e1b74ddc608abd2f98dfb16fbce75277b5232a127f2087ef",
+            "ddb89be403809e325750d3d263cd78929c2942b7942a34b77e122c9594a74c8c",
+            "5dc9da79a70659a9ad559cb701ded9a2ab9d823aad2f4960cfe370eff4604328",
+        ];
+        let hasher = DomainSeparated(Sha256Hasher);
+        for (size, root) in roots.iter().enumerate() {
+            assert_eq!(hex::encode(&tree.ct_root(size).unwrap()), *root);
+            for index in 0..size {
+                let path = tree.ct_inclusion_path(index, size).unwrap();
+                let leaf = tree.leaves()[index];
+                let root = tree.ct_root(size).unwrap();
+                assert!(verify_inclusion(
+                    &hasher,
+                    leaf,
+                    index as u64,
+                    size as u64,
+                    &path,
+                    root
+                ));
+                assert!(!verify_inclusion(
+                    &hasher,
+                    leaf,
+                    index as u64 ^ 1,
+                    size as u64,
+                    &path,
+                    root
+                ));
+                if size.is_power_of_two() {
+                    let padded = MerkleTree::from_leaves(
+                        height(size),
+                        hasher,
+                        tree.leaves()[..size].to_vec(),
+                    )
+                    .unwrap();
+                    assert_eq!(path, padded.get_proof(index).siblings);
+                }
+            }
+            for old_size in 0..=size {
+                let path = tree.ct_consistency_path(old_size, size).unwrap();
+                let (old, new) = (tree.ct_root(old_size).unwrap(), tree.ct_root(size).unwrap());
+                assert!(verify_consistency(
+                    &hasher,
+                    old_size as u64,
+                    size as u64,
+                    &path,
+                    old,
+                    new
+                ));
+                if old_size > 0 && old_size < size {
+                    assert!(!verify_consistency(
+                        &hasher,
+                        old_size as u64,
+                        size as u64,
+                        &path,
+                        new,
+                        new
+                    ));
+                }
+            }
+        }
+        // From the suite's consistency vectors: 6 to 8 leaves.
+        let path = tree.ct_consistency_path(6, 8).unwrap();
+        assert_eq!(
+            hex::encode(&path[0]),
+            "0ebc5d3437fbe2db158b9f126a1d118e308181031d0a949f8dededebc558ef6a"
+        );
+        assert_eq!(
+            tree.ct_inclusion_path(8, 8),
+            Err(MerkleError::IndexOutOfBounds { index: 8, len: 8 })
+        );
+        assert!(tree.ct_root(9).is_err());
+    }
+
+    #[test]
+    fn test_trans_item_encoding() {
+        let mut tree = MerkleTree::new(8);
+        for input in INPUTS {
+            tree.append_data(input);
+        }
+        let log_id = vec![0x2b, 0x06, 0x01];
+        let inclusion = InclusionProofDataV2 {
+            log_id: log_id.clone(),
+            tree_size: 7,
+            leaf_index: 5,
+            inclusion_path: tree.ct_inclusion_path(5, 7).unwrap(),
+        };
+        assert!(inclusion.verify(tree.leaves()[5], tree.ct_root(7).unwrap()));
+        let bytes = TransItem::InclusionProof(inclusion.clone())
+            .to_bytes()
+            .unwrap();
+        assert_eq!(&bytes[..6], &[0x00, 0x07, 3, 0x2b, 0x06, 0x01]);
+        assert_eq!(
+            TransItem::from_bytes(&bytes).unwrap(),
+            TransItem::InclusionProof(inclusion)
+        );
+
+        let consistency = ConsistencyProofDataV2 {
+            log_id: log_id.clone(),
+            tree_size_1: 3,
+            tree_size_2: 8,
+            consistency_path: tree.ct_consistency_path(3, 8).unwrap(),
+        };
+        let item = TransItem::ConsistencyProof(consistency);
+        assert_eq!(
+            TransItem::from_bytes(&item.to_bytes().unwrap()).unwrap(),
+            item
+        );
+
+        let head = TreeHeadDataV2 {
+            timestamp: 1_700_000_000_000,
+            tree_size: 8,
+            root_hash: tree.ct_root(8).unwrap(),
+            extensions: Vec::new(),
+        };
+        let encoded = head.to_bytes().unwrap();
+        assert_eq!(encoded.len(), 51);
+        assert_eq!(TreeHeadDataV2::from_bytes(&encoded).unwrap(), head);
+        let sth = SignedTreeHeadDataV2 {
+            log_id,
+            tree_head: head,
+            signature: vec![1; 64],
+        };
+        let mut bytes = TransItem::SignedTreeHead(sth.clone()).to_bytes().unwrap();
+        assert_eq!(SignedTreeHeadDataV2::from_bytes(&bytes[2..]).unwrap(), sth);
+
+        bytes.push(0);
+        assert!(TransItem::from_bytes(&bytes).is_err());
+        assert!(TransItem::from_bytes(&bytes[..40]).is_err());
+        assert!(TransItem::from_bytes(&[0x00, 0x01]).is_err());
+        let mut short_id = sth;
+        short_id.log_id = vec![1];
+        assert!(short_id.to_bytes().is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_audit_path_json() {
+        let mut tree = MerkleTree::new(8);
+        for input in INPUTS {
+            tree.append_data(input);
+        }
+        let proof = InclusionProofDataV2 {
+            log_id: vec![0x2b, 0x06],
+            tree_size: 2,
+            leaf_index: 1,
+            inclusion_path: tree.ct_inclusion_path(1, 2).unwrap(),
+        };
+        let audit = AuditPath::from(&proof);
+        // The leaf hash of "", as a log would report it.
+        let json =
+            r#"{"leaf_index":1,"audit_path":["bjQLnP+zepicpUTmu3gKLHiQHT+zNzh2hRGjBhevoB0="]}"#;
+        assert_eq!(serde_json::to_string(&audit).unwrap(), json);
+        assert_eq!(serde_json::from_str::<AuditPath>(json).unwrap(), audit);
+        assert!(audit.verify(tree.leaves()[1], 2, tree.ct_root(2).unwrap()));
+        assert_eq!(audit.into_inclusion_proof(vec![0x2b, 0x06], 2), proof);
+
+        #[cfg(feature = "json")]
+        {
+            let response = r#"{"leaf_index": 1, "audit_path": ["bjQLnP+zepicpUTmu3gKLHiQHT+zNzh2hRGjBhevoB0="], "extra": 1}"#;
+            assert_eq!(AuditPath::from_json(response).unwrap().to_json(), json);
+            assert!(AuditPath::from_json(r#"{"leaf_index":1,"audit_path":["Zg=="]}"#).is_err());
+        }
+    }
+}
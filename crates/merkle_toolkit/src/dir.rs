@@ -0,0 +1,258 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::{height, DomainSeparated, FileHasher, MerkleTree, NodeHasher, Proof, Sha256Hasher};
+
+/// Merkleizes every regular file below a directory and commits to the
+/// resulting `(path, file root)` entries with a second tree.
+///
+/// Entries are sorted by their `/`-separated path relative to the directory,
+/// so the root only depends on file names and contents. Symlinks and other
+/// special files are skipped.
+#[derive(Debug, Clone)]
+pub struct DirectoryHasher<H = DomainSeparated> {
+    files: FileHasher<H>,
+}
+
+/// A file committed to by a [`DirectoryTree`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileEntry {
+    pub path: String,
+    pub file_len: u64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub root: [u8; 32],
+}
+
+/// The merkleized contents of a directory, as produced by [`DirectoryHasher`].
+#[derive(Debug, Clone)]
+pub struct DirectoryTree<H = DomainSeparated> {
+    entries: Vec<FileEntry>,
+    tree: MerkleTree<H>,
+}
+
+/// Proof that a file with root `file_root` is present at `path`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileProof {
+    pub path: String,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub file_root: [u8; 32],
+    pub proof: Proof,
+}
+
+/// How a path differs between two [`DirectoryTree`]s, as reported by
+/// [`DirectoryTree::diff`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum EntryChange {
+    Added,
+    Removed,
+    /// Present in both with a different length or root.
+    Changed,
+}
+
+impl DirectoryHasher {
+    pub fn new(chunk_size: usize) -> Self {
+        Self::with_hasher(chunk_size, DomainSeparated(Sha256Hasher))
+    }
+}
+
+impl<H: NodeHasher + Clone> DirectoryHasher<H> {
+    pub fn with_hasher(chunk_size: usize, hasher: H) -> Self {
+        Self {
+            files: FileHasher::with_hasher(chunk_size, hasher),
+        }
+    }
+
+    pub fn hash_dir(&self, dir: impl AsRef<Path>) -> io::Result<DirectoryTree<H>> {
+        let dir = dir.as_ref();
+        let mut paths = Vec::new();
+        collect_files(dir, dir, &mut paths)?;
+        paths.sort();
+
+        let mut entries = Vec::with_capacity(paths.len());
+        for path in paths {
+            let file = self.files.hash_file(dir.join(&path))?;
+            entries.push(FileEntry {
+                path,
+                file_len: file.file_len(),
+                root: file.root(),
+            });
+        }
+        Ok(DirectoryTree::from_entries(
+            entries,
+            self.files.hasher().clone(),
+        ))
+    }
+}
+
+impl<H: NodeHasher> DirectoryTree<H> {
+    /// Builds the tree over already merkleized entries, which must be sorted
+    /// by path.
+    pub fn from_entries(entries: Vec<FileEntry>, hasher: H) -> Self {
+        let mut tree = MerkleTree::with_hasher(height(entries.len()), hasher);
+        for entry in &entries {
+            tree.append_leaf(entry_leaf(tree.hasher(), &entry.path, entry.root));
+        }
+        Self { entries, tree }
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        self.tree.root()
+    }
+
+    pub fn entries(&self) -> &[FileEntry] {
+        &self.entries
+    }
+
+    pub fn get(&self, path: &str) -> Option<&FileEntry> {
+        self.position(path).map(|index| &self.entries[index])
+    }
+
+    pub fn prove_file(&self, path: &str) -> Option<FileProof> {
+        let index = self.position(path)?;
+        Some(FileProof {
+            path: path.to_owned(),
+            file_root: self.entries[index].root,
+            proof: self.tree.get_proof(index),
+        })
+    }
+
+    /// The paths that differ from `self` to `current`, sorted.
+    pub fn diff<'a>(&'a self, current: &'a Self) -> Vec<(&'a str, EntryChange)> {
+        let mut changes = Vec::new();
+        let (mut old, mut new) = (
+            self.entries.iter().peekable(),
+            current.entries.iter().peekable(),
+        );
+        loop {
+            let change = match (old.peek(), new.peek()) {
+                (None, None) => return changes,
+                (Some(a), Some(b)) if a.path == b.path => {
+                    let changed = a != b;
+                    old.next();
+                    let b = new.next().unwrap();
+                    if !changed {
+                        continue;
+                    }
+                    (b.path.as_str(), EntryChange::Changed)
+                }
+                (Some(a), b) if b.is_none_or(|b| a.path < b.path) => {
+                    (old.next().unwrap().path.as_str(), EntryChange::Removed)
+                }
+                _ => (new.next().unwrap().path.as_str(), EntryChange::Added),
+            };
+            changes.push(change);
+        }
+    }
+
+    fn position(&self, path: &str) -> Option<usize> {
+        self.entries
+            .binary_search_by(|entry| entry.path.as_str().cmp(path))
+            .ok()
+    }
+}
+
+impl FileProof {
+    /// Verifies the proof with the default [`DirectoryHasher`] hasher.
+    pub fn verify(&self, root: [u8; 32]) -> bool {
+        self.verify_with(&DomainSeparated(Sha256Hasher), root)
+    }
+
+    pub fn verify_with<H: NodeHasher>(&self, hasher: &H, root: [u8; 32]) -> bool {
+        let leaf = entry_leaf(hasher, &self.path, self.file_root);
+        self.proof.verify_with(hasher, leaf, root)
+    }
+}
+
+/// Leaf committing to a directory entry: the path length as a big-endian
+/// `u64`, the path, then the file root.
+fn entry_leaf<H: NodeHasher>(hasher: &H, path: &str, file_root: [u8; 32]) -> [u8; 32] {
+    let mut data = Vec::with_capacity(8 + path.len() + 32);
+    data.extend_from_slice(&(path.len() as u64).to_be_bytes());
+    data.extend_from_slice(path.as_bytes());
+    data.extend_from_slice(&file_root);
+    hasher.hash_leaf(&data)
+}
+
+fn collect_files(base: &Path, dir: &Path, paths: &mut Vec<String>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let path = entry.path();
+        if file_type.is_dir() {
+            collect_files(base, &path, paths)?;
+        } else if file_type.is_file() {
+            let relative = path.strip_prefix(base).expect("walked path is below base");
+            let components = relative
+                .iter()
+                .map(|component| {
+                    component.to_str().ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("non UTF-8 path {}", path.display()),
+                        )
+                    })
+                })
+                .collect::<io::Result<Vec<_>>>()?;
+            paths.push(components.join("/"));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("merkle-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("a.txt"), b"hello").unwrap();
+        fs::write(dir.join("sub/b.bin"), [0u8; 100]).unwrap();
+        fs::write(dir.join("z.txt"), b"").unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_hash_dir_and_prove_files() {
+        let dir = scratch_dir("dir");
+        let tree = DirectoryHasher::new(16).hash_dir(&dir).unwrap();
+        let paths: Vec<_> = tree.entries().iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, ["a.txt", "sub/b.bin", "z.txt"]);
+        assert_eq!(tree.get("sub/b.bin").unwrap().file_len, 100);
+
+        let root = tree.root();
+        for path in paths {
+            let proof = tree.prove_file(path).unwrap();
+            assert!(proof.verify(root));
+        }
+        let mut forged = tree.prove_file("a.txt").unwrap();
+        forged.path = "b.txt".into();
+        assert!(!forged.verify(root));
+        assert!(tree.prove_file("missing").is_none());
+
+        fs::write(dir.join("a.txt"), b"hellO").unwrap();
+        fs::remove_file(dir.join("z.txt")).unwrap();
+        fs::write(dir.join("sub/c.txt"), b"new").unwrap();
+        let current = DirectoryHasher::new(16).hash_dir(&dir).unwrap();
+        assert_ne!(current.root(), root);
+        assert_eq!(
+            tree.diff(&current),
+            [
+                ("a.txt", EntryChange::Changed),
+                ("sub/c.txt", EntryChange::Added),
+                ("z.txt", EntryChange::Removed)
+            ]
+        );
+        assert!(current.diff(&current).is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,292 @@
+//! Token airdrops in the format of Uniswap's `merkle-distributor`.
+//!
+//! Each `(account, amount)` entry gets an index in the order of the
+//! checksummed account strings, and its leaf is
+//! `keccak256(abi.encodePacked(uint256 index, address account, uint256 amount))`.
+//! Leaves are sorted and hashed in sorted pairs with odd nodes carried up
+//! ([`TreeConfig::merkletreejs_sorted`]), so claims verify with
+//! OpenZeppelin's `MerkleProof.verify` and with [`verify_sorted_proof`].
+//!
+//! [`Distribution::claims_file`] produces the JSON claims file the
+//! distributor scripts publish: the root, the token total and, per account,
+//! its index, amount and proof.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::leaf::evm::{packed_leaf, Token};
+use crate::{
+    hex, verify_sorted_proof, CompatTree, HashAlgorithm, Keccak256Hasher, MerkleError, NodeHasher,
+    TreeConfig,
+};
+
+/// An Ethereum address.
+pub type Address = [u8; 20];
+
+/// The leaf committing to `amount` for `account` at `index`.
+pub fn leaf(index: usize, account: &Address, amount: u128) -> [u8; 32] {
+    packed_leaf(&[
+        Token::uint256(index as u128),
+        Token::Address(*account),
+        Token::uint256(amount),
+    ])
+}
+
+/// Checks a claim against `root` as the distributor contract does.
+pub fn verify_claim(
+    root: [u8; 32],
+    index: usize,
+    account: &Address,
+    amount: u128,
+    proof: &[[u8; 32]],
+) -> bool {
+    verify_sorted_proof(leaf(index, account, amount), proof, root)
+}
+
+/// `account` in EIP-55 mixed-case hex.
+pub fn checksum_address(account: &Address) -> String {
+    let lower = hex::encode(account);
+    let digest = Keccak256Hasher.hash(&[lower.as_bytes()]);
+    let mixed: String = lower
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            let nibble = digest[i / 2] >> (4 * (1 - i % 2)) & 0xf;
+            match nibble >= 8 {
+                true => c.to_ascii_uppercase(),
+                false => c,
+            }
+        })
+        .collect();
+    format!("0x{mixed}")
+}
+
+/// Parses a hex address in any case, with or without `0x`.
+pub fn parse_address(s: &str) -> Result<Address, MerkleError> {
+    hex::decode(s)?
+        .try_into()
+        .map_err(|_| MerkleError::InvalidEncoding("expected a 20 byte address"))
+}
+
+/// A merkle tree over airdrop entries.
+#[derive(Debug, Clone)]
+pub struct Distribution {
+    /// Entries in index order.
+    entries: Vec<(Address, u128)>,
+    tree: CompatTree,
+}
+
+impl Distribution {
+    /// Builds the tree over `entries`, rejecting duplicate accounts and
+    /// zero amounts as the distributor scripts do, and totals that do not
+    /// fit in a `u128`.
+    pub fn new(entries: impl IntoIterator<Item = (Address, u128)>) -> Result<Self, MerkleError> {
+        let mut entries: Vec<_> = entries
+            .into_iter()
+            .map(|(account, amount)| (checksum_address(&account), account, amount))
+            .collect();
+        entries.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        if entries.windows(2).any(|pair| pair[0].1 == pair[1].1) {
+            return Err(MerkleError::InvalidEncoding("duplicate account"));
+        }
+        if entries.iter().any(|&(_, _, amount)| amount == 0) {
+            return Err(MerkleError::InvalidEncoding("zero amount"));
+        }
+        entries
+            .iter()
+            .try_fold(0u128, |total, &(_, _, amount)| total.checked_add(amount))
+            .ok_or(MerkleError::InvalidEncoding("token total overflows"))?;
+        let entries: Vec<_> = entries
+            .into_iter()
+            .map(|(_, account, amount)| (account, amount))
+            .collect();
+        let leaves = entries
+            .iter()
+            .enumerate()
+            .map(|(index, (account, amount))| leaf(index, account, *amount))
+            .collect();
+        let tree = TreeConfig::merkletreejs_sorted(HashAlgorithm::Keccak256).build(leaves);
+        Ok(Self { entries, tree })
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        self.tree.root()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Entries in index order.
+    pub fn entries(&self) -> &[(Address, u128)] {
+        &self.entries
+    }
+
+    pub fn token_total(&self) -> u128 {
+        self.entries.iter().map(|&(_, amount)| amount).sum()
+    }
+
+    /// The claim for `account`, if it has one.
+    pub fn claim(&self, account: &Address) -> Option<Claim> {
+        let index = self.entries.iter().position(|(a, _)| a == account)?;
+        Some(self.claim_at(index))
+    }
+
+    /// Every claim, keyed by checksummed account, with the root.
+    pub fn claims_file(&self) -> ClaimsFile {
+        ClaimsFile {
+            merkle_root: format!("0x{}", hex::encode(&self.root())),
+            token_total: amount_hex(self.token_total()),
+            claims: (0..self.len())
+                .map(|index| {
+                    (
+                        checksum_address(&self.entries[index].0),
+                        self.claim_at(index),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    fn claim_at(&self, index: usize) -> Claim {
+        let (account, amount) = self.entries[index];
+        let position = self.tree.position(&leaf(index, &account, amount)).unwrap();
+        let proof = self.tree.get_proof(position).unwrap();
+        Claim {
+            index,
+            amount: amount_hex(amount),
+            proof: proof
+                .iter()
+                .map(|sibling| format!("0x{}", hex::encode(sibling)))
+                .collect(),
+        }
+    }
+}
+
+/// The published claims: `{"merkleRoot", "tokenTotal", "claims"}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClaimsFile {
+    pub merkle_root: String,
+    /// Sum of all amounts, `0x` hex.
+    pub token_total: String,
+    pub claims: BTreeMap<String, Claim>,
+}
+
+/// One account's entry in a [`ClaimsFile`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Claim {
+    pub index: usize,
+    /// The amount, `0x` hex.
+    pub amount: String,
+    pub proof: Vec<String>,
+}
+
+impl ClaimsFile {
+    /// Checks the claim of `account` against the file's root.
+    pub fn verify_claim(&self, account: &Address) -> Result<bool, MerkleError> {
+        let Some(claim) = self.claims.get(&checksum_address(account)) else {
+            return Ok(false);
+        };
+        let proof = claim
+            .proof
+            .iter()
+            .map(|sibling| hex::decode_hash(sibling))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(verify_claim(
+            hex::decode_hash(&self.merkle_root)?,
+            claim.index,
+            account,
+            parse_amount(&claim.amount)?,
+            &proof,
+        ))
+    }
+
+    /// Checks every claim, and that the amounts add up to the token total.
+    pub fn verify(&self) -> Result<bool, MerkleError> {
+        let mut total = 0u128;
+        for (account, claim) in &self.claims {
+            total = total
+                .checked_add(parse_amount(&claim.amount)?)
+                .ok_or(MerkleError::InvalidEncoding("token total overflows"))?;
+            if !self.verify_claim(&parse_address(account)?)? {
+                return Ok(false);
+            }
+        }
+        Ok(total == parse_amount(&self.token_total)?)
+    }
+}
+
+/// `0x` hex with an even number of digits, as ethers' `toHexString`.
+fn amount_hex(amount: u128) -> String {
+    let bytes = amount.to_be_bytes();
+    let start = bytes.iter().position(|&b| b != 0).unwrap_or(15);
+    format!("0x{}", hex::encode(&bytes[start..]))
+}
+
+fn parse_amount(s: &str) -> Result<u128, MerkleError> {
+    let digits = s.strip_prefix("0x").unwrap_or(s);
+    u128::from_str_radix(digits, 16)
+        .map_err(|_| MerkleError::InvalidEncoding("amount is not a 128-bit hex number"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksum_address() {
+        // From the EIP-55 test cases.
+        let account = parse_address("0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed").unwrap();
+        assert_eq!(
+            checksum_address(&account),
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"
+        );
+    }
+
+    #[test]
+    fn test_claims_verify() {
+        let entries = [([3u8; 20], 100), ([0xab; 20], 250), ([1; 20], 7)];
+        let distribution = Distribution::new(entries).unwrap();
+        assert_eq!(distribution.token_total(), 357);
+        assert_eq!(distribution.entries()[0], ([1; 20], 7));
+
+        let file = distribution.claims_file();
+        assert_eq!(file.token_total, "0x0165");
+        let json = serde_json::to_string(&file).unwrap();
+        assert!(json.starts_with(r#"{"merkleRoot":"0x"#));
+        let parsed: ClaimsFile = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, file);
+        assert!(parsed.verify().unwrap());
+        for (account, amount) in entries {
+            assert!(parsed.verify_claim(&account).unwrap());
+            let claim = distribution.claim(&account).unwrap();
+            let proof: Vec<_> = claim
+                .proof
+                .iter()
+                .map(|sibling| hex::decode_hash(sibling).unwrap())
+                .collect();
+            let root = distribution.root();
+            assert!(verify_claim(root, claim.index, &account, amount, &proof));
+            assert!(!verify_claim(
+                root,
+                claim.index,
+                &account,
+                amount + 1,
+                &proof
+            ));
+        }
+        assert!(!parsed.verify_claim(&[9; 20]).unwrap());
+
+        let mut tampered = file.clone();
+        tampered.claims.values_mut().next().unwrap().amount = "0x08".into();
+        assert!(!tampered.verify().unwrap());
+        assert!(Distribution::new([([1; 20], 5), ([1; 20], 6)]).is_err());
+        assert!(Distribution::new([([1; 20], 0)]).is_err());
+    }
+}
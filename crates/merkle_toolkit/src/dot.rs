@@ -0,0 +1,91 @@
+use std::fmt::Write;
+
+use crate::{hex, MerkleTree, NodeHasher, Proof};
+
+impl<H: NodeHasher> MerkleTree<H> {
+    /// Renders the tree as a Graphviz DOT digraph, one box per cached node
+    /// labelled with its level, index and the first four bytes of its hash,
+    /// with dashed boxes for the zero-hash padding. Pipe the output through
+    /// `dot -Tsvg` to view it.
+    ///
+    /// With `highlight`, the nodes on the proof's path are filled blue and
+    /// its siblings orange, which makes it easy to see where two
+    /// implementations first disagree. Every cached node is drawn, so this
+    /// is only legible for small trees.
+    pub fn to_dot(&self, highlight: Option<&Proof>) -> String {
+        let top = self.levels.len() - 1;
+        let count = |level: usize| match level <= top {
+            true => self.levels[level]
+                .len()
+                .max(usize::from(level == self.depth)),
+            false => 1,
+        };
+        let shifted = |level: usize| {
+            highlight.map(|proof| proof.index.checked_shr(level as u32).unwrap_or(0))
+        };
+        let fill = |level: usize, index: usize| match shifted(level) {
+            Some(on_path) if on_path == index => Some("#9ecae1"),
+            Some(on_path) if on_path ^ 1 == index && level < self.depth => Some("#fdd49e"),
+            _ => None,
+        };
+        let style = |level: usize, index: usize, dashed: bool| match (fill(level, index), dashed) {
+            (Some(color), true) => format!(", style=\"dashed,filled\", fillcolor=\"{color}\""),
+            (Some(color), false) => format!(", style=filled, fillcolor=\"{color}\""),
+            (None, true) => ", style=dashed".to_string(),
+            (None, false) => String::new(),
+        };
+
+        let mut dot = String::from("digraph merkle {\n");
+        dot.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+        for level in (0..=self.depth).rev() {
+            for index in 0..count(level) {
+                let node = self.node(level, index).unwrap_or(self.zeros[level]);
+                let _ = writeln!(
+                    dot,
+                    "    n{level}_{index} [label=\"{level}:{index}\\n{}\"{}];",
+                    hex::encode(&node[..4]),
+                    style(level, index, false),
+                );
+                if level == 0 {
+                    continue;
+                }
+                for child in [2 * index, 2 * index + 1] {
+                    let below = level - 1;
+                    if child < count(below) {
+                        let _ = writeln!(dot, "    n{level}_{index} -> n{below}_{child};");
+                    } else {
+                        let _ = writeln!(
+                            dot,
+                            "    z{below}_{child} [label=\"zero({below})\"{}];",
+                            style(below, child, true),
+                        );
+                        let _ = writeln!(dot, "    n{level}_{index} -> z{below}_{child};");
+                    }
+                }
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dot_marks_padding_and_proof_path() {
+        let mut tree = MerkleTree::new(2);
+        for data in [b"a", b"b", b"c"] {
+            tree.append_data(data);
+        }
+        let dot = tree.to_dot(Some(&tree.get_proof(2)));
+        let root = hex::encode(&tree.root()[..4]);
+        assert!(dot.starts_with("digraph merkle {\n"));
+        assert!(dot.contains(&format!("n2_0 [label=\"2:0\\n{root}\", style=filled")));
+        assert!(dot.contains("n1_1 -> n0_2;"));
+        assert!(dot.contains("z0_3 [label=\"zero(0)\", style=\"dashed,filled\""));
+        assert!(dot.contains("n1_0 [label=\"1:0\\n"));
+        assert_eq!(dot.matches("fillcolor").count(), 5);
+    }
+}
@@ -0,0 +1,73 @@
+use core::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MerkleError {
+    /// The requested depth exceeds the supported maximum.
+    DepthTooLarge { depth: usize, max: usize },
+    /// A leaf index was not smaller than the number of leaves.
+    IndexOutOfBounds { index: usize, len: usize },
+    /// The tree already holds `2^depth` leaves.
+    TreeFull { capacity: usize },
+    /// A tree size larger than the number of leaves was requested.
+    SizeOutOfBounds { size: usize, len: usize },
+    /// A consistency proof was requested from a larger to a smaller tree.
+    InvalidConsistencyRange { old_size: usize, new_size: usize },
+    /// A serialized value could not be decoded.
+    InvalidEncoding(&'static str),
+    /// A proof does not lead to any root the structure accepts.
+    InvalidProof,
+    /// A proof has a different number of siblings than the tree's depth.
+    InvalidProofLength { expected: usize, actual: usize },
+    /// The leaf a proof was made for has since been replaced.
+    LeafModified { index: usize },
+    /// A stored node disagrees with the nodes it is derived from, as found by
+    /// [`MerkleTree::check_invariants`](crate::MerkleTree::check_invariants).
+    CorruptNode { level: usize, index: usize },
+    /// A checkpoint was released, or discarded by restoring an earlier one.
+    UnknownCheckpoint { id: u64 },
+    /// A signature does not match the data and key it was checked against.
+    InvalidSignature,
+    /// A [`NodeStore`](crate::NodeStore) backend failed to read or write.
+    #[cfg(feature = "std")]
+    Storage(std::string::String),
+}
+
+impl fmt::Display for MerkleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DepthTooLarge { depth, max } => {
+                write!(f, "depth {depth} exceeds the maximum of {max}")
+            }
+            Self::IndexOutOfBounds { index, len } => {
+                write!(f, "index {index} out of bounds for {len} leaves")
+            }
+            Self::TreeFull { capacity } => write!(f, "tree is full ({capacity} leaves)"),
+            Self::SizeOutOfBounds { size, len } => {
+                write!(f, "tree size {size} exceeds the {len} leaves in the tree")
+            }
+            Self::InvalidConsistencyRange { old_size, new_size } => {
+                write!(f, "old size {old_size} is larger than new size {new_size}")
+            }
+            Self::InvalidEncoding(reason) => write!(f, "invalid encoding: {reason}"),
+            Self::InvalidProof => write!(f, "proof does not match a known root"),
+            Self::InvalidProofLength { expected, actual } => {
+                write!(f, "proof has {actual} siblings, expected {expected}")
+            }
+            Self::LeafModified { index } => {
+                write!(f, "leaf {index} was modified after the proof was made")
+            }
+            Self::CorruptNode { level, index } => {
+                write!(
+                    f,
+                    "node {index} at level {level} is inconsistent with the leaves"
+                )
+            }
+            Self::UnknownCheckpoint { id } => write!(f, "no checkpoint with id {id}"),
+            Self::InvalidSignature => write!(f, "signature verification failed"),
+            #[cfg(feature = "std")]
+            Self::Storage(reason) => write!(f, "storage error: {reason}"),
+        }
+    }
+}
+
+impl core::error::Error for MerkleError {}
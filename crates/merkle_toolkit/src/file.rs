@@ -0,0 +1,326 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::ops::Range;
+use std::path::Path;
+
+use crate::{
+    height, DomainSeparated, MerkleError, MerkleTree, Multiproof, NodeHasher, Proof, Sha256Hasher,
+};
+
+/// Splits a byte stream into fixed-size chunks and builds a tree with one
+/// leaf per chunk.
+///
+/// Each chunk is turned into a leaf with [`NodeHasher::hash_leaf`]; the last
+/// chunk may be shorter than the chunk size.
+#[derive(Debug, Clone)]
+pub struct FileHasher<H = DomainSeparated> {
+    chunk_size: usize,
+    hasher: H,
+}
+
+/// A merkleized file, as produced by [`FileHasher`].
+#[derive(Debug, Clone)]
+pub struct FileTree<H = DomainSeparated> {
+    chunk_size: usize,
+    file_len: u64,
+    tree: MerkleTree<H>,
+}
+
+/// Proof that a chunk is the bytes at [`ChunkProof::range`] of a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+pub struct ChunkProof {
+    pub chunk_size: usize,
+    pub file_len: u64,
+    pub proof: Proof,
+}
+
+/// Proof that some bytes are the [`ByteRangeProof::range`] of a file.
+///
+/// Chunks are the unit of hashing, so the bytes are verified as part of the
+/// whole chunks around them, [`ByteRangeProof::chunk_range`] of the file,
+/// and the proof is a [`Multiproof`] for those chunks: the siblings of the
+/// run of chunks as a whole, not of each chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+pub struct ByteRangeProof {
+    pub chunk_size: usize,
+    pub file_len: u64,
+    pub offset: u64,
+    pub len: u64,
+    pub proof: Multiproof,
+}
+
+impl FileHasher {
+    pub fn new(chunk_size: usize) -> Self {
+        Self::with_hasher(chunk_size, DomainSeparated(Sha256Hasher))
+    }
+}
+
+impl<H: NodeHasher + Clone> FileHasher<H> {
+    pub fn with_hasher(chunk_size: usize, hasher: H) -> Self {
+        assert!(chunk_size > 0, "chunk size must be non-zero");
+        Self { chunk_size, hasher }
+    }
+
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    pub fn hasher(&self) -> &H {
+        &self.hasher
+    }
+
+    pub fn hash_file(&self, path: impl AsRef<Path>) -> io::Result<FileTree<H>> {
+        self.hash_reader(File::open(path)?)
+    }
+
+    pub fn hash_reader(&self, mut reader: impl Read) -> io::Result<FileTree<H>> {
+        let mut leaves = Vec::new();
+        let mut file_len = 0u64;
+        let mut chunk = vec![0u8; self.chunk_size];
+        loop {
+            let n = read_chunk(&mut reader, &mut chunk)?;
+            if n == 0 {
+                break;
+            }
+            file_len += n as u64;
+            leaves.push(self.hasher.hash_leaf(&chunk[..n]));
+            if n < self.chunk_size {
+                break;
+            }
+        }
+
+        let mut tree = MerkleTree::try_with_hasher(height(leaves.len()), self.hasher.clone())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        for leaf in leaves {
+            tree.append_leaf(leaf);
+        }
+        Ok(FileTree {
+            chunk_size: self.chunk_size,
+            file_len,
+            tree,
+        })
+    }
+}
+
+impl<H: NodeHasher> FileTree<H> {
+    pub fn root(&self) -> [u8; 32] {
+        self.tree.root()
+    }
+
+    pub fn file_len(&self) -> u64 {
+        self.file_len
+    }
+
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn tree(&self) -> &MerkleTree<H> {
+        &self.tree
+    }
+
+    pub fn prove_chunk(&self, index: usize) -> Result<ChunkProof, MerkleError> {
+        Ok(ChunkProof {
+            chunk_size: self.chunk_size,
+            file_len: self.file_len,
+            proof: self.tree.try_get_proof(index)?,
+        })
+    }
+
+    /// Proves the `len` bytes at `byte_offset`. An empty range proves the
+    /// chunk holding `byte_offset`, which must be inside the file.
+    pub fn prove_range(&self, byte_offset: u64, len: u64) -> Result<ByteRangeProof, MerkleError> {
+        match byte_offset.checked_add(len) {
+            Some(end) if byte_offset < self.file_len && end <= self.file_len => {}
+            _ => {
+                return Err(MerkleError::IndexOutOfBounds {
+                    index: usize::try_from(byte_offset.saturating_add(len.max(1)) - 1)
+                        .unwrap_or(usize::MAX),
+                    len: self.file_len as usize,
+                })
+            }
+        }
+        let chunks: Vec<usize> = chunk_indices(self.chunk_size, byte_offset, len).collect();
+        Ok(ByteRangeProof {
+            chunk_size: self.chunk_size,
+            file_len: self.file_len,
+            offset: byte_offset,
+            len,
+            proof: self.tree.get_multiproof(&chunks)?,
+        })
+    }
+}
+
+impl ChunkProof {
+    /// Byte range of the file covered by the proven chunk.
+    pub fn range(&self) -> Range<u64> {
+        let start = self.proof.index as u64 * self.chunk_size as u64;
+        start..self.file_len.min(start + self.chunk_size as u64)
+    }
+
+    /// Verifies the proof with the default [`FileHasher`] hasher.
+    pub fn verify(&self, chunk: &[u8], root: [u8; 32]) -> bool {
+        self.verify_with(&DomainSeparated(Sha256Hasher), chunk, root)
+    }
+
+    /// Checks that `chunk` has the length implied by its position in the
+    /// file, and that it is the leaf proven by the inner proof.
+    pub fn verify_with<H: NodeHasher>(&self, hasher: &H, chunk: &[u8], root: [u8; 32]) -> bool {
+        if self.chunk_size == 0 {
+            return false;
+        }
+        let expected_chunks = self.file_len.div_ceil(self.chunk_size as u64);
+        let range = self.range();
+        self.proof.size as u64 == expected_chunks
+            && range.end - range.start == chunk.len() as u64
+            && self
+                .proof
+                .verify_with(hasher, hasher.hash_leaf(chunk), root)
+    }
+}
+
+impl ByteRangeProof {
+    /// The proven bytes of the file.
+    pub fn range(&self) -> Range<u64> {
+        self.offset..self.offset + self.len
+    }
+
+    /// The bytes of the file making up the chunks around
+    /// [`ByteRangeProof::range`], which the proof is verified over.
+    pub fn chunk_range(&self) -> Range<u64> {
+        let chunks = self.chunks();
+        let chunk_size = self.chunk_size as u64;
+        chunks.start as u64 * chunk_size..self.file_len.min(chunks.end as u64 * chunk_size)
+    }
+
+    /// Verifies the proof with the default [`FileHasher`] hasher.
+    pub fn verify(&self, chunks: &[u8], root: [u8; 32]) -> bool {
+        self.verify_with(&DomainSeparated(Sha256Hasher), chunks, root)
+    }
+
+    /// Checks that `chunks` are the bytes at [`ByteRangeProof::chunk_range`]
+    /// of the file with this root. The proven bytes are then
+    /// `chunks[offset - chunk_range.start..][..len]`.
+    pub fn verify_with<H: NodeHasher>(&self, hasher: &H, chunks: &[u8], root: [u8; 32]) -> bool {
+        if self.chunk_size == 0
+            || self.offset >= self.file_len
+            || self.file_len - self.offset < self.len
+        {
+            return false;
+        }
+        let range = self.chunk_range();
+        let leaves: Vec<[u8; 32]> = chunks
+            .chunks(self.chunk_size)
+            .map(|chunk| hasher.hash_leaf(chunk))
+            .collect();
+        self.proof.size as u64 == self.file_len.div_ceil(self.chunk_size as u64)
+            && self.proof.indices.iter().copied().eq(self.chunks())
+            && range.end - range.start == chunks.len() as u64
+            && MerkleTree::verify_multiproof_with(hasher, &self.proof, &leaves, root)
+    }
+
+    fn chunks(&self) -> Range<usize> {
+        chunk_indices(self.chunk_size, self.offset, self.len)
+    }
+}
+
+/// Indices of the chunks holding the `len` bytes at `offset`, or the byte at
+/// `offset` if `len` is zero.
+fn chunk_indices(chunk_size: usize, offset: u64, len: u64) -> Range<usize> {
+    let first = offset / chunk_size as u64;
+    let last = (offset + len.max(1) - 1) / chunk_size as u64;
+    first as usize..last as usize + 1
+}
+
+/// Reads until `buf` is full or the reader is exhausted.
+fn read_chunk(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_reader_chunks_input() {
+        let data: Vec<u8> = (0u8..10).collect();
+        let file = FileHasher::new(4).hash_reader(&data[..]).unwrap();
+        assert_eq!(file.chunk_count(), 3);
+        assert_eq!(file.file_len(), 10);
+
+        let mut expected = MerkleTree::new(2);
+        for chunk in data.chunks(4) {
+            expected.append_data(chunk);
+        }
+        assert_eq!(file.root(), expected.root());
+    }
+
+    #[test]
+    fn test_chunk_proofs() {
+        let data: Vec<u8> = (0u8..10).collect();
+        let file = FileHasher::new(4).hash_reader(&data[..]).unwrap();
+        let root = file.root();
+
+        for (index, chunk) in data.chunks(4).enumerate() {
+            let proof = file.prove_chunk(index).unwrap();
+            let range = proof.range();
+            assert_eq!(&data[range.start as usize..range.end as usize], chunk);
+            assert!(proof.verify(chunk, root));
+        }
+
+        let last = file.prove_chunk(2).unwrap();
+        assert!(!last.verify(&[8, 9, 0], root));
+        assert!(!file.prove_chunk(0).unwrap().verify(&data[4..8], root));
+    }
+
+    #[test]
+    fn test_byte_range_proofs() {
+        let data: Vec<u8> = (0u8..=255).cycle().take(1000).collect();
+        let file = FileHasher::new(64).hash_reader(&data[..]).unwrap();
+        let root = file.root();
+
+        for (offset, len) in [(0, 1), (100, 200), (63, 2), (960, 40), (999, 0), (0, 1000)] {
+            let proof = file.prove_range(offset, len).unwrap();
+            let chunks = proof.chunk_range();
+            let bytes = &data[chunks.start as usize..chunks.end as usize];
+            assert!(proof.verify(bytes, root));
+            let start = (offset - chunks.start) as usize;
+            assert_eq!(
+                &bytes[start..][..len as usize],
+                &data[offset as usize..][..len as usize]
+            );
+
+            let mut tampered = bytes.to_vec();
+            tampered[0] ^= 1;
+            assert!(!proof.verify(&tampered, root));
+        }
+        // Bytes 100..300 span chunks 1 to 4 of 16, proven with four
+        // siblings where four chunk proofs would take sixteen.
+        assert_eq!(file.prove_range(100, 200).unwrap().proof.nodes.len(), 4);
+        assert!(file.prove_range(900, 101).is_err());
+        assert!(file.prove_range(1000, 0).is_err());
+    }
+}
@@ -0,0 +1,271 @@
+use crate::{DomainSeparated, MerkleError, MerkleTree, NodeHasher, Proof, Sha256Hasher};
+
+/// Many shard trees under a top tree whose leaves are the shard roots.
+///
+/// Shards are independent [`MerkleTree`]s, so batches for different shards
+/// can be appended in parallel, and a shard's root can be published as soon
+/// as the shard is done while others keep growing. The forest root commits
+/// to every shard: after each append the shard's new root replaces its leaf
+/// in the top tree, which costs one path rehash.
+#[derive(Debug, Clone)]
+pub struct Forest<H = DomainSeparated> {
+    shard_depth: usize,
+    shards: Vec<MerkleTree<H>>,
+    top: MerkleTree<H>,
+}
+
+/// Inclusion proof for a leaf of a [`Forest`]: the leaf up to its shard's
+/// root, then that root up to the forest root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+pub struct ForestProof {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub shard_root: [u8; 32],
+    /// Proof of the leaf in its shard.
+    pub leaf: Proof,
+    /// Proof of `shard_root` in the top tree; its index is the shard's.
+    pub shard: Proof,
+}
+
+impl ForestProof {
+    /// Verifies the proof with the default [`MerkleTree`] hasher.
+    pub fn verify(&self, leaf: [u8; 32], root: [u8; 32]) -> bool {
+        self.verify_with(&DomainSeparated(Sha256Hasher), leaf, root)
+    }
+
+    pub fn verify_with<H: NodeHasher>(&self, hasher: &H, leaf: [u8; 32], root: [u8; 32]) -> bool {
+        self.leaf.verify_with(hasher, leaf, self.shard_root)
+            && self.shard.verify_with(hasher, self.shard_root, root)
+    }
+}
+
+impl Forest {
+    /// A forest of up to `2^depth` shards holding up to `2^shard_depth`
+    /// leaves each, hashed with domain-separated SHA-256.
+    pub fn new(shard_depth: usize, depth: usize) -> Result<Self, MerkleError> {
+        Self::with_hasher(shard_depth, depth, DomainSeparated(Sha256Hasher))
+    }
+}
+
+impl<H: NodeHasher + Clone> Forest<H> {
+    pub fn with_hasher(shard_depth: usize, depth: usize, hasher: H) -> Result<Self, MerkleError> {
+        MerkleTree::try_with_hasher(shard_depth, hasher.clone())?;
+        Ok(Self {
+            shard_depth,
+            shards: Vec::new(),
+            top: MerkleTree::try_with_hasher(depth, hasher)?,
+        })
+    }
+
+    pub fn shard_depth(&self) -> usize {
+        self.shard_depth
+    }
+
+    /// Depth of the top tree.
+    pub fn depth(&self) -> usize {
+        self.top.depth()
+    }
+
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    pub fn shard(&self, shard: usize) -> Option<&MerkleTree<H>> {
+        self.shards.get(shard)
+    }
+
+    /// The top tree, whose leaves are the shard roots.
+    pub fn top(&self) -> &MerkleTree<H> {
+        &self.top
+    }
+
+    /// Total number of leaves across all shards.
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(MerkleTree::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(MerkleTree::is_empty)
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        self.top.root()
+    }
+
+    /// Adds an empty shard and returns its index.
+    pub fn add_shard(&mut self) -> Result<usize, MerkleError> {
+        let shard = MerkleTree::with_hasher(self.shard_depth, self.top.hasher().clone());
+        self.top.try_append_leaf(shard.root())?;
+        self.shards.push(shard);
+        Ok(self.shards.len() - 1)
+    }
+
+    /// Appends `leaf` to `shard` and returns its index in the shard.
+    pub fn append_leaf(&mut self, shard: usize, leaf: [u8; 32]) -> Result<usize, MerkleError> {
+        let tree = self.shard_mut(shard)?;
+        tree.try_append_leaf(leaf)?;
+        let (index, root) = (tree.len() - 1, tree.try_root()?);
+        self.top.set_leaf(shard, root)?;
+        Ok(index)
+    }
+
+    /// Appends the leaf derived from `data` with [`NodeHasher::hash_leaf`].
+    pub fn append_data(&mut self, shard: usize, data: &[u8]) -> Result<usize, MerkleError> {
+        let leaf = self.top.hasher().hash_leaf(data);
+        self.append_leaf(shard, leaf)
+    }
+
+    /// Appends `batches[i]` to shard `i`, updating the top tree once per
+    /// shard. Nothing is appended if any batch does not fit.
+    pub fn extend_shards(&mut self, batches: &[Vec<[u8; 32]>]) -> Result<(), MerkleError> {
+        self.check_batches(batches)?;
+        for (tree, batch) in self.shards.iter_mut().zip(batches) {
+            batch.iter().for_each(|&leaf| tree.append_leaf(leaf));
+        }
+        self.update_top(batches)
+    }
+
+    /// Parallel [`Forest::extend_shards`], filling each shard on the rayon
+    /// thread pool.
+    #[cfg(feature = "rayon")]
+    pub fn par_extend_shards(&mut self, batches: &[Vec<[u8; 32]>]) -> Result<(), MerkleError>
+    where
+        H: Send,
+    {
+        use rayon::prelude::*;
+
+        self.check_batches(batches)?;
+        self.shards
+            .par_iter_mut()
+            .zip(batches)
+            .for_each(|(tree, batch)| batch.iter().for_each(|&leaf| tree.append_leaf(leaf)));
+        self.update_top(batches)
+    }
+
+    /// Proof for the leaf at `index` of `shard` against [`Forest::root`].
+    pub fn get_proof(&self, shard: usize, index: usize) -> Result<ForestProof, MerkleError> {
+        let tree = self
+            .shards
+            .get(shard)
+            .ok_or(MerkleError::IndexOutOfBounds {
+                index: shard,
+                len: self.shards.len(),
+            })?;
+        Ok(ForestProof {
+            shard_root: tree.try_root()?,
+            leaf: tree.try_get_proof(index)?,
+            shard: self.top.try_get_proof(shard)?,
+        })
+    }
+
+    fn shard_mut(&mut self, shard: usize) -> Result<&mut MerkleTree<H>, MerkleError> {
+        let len = self.shards.len();
+        self.shards
+            .get_mut(shard)
+            .ok_or(MerkleError::IndexOutOfBounds { index: shard, len })
+    }
+
+    fn check_batches(&self, batches: &[Vec<[u8; 32]>]) -> Result<(), MerkleError> {
+        if batches.len() > self.shards.len() {
+            return Err(MerkleError::IndexOutOfBounds {
+                index: batches.len() - 1,
+                len: self.shards.len(),
+            });
+        }
+        for (tree, batch) in self.shards.iter().zip(batches) {
+            if tree.len() + batch.len() > tree.capacity() {
+                return Err(MerkleError::TreeFull {
+                    capacity: tree.capacity(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn update_top(&mut self, batches: &[Vec<[u8; 32]>]) -> Result<(), MerkleError> {
+        for (shard, batch) in batches.iter().enumerate() {
+            if !batch.is_empty() {
+                self.top.set_leaf(shard, self.shards[shard].try_root()?)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_composite_proofs() {
+        let mut forest = Forest::new(3, 2).unwrap();
+        let empty = forest.root();
+        for _ in 0..3 {
+            forest.add_shard().unwrap();
+        }
+        assert_ne!(forest.root(), empty);
+        for i in 0u8..10 {
+            assert_eq!(
+                forest.append_data(usize::from(i % 3), &[i]).unwrap(),
+                usize::from(i / 3)
+            );
+        }
+        assert_eq!(forest.len(), 10);
+        let root = forest.root();
+        for shard in 0..3 {
+            let tree = forest.shard(shard).unwrap();
+            assert_eq!(forest.top().leaves()[shard], tree.root());
+            for index in 0..tree.len() {
+                let proof = forest.get_proof(shard, index).unwrap();
+                assert!(proof.verify(tree.leaves()[index], root));
+                assert!(!proof.verify([0; 32], root));
+            }
+        }
+        assert!(forest.get_proof(3, 0).is_err());
+        assert!(forest.append_leaf(5, [0; 32]).is_err());
+        forest.add_shard().unwrap();
+        assert_eq!(
+            forest.add_shard(),
+            Err(MerkleError::TreeFull { capacity: 4 })
+        );
+    }
+
+    #[test]
+    fn test_extend_shards_matches_appends() {
+        let batches: Vec<Vec<[u8; 32]>> = (0u8..4)
+            .map(|shard| (0..shard * 2).map(|i| [shard * 16 + i; 32]).collect())
+            .collect();
+        let mut appended = Forest::new(4, 2).unwrap();
+        let mut extended = Forest::new(4, 2).unwrap();
+        for _ in 0..4 {
+            appended.add_shard().unwrap();
+            extended.add_shard().unwrap();
+        }
+        for (shard, batch) in batches.iter().enumerate() {
+            for &leaf in batch {
+                appended.append_leaf(shard, leaf).unwrap();
+            }
+        }
+        extended.extend_shards(&batches).unwrap();
+        assert_eq!(extended.root(), appended.root());
+        assert_eq!(
+            extended.extend_shards(&[vec![[0; 32]; 17]]),
+            Err(MerkleError::TreeFull { capacity: 16 })
+        );
+        assert_eq!(extended.root(), appended.root());
+
+        #[cfg(feature = "rayon")]
+        {
+            let mut parallel = Forest::new(4, 2).unwrap();
+            for _ in 0..4 {
+                parallel.add_shard().unwrap();
+            }
+            parallel.par_extend_shards(&batches).unwrap();
+            assert_eq!(parallel.root(), appended.root());
+        }
+    }
+}
@@ -0,0 +1,399 @@
+use sha2::{Digest, Sha256};
+use sha3::Keccak256;
+
+use crate::MerkleError;
+
+/// Hash function used to combine tree nodes.
+///
+/// Implementors only need to provide [`NodeHasher::hash`]; leaf and node
+/// hashing are derived from it by hashing the data, or the concatenation of
+/// the left and right children, respectively.
+pub trait NodeHasher {
+    /// Hashes the concatenation of `parts` into a 32 byte digest.
+    fn hash(&self, parts: &[&[u8]]) -> [u8; 32];
+
+    /// Derives a leaf from raw leaf data.
+    fn hash_leaf(&self, data: &[u8]) -> [u8; 32] {
+        self.hash(&[data])
+    }
+
+    fn hash_nodes(&self, left: [u8; 32], right: [u8; 32]) -> [u8; 32] {
+        self.hash(&[&left, &right])
+    }
+
+    /// Hashes the children of a node in an n-ary tree, in order. For two
+    /// children this must agree with [`NodeHasher::hash_nodes`].
+    fn hash_children(&self, children: &[[u8; 32]]) -> [u8; 32] {
+        self.hash(&[children.as_flattened()])
+    }
+
+    /// Hashes each consecutive pair of `children` into the matching entry of
+    /// `parents`, which must be half as long.
+    ///
+    /// Hashers can override this to hash a whole level with a vectorized
+    /// backend.
+    fn hash_pairs(&self, children: &[[u8; 32]], parents: &mut [[u8; 32]]) {
+        for (pair, parent) in children.chunks_exact(2).zip(parents) {
+            *parent = self.hash_nodes(pair[0], pair[1]);
+        }
+    }
+
+    /// Like [`NodeHasher::hash_pairs`], but hashing `prefix || left || right`.
+    fn hash_prefixed_pairs(&self, prefix: &[u8], children: &[[u8; 32]], parents: &mut [[u8; 32]]) {
+        for (pair, parent) in children.chunks_exact(2).zip(parents) {
+            *parent = self.hash(&[prefix, &pair[0], &pair[1]]);
+        }
+    }
+}
+
+/// Prefix prepended to leaf data by [`DomainSeparated`].
+pub const LEAF_PREFIX: u8 = 0x00;
+/// Prefix prepended to child nodes by [`DomainSeparated`].
+pub const NODE_PREFIX: u8 = 0x01;
+
+/// Wraps a hasher so leaves and internal nodes are hashed under distinct
+/// prefixes, as in RFC 6962 (Certificate Transparency).
+///
+/// Without it an internal node is a valid preimage for a leaf, letting an
+/// attacker present the two children of a node as leaf data.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DomainSeparated<H = Sha256Hasher>(pub H);
+
+impl<H: NodeHasher> NodeHasher for DomainSeparated<H> {
+    fn hash(&self, parts: &[&[u8]]) -> [u8; 32] {
+        self.0.hash(parts)
+    }
+
+    fn hash_leaf(&self, data: &[u8]) -> [u8; 32] {
+        self.0.hash(&[&[LEAF_PREFIX], data])
+    }
+
+    fn hash_nodes(&self, left: [u8; 32], right: [u8; 32]) -> [u8; 32] {
+        self.0.hash(&[&[NODE_PREFIX], &left, &right])
+    }
+
+    fn hash_children(&self, children: &[[u8; 32]]) -> [u8; 32] {
+        self.0.hash(&[&[NODE_PREFIX], children.as_flattened()])
+    }
+
+    fn hash_pairs(&self, children: &[[u8; 32]], parents: &mut [[u8; 32]]) {
+        self.0
+            .hash_prefixed_pairs(&[NODE_PREFIX], children, parents)
+    }
+}
+
+/// Wraps a hasher so each pair of children is hashed in ascending byte
+/// order, as OpenZeppelin's `MerkleProof.sol` does.
+///
+/// Order no longer depends on the leaf index, so proofs verify from the
+/// siblings alone with [`crate::verify_sorted_proof`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SortedPair<H = Keccak256Hasher>(pub H);
+
+impl<H: NodeHasher> NodeHasher for SortedPair<H> {
+    fn hash(&self, parts: &[&[u8]]) -> [u8; 32] {
+        self.0.hash(parts)
+    }
+
+    fn hash_leaf(&self, data: &[u8]) -> [u8; 32] {
+        self.0.hash_leaf(data)
+    }
+
+    fn hash_nodes(&self, left: [u8; 32], right: [u8; 32]) -> [u8; 32] {
+        if left <= right {
+            self.0.hash_nodes(left, right)
+        } else {
+            self.0.hash_nodes(right, left)
+        }
+    }
+
+    /// Only pairs are sorted; wider nodes are hashed in order.
+    fn hash_children(&self, children: &[[u8; 32]]) -> [u8; 32] {
+        match children {
+            [left, right] => self.hash_nodes(*left, *right),
+            _ => self.0.hash_children(children),
+        }
+    }
+}
+
+/// SHA-256, the default hasher.
+///
+/// Whole levels are hashed with a multi-buffer AVX2 backend when the CPU
+/// supports it and lacks the SHA extensions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Sha256Hasher;
+
+impl NodeHasher for Sha256Hasher {
+    fn hash(&self, parts: &[&[u8]]) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        for part in parts {
+            hasher.update(part);
+        }
+        hasher.finalize().into()
+    }
+
+    #[cfg(feature = "std")]
+    fn hash_pairs(&self, children: &[[u8; 32]], parents: &mut [[u8; 32]]) {
+        crate::simd::sha256_pairs(&[], children, parents)
+    }
+
+    #[cfg(feature = "std")]
+    fn hash_prefixed_pairs(&self, prefix: &[u8], children: &[[u8; 32]], parents: &mut [[u8; 32]]) {
+        crate::simd::sha256_pairs(prefix, children, parents)
+    }
+}
+
+/// SHA-256 applied twice, as Bitcoin hashes transactions and merkle nodes.
+///
+/// Hashing raw transaction bytes as a leaf yields its txid in internal byte
+/// order; block explorers display txids and roots byte-reversed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DoubleSha256Hasher;
+
+impl NodeHasher for DoubleSha256Hasher {
+    fn hash(&self, parts: &[&[u8]]) -> [u8; 32] {
+        Sha256Hasher.hash(&[&Sha256Hasher.hash(parts)])
+    }
+}
+
+/// Keccak-256 as used by the EVM (not NIST SHA3-256).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Keccak256Hasher;
+
+impl NodeHasher for Keccak256Hasher {
+    fn hash(&self, parts: &[&[u8]]) -> [u8; 32] {
+        let mut hasher = Keccak256::new();
+        for part in parts {
+            hasher.update(part);
+        }
+        hasher.finalize().into()
+    }
+}
+
+/// HMAC-SHA256 (RFC 2104) under a secret key, making the root a MAC over
+/// the leaves: without the key, no one can compute roots or forge proofs.
+///
+/// Wrap it in [`DomainSeparated`] to key leaves and nodes under distinct
+/// prefixes, as [`crate::MerkleTree::keyed`] does. `Debug` leaves out the key,
+/// and with the `zeroize` feature it is wiped on drop.
+#[derive(Clone)]
+pub struct HmacSha256Hasher {
+    pub(crate) inner_pad: [u8; 64],
+    pub(crate) outer_pad: [u8; 64],
+}
+
+impl HmacSha256Hasher {
+    /// Keys longer than the 64 byte block are hashed first, as in RFC 2104.
+    pub fn new(key: &[u8]) -> Self {
+        let mut block = [0u8; 64];
+        if key.len() > block.len() {
+            block[..32].copy_from_slice(&Sha256::digest(key));
+        } else {
+            block[..key.len()].copy_from_slice(key);
+        }
+        Self {
+            inner_pad: block.map(|byte| byte ^ 0x36),
+            outer_pad: block.map(|byte| byte ^ 0x5c),
+        }
+    }
+}
+
+impl core::fmt::Debug for HmacSha256Hasher {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("HmacSha256Hasher").finish_non_exhaustive()
+    }
+}
+
+impl NodeHasher for HmacSha256Hasher {
+    fn hash(&self, parts: &[&[u8]]) -> [u8; 32] {
+        let mut inner = Sha256::new();
+        inner.update(self.inner_pad);
+        for part in parts {
+            inner.update(part);
+        }
+        let mut outer = Sha256::new();
+        outer.update(self.outer_pad);
+        outer.update(inner.finalize());
+        outer.finalize().into()
+    }
+}
+
+/// Hash functions selectable at runtime through [`RuntimeHasher`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    Keccak256,
+    DoubleSha256,
+}
+
+impl core::str::FromStr for HashAlgorithm {
+    type Err = MerkleError;
+
+    /// Parses `"sha256"`, `"keccak256"` or `"sha256d"`, ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("sha256") {
+            Ok(Self::Sha256)
+        } else if s.eq_ignore_ascii_case("keccak256") {
+            Ok(Self::Keccak256)
+        } else if s.eq_ignore_ascii_case("sha256d") {
+            Ok(Self::DoubleSha256)
+        } else {
+            Err(MerkleError::InvalidEncoding("unknown hash algorithm"))
+        }
+    }
+}
+
+/// A hasher chosen at runtime rather than through the type system, for
+/// language bindings and configuration files.
+///
+/// The default is domain-separated SHA-256, matching [`crate::MerkleTree::new`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RuntimeHasher {
+    pub algorithm: HashAlgorithm,
+    /// Apply the [`DomainSeparated`] prefixes.
+    pub domain_separation: bool,
+    /// Hash children in ascending order, as [`SortedPair`] does.
+    pub sorted_pairs: bool,
+}
+
+impl Default for RuntimeHasher {
+    fn default() -> Self {
+        Self {
+            algorithm: HashAlgorithm::Sha256,
+            domain_separation: true,
+            sorted_pairs: false,
+        }
+    }
+}
+
+impl RuntimeHasher {
+    fn dispatch<R>(&self, f: impl FnOnce(&dyn NodeHasher) -> R) -> R {
+        match (self.algorithm, self.domain_separation) {
+            (HashAlgorithm::Sha256, true) => f(&DomainSeparated(Sha256Hasher)),
+            (HashAlgorithm::Sha256, false) => f(&Sha256Hasher),
+            (HashAlgorithm::Keccak256, true) => f(&DomainSeparated(Keccak256Hasher)),
+            (HashAlgorithm::Keccak256, false) => f(&Keccak256Hasher),
+            (HashAlgorithm::DoubleSha256, true) => f(&DomainSeparated(DoubleSha256Hasher)),
+            (HashAlgorithm::DoubleSha256, false) => f(&DoubleSha256Hasher),
+        }
+    }
+}
+
+impl NodeHasher for RuntimeHasher {
+    fn hash(&self, parts: &[&[u8]]) -> [u8; 32] {
+        self.dispatch(|hasher| hasher.hash(parts))
+    }
+
+    fn hash_leaf(&self, data: &[u8]) -> [u8; 32] {
+        self.dispatch(|hasher| hasher.hash_leaf(data))
+    }
+
+    fn hash_nodes(&self, left: [u8; 32], right: [u8; 32]) -> [u8; 32] {
+        let (left, right) = if self.sorted_pairs && left > right {
+            (right, left)
+        } else {
+            (left, right)
+        };
+        self.dispatch(|hasher| hasher.hash_nodes(left, right))
+    }
+
+    fn hash_children(&self, children: &[[u8; 32]]) -> [u8; 32] {
+        match children {
+            [left, right] => self.hash_nodes(*left, *right),
+            _ => self.dispatch(|hasher| hasher.hash_children(children)),
+        }
+    }
+
+    fn hash_pairs(&self, children: &[[u8; 32]], parents: &mut [[u8; 32]]) {
+        if self.sorted_pairs {
+            for (pair, parent) in children.chunks_exact(2).zip(parents) {
+                *parent = self.hash_nodes(pair[0], pair[1]);
+            }
+            return;
+        }
+        self.dispatch(|hasher| hasher.hash_pairs(children, parents))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keccak_empty_input() {
+        let expected = [
+            0xc5, 0xd2, 0x46, 0x01, 0x86, 0xf7, 0x23, 0x3c, 0x92, 0x7e, 0x7d, 0xb2, 0xdc, 0xc7,
+            0x03, 0xc0, 0xe5, 0x00, 0xb6, 0x53, 0xca, 0x82, 0x27, 0x3b, 0x7b, 0xfa, 0xd8, 0x04,
+            0x5d, 0x85, 0xa4, 0x70,
+        ];
+        assert_eq!(Keccak256Hasher.hash(&[]), expected);
+    }
+
+    #[test]
+    fn test_domain_separated_prefixes() {
+        let hasher = DomainSeparated(Sha256Hasher);
+        assert_eq!(hasher.hash_leaf(b"x"), Sha256Hasher.hash(&[&[0x00], b"x"]));
+        assert_eq!(
+            hasher.hash_nodes([1u8; 32], [2u8; 32]),
+            Sha256Hasher.hash(&[&[0x01], &[1u8; 32], &[2u8; 32]])
+        );
+        assert_ne!(
+            hasher.hash_nodes([1u8; 32], [2u8; 32]),
+            Sha256Hasher.hash_nodes([1u8; 32], [2u8; 32])
+        );
+    }
+
+    #[test]
+    fn test_hmac_sha256_vectors() {
+        // RFC 4231 test cases 2 and 6 (a key longer than the block size).
+        let mac = HmacSha256Hasher::new(b"Jefe").hash(&[b"what do ya want ", b"for nothing?"]);
+        let expected = [
+            0x5b, 0xdc, 0xc1, 0x46, 0xbf, 0x60, 0x75, 0x4e, 0x6a, 0x04, 0x24, 0x26, 0x08, 0x95,
+            0x75, 0xc7, 0x5a, 0x00, 0x3f, 0x08, 0x9d, 0x27, 0x39, 0x83, 0x9d, 0xec, 0x58, 0xb9,
+            0x64, 0xec, 0x38, 0x43,
+        ];
+        assert_eq!(mac, expected);
+        let mac = HmacSha256Hasher::new(&[0xaa; 131])
+            .hash(&[b"Test Using Larger Than Block-Size Key - Hash Key First"]);
+        let expected = [
+            0x60, 0xe4, 0x31, 0x59, 0x1e, 0xe0, 0xb6, 0x7f, 0x0d, 0x8a, 0x26, 0xaa, 0xcb, 0xf5,
+            0xb7, 0x7f, 0x8e, 0x0b, 0xc6, 0x21, 0x37, 0x28, 0xc5, 0x14, 0x05, 0x46, 0x04, 0x0f,
+            0x0e, 0xe3, 0x7f, 0x54,
+        ];
+        assert_eq!(mac, expected);
+    }
+
+    #[test]
+    fn test_runtime_hasher_matches_static_hashers() {
+        let (left, right) = ([3u8; 32], [4u8; 32]);
+        let keccak = RuntimeHasher {
+            algorithm: HashAlgorithm::Keccak256,
+            domain_separation: false,
+            sorted_pairs: false,
+        };
+        assert_eq!(
+            keccak.hash_nodes(left, right),
+            Keccak256Hasher.hash_nodes(left, right)
+        );
+        assert_eq!(
+            RuntimeHasher::default().hash_leaf(b"a"),
+            DomainSeparated(Sha256Hasher).hash_leaf(b"a")
+        );
+        assert_eq!("Keccak256".parse(), Ok(HashAlgorithm::Keccak256));
+        assert!("md5".parse::<HashAlgorithm>().is_err());
+    }
+
+    #[test]
+    fn test_hash_nodes_concatenates() {
+        let left = [1u8; 32];
+        let right = [2u8; 32];
+        let mut joined = [0u8; 64];
+        joined[..32].copy_from_slice(&left);
+        joined[32..].copy_from_slice(&right);
+        assert_eq!(
+            Sha256Hasher.hash_nodes(left, right),
+            <[u8; 32]>::from(Sha256::digest(joined))
+        );
+    }
+}
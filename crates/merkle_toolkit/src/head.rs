@@ -0,0 +1,124 @@
+use crate::{DomainSeparated, MerkleError, MerkleTree, NodeHasher, NodeStore, Proof, Sha256Hasher};
+
+/// A root together with the number of leaves it was computed over.
+///
+/// A bare root says nothing about the tree's size: with zero padding, a
+/// tree of `n` leaves and the same tree extended by leaves that happen to be
+/// zero share a root, and so does any tree whose proof paths coincide. The
+/// [`TreeHead::commitment`] mixes the size into the root, as SSZ mixes in a
+/// list's length and RFC 6962 signs the size with the root, so a proof
+/// checked against it only holds for a tree of exactly `size` leaves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+pub struct TreeHead {
+    pub size: u64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub root: [u8; 32],
+}
+
+impl TreeHead {
+    /// The commitment under the default [`MerkleTree`] hasher.
+    pub fn commitment(&self) -> [u8; 32] {
+        self.commitment_with(&DomainSeparated(Sha256Hasher))
+    }
+
+    /// `hash_nodes(root, size)`, the size as a little-endian `u64` padded to
+    /// 32 bytes. With plain [`Sha256Hasher`] this is SSZ's
+    /// [`mix_in_length`](crate::ssz::mix_in_length).
+    pub fn commitment_with<H: NodeHasher>(&self, hasher: &H) -> [u8; 32] {
+        let mut size = [0u8; 32];
+        size[..8].copy_from_slice(&self.size.to_le_bytes());
+        hasher.hash_nodes(self.root, size)
+    }
+
+    /// Verifies a proof with the default [`MerkleTree`] hasher.
+    pub fn verify(&self, leaf: [u8; 32], proof: &Proof) -> bool {
+        self.verify_with(&DomainSeparated(Sha256Hasher), leaf, proof)
+    }
+
+    /// Checks that `proof` was made for a tree of this size and leads from
+    /// `leaf` to this root.
+    pub fn verify_with<H: NodeHasher>(&self, hasher: &H, leaf: [u8; 32], proof: &Proof) -> bool {
+        proof.size as u64 == self.size && proof.verify_with(hasher, leaf, self.root)
+    }
+}
+
+impl Proof {
+    /// Verifies the proof against a [`TreeHead::commitment`] under the
+    /// default [`MerkleTree`] hasher.
+    pub fn verify_committed(&self, leaf: [u8; 32], commitment: [u8; 32]) -> bool {
+        self.verify_committed_with(&DomainSeparated(Sha256Hasher), leaf, commitment)
+    }
+
+    /// Recomputes the root from `leaf` and the siblings, and checks that it
+    /// and the proof's size make up `commitment`.
+    pub fn verify_committed_with<H: NodeHasher>(
+        &self,
+        hasher: &H,
+        leaf: [u8; 32],
+        commitment: [u8; 32],
+    ) -> bool {
+        let root =
+            self.siblings
+                .iter()
+                .enumerate()
+                .fold(leaf, |node, (level, &sibling)| {
+                    match self.index.checked_shr(level as u32).unwrap_or(0) & 1 {
+                        0 => hasher.hash_nodes(node, sibling),
+                        _ => hasher.hash_nodes(sibling, node),
+                    }
+                });
+        let head = TreeHead {
+            size: self.size as u64,
+            root,
+        };
+        self.index < self.size && head.commitment_with(hasher) == commitment
+    }
+}
+
+impl<H: NodeHasher, S: NodeStore> MerkleTree<H, S> {
+    /// The current size and root.
+    pub fn tree_head(&self) -> Result<TreeHead, MerkleError> {
+        Ok(TreeHead {
+            size: self.len() as u64,
+            root: self.try_root()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commitment_binds_size() {
+        let mut tree = MerkleTree::legacy(3);
+        tree.append_leaf([5; 32]);
+        tree.append_leaf([6; 32]);
+        let mut padded = tree.clone();
+        padded.append_leaf([0; 32]);
+        // A zero leaf leaves the root unchanged, but not the head.
+        assert_eq!(tree.root(), padded.root());
+        let (head, padded_head) = (tree.tree_head().unwrap(), padded.tree_head().unwrap());
+        assert_ne!(
+            head.commitment_with(&Sha256Hasher),
+            padded_head.commitment_with(&Sha256Hasher)
+        );
+        assert_eq!(
+            head.commitment_with(&Sha256Hasher),
+            crate::ssz::mix_in_length(tree.root(), 2)
+        );
+
+        let proof = tree.get_proof(1);
+        assert!(head.verify_with(&Sha256Hasher, [6; 32], &proof));
+        assert!(!padded_head.verify_with(&Sha256Hasher, [6; 32], &proof));
+        let commitment = head.commitment_with(&Sha256Hasher);
+        assert!(proof.verify_committed_with(&Sha256Hasher, [6; 32], commitment));
+        let replayed = padded.get_proof(1);
+        assert!(!replayed.verify_committed_with(&Sha256Hasher, [6; 32], commitment));
+    }
+}
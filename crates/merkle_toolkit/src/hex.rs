@@ -0,0 +1,55 @@
+//! Lowercase hex encoding used by the textual proof formats.
+
+use crate::MerkleError;
+
+pub fn encode(bytes: &[u8]) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        out.push(DIGITS[usize::from(byte >> 4)] as char);
+        out.push(DIGITS[usize::from(byte & 0xf)] as char);
+    }
+    out
+}
+
+/// Decodes hex in either case, with or without a leading `0x`.
+pub fn decode(hex: &str) -> Result<Vec<u8>, MerkleError> {
+    let hex = hex.strip_prefix("0x").unwrap_or(hex).as_bytes();
+    if hex.len() % 2 != 0 {
+        return Err(MerkleError::InvalidEncoding("odd number of hex digits"));
+    }
+    hex.chunks_exact(2)
+        .map(|pair| Ok(digit(pair[0])? << 4 | digit(pair[1])?))
+        .collect()
+}
+
+/// Decodes exactly 32 bytes of hex.
+pub fn decode_hash(hex: &str) -> Result<[u8; 32], MerkleError> {
+    decode(hex)?
+        .try_into()
+        .map_err(|_| MerkleError::InvalidEncoding("expected 32 bytes of hex"))
+}
+
+fn digit(c: u8) -> Result<u8, MerkleError> {
+    match c {
+        b'0'..=b'9' => Ok(c - b'0'),
+        b'a'..=b'f' => Ok(c - b'a' + 10),
+        b'A'..=b'F' => Ok(c - b'A' + 10),
+        _ => Err(MerkleError::InvalidEncoding("invalid hex digit")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_roundtrip() {
+        let bytes = [0x00, 0x7f, 0xab, 0xff];
+        assert_eq!(encode(&bytes), "007fabff");
+        assert_eq!(decode("0x007FabfF").unwrap(), bytes);
+        assert!(decode("abc").is_err());
+        assert!(decode("zz").is_err());
+        assert!(decode_hash("00").is_err());
+    }
+}
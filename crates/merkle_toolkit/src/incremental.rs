@@ -0,0 +1,164 @@
+use crate::{zero_hashes_with, MerkleError, NodeHasher, Sha256Hasher};
+
+const MAX_DEPTH: usize = usize::BITS as usize - 1;
+
+/// An append-only merkle tree of fixed depth that only stores its right
+/// frontier.
+///
+/// Unfilled positions are treated as empty subtrees, so the root always
+/// covers `2^depth` leaves. Memory use is `O(depth)` regardless of how many
+/// leaves have been appended, and both [`IncrementalMerkleTree::append`] and
+/// [`IncrementalMerkleTree::root`] run in `O(depth)`.
+#[derive(Debug, Clone)]
+pub struct IncrementalMerkleTree<H = Sha256Hasher> {
+    depth: usize,
+    len: usize,
+    /// `frontier[level]` is the last left child seen at `level` that is
+    /// still waiting for its right sibling. The extra slot at `depth` holds
+    /// the root once the tree is full.
+    frontier: Vec<[u8; 32]>,
+    /// `zeros[level]` is the root of an empty subtree of height `level`.
+    zeros: Vec<[u8; 32]>,
+    hasher: H,
+}
+
+impl IncrementalMerkleTree {
+    pub fn new(depth: usize) -> Self {
+        Self::with_hasher(depth, Sha256Hasher)
+    }
+
+    pub fn try_new(depth: usize) -> Result<Self, MerkleError> {
+        Self::try_with_hasher(depth, Sha256Hasher)
+    }
+}
+
+impl<H: NodeHasher> IncrementalMerkleTree<H> {
+    pub fn with_hasher(depth: usize, hasher: H) -> Self {
+        Self::try_with_hasher(depth, hasher).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    pub fn try_with_hasher(depth: usize, hasher: H) -> Result<Self, MerkleError> {
+        if depth > MAX_DEPTH {
+            return Err(MerkleError::DepthTooLarge {
+                depth,
+                max: MAX_DEPTH,
+            });
+        }
+        Ok(Self {
+            depth,
+            len: 0,
+            frontier: vec![[0u8; 32]; depth + 1],
+            zeros: zero_hashes_with(&hasher, depth),
+            hasher,
+        })
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Maximum number of leaves the tree can hold, `2^depth`.
+    pub fn capacity(&self) -> usize {
+        1 << self.depth
+    }
+
+    pub fn append(&mut self, leaf: [u8; 32]) -> Result<(), MerkleError> {
+        if self.len >= self.capacity() {
+            return Err(MerkleError::TreeFull {
+                capacity: self.capacity(),
+            });
+        }
+        let mut node = leaf;
+        let mut index = self.len;
+        self.len += 1;
+        let mut level = 0;
+        while index & 1 == 1 {
+            node = self.hasher.hash_nodes(self.frontier[level], node);
+            index >>= 1;
+            level += 1;
+        }
+        self.frontier[level] = node;
+        Ok(())
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        if self.len == self.capacity() {
+            return self.frontier[self.depth];
+        }
+        let mut node = self.zeros[0];
+        let mut size = self.len;
+        for level in 0..self.depth {
+            node = if size & 1 == 1 {
+                self.hasher.hash_nodes(self.frontier[level], node)
+            } else {
+                self.hasher.hash_nodes(node, self.zeros[level])
+            };
+            size >>= 1;
+        }
+        node
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Root of a depth `depth` tree over `leaves`, padded with zero leaves.
+    fn padded_root(leaves: &[[u8; 32]], depth: usize) -> [u8; 32] {
+        let mut level = leaves.to_vec();
+        level.resize(1 << depth, [0u8; 32]);
+        while level.len() > 1 {
+            level = level
+                .chunks(2)
+                .map(|pair| Sha256Hasher.hash_nodes(pair[0], pair[1]))
+                .collect();
+        }
+        level[0]
+    }
+
+    #[test]
+    fn test_empty_root_is_zero_hash() {
+        let expected = [
+            0xf5, 0xa5, 0xfd, 0x42, 0xd1, 0x6a, 0x20, 0x30, 0x27, 0x98, 0xef, 0x6e, 0xd3, 0x09,
+            0x97, 0x9b, 0x43, 0x00, 0x3d, 0x23, 0x20, 0xd9, 0xf0, 0xe8, 0xea, 0x98, 0x31, 0xa9,
+            0x27, 0x59, 0xfb, 0x4b,
+        ];
+        assert_eq!(IncrementalMerkleTree::new(1).root(), expected);
+    }
+
+    #[test]
+    fn test_append_matches_padded_root() {
+        let mut tree = IncrementalMerkleTree::new(4);
+        let mut leaves = Vec::new();
+        for i in 0u8..16 {
+            let leaf = [i + 1; 32];
+            tree.append(leaf).unwrap();
+            leaves.push(leaf);
+            assert_eq!(tree.root(), padded_root(&leaves, 4));
+        }
+        assert_eq!(
+            tree.append([0u8; 32]).unwrap_err(),
+            MerkleError::TreeFull { capacity: 16 }
+        );
+    }
+
+    #[test]
+    fn test_matches_merkle_tree() {
+        let mut tree = IncrementalMerkleTree::new(7);
+        let mut cached = crate::MerkleTree::legacy(7);
+        assert_eq!(tree.root(), cached.root());
+        for i in 0u8..21 {
+            tree.append([i; 32]).unwrap();
+            cached.append_leaf([i; 32]);
+            assert_eq!(tree.root(), cached.root());
+        }
+    }
+}
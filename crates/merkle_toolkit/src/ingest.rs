@@ -0,0 +1,227 @@
+//! Streaming leaves out of CSV and JSONL snapshots.
+//!
+//! The loaders read one record per line and hand its fields to an encoder,
+//! a closure from the record's fields to a leaf, so a file of any size can
+//! be fed to [`RootBuilder`](crate::RootBuilder) or
+//! [`MerkleTree::append_leaf`](crate::MerkleTree::append_leaf) without being
+//! loaded whole. Errors carry the line number of the offending record.
+//!
+//! CSV fields may be quoted, with `""` for a literal quote, but a record must
+//! fit on one line. JSONL records are objects whose named fields are passed
+//! to the encoder in the order given (feature `json`).
+
+use std::io::{self, BufRead};
+
+use crate::leaf::evm::{packed_leaf, Token};
+use crate::{hex, MerkleError, NodeHasher};
+
+/// The first field as a hex-encoded leaf.
+pub fn hex_leaf(fields: &[&str]) -> Result<[u8; 32], MerkleError> {
+    hex::decode_hash(field(fields, 0)?)
+}
+
+/// The first field's bytes, hashed with [`NodeHasher::hash_leaf`].
+pub fn data_leaf<H: NodeHasher>(hasher: H) -> impl FnMut(&[&str]) -> Result<[u8; 32], MerkleError> {
+    move |fields| Ok(hasher.hash_leaf(field(fields, 0)?.as_bytes()))
+}
+
+/// `keccak256(abi.encodePacked(address, uint256))` of an address and an
+/// amount, the amount in decimal or `0x` hex.
+pub fn address_amount(fields: &[&str]) -> Result<[u8; 32], MerkleError> {
+    let address: [u8; 20] = hex::decode(field(fields, 0)?)?
+        .try_into()
+        .map_err(|_| MerkleError::InvalidEncoding("expected a 20 byte address"))?;
+    let amount = parse_uint256(field(fields, 1)?)?;
+    Ok(packed_leaf(&[
+        Token::Address(address),
+        Token::Uint(256, amount),
+    ]))
+}
+
+/// Parses a non-negative integer below `2^256`, in decimal or `0x` hex, as a
+/// big-endian word.
+pub fn parse_uint256(s: &str) -> Result<[u8; 32], MerkleError> {
+    let invalid = MerkleError::InvalidEncoding("expected a uint256");
+    let (digits, radix) = match s.strip_prefix("0x") {
+        Some(digits) => (digits, 16),
+        None => (s, 10),
+    };
+    if digits.is_empty() {
+        return Err(invalid);
+    }
+    let mut word = [0u8; 32];
+    for c in digits.chars() {
+        let mut carry = c.to_digit(radix).ok_or(invalid.clone())?;
+        for byte in word.iter_mut().rev() {
+            let value = u32::from(*byte) * radix + carry;
+            *byte = value as u8;
+            carry = value >> 8;
+        }
+        if carry != 0 {
+            return Err(invalid);
+        }
+    }
+    Ok(word)
+}
+
+/// Leaves from the CSV records of `reader`, skipping the first line when
+/// `header` is set and blank lines anywhere.
+pub fn csv_leaves<R: BufRead, F>(
+    reader: R,
+    header: bool,
+    mut encode: F,
+) -> impl Iterator<Item = io::Result<[u8; 32]>>
+where
+    F: FnMut(&[&str]) -> Result<[u8; 32], MerkleError>,
+{
+    records(reader, header, move |line| {
+        let fields = split_csv(line)?;
+        let fields: Vec<&str> = fields.iter().map(String::as_str).collect();
+        encode(&fields)
+    })
+}
+
+/// Leaves from the JSON objects of `reader`, one per line, passing the
+/// values of `fields` to `encode`. Strings are passed unquoted and numbers
+/// as written; amounts past `2^64` must be strings to survive parsing.
+#[cfg(feature = "json")]
+pub fn jsonl_leaves<R: BufRead, F>(
+    reader: R,
+    fields: &[&str],
+    mut encode: F,
+) -> impl Iterator<Item = io::Result<[u8; 32]>>
+where
+    F: FnMut(&[&str]) -> Result<[u8; 32], MerkleError>,
+{
+    let names: Vec<String> = fields.iter().map(|name| name.to_string()).collect();
+    records(reader, false, move |line| {
+        let record: serde_json::Map<String, serde_json::Value> = serde_json::from_str(line)
+            .map_err(|_| MerkleError::InvalidEncoding("line is not a JSON object"))?;
+        let values = names
+            .iter()
+            .map(|name| match record.get(name) {
+                Some(serde_json::Value::String(value)) => Ok(value.clone()),
+                Some(serde_json::Value::Number(value)) => Ok(value.to_string()),
+                Some(_) => Err(MerkleError::InvalidEncoding(
+                    "field is not a string or number",
+                )),
+                None => Err(MerkleError::InvalidEncoding("missing field")),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let values: Vec<&str> = values.iter().map(String::as_str).collect();
+        encode(&values)
+    })
+}
+
+fn records<R: BufRead>(
+    reader: R,
+    header: bool,
+    mut leaf: impl FnMut(&str) -> Result<[u8; 32], MerkleError>,
+) -> impl Iterator<Item = io::Result<[u8; 32]>> {
+    reader
+        .lines()
+        .enumerate()
+        .skip(usize::from(header))
+        .filter(|(_, line)| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .map(move |(number, line)| {
+            let line = line?;
+            leaf(line.trim_end_matches('\r')).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: {e}", number + 1),
+                )
+            })
+        })
+}
+
+fn field<'a>(fields: &[&'a str], index: usize) -> Result<&'a str, MerkleError> {
+    fields
+        .get(index)
+        .map(|field| field.trim())
+        .ok_or(MerkleError::InvalidEncoding("record has too few fields"))
+}
+
+fn split_csv(line: &str) -> Result<Vec<String>, MerkleError> {
+    let mut fields = vec![String::new()];
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        let current = fields.last_mut().unwrap();
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                current.push('"');
+            }
+            '"' if quoted => quoted = false,
+            '"' if current.is_empty() => quoted = true,
+            ',' if !quoted => fields.push(String::new()),
+            c => current.push(c),
+        }
+    }
+    if quoted {
+        return Err(MerkleError::InvalidEncoding("unterminated quoted field"));
+    }
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DomainSeparated, Keccak256Hasher, RootBuilder, Sha256Hasher};
+
+    #[test]
+    fn test_csv_encodings() {
+        let csv = "address,amount\n\
+                   0x1111111111111111111111111111111111111111,1000\n\
+                   \n\
+                   \"0x2222222222222222222222222222222222222222\",0x10\r\n";
+        let leaves: Vec<_> = csv_leaves(csv.as_bytes(), true, address_amount)
+            .collect::<io::Result<_>>()
+            .unwrap();
+        let mut packed = vec![0x11; 20];
+        packed.extend_from_slice(&parse_uint256("1000").unwrap());
+        assert_eq!(leaves[0], Keccak256Hasher.hash(&[&packed]));
+        assert_eq!(parse_uint256("0x10").unwrap()[31], 16);
+        assert_eq!(leaves.len(), 2);
+
+        let mut builder = RootBuilder::new();
+        for leaf in csv_leaves(
+            "a\n\"b,\"\"c\"\"\"\n".as_bytes(),
+            false,
+            data_leaf(DomainSeparated(Sha256Hasher)),
+        ) {
+            builder.push(leaf.unwrap());
+        }
+        assert_eq!(builder.len(), 2);
+        assert_eq!(split_csv("\"b,\"\"c\"\"\",d").unwrap(), ["b,\"c\"", "d"]);
+
+        let error = csv_leaves("x,1\n0x11,2\n".as_bytes(), false, address_amount)
+            .next()
+            .unwrap()
+            .unwrap_err();
+        assert!(error.to_string().starts_with("line 1:"));
+        assert!(parse_uint256(&format!("1{}", "0".repeat(78))).is_err());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_jsonl_matches_csv() {
+        let address = "0x3333333333333333333333333333333333333333";
+        let jsonl = format!(
+            "{{\"account\":\"{address}\",\"amount\":42}}\n{{\"amount\":\"7\",\"account\":\"{address}\"}}\n"
+        );
+        let csv = format!("{address},42\n{address},7\n");
+        let from_json: Vec<_> =
+            jsonl_leaves(jsonl.as_bytes(), &["account", "amount"], address_amount)
+                .collect::<io::Result<_>>()
+                .unwrap();
+        let from_csv: Vec<_> = csv_leaves(csv.as_bytes(), false, address_amount)
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(from_json, from_csv);
+        assert!(jsonl_leaves("{}\n".as_bytes(), &["account"], hex_leaf)
+            .next()
+            .unwrap()
+            .is_err());
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+
+use crate::{MerkleError, MerkleTree, NodeHasher, NodeStore};
+
+impl<H: NodeHasher, S: NodeStore> MerkleTree<H, S> {
+    /// Recomputes every cached node from the leaves and checks it against
+    /// the store, along with the shape of each level and, when enabled, the
+    /// leaf index. Reports the first node found out of place.
+    ///
+    /// This reads the whole tree, so it is meant for tests, audits and
+    /// stores restored from outside sources rather than the hot path.
+    pub fn check_invariants(&self) -> Result<(), MerkleError> {
+        let len = self.len();
+        if len > self.capacity() {
+            return Err(MerkleError::CorruptNode {
+                level: 0,
+                index: len,
+            });
+        }
+        let mut level = 0;
+        while self.levels.level_len(level) > 1 {
+            let nodes = self.levels.level_len(level);
+            let parents = self.levels.level_len(level + 1);
+            if parents != nodes.div_ceil(2) {
+                return Err(MerkleError::CorruptNode {
+                    level: level + 1,
+                    index: parents,
+                });
+            }
+            for index in 0..parents {
+                let node = |index| -> Result<[u8; 32], MerkleError> {
+                    Ok(self.levels.get(level, index)?.unwrap_or(self.zeros[level]))
+                };
+                let expected = self
+                    .hasher
+                    .hash_nodes(node(2 * index)?, node(2 * index + 1)?);
+                if self.levels.get(level + 1, index)? != Some(expected) {
+                    return Err(MerkleError::CorruptNode {
+                        level: level + 1,
+                        index,
+                    });
+                }
+            }
+            level += 1;
+        }
+        if self.levels.levels() != level + 1 {
+            return Err(MerkleError::CorruptNode {
+                level: level + 1,
+                index: 0,
+            });
+        }
+        if let Some(map) = &self.leaf_index {
+            let mut expected = HashMap::with_capacity(map.len());
+            for index in 0..len {
+                let leaf = self.levels.get(0, index)?.unwrap_or(self.zeros[0]);
+                expected.entry(leaf).or_insert(index);
+            }
+            if let Some((_, &index)) = map
+                .iter()
+                .find(|(leaf, index)| expected.get(*leaf) != Some(index))
+            {
+                return Err(MerkleError::CorruptNode { level: 0, index });
+            }
+            if map.len() != expected.len() {
+                return Err(MerkleError::CorruptNode { level: 0, index: 0 });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detects_corrupt_nodes() {
+        let mut tree = MerkleTree::new(4).with_leaf_index();
+        for i in 0u8..5 {
+            tree.append_leaf([i; 32]);
+        }
+        tree.check_invariants().unwrap();
+
+        let mut corrupt = tree.clone();
+        corrupt.levels[1][2] = [9; 32];
+        assert_eq!(
+            corrupt.check_invariants(),
+            Err(MerkleError::CorruptNode { level: 1, index: 2 })
+        );
+        let mut stale = tree.clone();
+        stale.levels[0][4] = [7; 32];
+        assert_eq!(
+            stale.check_invariants(),
+            Err(MerkleError::CorruptNode { level: 1, index: 2 })
+        );
+        let mut truncated = tree;
+        truncated.levels[2].pop();
+        assert_eq!(
+            truncated.check_invariants(),
+            Err(MerkleError::CorruptNode { level: 2, index: 1 })
+        );
+    }
+}
@@ -0,0 +1,520 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::str::FromStr;
+
+use crate::{
+    height, zero_hashes_with, DomainSeparated, MerkleError, MerkleTree, NodeHasher, Sha256Hasher,
+};
+
+/// Multicodec of dag-cbor blocks.
+pub const DAG_CBOR: u8 = 0x71;
+
+/// Multihash code of SHA2-256.
+const SHA2_256: u8 = 0x12;
+
+const BASE32: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+// A tree is exported as dag-cbor blocks:
+//
+//   tree:     { "root": CID?, "size": uint, "depth": uint }
+//   leaf:     { "hash": bytes }
+//   internal: { "hash": bytes, "left": CID, "right": CID? }
+//
+// `hash` is the node as the tree's hasher computed it, and a missing
+// `right` is the empty-subtree padding of an odd level. Map keys are in the
+// length-first order dag-cbor requires, so the encoding is canonical and
+// every block has exactly one CID.
+
+/// A version 1 CID of a dag-cbor block hashed with SHA2-256, the only kind
+/// this crate writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Cid([u8; 36]);
+
+impl Cid {
+    /// The CID of a dag-cbor `block`.
+    pub fn of(block: &[u8]) -> Self {
+        let mut bytes = [0u8; 36];
+        bytes[..4].copy_from_slice(&[1, DAG_CBOR, SHA2_256, 32]);
+        bytes[4..].copy_from_slice(&Sha256Hasher.hash(&[block]));
+        Self(bytes)
+    }
+
+    /// The binary form: version, codec, hash code, digest length, digest.
+    pub fn to_bytes(&self) -> [u8; 36] {
+        self.0
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MerkleError> {
+        let cid: [u8; 36] = bytes
+            .try_into()
+            .map_err(|_| MerkleError::InvalidEncoding("CID is not 36 bytes"))?;
+        if cid[..4] != [1, DAG_CBOR, SHA2_256, 32] {
+            return Err(MerkleError::InvalidEncoding(
+                "only CIDv1 dag-cbor sha2-256 is supported",
+            ));
+        }
+        Ok(Self(cid))
+    }
+
+    pub fn digest(&self) -> [u8; 32] {
+        self.0[4..].try_into().unwrap()
+    }
+}
+
+/// The multibase base32 form IPFS tools print, starting with `bafyrei`.
+impl fmt::Display for Cid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut text = String::from("b");
+        let (mut buffer, mut bits) = (0u16, 0);
+        for &byte in &self.0 {
+            buffer = buffer << 8 | u16::from(byte);
+            bits += 8;
+            while bits >= 5 {
+                bits -= 5;
+                text.push(BASE32[usize::from(buffer >> bits & 31)] as char);
+            }
+        }
+        if bits > 0 {
+            text.push(BASE32[usize::from(buffer << (5 - bits) & 31)] as char);
+        }
+        f.write_str(&text)
+    }
+}
+
+impl FromStr for Cid {
+    type Err = MerkleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = MerkleError::InvalidEncoding("not a base32 CID");
+        let digits = s.strip_prefix('b').ok_or(invalid.clone())?;
+        let mut bytes = Vec::with_capacity(36);
+        let (mut buffer, mut bits) = (0u16, 0);
+        for c in digits.bytes() {
+            let value = BASE32.iter().position(|&d| d == c).ok_or(invalid.clone())?;
+            buffer = buffer << 5 | value as u16;
+            bits += 5;
+            if bits >= 8 {
+                bits -= 8;
+                bytes.push((buffer >> bits) as u8);
+            }
+        }
+        Self::from_bytes(&bytes)
+    }
+}
+
+/// A dag-cbor block with its CID.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub cid: Cid,
+    pub data: Vec<u8>,
+}
+
+impl Block {
+    fn new(data: Vec<u8>) -> Self {
+        Self {
+            cid: Cid::of(&data),
+            data,
+        }
+    }
+}
+
+impl MerkleTree {
+    /// Reads a CAR written by [`MerkleTree::write_car`] with the default
+    /// hasher.
+    pub fn read_car(reader: impl Read) -> io::Result<Self> {
+        Self::read_car_with(reader, DomainSeparated(Sha256Hasher))
+    }
+}
+
+impl<H: NodeHasher> MerkleTree<H> {
+    /// Every node as a dag-cbor block, children before parents and the tree
+    /// block last. Its CID is the root of the export.
+    pub fn to_blocks(&self) -> Vec<Block> {
+        let mut blocks = Vec::new();
+        let mut below: Vec<Cid> = Vec::new();
+        for (level, nodes) in self.levels.iter().enumerate() {
+            let mut cids = Vec::with_capacity(nodes.len());
+            for (index, node) in nodes.iter().enumerate() {
+                let mut entries = vec![("hash", Value::Bytes(node.to_vec()))];
+                if level > 0 {
+                    entries.push(("left", Value::Cid(below[2 * index])));
+                    if let Some(&right) = below.get(2 * index + 1) {
+                        entries.push(("right", Value::Cid(right)));
+                    }
+                }
+                let block = Block::new(encode_map(&entries));
+                cids.push(block.cid);
+                blocks.push(block);
+            }
+            below = cids;
+        }
+        let mut entries = Vec::new();
+        if !self.is_empty() {
+            entries.push(("root", Value::Cid(below[0])));
+        }
+        entries.push(("size", Value::Uint(self.len() as u64)));
+        entries.push(("depth", Value::Uint(self.depth as u64)));
+        blocks.push(Block::new(encode_map(&entries)));
+        blocks
+    }
+
+    /// Writes the tree as a CARv1 file rooted at the tree block, with
+    /// repeated blocks (from equal leaves) written once.
+    pub fn write_car(&self, mut writer: impl Write) -> io::Result<()> {
+        let blocks = self.to_blocks();
+        let root = blocks.last().unwrap().cid;
+        let header = encode_map(&[
+            ("roots", Value::Array(vec![Value::Cid(root)])),
+            ("version", Value::Uint(1)),
+        ]);
+        write_varint(&mut writer, header.len() as u64)?;
+        writer.write_all(&header)?;
+        let mut written = HashSet::new();
+        for block in blocks.iter().rev() {
+            if written.insert(block.cid) {
+                write_varint(&mut writer, (36 + block.data.len()) as u64)?;
+                writer.write_all(&block.cid.to_bytes())?;
+                writer.write_all(&block.data)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a CAR holding a tree, checking every block against its CID and
+    /// every node against its children before rebuilding the tree from the
+    /// leaves.
+    pub fn read_car_with(mut reader: impl Read, hasher: H) -> io::Result<Self> {
+        let header = read_section(&mut reader)?.ok_or_else(|| invalid("empty CAR"))?;
+        let mut fields = decode_map(&header)?;
+        let root = match (fields.remove("roots"), fields.remove("version")) {
+            (Some(Value::Array(roots)), Some(Value::Uint(1))) => match roots[..] {
+                [Value::Cid(root)] => root,
+                _ => return Err(invalid("CAR must have exactly one root")),
+            },
+            _ => return Err(invalid("not a CARv1 header")),
+        };
+
+        let mut blocks = HashMap::new();
+        while let Some(section) = read_section(&mut reader)? {
+            let cid = Cid::from_bytes(section.get(..36).ok_or_else(|| invalid("short block"))?)
+                .map_err(invalid_data)?;
+            let data = section[36..].to_vec();
+            if Cid::of(&data) != cid {
+                return Err(invalid("block does not match its CID"));
+            }
+            blocks.insert(cid, data);
+        }
+
+        let mut tree = fields_of(&blocks, &root)?;
+        let (size, depth) = match (tree.remove("size"), tree.remove("depth")) {
+            (Some(Value::Uint(size)), Some(Value::Uint(depth))) => (
+                usize::try_from(size).map_err(|_| invalid("tree size too large"))?,
+                usize::try_from(depth).map_err(|_| invalid("tree depth too large"))?,
+            ),
+            _ => return Err(invalid("missing tree block")),
+        };
+        // `size` is only checked against the leaves once they are collected.
+        let mut leaves = Vec::new();
+        let top = match tree.remove("root") {
+            Some(Value::Cid(top)) => {
+                let mut walk = Walk {
+                    blocks: &blocks,
+                    hasher: &hasher,
+                    zeros: zero_hashes_with(&hasher, height(size)),
+                    leaves: &mut leaves,
+                };
+                Some(walk.node(&top, height(size))?)
+            }
+            None => None,
+            _ => return Err(invalid("malformed tree block")),
+        };
+        if leaves.len() != size {
+            return Err(invalid("leaf count does not match tree size"));
+        }
+        let tree = Self::from_leaves(depth, hasher, leaves).map_err(invalid_data)?;
+        if top.is_some_and(|top| Ok(top) != tree.node(height(size), 0)) {
+            return Err(invalid("root does not match the leaves"));
+        }
+        Ok(tree)
+    }
+}
+
+struct Walk<'a, H> {
+    blocks: &'a HashMap<Cid, Vec<u8>>,
+    hasher: &'a H,
+    zeros: Vec<[u8; 32]>,
+    leaves: &'a mut Vec<[u8; 32]>,
+}
+
+impl<H: NodeHasher> Walk<'_, H> {
+    /// Collects the leaves below the node block `cid` at `level` and returns
+    /// its checked hash.
+    fn node(&mut self, cid: &Cid, level: usize) -> io::Result<[u8; 32]> {
+        let mut fields = fields_of(self.blocks, cid)?;
+        let hash: [u8; 32] = match fields.remove("hash") {
+            Some(Value::Bytes(hash)) => hash
+                .try_into()
+                .map_err(|_| invalid("node hash is not 32 bytes"))?,
+            _ => return Err(invalid("node without a hash")),
+        };
+        if level == 0 {
+            self.leaves.push(hash);
+            return Ok(hash);
+        }
+        let left = match fields.remove("left") {
+            Some(Value::Cid(left)) => self.node(&left, level - 1)?,
+            _ => return Err(invalid("internal node without a left child")),
+        };
+        let right = match fields.remove("right") {
+            Some(Value::Cid(right)) => self.node(&right, level - 1)?,
+            None => self.zeros[level - 1],
+            _ => return Err(invalid("malformed right child")),
+        };
+        if self.hasher.hash_nodes(left, right) != hash {
+            return Err(invalid("node hash does not match its children"));
+        }
+        Ok(hash)
+    }
+}
+
+fn fields_of(blocks: &HashMap<Cid, Vec<u8>>, cid: &Cid) -> io::Result<HashMap<String, Value>> {
+    let block = blocks
+        .get(cid)
+        .ok_or_else(|| invalid("CAR is missing a block"))?;
+    decode_map(block)
+}
+
+/// The dag-cbor values tree blocks are made of.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Value {
+    Uint(u64),
+    Bytes(Vec<u8>),
+    Array(Vec<Value>),
+    Cid(Cid),
+}
+
+/// CBOR tag for CIDs in dag-cbor.
+const CID_TAG: u64 = 42;
+
+fn encode_head(out: &mut Vec<u8>, major: u8, value: u64) {
+    let major = major << 5;
+    match value {
+        0..=23 => out.push(major | value as u8),
+        24..=0xff => out.extend_from_slice(&[major | 24, value as u8]),
+        0x100..=0xffff => {
+            out.push(major | 25);
+            out.extend_from_slice(&(value as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(major | 26);
+            out.extend_from_slice(&(value as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(major | 27);
+            out.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+}
+
+fn encode_value(out: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Uint(value) => encode_head(out, 0, *value),
+        Value::Bytes(bytes) => {
+            encode_head(out, 2, bytes.len() as u64);
+            out.extend_from_slice(bytes);
+        }
+        Value::Array(values) => {
+            encode_head(out, 4, values.len() as u64);
+            values.iter().for_each(|value| encode_value(out, value));
+        }
+        // The bytes of a CID tag start with the identity multibase prefix.
+        Value::Cid(cid) => {
+            encode_head(out, 6, CID_TAG);
+            encode_head(out, 2, 37);
+            out.push(0);
+            out.extend_from_slice(&cid.to_bytes());
+        }
+    }
+}
+
+/// Encodes a map whose keys are already in dag-cbor order.
+fn encode_map(entries: &[(&str, Value)]) -> Vec<u8> {
+    let mut out = Vec::new();
+    encode_head(&mut out, 5, entries.len() as u64);
+    for (key, value) in entries {
+        encode_head(&mut out, 3, key.len() as u64);
+        out.extend_from_slice(key.as_bytes());
+        encode_value(&mut out, value);
+    }
+    out
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+}
+
+impl Decoder<'_> {
+    fn take(&mut self, len: usize) -> io::Result<&[u8]> {
+        if self.bytes.len() < len {
+            return Err(invalid("truncated dag-cbor"));
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn head(&mut self) -> io::Result<(u8, u64)> {
+        let initial = self.take(1)?[0];
+        let value = match initial & 31 {
+            small @ 0..=23 => u64::from(small),
+            24 => u64::from(self.take(1)?[0]),
+            25 => u64::from(u16::from_be_bytes(self.take(2)?.try_into().unwrap())),
+            26 => u64::from(u32::from_be_bytes(self.take(4)?.try_into().unwrap())),
+            27 => u64::from_be_bytes(self.take(8)?.try_into().unwrap()),
+            _ => return Err(invalid("unsupported dag-cbor length")),
+        };
+        Ok((initial >> 5, value))
+    }
+
+    fn len(&mut self, major: u8) -> io::Result<usize> {
+        match self.head()? {
+            (found, len) if found == major => {
+                usize::try_from(len).map_err(|_| invalid("dag-cbor length too large"))
+            }
+            _ => Err(invalid("unexpected dag-cbor type")),
+        }
+    }
+
+    fn value(&mut self) -> io::Result<Value> {
+        let (major, value) = self.head()?;
+        Ok(match (major, value) {
+            (0, value) => Value::Uint(value),
+            (2, len) => Value::Bytes(self.take(len as usize)?.to_vec()),
+            (4, len) => Value::Array((0..len).map(|_| self.value()).collect::<io::Result<_>>()?),
+            (6, CID_TAG) => {
+                let len = self.len(2)?;
+                match self.take(len)?.split_first() {
+                    Some((0, cid)) => Value::Cid(Cid::from_bytes(cid).map_err(invalid_data)?),
+                    _ => return Err(invalid("malformed CID")),
+                }
+            }
+            _ => return Err(invalid("unsupported dag-cbor value")),
+        })
+    }
+}
+
+fn decode_map(bytes: &[u8]) -> io::Result<HashMap<String, Value>> {
+    let mut decoder = Decoder { bytes };
+    let len = decoder.len(5)?;
+    // Every entry takes at least two bytes, so a lying length cannot
+    // reserve more than the input could hold.
+    let mut map = HashMap::with_capacity(len.min(decoder.bytes.len() / 2));
+    for _ in 0..len {
+        let key_len = decoder.len(3)?;
+        let key = std::str::from_utf8(decoder.take(key_len)?)
+            .map_err(|_| invalid("map key is not UTF-8"))?
+            .to_owned();
+        let value = decoder.value()?;
+        if map.insert(key, value).is_some() {
+            return Err(invalid("duplicate map key"));
+        }
+    }
+    if !decoder.bytes.is_empty() {
+        return Err(invalid("trailing bytes after dag-cbor map"));
+    }
+    Ok(map)
+}
+
+fn write_varint(writer: &mut impl Write, mut value: u64) -> io::Result<()> {
+    while value >= 0x80 {
+        writer.write_all(&[value as u8 | 0x80])?;
+        value >>= 7;
+    }
+    writer.write_all(&[value as u8])
+}
+
+/// Reads one length-prefixed CAR section, or `None` at the end of input.
+fn read_section(reader: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut len = 0u64;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0u8];
+        if reader.read(&mut byte)? == 0 {
+            if shift == 0 {
+                return Ok(None);
+            }
+            return Err(invalid("truncated section length"));
+        }
+        len |= u64::from(byte[0] & 0x7f) << shift;
+        if byte[0] & 0x80 == 0 {
+            // The length is untrusted, so read what is there and then check
+            // it rather than allocating the section up front.
+            let mut section = Vec::new();
+            reader.take(len).read_to_end(&mut section)?;
+            if section.len() as u64 != len {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    MerkleError::InvalidEncoding("truncated section"),
+                ));
+            }
+            return Ok(Some(section));
+        }
+    }
+    Err(invalid("section length overflows"))
+}
+
+fn invalid(reason: &'static str) -> io::Error {
+    invalid_data(MerkleError::InvalidEncoding(reason))
+}
+
+fn invalid_data(error: MerkleError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cid_string() {
+        // The CID of the empty dag-cbor map.
+        let cid = Cid::of(&encode_map(&[]));
+        assert_eq!(
+            cid.to_string(),
+            "bafyreigbtj4x7ip5legnfznufuopl4sg4knzc2cof6duas4b3q2fy6swua"
+        );
+        assert_eq!(cid.to_string().parse::<Cid>().unwrap(), cid);
+        assert!("bafy".parse::<Cid>().is_err());
+    }
+
+    #[test]
+    fn test_car_round_trip() {
+        let mut tree = MerkleTree::new(4);
+        for i in 0u8..11 {
+            tree.append_data(&[i % 5]);
+        }
+        let mut car = Vec::new();
+        tree.write_car(&mut car).unwrap();
+        let loaded = MerkleTree::read_car(&car[..]).unwrap();
+        assert_eq!(loaded.levels, tree.levels);
+        assert_eq!(loaded.depth(), 4);
+
+        // Tampering with any byte of a block breaks its CID.
+        let mut tampered = car.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(MerkleTree::read_car(&tampered[..]).is_err());
+        assert!(MerkleTree::read_car(&car[..car.len() - 40]).is_err());
+
+        let empty = MerkleTree::new(3);
+        let mut car = Vec::new();
+        empty.write_car(&mut car).unwrap();
+        assert!(MerkleTree::read_car(&car[..]).unwrap().is_empty());
+
+        // Section lengths and counts are untrusted and must not be allocated
+        // ahead of the bytes that back them.
+        let error = MerkleTree::read_car(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f][..])
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        assert!(decode_map(&[0xbb, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]).is_err());
+    }
+}
@@ -0,0 +1,675 @@
+//! A Jellyfish Merkle Tree, the versioned authenticated key-value store of
+//! Diem, Aptos and Penumbra.
+//!
+//! Keys are hashed to 256 bit paths and stored in a radix-16 tree, where a
+//! leaf sits at the shallowest node its path is unique under. Each internal
+//! node hashes its 16 child slots as a four level binary tree, so proofs are
+//! plain binary sibling paths. Hashing follows the `jmt` crate with SHA-256,
+//! so roots and proofs are interchangeable with it.
+//!
+//! Every [`JellyfishMerkleTree::put_value_set`] writes the nodes it changes
+//! under a new version and leaves the old ones in place, so every version
+//! stays readable and provable until its stale nodes are pruned.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{MerkleError, NodeHasher, Sha256Hasher};
+
+/// Hash of an empty subtree.
+pub const PLACEHOLDER_HASH: [u8; 32] = *b"SPARSE_MERKLE_PLACEHOLDER_HASH__";
+
+const LEAF_DOMAIN_SEPARATOR: &[u8] = b"JMT::LeafNode";
+const INTERNAL_DOMAIN_SEPARATOR: &[u8] = b"JMT::IntrnalNode";
+
+pub type Version = u64;
+
+/// The tree path of a key: the SHA-256 of its bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+pub struct KeyHash(#[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))] pub [u8; 32]);
+
+impl KeyHash {
+    pub fn with(key: impl AsRef<[u8]>) -> Self {
+        Self(Sha256Hasher.hash(&[key.as_ref()]))
+    }
+
+    fn nibble(&self, index: usize) -> u8 {
+        self.0[index / 2] >> (4 * (1 - index % 2)) & 0xf
+    }
+
+    fn bit(&self, index: usize) -> bool {
+        self.0[index / 8] >> (7 - index % 8) & 1 == 1
+    }
+
+    fn common_prefix_bits(&self, other: &Self) -> usize {
+        (0..256)
+            .find(|&index| self.bit(index) != other.bit(index))
+            .unwrap_or(256)
+    }
+}
+
+/// A key with the hash of its value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+pub struct LeafNode {
+    pub key_hash: KeyHash,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub value_hash: [u8; 32],
+}
+
+impl LeafNode {
+    pub fn new(key_hash: KeyHash, value: &[u8]) -> Self {
+        Self {
+            key_hash,
+            value_hash: Sha256Hasher.hash(&[value]),
+        }
+    }
+
+    pub fn hash(&self) -> [u8; 32] {
+        Sha256Hasher.hash(&[LEAF_DOMAIN_SEPARATOR, &self.key_hash.0, &self.value_hash])
+    }
+}
+
+/// What an internal node keeps about each child.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+pub struct Child {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub hash: [u8; 32],
+    /// Version the child was written at, which locates it in storage.
+    pub version: Version,
+    pub is_leaf: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+pub enum Node {
+    /// The root of an empty tree.
+    Null,
+    /// Children keyed by nibble.
+    Internal(BTreeMap<u8, Child>),
+    Leaf(LeafNode),
+}
+
+impl Node {
+    pub fn hash(&self) -> [u8; 32] {
+        match self {
+            Self::Null => PLACEHOLDER_HASH,
+            Self::Internal(children) => slots_hash(children, 0, 16),
+            Self::Leaf(leaf) => leaf.hash(),
+        }
+    }
+}
+
+/// Where a node is stored: the version that wrote it and its nibble path
+/// from the root, one nibble per byte.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+pub struct NodeKey {
+    pub version: Version,
+    pub nibble_path: Vec<u8>,
+}
+
+impl NodeKey {
+    pub fn root(version: Version) -> Self {
+        Self {
+            version,
+            nibble_path: Vec::new(),
+        }
+    }
+
+    fn child(&self, nibble: u8, version: Version) -> Self {
+        let mut nibble_path = self.nibble_path.clone();
+        nibble_path.push(nibble);
+        Self {
+            version,
+            nibble_path,
+        }
+    }
+}
+
+/// A node superseded at `stale_since_version`, which can be pruned once no
+/// earlier version is read any more.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaleNode {
+    pub stale_since_version: Version,
+    pub node_key: NodeKey,
+}
+
+/// A value write keyed by key and version; `None` records a deletion.
+pub type VersionedValue = ((KeyHash, Version), Option<Vec<u8>>);
+
+/// Everything one [`JellyfishMerkleTree::put_value_set`] call writes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TreeUpdateBatch {
+    pub nodes: Vec<(NodeKey, Node)>,
+    pub stale_nodes: Vec<StaleNode>,
+    pub values: Vec<VersionedValue>,
+}
+
+/// Read access to persisted nodes and values.
+pub trait TreeReader {
+    fn get_node(&self, node_key: &NodeKey) -> Result<Option<Node>, MerkleError>;
+
+    /// The value of `key_hash` as of `version`: the latest one written at or
+    /// before it.
+    fn get_value(
+        &self,
+        key_hash: KeyHash,
+        version: Version,
+    ) -> Result<Option<Vec<u8>>, MerkleError>;
+}
+
+/// Persists the output of [`JellyfishMerkleTree::put_value_set`].
+pub trait TreeWriter {
+    fn write_batch(&mut self, batch: &TreeUpdateBatch) -> Result<(), MerkleError>;
+}
+
+/// An in-memory [`TreeReader`] and [`TreeWriter`].
+#[derive(Debug, Clone, Default)]
+pub struct MemoryTreeStore {
+    nodes: HashMap<NodeKey, Node>,
+    values: BTreeMap<(KeyHash, Version), Option<Vec<u8>>>,
+}
+
+impl MemoryTreeStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Removes the nodes that went stale at or before `version`.
+    pub fn prune(&mut self, stale_nodes: &[StaleNode], version: Version) {
+        for stale in stale_nodes {
+            if stale.stale_since_version <= version {
+                self.nodes.remove(&stale.node_key);
+            }
+        }
+    }
+}
+
+impl TreeReader for MemoryTreeStore {
+    fn get_node(&self, node_key: &NodeKey) -> Result<Option<Node>, MerkleError> {
+        Ok(self.nodes.get(node_key).cloned())
+    }
+
+    fn get_value(
+        &self,
+        key_hash: KeyHash,
+        version: Version,
+    ) -> Result<Option<Vec<u8>>, MerkleError> {
+        Ok(self
+            .values
+            .range((key_hash, 0)..=(key_hash, version))
+            .next_back()
+            .and_then(|(_, value)| value.clone()))
+    }
+}
+
+impl TreeWriter for MemoryTreeStore {
+    fn write_batch(&mut self, batch: &TreeUpdateBatch) -> Result<(), MerkleError> {
+        self.nodes.extend(batch.nodes.iter().cloned());
+        self.values.extend(batch.values.iter().cloned());
+        Ok(())
+    }
+}
+
+/// Proof of a key's value, or of its absence, as a binary sibling path.
+///
+/// `leaf` is the leaf found where the key's path ends, if any; a different
+/// key there proves absence just like an empty slot does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+pub struct SparseMerkleProof {
+    pub leaf: Option<LeafNode>,
+    /// Siblings from the bottom of the path up to the root.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::vec"))]
+    pub siblings: Vec<[u8; 32]>,
+}
+
+impl SparseMerkleProof {
+    /// Checks that `key_hash` maps to `value` under `root`, or is absent
+    /// when `value` is `None`.
+    pub fn verify(&self, root: [u8; 32], key_hash: KeyHash, value: Option<&[u8]>) -> bool {
+        if self.siblings.len() > 256 {
+            return false;
+        }
+        let valid_leaf = match (value, &self.leaf) {
+            (Some(value), Some(leaf)) => *leaf == LeafNode::new(key_hash, value),
+            (Some(_), None) => false,
+            (None, Some(leaf)) => {
+                leaf.key_hash != key_hash
+                    && leaf.key_hash.common_prefix_bits(&key_hash) >= self.siblings.len()
+            }
+            (None, None) => true,
+        };
+        let leaf_hash = self.leaf.map_or(PLACEHOLDER_HASH, |leaf| leaf.hash());
+        let computed =
+            self.siblings
+                .iter()
+                .enumerate()
+                .fold(leaf_hash, |hash, (height, sibling)| {
+                    if key_hash.bit(self.siblings.len() - 1 - height) {
+                        internal_hash(sibling, &hash)
+                    } else {
+                        internal_hash(&hash, sibling)
+                    }
+                });
+        valid_leaf && computed == root
+    }
+}
+
+/// A view of the tree held by `reader`.
+pub struct JellyfishMerkleTree<'a, R> {
+    reader: &'a R,
+}
+
+impl<'a, R: TreeReader> JellyfishMerkleTree<'a, R> {
+    pub fn new(reader: &'a R) -> Self {
+        Self { reader }
+    }
+
+    /// Applies `values` (`None` deletes) on top of `version - 1`, or an empty
+    /// tree if that version doesn't exist, and returns the new root with the
+    /// batch to persist. Nothing is written until the batch is passed to a
+    /// [`TreeWriter`].
+    pub fn put_value_set(
+        &self,
+        values: impl IntoIterator<Item = (KeyHash, Option<Vec<u8>>)>,
+        version: Version,
+    ) -> Result<([u8; 32], TreeUpdateBatch), MerkleError> {
+        let mut update = Update {
+            reader: self.reader,
+            version,
+            batch: TreeUpdateBatch::default(),
+        };
+        let base = version.checked_sub(1).map(NodeKey::root);
+        let mut root = match &base {
+            Some(key) => match self.reader.get_node(key)? {
+                Some(node) => {
+                    update.mark_stale(key.clone());
+                    Pending::from_node(key, node)
+                }
+                None => Pending::Null,
+            },
+            None => Pending::Null,
+        };
+        for (key_hash, value) in values {
+            root = match &value {
+                Some(value) => update.insert(root, 0, LeafNode::new(key_hash, value))?,
+                None => update.delete(root, 0, key_hash)?,
+            };
+            update.batch.values.push(((key_hash, version), value));
+        }
+        let root = update.commit(root, NodeKey::root(version));
+        Ok((root.hash, update.batch))
+    }
+
+    pub fn get_root_hash(&self, version: Version) -> Result<[u8; 32], MerkleError> {
+        Ok(self.root(version)?.hash())
+    }
+
+    pub fn get(&self, key_hash: KeyHash, version: Version) -> Result<Option<Vec<u8>>, MerkleError> {
+        Ok(self.get_with_proof(key_hash, version)?.0)
+    }
+
+    /// The value of `key_hash` at `version` with a proof against that
+    /// version's root.
+    pub fn get_with_proof(
+        &self,
+        key_hash: KeyHash,
+        version: Version,
+    ) -> Result<(Option<Vec<u8>>, SparseMerkleProof), MerkleError> {
+        let mut node_key = NodeKey::root(version);
+        let mut node = self.root(version)?;
+        let mut siblings = Vec::new();
+        let leaf = 'path: loop {
+            let children = match node {
+                Node::Null => break None,
+                Node::Leaf(leaf) => break Some(leaf),
+                Node::Internal(children) => children,
+            };
+            let nibble = key_hash.nibble(node_key.nibble_path.len());
+            let (mut low, mut high) = (0, 16);
+            loop {
+                let mut in_range = children.range(low..high);
+                match (in_range.next(), in_range.next()) {
+                    (None, _) => break 'path None,
+                    (Some((&leaf_nibble, child)), None) if child.is_leaf => {
+                        node_key = node_key.child(leaf_nibble, child.version);
+                        match self.node(&node_key)? {
+                            Node::Leaf(leaf) => break 'path Some(leaf),
+                            _ => return Err(missing(&node_key)),
+                        }
+                    }
+                    (Some((_, child)), None) if high - low == 1 => {
+                        node_key = node_key.child(nibble, child.version);
+                        node = self.node(&node_key)?;
+                        break;
+                    }
+                    _ => {}
+                }
+                let middle = (low + high) / 2;
+                if nibble < middle {
+                    siblings.push(slots_hash(&children, middle, high));
+                    high = middle;
+                } else {
+                    siblings.push(slots_hash(&children, low, middle));
+                    low = middle;
+                }
+            }
+        };
+        siblings.reverse();
+        let value = match leaf {
+            Some(leaf) if leaf.key_hash == key_hash => self.reader.get_value(key_hash, version)?,
+            _ => None,
+        };
+        Ok((value, SparseMerkleProof { leaf, siblings }))
+    }
+
+    fn root(&self, version: Version) -> Result<Node, MerkleError> {
+        self.node(&NodeKey::root(version))
+    }
+
+    fn node(&self, node_key: &NodeKey) -> Result<Node, MerkleError> {
+        self.reader
+            .get_node(node_key)?
+            .ok_or_else(|| missing(node_key))
+    }
+}
+
+/// A subtree being rewritten by [`JellyfishMerkleTree::put_value_set`].
+enum Pending {
+    Null,
+    Leaf(LeafNode),
+    Internal(BTreeMap<u8, Slot>),
+}
+
+/// A child of a pending internal node: untouched in storage, or rewritten.
+enum Slot {
+    Stored(NodeKey, Child),
+    Pending(Pending),
+}
+
+impl Pending {
+    fn from_node(key: &NodeKey, node: Node) -> Self {
+        match node {
+            Node::Null => Self::Null,
+            Node::Leaf(leaf) => Self::Leaf(leaf),
+            Node::Internal(children) => Self::Internal(
+                children
+                    .into_iter()
+                    .map(|(nibble, child)| {
+                        (
+                            nibble,
+                            Slot::Stored(key.child(nibble, child.version), child),
+                        )
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+struct Update<'a, R> {
+    reader: &'a R,
+    version: Version,
+    batch: TreeUpdateBatch,
+}
+
+impl<R: TreeReader> Update<'_, R> {
+    fn load(&mut self, slot: Slot) -> Result<Pending, MerkleError> {
+        match slot {
+            Slot::Pending(pending) => Ok(pending),
+            Slot::Stored(key, _) => {
+                let node = self.reader.get_node(&key)?.ok_or_else(|| missing(&key))?;
+                self.mark_stale(key.clone());
+                Ok(Pending::from_node(&key, node))
+            }
+        }
+    }
+
+    fn mark_stale(&mut self, node_key: NodeKey) {
+        self.batch.stale_nodes.push(StaleNode {
+            stale_since_version: self.version,
+            node_key,
+        });
+    }
+
+    fn insert(
+        &mut self,
+        node: Pending,
+        depth: usize,
+        leaf: LeafNode,
+    ) -> Result<Pending, MerkleError> {
+        Ok(match node {
+            Pending::Null => Pending::Leaf(leaf),
+            Pending::Leaf(existing) if existing.key_hash == leaf.key_hash => Pending::Leaf(leaf),
+            Pending::Leaf(existing) => split(existing, leaf, depth),
+            Pending::Internal(mut children) => {
+                let nibble = leaf.key_hash.nibble(depth);
+                let child = match children.remove(&nibble) {
+                    Some(slot) => {
+                        let child = self.load(slot)?;
+                        self.insert(child, depth + 1, leaf)?
+                    }
+                    None => Pending::Leaf(leaf),
+                };
+                children.insert(nibble, Slot::Pending(child));
+                Pending::Internal(children)
+            }
+        })
+    }
+
+    fn delete(
+        &mut self,
+        node: Pending,
+        depth: usize,
+        key_hash: KeyHash,
+    ) -> Result<Pending, MerkleError> {
+        let mut children = match node {
+            Pending::Leaf(leaf) if leaf.key_hash == key_hash => return Ok(Pending::Null),
+            Pending::Internal(children) => children,
+            node => return Ok(node),
+        };
+        let nibble = key_hash.nibble(depth);
+        if let Some(slot) = children.remove(&nibble) {
+            let child = self.load(slot)?;
+            match self.delete(child, depth + 1, key_hash)? {
+                Pending::Null => {}
+                child => {
+                    children.insert(nibble, Slot::Pending(child));
+                }
+            }
+        }
+        // A lone leaf moves up to replace its parent.
+        let lone_leaf = children.len() == 1
+            && children.values().all(|slot| match slot {
+                Slot::Stored(_, child) => child.is_leaf,
+                Slot::Pending(child) => matches!(child, Pending::Leaf(_)),
+            });
+        if children.is_empty() {
+            Ok(Pending::Null)
+        } else if lone_leaf {
+            let slot = children.into_values().next().unwrap();
+            self.load(slot)
+        } else {
+            Ok(Pending::Internal(children))
+        }
+    }
+
+    /// Writes `node` and its rewritten descendants at `key`.
+    fn commit(&mut self, node: Pending, key: NodeKey) -> Child {
+        let node = match node {
+            Pending::Null => Node::Null,
+            Pending::Leaf(leaf) => Node::Leaf(leaf),
+            Pending::Internal(children) => Node::Internal(
+                children
+                    .into_iter()
+                    .map(|(nibble, slot)| {
+                        let child = match slot {
+                            Slot::Stored(_, child) => child,
+                            Slot::Pending(child) => {
+                                self.commit(child, key.child(nibble, self.version))
+                            }
+                        };
+                        (nibble, child)
+                    })
+                    .collect(),
+            ),
+        };
+        let child = Child {
+            hash: node.hash(),
+            version: self.version,
+            is_leaf: matches!(node, Node::Leaf(_)),
+        };
+        self.batch.nodes.push((key, node));
+        child
+    }
+}
+
+/// The smallest subtree holding two different leaves at `depth`.
+fn split(a: LeafNode, b: LeafNode, depth: usize) -> Pending {
+    let (nibble_a, nibble_b) = (a.key_hash.nibble(depth), b.key_hash.nibble(depth));
+    let children = if nibble_a == nibble_b {
+        BTreeMap::from([(nibble_a, Slot::Pending(split(a, b, depth + 1)))])
+    } else {
+        BTreeMap::from([
+            (nibble_a, Slot::Pending(Pending::Leaf(a))),
+            (nibble_b, Slot::Pending(Pending::Leaf(b))),
+        ])
+    };
+    Pending::Internal(children)
+}
+
+/// Hash of the child slots `low..high` of an internal node, viewed as a
+/// binary tree. Empty ranges hash to the placeholder and a range holding a
+/// single leaf to that leaf.
+fn slots_hash(children: &BTreeMap<u8, Child>, low: u8, high: u8) -> [u8; 32] {
+    let mut in_range = children.range(low..high);
+    match (in_range.next(), in_range.next()) {
+        (None, _) => PLACEHOLDER_HASH,
+        (Some((_, child)), None) if child.is_leaf || high - low == 1 => child.hash,
+        _ => {
+            let middle = (low + high) / 2;
+            internal_hash(
+                &slots_hash(children, low, middle),
+                &slots_hash(children, middle, high),
+            )
+        }
+    }
+}
+
+fn internal_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    Sha256Hasher.hash(&[INTERNAL_DOMAIN_SEPARATOR, left, right])
+}
+
+fn missing(node_key: &NodeKey) -> MerkleError {
+    MerkleError::Storage(format!(
+        "missing node at version {} path {:?}",
+        node_key.version, node_key.nibble_path
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hex;
+
+    fn commit(
+        store: &mut MemoryTreeStore,
+        values: Vec<(&str, Option<&str>)>,
+        version: Version,
+    ) -> [u8; 32] {
+        let values = values
+            .into_iter()
+            .map(|(key, value)| (KeyHash::with(key), value.map(|v| v.as_bytes().to_vec())));
+        let (root, batch) = JellyfishMerkleTree::new(&*store)
+            .put_value_set(values, version)
+            .unwrap();
+        store.write_batch(&batch).unwrap();
+        root
+    }
+
+    #[test]
+    fn test_roots_match_jmt_vectors() {
+        let mut store = MemoryTreeStore::new();
+        let empty = commit(&mut store, vec![], 0);
+        assert_eq!(empty, PLACEHOLDER_HASH);
+        let one = commit(&mut store, vec![("hello", Some("world"))], 1);
+        assert_eq!(
+            hex::encode(&one),
+            "6b97115f56e533f2fd443f7e76e4d1ff94d34e6b2e233f2dbdf5cfd1ec39a30c"
+        );
+        let two = commit(&mut store, vec![("goodbye", Some("world"))], 2);
+        assert_eq!(
+            hex::encode(&two),
+            "e1e1e220d4b4a9611a9a09dbcee964b4a6c3a40f6f4dee265ad45cf64568790b"
+        );
+        // Deleting a key restores the earlier root, and old versions stay
+        // readable.
+        assert_eq!(commit(&mut store, vec![("goodbye", None)], 3), one);
+        let tree = JellyfishMerkleTree::new(&store);
+        assert_eq!(tree.get_root_hash(2).unwrap(), two);
+        assert_eq!(
+            tree.get(KeyHash::with("goodbye"), 2).unwrap(),
+            Some(b"world".to_vec())
+        );
+        assert_eq!(tree.get(KeyHash::with("goodbye"), 3).unwrap(), None);
+    }
+
+    #[test]
+    fn test_inclusion_and_exclusion_proofs() {
+        let mut store = MemoryTreeStore::new();
+        let keys: Vec<String> = (0..200).map(|i| format!("key{i}")).collect();
+        let values = keys
+            .iter()
+            .map(|key| (key.as_str(), Some(key.as_str())))
+            .collect();
+        commit(&mut store, values, 0);
+        let removed = keys[..50].iter().map(|key| (key.as_str(), None)).collect();
+        let root = commit(&mut store, removed, 1);
+
+        let tree = JellyfishMerkleTree::new(&store);
+        for (i, key) in keys.iter().enumerate() {
+            let key_hash = KeyHash::with(key);
+            let (value, proof) = tree.get_with_proof(key_hash, 1).unwrap();
+            let expected = (i >= 50).then_some(key.as_bytes());
+            assert_eq!(value.as_deref(), expected);
+            assert!(proof.verify(root, key_hash, expected));
+            assert!(!proof.verify(root, key_hash, Some(b"other")));
+        }
+        let missing = KeyHash::with("missing");
+        let (value, proof) = tree.get_with_proof(missing, 1).unwrap();
+        assert_eq!(value, None);
+        assert!(proof.verify(root, missing, None));
+        assert!(tree.get_with_proof(missing, 2).is_err());
+    }
+}
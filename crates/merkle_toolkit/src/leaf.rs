@@ -0,0 +1,217 @@
+//! Leaf derivations shared with other ecosystems.
+
+/// Leaves as Solidity derives them, from values encoded with
+/// `abi.encodePacked` or `abi.encode` and hashed with keccak256.
+///
+/// ```
+/// use merkle_toolkit::leaf::evm::{packed_leaf, Token};
+///
+/// // keccak256(abi.encodePacked(account, amount))
+/// let leaf = packed_leaf(&[Token::Address([0x11; 20]), Token::uint256(1000)]);
+/// ```
+pub mod evm {
+    use crate::{Keccak256Hasher, NodeHasher};
+
+    /// A Solidity value. Integer widths are in bits, a multiple of 8 from 8
+    /// to 256; other widths panic when encoded.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum Token {
+        Address([u8; 20]),
+        /// `uintN`, as a big-endian word.
+        Uint(usize, [u8; 32]),
+        /// `intN`, as a sign-extended two's complement word.
+        Int(usize, [u8; 32]),
+        Bool(bool),
+        /// `bytesN`, with N from 1 to 32.
+        FixedBytes(Vec<u8>),
+        Bytes(Vec<u8>),
+        String(String),
+        /// A dynamic array `T[]`.
+        Array(Vec<Token>),
+    }
+
+    impl Token {
+        pub fn uint256(value: u128) -> Self {
+            Self::uint(256, value)
+        }
+
+        pub fn uint(bits: usize, value: u128) -> Self {
+            let mut word = [0u8; 32];
+            word[16..].copy_from_slice(&value.to_be_bytes());
+            Self::Uint(bits, word)
+        }
+
+        pub fn int256(value: i128) -> Self {
+            Self::int(256, value)
+        }
+
+        pub fn int(bits: usize, value: i128) -> Self {
+            let mut word = [if value < 0 { 0xff } else { 0 }; 32];
+            word[16..].copy_from_slice(&value.to_be_bytes());
+            Self::Int(bits, word)
+        }
+
+        fn is_dynamic(&self) -> bool {
+            matches!(self, Self::Bytes(_) | Self::String(_) | Self::Array(_))
+        }
+    }
+
+    /// `abi.encodePacked(tokens...)`: values at their natural width without
+    /// padding, except array elements, which take a full word each.
+    pub fn encode_packed(tokens: &[Token]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for token in tokens {
+            pack(token, false, &mut out);
+        }
+        out
+    }
+
+    /// `abi.encode(tokens...)`: the standard ABI encoding of the tokens as
+    /// a tuple.
+    pub fn encode(tokens: &[Token]) -> Vec<u8> {
+        let mut out = Vec::new();
+        encode_tuple(tokens, &mut out);
+        out
+    }
+
+    /// `keccak256(abi.encodePacked(tokens...))`.
+    pub fn packed_leaf(tokens: &[Token]) -> [u8; 32] {
+        Keccak256Hasher.hash(&[&encode_packed(tokens)])
+    }
+
+    /// `keccak256(abi.encode(tokens...))`.
+    pub fn encoded_leaf(tokens: &[Token]) -> [u8; 32] {
+        Keccak256Hasher.hash(&[&encode(tokens)])
+    }
+
+    /// `keccak256(bytes.concat(keccak256(abi.encode(tokens...))))`, the
+    /// leaf of OpenZeppelin's `StandardMerkleTree`. Hashing twice keeps a
+    /// 64 byte leaf preimage from being mistaken for an internal node.
+    pub fn double_hashed_leaf(tokens: &[Token]) -> [u8; 32] {
+        Keccak256Hasher.hash(&[&encoded_leaf(tokens)])
+    }
+
+    fn pack(token: &Token, in_array: bool, out: &mut Vec<u8>) {
+        match token {
+            Token::Bytes(bytes) => out.extend_from_slice(bytes),
+            Token::String(s) => out.extend_from_slice(s.as_bytes()),
+            Token::Array(items) => items.iter().for_each(|item| pack(item, true, out)),
+            _ if in_array => out.extend_from_slice(&word(token)),
+            Token::Address(address) => out.extend_from_slice(address),
+            Token::Uint(bits, _) | Token::Int(bits, _) => {
+                out.extend_from_slice(&word(token)[32 - bits / 8..])
+            }
+            Token::Bool(value) => out.push(u8::from(*value)),
+            Token::FixedBytes(bytes) => out.extend_from_slice(bytes),
+        }
+    }
+
+    /// The head word of a static token.
+    fn word(token: &Token) -> [u8; 32] {
+        let mut word = [0u8; 32];
+        match token {
+            Token::Address(address) => word[12..].copy_from_slice(address),
+            Token::Uint(bits, value) | Token::Int(bits, value) => {
+                assert!(
+                    bits % 8 == 0 && (8..=256).contains(bits),
+                    "invalid integer width {bits}"
+                );
+                word = *value;
+            }
+            Token::Bool(value) => word[31] = u8::from(*value),
+            Token::FixedBytes(bytes) => word[..bytes.len()].copy_from_slice(bytes),
+            Token::Bytes(_) | Token::String(_) | Token::Array(_) => {
+                unreachable!("dynamic tokens have no head word")
+            }
+        }
+        word
+    }
+
+    fn encode_tuple(tokens: &[Token], out: &mut Vec<u8>) {
+        let mut tail = Vec::new();
+        for token in tokens {
+            if token.is_dynamic() {
+                out.extend_from_slice(&length_word(32 * tokens.len() + tail.len()));
+                encode_tail(token, &mut tail);
+            } else {
+                out.extend_from_slice(&word(token));
+            }
+        }
+        out.extend_from_slice(&tail);
+    }
+
+    fn encode_tail(token: &Token, out: &mut Vec<u8>) {
+        let bytes = match token {
+            Token::Bytes(bytes) => bytes.as_slice(),
+            Token::String(s) => s.as_bytes(),
+            Token::Array(items) => {
+                out.extend_from_slice(&length_word(items.len()));
+                return encode_tuple(items, out);
+            }
+            _ => unreachable!("static tokens have no tail"),
+        };
+        out.extend_from_slice(&length_word(bytes.len()));
+        out.extend_from_slice(bytes);
+        out.resize(out.len().next_multiple_of(32), 0);
+    }
+
+    fn length_word(len: usize) -> [u8; 32] {
+        let mut word = [0u8; 32];
+        word[24..].copy_from_slice(&(len as u64).to_be_bytes());
+        word
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::hex;
+
+        #[test]
+        fn test_encode_packed_matches_solidity_docs() {
+            // abi.encodePacked(int16(-1), bytes1(0x42), uint16(0x03), string("Hello, world!"))
+            let packed = encode_packed(&[
+                Token::int(16, -1),
+                Token::FixedBytes(vec![0x42]),
+                Token::uint(16, 3),
+                Token::String("Hello, world!".into()),
+            ]);
+            assert_eq!(hex::encode(&packed), "ffff42000348656c6c6f2c20776f726c6421");
+            let array = encode_packed(&[Token::Array(vec![Token::Address([1; 20])])]);
+            assert_eq!(array.len(), 32);
+            assert_eq!(
+                packed_leaf(&[Token::Address([1; 20]), Token::uint256(5)]),
+                Keccak256Hasher.hash(&[&[1; 20], &word(&Token::uint256(5))])
+            );
+        }
+
+        #[test]
+        fn test_encode_matches_abi_spec() {
+            // The abi.encode example from the Solidity ABI specification:
+            // (0x123, [0x456, 0x789], "1234567890", "Hello, world!") as
+            // (uint256, uint32[], bytes10, bytes).
+            let encoded = encode(&[
+                Token::uint256(0x123),
+                Token::Array(vec![Token::uint(32, 0x456), Token::uint(32, 0x789)]),
+                Token::FixedBytes(b"1234567890".to_vec()),
+                Token::Bytes(b"Hello, world!".to_vec()),
+            ]);
+            let expected = [
+                "0000000000000000000000000000000000000000000000000000000000000123",
+                "0000000000000000000000000000000000000000000000000000000000000080",
+                "3132333435363738393000000000000000000000000000000000000000000000",
+                "00000000000000000000000000000000000000000000000000000000000000e0",
+                "0000000000000000000000000000000000000000000000000000000000000002",
+                "0000000000000000000000000000000000000000000000000000000000000456",
+                "0000000000000000000000000000000000000000000000000000000000000789",
+                "000000000000000000000000000000000000000000000000000000000000000d",
+                "48656c6c6f2c20776f726c642100000000000000000000000000000000000000",
+            ];
+            assert_eq!(hex::encode(&encoded), expected.concat());
+            let tokens = [Token::Address([2; 20]), Token::uint256(7)];
+            assert_eq!(
+                double_hashed_leaf(&tokens),
+                Keccak256Hasher.hash(&[&encoded_leaf(&tokens)])
+            );
+        }
+    }
+}
@@ -1,174 +1,287 @@
-use sha2::{Digest, Sha256};
+//! Merkle trees, proofs and the tooling around them.
+//!
+//! Everything except proof verification needs the default `std` feature.
+//! Without it the crate is `no_std` and allocation free, exposing only the
+//! hashers and [`verify_proof`].
+//!
+//! With the `tracing` feature, tree construction, proof generation and
+//! verification run inside `tracing` spans named `merkle.*`, recording leaf
+//! counts and indices; a subscriber that times spans, such as
+//! `tracing_subscriber::fmt` with span close events, reports their
+//! durations. Bulk operations use the `debug` level and per-proof ones
+//! `trace`.
 
-#[derive(Debug, Clone)]
-pub struct MerkleTree {
-    pub depth: usize,
-    pub leaves: Vec<[u8; 32]>,
-}
-
-impl MerkleTree {
-    pub fn new(depth: usize) -> Self {
-        assert!(depth <= 27);
-        Self {
-            depth,
-            leaves: Vec::new(),
-        }
-    }
-
-    pub fn append_leaf(&mut self, leaf: [u8; 32]) {
-        self.leaves.push(leaf);
-    }
-
-    pub fn root(&self) -> [u8; 32] {
-        let mut level = self.leaves.clone();
-        while level.len() > 1 {
-            level = level
-                .chunks(2)
-                .map(|pair| {
-                    let left = pair[0];
-                    let right = if pair.len() == 2 { pair[1] } else { [0u8; 32] };
-                    hash_nodes(left, right)
-                })
-                .collect();
-        }
-        if level.is_empty() {
-            [0u8; 32]
-        } else {
-            level[0]
-        }
-    }
-
-    pub fn get_proof(&self, index: usize) -> Vec<[u8; 32]> {
-        assert!(index < self.leaves.len());
-        let mut proof = Vec::new();
-        let mut current_index = index;
-        let mut level = self.leaves.clone();
-
-        while level.len() > 1 {
-            let sibling_index = if current_index % 2 == 0 {
-                current_index + 1
-            } else {
-                current_index - 1
-            };
-            let sibling = if sibling_index < level.len() {
-                level[sibling_index]
-            } else {
-                [0u8; 32]
-            };
-            proof.push(sibling);
-
-            level = level
-                .chunks(2)
-                .map(|pair| {
-                    let left = pair[0];
-                    let right = if pair.len() == 2 { pair[1] } else { [0u8; 32] };
-                    hash_nodes(left, right)
-                })
-                .collect();
-
-            current_index /= 2;
-        }
-
-        proof
-    }
-
-    pub fn get_proof_optimized(&self, index: usize) -> Vec<[u8; 32]> {
-        assert!(index < self.leaves.len());
-        let mut proof = Vec::new();
-        let mut current_index = index;
-        let mut levels: Vec<Vec<[u8; 32]>> = vec![self.leaves.clone()];
-
-        while levels.last().unwrap().len() > 1 {
-            let prev = levels.last().unwrap();
-            let mut next = Vec::with_capacity(prev.len().div_ceil(2));
-            for pair in prev.chunks(2) {
-                let left = pair[0];
-                let right = if pair.len() == 2 { pair[1] } else { [0u8; 32] };
-                next.push(hash_nodes(left, right));
-            }
-            levels.push(next);
-        }
-
-        for level in &levels[..levels.len() - 1] {
-            let sibling_index = if current_index % 2 == 0 {
-                current_index + 1
-            } else {
-                current_index - 1
-            };
-            let sibling = if sibling_index < level.len() {
-                level[sibling_index]
-            } else {
-                [0u8; 32]
-            };
-            proof.push(sibling);
-            current_index /= 2;
-        }
+#![cfg_attr(not(feature = "std"), no_std)]
 
-        proof
-    }
-
-    pub fn verify_proof(leaf: [u8; 32], proof: &[[u8; 32]], index: usize, root: [u8; 32]) -> bool {
-        let mut computed_hash = leaf;
-        let mut idx = index;
-        for sibling in proof {
-            computed_hash = if idx % 2 == 0 {
-                hash_nodes(computed_hash, *sibling)
-            } else {
-                hash_nodes(*sibling, computed_hash)
-            };
-            idx /= 2;
-        }
-        computed_hash == root
-    }
-}
-
-pub fn hash_nodes(left: [u8; 32], right: [u8; 32]) -> [u8; 32] {
-    let mut hasher = Sha256::new();
-    hasher.update(left);
-    hasher.update(right);
-    hasher.finalize().into()
+/// Enters a `tracing` span at `$level` for the rest of the enclosing block
+/// with the `tracing` feature, and expands to nothing without it.
+#[cfg(feature = "std")]
+macro_rules! span {
+    ($level:ident, $name:literal $(, $($fields:tt)*)?) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::span!(tracing::Level::$level, $name $(, $($fields)*)?).entered();
+    };
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use sha2::Sha256;
-
-    #[test]
-    fn test_merkle_root_and_proof() {
-        let mut tree = MerkleTree::new(3);
-
-        let leaf1 = Sha256::digest(b"leaf1").into();
-        let leaf2 = Sha256::digest(b"leaf2").into();
-        let leaf3 = Sha256::digest(b"leaf3").into();
-
-        tree.append_leaf(leaf1);
-        tree.append_leaf(leaf2);
-        tree.append_leaf(leaf3);
+#[cfg(feature = "std")]
+mod append;
+#[cfg(feature = "std")]
+mod async_store;
+#[cfg(feature = "std")]
+mod bao;
+#[cfg(feature = "std")]
+pub mod base64;
+#[cfg(feature = "std")]
+mod batch;
+#[cfg(feature = "std")]
+pub mod bittorrent;
+#[cfg(feature = "std")]
+mod cache;
+#[cfg(feature = "std")]
+mod canopy;
+#[cfg(feature = "serde")]
+pub mod chainpoint;
+#[cfg(feature = "std")]
+mod checkpoint;
+#[cfg(feature = "std")]
+pub mod codegen;
+#[cfg(feature = "std")]
+mod compat;
+#[cfg(feature = "std")]
+mod concurrent;
+#[cfg(feature = "std")]
+mod consistency;
+#[cfg(feature = "std")]
+mod cost;
+#[cfg(feature = "std")]
+pub mod ct;
+#[cfg(feature = "std")]
+mod dir;
+#[cfg(feature = "serde")]
+pub mod distributor;
+#[cfg(feature = "std")]
+mod dot;
+mod error;
+#[cfg(feature = "std")]
+mod file;
+#[cfg(feature = "std")]
+mod forest;
+mod hasher;
+#[cfg(feature = "std")]
+mod head;
+#[cfg(feature = "std")]
+pub mod hex;
+#[cfg(feature = "std")]
+mod incremental;
+#[cfg(feature = "std")]
+pub mod ingest;
+#[cfg(feature = "std")]
+mod invariants;
+#[cfg(feature = "ipld")]
+mod ipld;
+#[cfg(feature = "std")]
+pub mod jmt;
+#[cfg(feature = "std")]
+pub mod leaf;
+#[cfg(feature = "std")]
+mod lookup;
+#[cfg(feature = "mmap")]
+mod mmap;
+#[cfg(feature = "std")]
+mod mst;
+#[cfg(feature = "std")]
+mod multiproof;
+#[cfg(feature = "std")]
+mod nary;
+#[cfg(feature = "std")]
+mod nmt;
+#[cfg(feature = "std")]
+mod observer;
+#[cfg(feature = "std")]
+pub mod ots;
+#[cfg(feature = "std")]
+mod partial;
+#[cfg(feature = "std")]
+mod persistent;
+#[cfg(feature = "std")]
+mod prolly;
+#[cfg(feature = "std")]
+mod proof;
+#[cfg(feature = "std")]
+mod prune;
+#[cfg(feature = "std")]
+pub mod reconcile;
+#[cfg(feature = "std")]
+mod removal;
+#[cfg(feature = "std")]
+mod retention;
+#[cfg(feature = "rocksdb")]
+mod rocks;
+#[cfg(feature = "std")]
+mod salted;
+#[cfg(feature = "zeroize")]
+mod secret;
+#[cfg(feature = "serde")]
+pub mod serde_hex;
+#[cfg(feature = "std")]
+mod shared;
+#[cfg(feature = "std")]
+mod simd;
+#[cfg(feature = "sled")]
+mod sled_store;
+#[cfg(feature = "std")]
+mod snapshot;
+#[cfg(feature = "std")]
+mod sorted;
+#[cfg(feature = "std")]
+mod sparse;
+#[cfg(feature = "std")]
+pub mod ssz;
+#[cfg(feature = "std")]
+mod stats;
+#[cfg(feature = "std")]
+mod sth;
+#[cfg(feature = "std")]
+mod store;
+#[cfg(feature = "std")]
+mod stream;
+#[cfg(feature = "std")]
+mod subtree;
+#[cfg(feature = "std")]
+pub mod telemetry;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+#[cfg(feature = "std")]
+mod transaction;
+#[cfg(feature = "std")]
+mod transparency;
+#[cfg(feature = "std")]
+mod tree;
+#[cfg(feature = "std")]
+mod typed;
+#[cfg(feature = "std")]
+mod update;
+mod verify;
+#[cfg(feature = "std")]
+mod versioned;
+#[cfg(feature = "std")]
+mod wal;
+#[cfg(feature = "std")]
+mod witness;
 
-        let root = tree.root();
-
-        for i in 0..tree.leaves.len() {
-            let leaf = tree.leaves[i];
-            let proof_1 = tree.get_proof(i);
-            assert!(MerkleTree::verify_proof(leaf, &proof_1, i, root));
-            let proof_2 = tree.get_proof_optimized(i);
-            assert!(MerkleTree::verify_proof(leaf, &proof_2, i, root));
-            assert_eq!(proof_1, proof_2);
-        }
-    }
-
-    #[test]
-    fn test_invalid_proof_fails() {
-        let mut tree = MerkleTree::new(3);
-
-        tree.append_leaf(Sha256::digest(b"a").into());
-        tree.append_leaf(Sha256::digest(b"b").into());
-
-        let bad_leaf = Sha256::digest(b"c").into();
-        let proof = tree.get_proof(0);
-        let root = tree.root();
-
-        assert!(!MerkleTree::verify_proof(bad_leaf, &proof, 0, root));
-    }
-}
+#[cfg(feature = "std")]
+pub use async_store::AsyncNodeStore;
+#[cfg(feature = "tokio")]
+pub use async_store::BlockingStore;
+#[cfg(feature = "std")]
+pub use bao::StreamVerifier;
+#[cfg(feature = "std")]
+pub use batch::ProofItem;
+#[cfg(feature = "std")]
+pub use cache::{CacheStats, CachedStore};
+#[cfg(feature = "std")]
+pub use canopy::Canopy;
+#[cfg(feature = "std")]
+pub use checkpoint::CheckpointId;
+#[cfg(feature = "std")]
+pub use compat::{CompatTree, TreeConfig};
+#[cfg(feature = "std")]
+pub use concurrent::{prove_from_leaves, ConcurrentMerkleTree, CONCURRENT_MAX_DEPTH};
+#[cfg(feature = "std")]
+pub use consistency::ConsistencyProof;
+#[cfg(feature = "std")]
+pub use cost::{
+    estimate_calldata_gas, estimate_proof_size, ProofEncoding, CALLDATA_NONZERO_BYTE_GAS,
+    CALLDATA_ZERO_BYTE_GAS,
+};
+#[cfg(feature = "std")]
+pub use dir::{DirectoryHasher, DirectoryTree, EntryChange, FileEntry, FileProof};
+pub use error::MerkleError;
+#[cfg(feature = "std")]
+pub use file::{ByteRangeProof, ChunkProof, FileHasher, FileTree};
+#[cfg(feature = "std")]
+pub use forest::{Forest, ForestProof};
+pub use hasher::{
+    DomainSeparated, DoubleSha256Hasher, HashAlgorithm, HmacSha256Hasher, Keccak256Hasher,
+    NodeHasher, RuntimeHasher, Sha256Hasher, SortedPair, LEAF_PREFIX, NODE_PREFIX,
+};
+#[cfg(feature = "std")]
+pub use head::TreeHead;
+#[cfg(feature = "std")]
+pub use incremental::IncrementalMerkleTree;
+#[cfg(feature = "ipld")]
+pub use ipld::{Block, Cid, DAG_CBOR};
+#[cfg(feature = "mmap")]
+pub use mmap::MmapStore;
+#[cfg(feature = "std")]
+pub use mst::{key_layer, MerkleSearchTree, MstNode};
+#[cfg(feature = "std")]
+pub use multiproof::Multiproof;
+#[cfg(feature = "std")]
+pub use nary::{NaryMerkleTree, NaryProof};
+#[cfg(feature = "std")]
+pub use nmt::{NamespaceProof, NamespacedHash, NamespacedMerkleTree, CELESTIA_NAMESPACE_LEN};
+#[cfg(feature = "std")]
+pub use observer::ObservedMerkleTree;
+#[cfg(feature = "std")]
+pub use partial::PartialMerkleTree;
+#[cfg(feature = "std")]
+pub use persistent::{PersistentHistory, PersistentMerkleTree};
+#[cfg(feature = "std")]
+pub use prolly::{KeyDiff, ProllyNode, ProllyTree, DEFAULT_CHUNK_SIZE};
+#[cfg(feature = "std")]
+pub use proof::{DirectionalProof, Proof};
+#[cfg(feature = "std")]
+pub use prune::PrunedStore;
+#[cfg(feature = "std")]
+pub use removal::TOMBSTONE;
+#[cfg(feature = "std")]
+pub use retention::RetentionPolicy;
+#[cfg(feature = "rocksdb")]
+pub use rocks::{RocksStore, DEFAULT_CACHE_LEVELS};
+#[cfg(feature = "std")]
+pub use salted::{salted_leaf, SaltedMerkleTree, SaltedProof};
+#[cfg(feature = "zeroize")]
+pub use secret::SecretMerkleTree;
+#[cfg(feature = "std")]
+pub use shared::SharedMerkleTree;
+#[cfg(feature = "sled")]
+pub use sled_store::SledStore;
+#[cfg(feature = "std")]
+pub use snapshot::{SNAPSHOT_MAGIC, SNAPSHOT_VERSION};
+#[cfg(feature = "std")]
+pub use sorted::{BoundingLeaf, NonMembershipProof, SortedMerkleTree};
+#[cfg(feature = "std")]
+pub use sparse::{SparseMerkleProof, SparseMerkleTree, SPARSE_DEPTH};
+#[cfg(feature = "std")]
+pub use stats::TreeStats;
+#[cfg(feature = "std")]
+pub use sth::{SignedTreeHead, TreeHeadSigner, TreeHeadVerifier};
+#[cfg(feature = "std")]
+pub use store::{MemoryStore, NodeStore};
+#[cfg(feature = "std")]
+pub use stream::{root_from_iter, root_from_iter_with, RootBuilder};
+#[cfg(feature = "std")]
+pub use subtree::SubtreeProof;
+#[cfg(feature = "std")]
+pub use transaction::TreeTransaction;
+#[cfg(feature = "std")]
+pub use transparency::{LogClient, TransparencyLog};
+#[cfg(feature = "std")]
+pub(crate) use tree::{capacity, height, pad_root};
+#[cfg(feature = "std")]
+pub use tree::{hash_nodes, zero_hashes, zero_hashes_with, MerkleTree, TreeMetadata, MAX_DEPTH};
+#[cfg(feature = "std")]
+pub use typed::{LeafHash, TypedMerkleTree};
+#[cfg(feature = "std")]
+pub use update::LeafUpdate;
+pub use verify::{
+    verify_data_proof, verify_proof, verify_proof_strict, verify_sorted_proof,
+    verify_sorted_proof_with,
+};
+#[cfg(feature = "std")]
+pub use versioned::VersionedMerkleTree;
+#[cfg(feature = "std")]
+pub use wal::{WalMerkleTree, WAL_MAGIC, WAL_VERSION};
+#[cfg(feature = "std")]
+pub use witness::AppendWitness;
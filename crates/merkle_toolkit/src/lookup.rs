@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+
+use crate::{MerkleError, MerkleTree, NodeHasher, NodeStore, Proof};
+
+impl<H: NodeHasher> MerkleTree<H> {
+    /// Keeps a map from each leaf to its index, making
+    /// [`MerkleTree::index_of`] and the lookups built on it `O(1)` at the
+    /// cost of about 40 bytes per distinct leaf.
+    pub fn with_leaf_index(mut self) -> Self {
+        self.enable_leaf_index();
+        self
+    }
+
+    /// Builds the leaf map of [`MerkleTree::with_leaf_index`] for an
+    /// existing tree; appends and updates keep it current from then on.
+    pub fn enable_leaf_index(&mut self) {
+        let mut map = HashMap::with_capacity(self.len());
+        for (index, &leaf) in self.levels[0].iter().enumerate() {
+            map.entry(leaf).or_insert(index);
+        }
+        self.leaf_index = Some(map);
+    }
+
+    pub fn has_leaf_index(&self) -> bool {
+        self.leaf_index.is_some()
+    }
+
+    /// The first index holding `leaf`: a map lookup with the leaf index
+    /// enabled, a scan of the leaves otherwise.
+    pub fn index_of(&self, leaf: &[u8; 32]) -> Option<usize> {
+        match &self.leaf_index {
+            Some(map) => map.get(leaf).copied(),
+            None => self.levels[0]
+                .iter()
+                .position(|candidate| candidate == leaf),
+        }
+    }
+
+    pub fn contains(&self, leaf: &[u8; 32]) -> bool {
+        self.index_of(leaf).is_some()
+    }
+
+    /// Inclusion proof for the first occurrence of `leaf`, if it is in the
+    /// tree.
+    pub fn prove_leaf(&self, leaf: &[u8; 32]) -> Option<Proof> {
+        self.index_of(leaf).map(|index| self.get_proof(index))
+    }
+}
+
+impl<H, S> MerkleTree<H, S> {
+    /// Records `leaf` at a newly written `index`.
+    pub(crate) fn index_leaf(&mut self, index: usize, leaf: [u8; 32]) {
+        if let Some(map) = &mut self.leaf_index {
+            map.entry(leaf).or_insert(index);
+        }
+    }
+}
+
+impl<H: NodeHasher, S: NodeStore> MerkleTree<H, S> {
+    /// Moves the map entry of the leaf at `index` from `old_leaf` to
+    /// `leaf`, falling back to a later copy of `old_leaf` if there is one.
+    pub(crate) fn reindex_leaf(
+        &mut self,
+        index: usize,
+        old_leaf: Option<[u8; 32]>,
+        leaf: [u8; 32],
+    ) -> Result<(), MerkleError> {
+        let Some(map) = &self.leaf_index else {
+            return Ok(());
+        };
+        if let Some(old_leaf) = old_leaf.filter(|old| map.get(old) == Some(&index)) {
+            let mut next = None;
+            for at in index + 1..self.len() {
+                if self.levels.get(0, at)? == Some(old_leaf) {
+                    next = Some(at);
+                    break;
+                }
+            }
+            let map = self.leaf_index.as_mut().unwrap();
+            match next {
+                Some(at) => map.insert(old_leaf, at),
+                None => map.remove(&old_leaf),
+            };
+        }
+        let map = self.leaf_index.as_mut().unwrap();
+        let first = map.entry(leaf).or_insert(index);
+        *first = (*first).min(index);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_with_and_without_index() {
+        let mut plain = MerkleTree::new(4);
+        let mut indexed = MerkleTree::new(4).with_leaf_index();
+        for i in [3u8, 1, 4, 1, 5] {
+            plain.append_leaf([i; 32]);
+            indexed.append_leaf([i; 32]);
+        }
+        assert!(indexed.has_leaf_index() && !plain.has_leaf_index());
+        for tree in [&plain, &indexed] {
+            assert_eq!(tree.index_of(&[1; 32]), Some(1));
+            assert!(tree.contains(&[5; 32]));
+            assert!(!tree.contains(&[9; 32]));
+            let proof = tree.prove_leaf(&[4; 32]).unwrap();
+            assert_eq!(proof.index, 2);
+            assert!(proof.verify([4; 32], tree.root()));
+            assert_eq!(tree.prove_leaf(&[9; 32]), None);
+        }
+    }
+
+    #[test]
+    fn test_index_follows_updates() {
+        let mut tree = MerkleTree::new(3);
+        for i in [7u8, 8, 7] {
+            tree.append_leaf([i; 32]);
+        }
+        tree.enable_leaf_index();
+        tree.set_leaf(0, [9; 32]).unwrap();
+        assert_eq!(tree.index_of(&[7; 32]), Some(2));
+        assert_eq!(tree.index_of(&[9; 32]), Some(0));
+        tree.set_leaf(2, [8; 32]).unwrap();
+        assert!(!tree.contains(&[7; 32]));
+        assert_eq!(tree.index_of(&[8; 32]), Some(1));
+        tree.set_leaf(1, [0; 32]).unwrap();
+        assert_eq!(tree.index_of(&[8; 32]), Some(2));
+    }
+}
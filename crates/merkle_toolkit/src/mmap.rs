@@ -0,0 +1,140 @@
+use std::fs::File;
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use crate::{
+    height, zero_hashes_with, DomainSeparated, MemoryStore, MerkleError, MerkleTree, NodeHasher,
+    NodeStore, Sha256Hasher,
+};
+
+/// A [`NodeStore`] whose leaves are a read-only memory-mapped file of
+/// concatenated 32 byte leaves. Only the levels above the leaves are held in
+/// memory, so the tree costs about as much heap as its leaf count in nodes,
+/// not twice that.
+pub struct MmapStore {
+    leaves: Mmap,
+    /// Levels above the leaves, `upper[0]` being level 1.
+    upper: MemoryStore,
+}
+
+impl MmapStore {
+    pub fn leaves(&self) -> &[[u8; 32]] {
+        // SAFETY: `[u8; 32]` has alignment 1 and the map length is a multiple
+        // of 32, checked when the store was opened.
+        unsafe { std::slice::from_raw_parts(self.leaves.as_ptr().cast(), self.leaves.len() / 32) }
+    }
+}
+
+impl NodeStore for MmapStore {
+    fn levels(&self) -> usize {
+        1 + self.upper.len()
+    }
+
+    fn level_len(&self, level: usize) -> usize {
+        match level {
+            0 => self.leaves().len(),
+            level => self.upper.level_len(level - 1),
+        }
+    }
+
+    fn get(&self, level: usize, index: usize) -> Result<Option<[u8; 32]>, MerkleError> {
+        match level {
+            0 => Ok(self.leaves().get(index).copied()),
+            level => NodeStore::get(&self.upper, level - 1, index),
+        }
+    }
+
+    fn put(&mut self, level: usize, index: usize, node: [u8; 32]) -> Result<(), MerkleError> {
+        match level {
+            0 => Err(MerkleError::Storage(
+                "memory-mapped leaves are read-only".into(),
+            )),
+            level => self.upper.put(level - 1, index, node),
+        }
+    }
+}
+
+impl MerkleTree<DomainSeparated, MmapStore> {
+    /// Maps the leaf file at `path` and builds a tree over it with the
+    /// default hasher. See [`MerkleTree::from_mmap_with`].
+    pub fn from_mmap(path: impl AsRef<Path>) -> Result<Self, MerkleError> {
+        Self::from_mmap_with(path, DomainSeparated(Sha256Hasher))
+    }
+}
+
+impl<H: NodeHasher> MerkleTree<H, MmapStore> {
+    /// Maps the leaf file at `path` read-only and hashes the levels above
+    /// it. The tree has the smallest depth that fits the leaves; appends and
+    /// leaf updates fail with [`MerkleError::Storage`].
+    ///
+    /// The file must not be modified while the tree is alive.
+    pub fn from_mmap_with(path: impl AsRef<Path>, hasher: H) -> Result<Self, MerkleError> {
+        let file = File::open(path).map_err(|e| MerkleError::Storage(e.to_string()))?;
+        // SAFETY: the map is read-only and callers promise not to modify
+        // the file underneath it.
+        let leaves =
+            unsafe { Mmap::map(&file) }.map_err(|e| MerkleError::Storage(e.to_string()))?;
+        if leaves.len() % 32 != 0 {
+            return Err(MerkleError::InvalidEncoding(
+                "leaf file length is not a multiple of 32",
+            ));
+        }
+        let len = leaves.len() / 32;
+        let mut store = MmapStore {
+            leaves,
+            upper: Vec::new(),
+        };
+        let zeros = zero_hashes_with(&hasher, height(len));
+        let mut level = parents(&hasher, store.leaves(), zeros[0]);
+        while !level.is_empty() {
+            let next = parents(&hasher, &level, zeros[store.upper.len() + 1]);
+            store.upper.push(level);
+            level = next;
+        }
+        Self::with_store(height(len), hasher, store)
+    }
+}
+
+/// The level above `children`, pairing an odd last child with the empty
+/// subtree `zero`. Empty once `children` is a single root.
+fn parents<H: NodeHasher>(hasher: &H, children: &[[u8; 32]], zero: [u8; 32]) -> Vec<[u8; 32]> {
+    if children.len() <= 1 {
+        return Vec::new();
+    }
+    let even = children.len() & !1;
+    let mut parents = vec![[0u8; 32]; children.len().div_ceil(2)];
+    hasher.hash_pairs(&children[..even], &mut parents[..even / 2]);
+    if let Some(&last) = children.get(even) {
+        parents[even / 2] = hasher.hash_nodes(last, zero);
+    }
+    parents
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mmap_tree_matches_in_memory() {
+        let path = std::env::temp_dir().join(format!("merkle-mmap-{}", std::process::id()));
+        let hasher = DomainSeparated(Sha256Hasher);
+        let leaves: Vec<[u8; 32]> = (0u8..13).map(|i| hasher.hash_leaf(&[i])).collect();
+        std::fs::write(&path, leaves.concat()).unwrap();
+
+        let mut mapped = MerkleTree::from_mmap(&path).unwrap();
+        let memory = MerkleTree::from_leaves(4, hasher, leaves.clone()).unwrap();
+        assert_eq!(mapped.len(), 13);
+        assert_eq!(mapped.depth(), 4);
+        assert_eq!(mapped.store().leaves(), &leaves[..]);
+        assert_eq!(mapped.root(), memory.root());
+        for index in 0..13 {
+            assert_eq!(mapped.get_proof(index), memory.get_proof(index));
+        }
+        assert!(mapped.try_append_leaf([0u8; 32]).is_err());
+
+        std::fs::write(&path, [0u8; 33]).unwrap();
+        assert!(MerkleTree::from_mmap(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,497 @@
+use std::cmp::Ordering;
+
+use crate::{KeyDiff, NodeHasher, Sha256Hasher};
+
+/// A node of a [`MerkleSearchTree`]: keys of one layer with the subtrees of
+/// lower layers between them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MstNode {
+    layer: u32,
+    /// Subtree holding the keys before the first entry.
+    left: Option<Box<MstNode>>,
+    entries: Vec<MstEntry>,
+    hash: [u8; 32],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MstEntry {
+    key: Vec<u8>,
+    value: Vec<u8>,
+    /// Subtree holding the keys between this entry and the next.
+    right: Option<Box<MstNode>>,
+}
+
+type Subtree = Option<Box<MstNode>>;
+
+impl MstNode {
+    fn new(layer: u32, left: Subtree, entries: Vec<MstEntry>) -> Subtree {
+        if entries.is_empty() {
+            return left;
+        }
+        let hash_of = |subtree: &Subtree| subtree.as_ref().map_or([0u8; 32], |node| node.hash);
+        let mut bytes = layer.to_be_bytes().to_vec();
+        bytes.extend_from_slice(&hash_of(&left));
+        for entry in &entries {
+            bytes.extend_from_slice(&(entry.key.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(&entry.key);
+            bytes.extend_from_slice(&Sha256Hasher.hash(&[&entry.value]));
+            bytes.extend_from_slice(&hash_of(&entry.right));
+        }
+        Some(Box::new(Self {
+            layer,
+            left,
+            entries,
+            hash: Sha256Hasher.hash(&[&bytes]),
+        }))
+    }
+
+    pub fn hash(&self) -> [u8; 32] {
+        self.hash
+    }
+
+    pub fn layer(&self) -> u32 {
+        self.layer
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &[u8]> {
+        self.entries.iter().map(|entry| entry.key.as_slice())
+    }
+
+    /// Splits the node into the keys below and above `key`, which it must
+    /// not hold.
+    fn split(self, key: &[u8]) -> (Subtree, Subtree) {
+        let Self {
+            layer,
+            mut left,
+            mut entries,
+            ..
+        } = self;
+        let upper = entries.split_off(entries.partition_point(|entry| entry.key.as_slice() < key));
+        let straddling = match entries.last_mut() {
+            Some(entry) => &mut entry.right,
+            None => &mut left,
+        };
+        let (low, high) = straddling
+            .take()
+            .map_or((None, None), |node| node.split(key));
+        *straddling = low;
+        (
+            Self::new(layer, left, entries),
+            Self::new(layer, high, upper),
+        )
+    }
+}
+
+/// Joins two subtrees where every key of `low` sorts before those of `high`.
+fn merge(low: Subtree, high: Subtree) -> Subtree {
+    let (low, high) = match (low, high) {
+        (None, high) => return high,
+        (low, None) => return low,
+        (Some(low), Some(high)) => (*low, *high),
+    };
+    match low.layer.cmp(&high.layer) {
+        Ordering::Less => MstNode::new(
+            high.layer,
+            merge(Some(Box::new(low)), high.left),
+            high.entries,
+        ),
+        Ordering::Greater => {
+            let mut entries = low.entries;
+            let last = entries.last_mut().unwrap();
+            last.right = merge(last.right.take(), Some(Box::new(high)));
+            MstNode::new(low.layer, low.left, entries)
+        }
+        Ordering::Equal => {
+            let mut entries = low.entries;
+            let last = entries.last_mut().unwrap();
+            last.right = merge(last.right.take(), high.left);
+            entries.extend(high.entries);
+            MstNode::new(low.layer, low.left, entries)
+        }
+    }
+}
+
+/// Inserts `key` at `layer` below `node`, returning the new subtree and the
+/// value it replaced.
+fn insert(node: Subtree, key: Vec<u8>, value: Vec<u8>, layer: u32) -> (Subtree, Option<Vec<u8>>) {
+    let Some(node) = node else {
+        let entry = MstEntry {
+            key,
+            value,
+            right: None,
+        };
+        return (MstNode::new(layer, None, vec![entry]), None);
+    };
+    if layer > node.layer {
+        let (low, high) = node.split(&key);
+        let entry = MstEntry {
+            key,
+            value,
+            right: high,
+        };
+        return (MstNode::new(layer, low, vec![entry]), None);
+    }
+    let MstNode {
+        layer: node_layer,
+        mut left,
+        mut entries,
+        ..
+    } = *node;
+    let replaced = match entries.binary_search_by(|entry| entry.key.cmp(&key)) {
+        Ok(i) => Some(std::mem::replace(&mut entries[i].value, value)),
+        Err(i) => {
+            let child = match i.checked_sub(1) {
+                Some(previous) => &mut entries[previous].right,
+                None => &mut left,
+            };
+            if layer == node_layer {
+                let (low, high) = child.take().map_or((None, None), |node| node.split(&key));
+                *child = low;
+                entries.insert(
+                    i,
+                    MstEntry {
+                        key,
+                        value,
+                        right: high,
+                    },
+                );
+                None
+            } else {
+                let (subtree, replaced) = insert(child.take(), key, value, layer);
+                *child = subtree;
+                replaced
+            }
+        }
+    };
+    (MstNode::new(node_layer, left, entries), replaced)
+}
+
+fn remove(node: Subtree, key: &[u8]) -> (Subtree, Option<Vec<u8>>) {
+    let Some(node) = node else {
+        return (None, None);
+    };
+    let MstNode {
+        layer,
+        mut left,
+        mut entries,
+        hash,
+    } = *node;
+    let removed = match entries.binary_search_by(|entry| entry.key.as_slice().cmp(key)) {
+        Ok(i) => {
+            let removed = entries.remove(i);
+            let child = match i.checked_sub(1) {
+                Some(previous) => &mut entries[previous].right,
+                None => &mut left,
+            };
+            *child = merge(child.take(), removed.right);
+            Some(removed.value)
+        }
+        Err(i) => {
+            let child = match i.checked_sub(1) {
+                Some(previous) => &mut entries[previous].right,
+                None => &mut left,
+            };
+            let (subtree, removed) = remove(child.take(), key);
+            *child = subtree;
+            removed
+        }
+    };
+    match removed {
+        Some(_) => (MstNode::new(layer, left, entries), removed),
+        None => {
+            let node = MstNode {
+                layer,
+                left,
+                entries,
+                hash,
+            };
+            (Some(Box::new(node)), None)
+        }
+    }
+}
+
+/// A Merkle Search Tree, as used by the AT Protocol and described by Auvolat
+/// and Taïani: a search tree whose shape is fixed by its keys alone.
+///
+/// Each key gets a layer from the number of leading zero bits of its SHA-256,
+/// two bits per layer, so about one key in four moves up a layer. A node
+/// holds the keys of one layer in order, with subtrees of lower layers
+/// between them. Replicas holding the same keys and values therefore end up
+/// with identical trees and root hashes no matter the order writes arrived
+/// in, and [`MerkleSearchTree::diff`] can skip every subtree whose hash the
+/// two sides agree on.
+///
+/// Node hashes cover each key, the SHA-256 of its value and the subtree
+/// hashes; the layout is this crate's own, not the AT Protocol's DAG-CBOR.
+/// Layers with no keys between a node and its subtree are skipped instead
+/// of getting empty nodes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MerkleSearchTree {
+    root: Subtree,
+    len: usize,
+}
+
+/// Layer of `key`: half the leading zero bits of its SHA-256.
+pub fn key_layer(key: &[u8]) -> u32 {
+    let hash = Sha256Hasher.hash(&[key]);
+    let zeros = hash
+        .iter()
+        .position(|&byte| byte != 0)
+        .map_or(256, |i| i as u32 * 8 + hash[i].leading_zeros());
+    zeros / 2
+}
+
+impl MerkleSearchTree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Root hash, or `None` for an empty tree.
+    pub fn root_hash(&self) -> Option<[u8; 32]> {
+        self.root.as_ref().map(|node| node.hash)
+    }
+
+    pub fn root(&self) -> Option<&MstNode> {
+        self.root.as_deref()
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<&[u8]> {
+        let mut node = self.root.as_deref()?;
+        loop {
+            match node
+                .entries
+                .binary_search_by(|entry| entry.key.as_slice().cmp(key))
+            {
+                Ok(i) => return Some(&node.entries[i].value),
+                Err(0) => node = node.left.as_deref()?,
+                Err(i) => node = node.entries[i - 1].right.as_deref()?,
+            }
+        }
+    }
+
+    /// Sets `key` to `value`, returning the value it replaced.
+    pub fn insert(&mut self, key: Vec<u8>, value: Vec<u8>) -> Option<Vec<u8>> {
+        let layer = key_layer(&key);
+        let (root, replaced) = insert(self.root.take(), key, value, layer);
+        self.root = root;
+        if replaced.is_none() {
+            self.len += 1;
+        }
+        replaced
+    }
+
+    pub fn remove(&mut self, key: &[u8]) -> Option<Vec<u8>> {
+        let (root, removed) = remove(self.root.take(), key);
+        self.root = root;
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    /// All entries in key order.
+    pub fn entries(&self) -> Vec<(&[u8], &[u8])> {
+        let mut entries = Vec::with_capacity(self.len);
+        let mut cursor = Cursor::new(self);
+        while let Some(item) = cursor.stack.last() {
+            match item {
+                Item::Node(_) => cursor.expand(),
+                Item::Entry(entry) => {
+                    entries.push((entry.key.as_slice(), entry.value.as_slice()));
+                    cursor.stack.pop();
+                }
+            }
+        }
+        entries
+    }
+
+    /// The changes that turn `self` into `other`, in key order. Subtrees
+    /// with matching hashes on both sides are skipped without being walked,
+    /// so replicas that mostly agree compare in time proportional to their
+    /// differences.
+    pub fn diff(&self, other: &Self) -> Vec<KeyDiff> {
+        let mut diff = Vec::new();
+        let mut ours = Cursor::new(self);
+        let mut theirs = Cursor::new(other);
+        loop {
+            match (ours.stack.last(), theirs.stack.last()) {
+                (None, None) => break,
+                (Some(Item::Node(a)), Some(Item::Node(b))) if a.hash == b.hash => {
+                    ours.stack.pop();
+                    theirs.stack.pop();
+                }
+                (Some(Item::Entry(a)), Some(Item::Entry(b))) => match a.key.cmp(&b.key) {
+                    Ordering::Less => diff.push(ours.removed()),
+                    Ordering::Greater => diff.push(theirs.added()),
+                    Ordering::Equal => {
+                        let (old, new) = (ours.pop_entry(), theirs.pop_entry());
+                        if old.value != new.value {
+                            diff.push(KeyDiff::Modified {
+                                key: old.key.clone(),
+                                old: old.value.clone(),
+                                new: new.value.clone(),
+                            });
+                        }
+                    }
+                },
+                (Some(Item::Entry(_)), None) => diff.push(ours.removed()),
+                (None, Some(Item::Entry(_))) => diff.push(theirs.added()),
+                // Open the higher node first, so subtrees of the same layer
+                // line up for the hash check above.
+                (a, b) => {
+                    if layer(a) >= layer(b) {
+                        ours.expand();
+                    } else {
+                        theirs.expand();
+                    }
+                }
+            }
+        }
+        diff
+    }
+}
+
+enum Item<'a> {
+    Node(&'a MstNode),
+    Entry(&'a MstEntry),
+}
+
+fn layer(item: Option<&Item>) -> i64 {
+    match item {
+        Some(Item::Node(node)) => i64::from(node.layer),
+        _ => -1,
+    }
+}
+
+/// The rest of a tree in key order, as a stack whose top comes first.
+struct Cursor<'a> {
+    stack: Vec<Item<'a>>,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(tree: &'a MerkleSearchTree) -> Self {
+        Self {
+            stack: tree.root.as_deref().map(Item::Node).into_iter().collect(),
+        }
+    }
+
+    fn expand(&mut self) {
+        let Some(Item::Node(node)) = self.stack.pop() else {
+            unreachable!("only nodes are expanded");
+        };
+        for entry in node.entries.iter().rev() {
+            self.stack.extend(entry.right.as_deref().map(Item::Node));
+            self.stack.push(Item::Entry(entry));
+        }
+        self.stack.extend(node.left.as_deref().map(Item::Node));
+    }
+
+    fn pop_entry(&mut self) -> &'a MstEntry {
+        match self.stack.pop() {
+            Some(Item::Entry(entry)) => entry,
+            _ => unreachable!("top of the stack is an entry"),
+        }
+    }
+
+    fn removed(&mut self) -> KeyDiff {
+        let entry = self.pop_entry();
+        KeyDiff::Removed {
+            key: entry.key.clone(),
+            value: entry.value.clone(),
+        }
+    }
+
+    fn added(&mut self) -> KeyDiff {
+        let entry = self.pop_entry();
+        KeyDiff::Added {
+            key: entry.key.clone(),
+            value: entry.value.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(i: u32) -> Vec<u8> {
+        format!("app.bsky.feed.post/{i:06}").into_bytes()
+    }
+
+    #[test]
+    fn test_shape_is_independent_of_insertion_order() {
+        let mut forward = MerkleSearchTree::new();
+        let mut shuffled = MerkleSearchTree::new();
+        for i in 0..1000 {
+            forward.insert(key(i), i.to_be_bytes().to_vec());
+            shuffled.insert(
+                key(i * 7919 % 1000),
+                (i * 7919 % 1000).to_be_bytes().to_vec(),
+            );
+        }
+        assert_eq!(forward, shuffled);
+        assert_eq!(forward.len(), 1000);
+        assert!(forward.root().unwrap().layer() >= 2);
+        assert_eq!(forward.get(&key(42)), Some(&42u32.to_be_bytes()[..]));
+        assert_eq!(forward.get(b"missing"), None);
+        let keys: Vec<_> = forward
+            .entries()
+            .into_iter()
+            .map(|(key, _)| key.to_vec())
+            .collect();
+        assert_eq!(keys, (0..1000).map(key).collect::<Vec<_>>());
+
+        // Removing keys gives the tree that never had them.
+        let mut fewer = MerkleSearchTree::new();
+        for i in 0..1000 {
+            if i % 3 == 0 {
+                assert!(forward.remove(&key(i)).is_some());
+            } else {
+                fewer.insert(key(i), i.to_be_bytes().to_vec());
+            }
+        }
+        assert_eq!(forward.remove(&key(0)), None);
+        assert_eq!(forward.root_hash(), fewer.root_hash());
+        assert_eq!(forward, fewer);
+    }
+
+    #[test]
+    fn test_diff_between_replicas() {
+        let mut a = MerkleSearchTree::new();
+        for i in 0..500 {
+            a.insert(key(i), vec![0]);
+        }
+        let mut b = a.clone();
+        b.remove(&key(3));
+        b.insert(key(250), vec![1]);
+        b.insert(key(900), vec![2]);
+        assert_eq!(
+            a.diff(&b),
+            [
+                KeyDiff::Removed {
+                    key: key(3),
+                    value: vec![0],
+                },
+                KeyDiff::Modified {
+                    key: key(250),
+                    old: vec![0],
+                    new: vec![1],
+                },
+                KeyDiff::Added {
+                    key: key(900),
+                    value: vec![2],
+                },
+            ]
+        );
+        assert!(a.diff(&a.clone()).is_empty());
+        assert_eq!(MerkleSearchTree::new().diff(&a).len(), 500);
+    }
+}
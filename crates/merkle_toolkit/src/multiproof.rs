@@ -0,0 +1,457 @@
+use crate::{height, pad_root, zero_hashes_with, MerkleError, MerkleTree, NodeHasher, MAX_DEPTH};
+
+/// A single proof covering several leaves of the same tree.
+///
+/// Siblings shared between the paths of the proven leaves, or computable from
+/// other proven leaves, are only included once (or not at all). `nodes` are
+/// ordered level by level from the leaves up, and by index within a level.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+pub struct Multiproof {
+    /// Number of leaves in the tree the proof was generated from.
+    pub size: usize,
+    /// Depth of that tree, which the root is padded to.
+    pub depth: usize,
+    /// Proven leaf indices, sorted and deduplicated.
+    pub indices: Vec<usize>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::vec"))]
+    pub nodes: Vec<[u8; 32]>,
+}
+
+impl Multiproof {
+    /// Encodes the proof compactly: the tree size (big-endian `u64`), leaf
+    /// count (big-endian `u32`) and depth (one byte), a bitfield in place of
+    /// the indices, then the nodes.
+    ///
+    /// The bitfield describes the part of the tree holding proven leaves,
+    /// walked depth first from the root: every visited node with a right
+    /// child inside the tree gets one bit per child, set if that child has a
+    /// proven leaf below it. Indices cost two bits per node on the union of
+    /// their paths instead of eight bytes each, and the bitfield is packed
+    /// least significant bit first.
+    pub fn to_compact_bytes(&self) -> Vec<u8> {
+        let mut bits = Vec::new();
+        if let Some(&last) = self.indices.last() {
+            debug_assert!(last < self.size);
+            encode_shape(&self.indices, height(self.size), 0, self.size, &mut bits);
+        }
+        let mut bytes = Vec::with_capacity(13 + bits.len().div_ceil(8) + self.nodes.len() * 32);
+        bytes.extend_from_slice(&(self.size as u64).to_be_bytes());
+        bytes.extend_from_slice(&(self.indices.len() as u32).to_be_bytes());
+        bytes.push(self.depth as u8);
+        for byte in bits.chunks(8) {
+            bytes.push(
+                byte.iter()
+                    .rev()
+                    .fold(0, |acc, &bit| acc << 1 | u8::from(bit)),
+            );
+        }
+        for node in &self.nodes {
+            bytes.extend_from_slice(node);
+        }
+        bytes
+    }
+
+    pub fn from_compact_bytes(bytes: &[u8]) -> Result<Self, MerkleError> {
+        let invalid = MerkleError::InvalidEncoding("malformed compact multiproof");
+        if bytes.len() < 13 {
+            return Err(invalid);
+        }
+        let size = usize::try_from(u64::from_be_bytes(bytes[..8].try_into().unwrap()))
+            .map_err(|_| MerkleError::InvalidEncoding("tree size does not fit in usize"))?;
+        let count = u32::from_be_bytes(bytes[8..12].try_into().unwrap()) as usize;
+        let depth = bytes[12] as usize;
+        if count > size || height(size) > depth || depth > MAX_DEPTH {
+            return Err(invalid);
+        }
+
+        let mut bits = BitReader {
+            bytes: &bytes[13..],
+            position: 0,
+        };
+        // `count` is unchecked until the shape is decoded, so the indices
+        // only grow with the bits actually present.
+        let mut indices = Vec::new();
+        if count > 0 {
+            decode_shape(&mut bits, height(size), 0, size, &mut indices).ok_or(invalid.clone())?;
+        }
+        let nodes = &bytes[13 + bits.position.div_ceil(8)..];
+        if indices.len() != count || nodes.len() % 32 != 0 {
+            return Err(invalid);
+        }
+        Ok(Self {
+            size,
+            depth,
+            indices,
+            nodes: nodes
+                .chunks_exact(32)
+                .map(|node| node.try_into().unwrap())
+                .collect(),
+        })
+    }
+}
+
+/// Appends the shape bits for the subtree at `(level, index)`, which holds
+/// at least one of the sorted `indices`.
+fn encode_shape(indices: &[usize], level: usize, index: usize, size: usize, bits: &mut Vec<bool>) {
+    if level == 0 {
+        return;
+    }
+    let (left, right) = (2 * index, 2 * index + 1);
+    let split = indices.partition_point(|&leaf| leaf < right << (level - 1));
+    let (left_indices, right_indices) = indices.split_at(split);
+    if right << (level - 1) < size {
+        bits.push(!left_indices.is_empty());
+        bits.push(!right_indices.is_empty());
+    }
+    if !left_indices.is_empty() {
+        encode_shape(left_indices, level - 1, left, size, bits);
+    }
+    if !right_indices.is_empty() {
+        encode_shape(right_indices, level - 1, right, size, bits);
+    }
+}
+
+fn decode_shape(
+    bits: &mut BitReader,
+    level: usize,
+    index: usize,
+    size: usize,
+    indices: &mut Vec<usize>,
+) -> Option<()> {
+    if level == 0 {
+        indices.push(index);
+        return Some(());
+    }
+    let (left, right) = (2 * index, 2 * index + 1);
+    let (has_left, has_right) = if right << (level - 1) < size {
+        (bits.next()?, bits.next()?)
+    } else {
+        (true, false)
+    };
+    if !has_left && !has_right {
+        return None;
+    }
+    if has_left {
+        decode_shape(bits, level - 1, left, size, indices)?;
+    }
+    if has_right {
+        decode_shape(bits, level - 1, right, size, indices)?;
+    }
+    Some(())
+}
+
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl BitReader<'_> {
+    fn next(&mut self) -> Option<bool> {
+        let byte = self.bytes.get(self.position / 8)?;
+        let bit = byte >> (self.position % 8) & 1 == 1;
+        self.position += 1;
+        Some(bit)
+    }
+}
+
+impl MerkleTree {
+    /// Verifies `proof` for `leaves`, given in the order of `proof.indices`.
+    ///
+    /// The tree size and depth are taken from the proof as given; use
+    /// [`MerkleTree::verify_multiproof_strict`] when the verifier knows them.
+    pub fn verify_multiproof(proof: &Multiproof, leaves: &[[u8; 32]], root: [u8; 32]) -> bool {
+        Self::verify_multiproof_with(
+            &crate::DomainSeparated(crate::Sha256Hasher),
+            proof,
+            leaves,
+            root,
+        )
+    }
+
+    /// Like [`MerkleTree::verify_multiproof`], but rejects a proof for any
+    /// tree other than one of `size` leaves and `depth` levels.
+    pub fn verify_multiproof_strict(
+        proof: &Multiproof,
+        leaves: &[[u8; 32]],
+        size: usize,
+        depth: usize,
+        root: [u8; 32],
+    ) -> bool {
+        Self::verify_multiproof_strict_with(
+            &crate::DomainSeparated(crate::Sha256Hasher),
+            proof,
+            leaves,
+            size,
+            depth,
+            root,
+        )
+    }
+}
+
+impl<H: NodeHasher> MerkleTree<H> {
+    pub fn get_multiproof(&self, indices: &[usize]) -> Result<Multiproof, MerkleError> {
+        span!(
+            DEBUG,
+            "merkle.prove_multi",
+            indices = indices.len(),
+            leaves = self.len()
+        );
+        let mut indices = indices.to_vec();
+        indices.sort_unstable();
+        indices.dedup();
+        if let Some(&index) = indices.last() {
+            self.check_index(index)?;
+        }
+
+        let mut nodes = Vec::new();
+        let mut known = indices.clone();
+        for level in &self.levels[..self.levels.len() - 1] {
+            let mut parents = Vec::with_capacity(known.len());
+            let mut i = 0;
+            while i < known.len() {
+                let index = known[i];
+                if index % 2 == 0 && known.get(i + 1) == Some(&(index + 1)) {
+                    i += 1;
+                } else if let Some(sibling) = level.get(index ^ 1) {
+                    nodes.push(*sibling);
+                }
+                parents.push(index / 2);
+                i += 1;
+            }
+            known = parents;
+        }
+
+        Ok(Multiproof {
+            size: self.len(),
+            depth: self.depth,
+            indices,
+            nodes,
+        })
+    }
+
+    pub fn verify_multiproof_with(
+        hasher: &H,
+        proof: &Multiproof,
+        leaves: &[[u8; 32]],
+        root: [u8; 32],
+    ) -> bool {
+        span!(
+            DEBUG,
+            "merkle.verify_multi",
+            indices = proof.indices.len(),
+            size = proof.size
+        );
+        if proof.indices.is_empty()
+            || proof.indices.len() != leaves.len()
+            || proof.indices.windows(2).any(|pair| pair[0] >= pair[1])
+            || *proof.indices.last().unwrap() >= proof.size
+            || height(proof.size) > proof.depth
+            || proof.depth > MAX_DEPTH
+        {
+            return false;
+        }
+        let zeros = zero_hashes_with(hasher, proof.depth);
+
+        let mut nodes = proof.nodes.iter().copied();
+        let mut known: Vec<(usize, [u8; 32])> = proof
+            .indices
+            .iter()
+            .copied()
+            .zip(leaves.iter().copied())
+            .collect();
+        let top = height(proof.size);
+        for (level, &zero) in zeros[..top].iter().enumerate() {
+            let level_len = ((proof.size - 1) >> level) + 1;
+            let mut parents = Vec::with_capacity(known.len());
+            let mut i = 0;
+            while i < known.len() {
+                let (index, node) = known[i];
+                let parent = match known.get(i + 1) {
+                    Some(&(next, right)) if index % 2 == 0 && next == index + 1 => {
+                        i += 1;
+                        hasher.hash_nodes(node, right)
+                    }
+                    _ => {
+                        let sibling = if index ^ 1 < level_len {
+                            match nodes.next() {
+                                Some(sibling) => sibling,
+                                None => return false,
+                            }
+                        } else {
+                            zero
+                        };
+                        if index % 2 == 0 {
+                            hasher.hash_nodes(node, sibling)
+                        } else {
+                            hasher.hash_nodes(sibling, node)
+                        }
+                    }
+                };
+                parents.push((index / 2, parent));
+                i += 1;
+            }
+            known = parents;
+        }
+        match known[..] {
+            [(0, node)] => {
+                nodes.next().is_none() && pad_root(hasher, &zeros, node, top, proof.depth) == root
+            }
+            _ => false,
+        }
+    }
+
+    /// Like [`MerkleTree::verify_multiproof_with`], but rejects a proof for
+    /// any tree other than one of `size` leaves and `depth` levels.
+    ///
+    /// A multiproof's size and depth are chosen by the prover, and a trailing
+    /// zero leaf or extra padding levels can leave the root unchanged.
+    pub fn verify_multiproof_strict_with(
+        hasher: &H,
+        proof: &Multiproof,
+        leaves: &[[u8; 32]],
+        size: usize,
+        depth: usize,
+        root: [u8; 32],
+    ) -> bool {
+        (proof.size, proof.depth) == (size, depth)
+            && Self::verify_multiproof_with(hasher, proof, leaves, root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multiproof_roundtrip() {
+        let mut tree = MerkleTree::new(4);
+        for i in 0u8..11 {
+            tree.append_data(&[i]);
+        }
+        let root = tree.root();
+
+        for indices in [
+            vec![0],
+            vec![10],
+            vec![3, 0, 3, 7],
+            vec![8, 9, 10],
+            (0..11).collect(),
+        ] {
+            let proof = tree.get_multiproof(&indices).unwrap();
+            let leaves: Vec<_> = proof.indices.iter().map(|&i| tree.leaves()[i]).collect();
+            assert!(MerkleTree::verify_multiproof(&proof, &leaves, root));
+
+            let mut bad_leaves = leaves.clone();
+            bad_leaves[0][0] ^= 1;
+            assert!(!MerkleTree::verify_multiproof(&proof, &bad_leaves, root));
+        }
+
+        let single = tree.get_multiproof(&[5]).unwrap();
+        assert_eq!(single.nodes, tree.get_proof(5).siblings);
+        assert!(tree
+            .get_multiproof(&(0..11).collect::<Vec<_>>())
+            .unwrap()
+            .nodes
+            .is_empty());
+    }
+
+    #[test]
+    fn test_compact_encoding() {
+        let mut tree = MerkleTree::new(10);
+        for i in 0u16..1000 {
+            tree.append_data(&i.to_le_bytes());
+        }
+        for indices in [
+            vec![0],
+            vec![999],
+            vec![1, 2, 500, 998],
+            (100..164).collect(),
+        ] {
+            let proof = tree.get_multiproof(&indices).unwrap();
+            let compact = proof.to_compact_bytes();
+            assert_eq!(Multiproof::from_compact_bytes(&compact).unwrap(), proof);
+
+            // Separate proofs repeat shared siblings; a plain multiproof
+            // spends eight bytes per index.
+            let separate: usize = indices
+                .iter()
+                .map(|&i| tree.get_proof(i).to_bytes().len())
+                .sum();
+            let plain = 16 + 8 * proof.indices.len() + 32 * proof.nodes.len();
+            assert!(compact.len() < plain);
+            assert!(compact.len() <= separate);
+        }
+        // An aligned block of 64 leaves: two bits for each of the four nodes
+        // above it and the 63 inside it, and one sibling per level above.
+        let proof = tree
+            .get_multiproof(&(128..192).collect::<Vec<_>>())
+            .unwrap();
+        let compact = proof.to_compact_bytes();
+        assert_eq!(compact.len(), 13 + (2 * 67usize).div_ceil(8) + 4 * 32);
+        assert!(Multiproof::from_compact_bytes(&compact[..14]).is_err());
+
+        // A header claiming 2^32 - 1 leaves of a 2^40 leaf tree, and no bits.
+        let mut lying = (1u64 << 40).to_be_bytes().to_vec();
+        lying.extend_from_slice(&u32::MAX.to_be_bytes());
+        lying.push(40);
+        assert!(Multiproof::from_compact_bytes(&lying).is_err());
+    }
+
+    #[test]
+    fn test_multiproof_deduplicates_siblings() {
+        let mut tree = MerkleTree::new(3);
+        for i in 0u8..8 {
+            tree.append_data(&[i]);
+        }
+        // Leaves 0 and 1 share every sibling above the first level.
+        let proof = tree.get_multiproof(&[0, 1]).unwrap();
+        assert_eq!(proof.nodes.len(), 2);
+        assert_eq!(
+            tree.get_multiproof(&[12]).unwrap_err(),
+            MerkleError::IndexOutOfBounds { index: 12, len: 8 }
+        );
+
+        // Levels above the leaves only pad with zeros and cost no nodes.
+        let mut deep = MerkleTree::new(9);
+        for i in 0u8..5 {
+            deep.append_data(&[i]);
+        }
+        let proof = deep.get_multiproof(&[1, 4]).unwrap();
+        let leaves = [deep.leaves()[1], deep.leaves()[4]];
+        assert_eq!(proof.nodes.len(), 2);
+        assert!(MerkleTree::verify_multiproof(&proof, &leaves, deep.root()));
+        let compact = Multiproof::from_compact_bytes(&proof.to_compact_bytes()).unwrap();
+        assert_eq!(compact, proof);
+    }
+
+    #[test]
+    fn test_multiproof_strict_checks_shape() {
+        let mut tree = MerkleTree::new(3);
+        for i in 0u8..5 {
+            tree.append_data(&[i]);
+        }
+        let root = tree.root();
+        let proof = tree.get_multiproof(&[1, 4]).unwrap();
+        let leaves = [tree.leaves()[1], tree.leaves()[4]];
+        assert!(MerkleTree::verify_multiproof_strict(
+            &proof, &leaves, 5, 3, root
+        ));
+
+        // A zero leaf appended to the claimed tree leaves the root as it was.
+        let mut grown = proof.clone();
+        grown.size = 6;
+        grown.nodes.insert(1, [0; 32]);
+        assert!(MerkleTree::verify_multiproof(&grown, &leaves, root));
+        assert!(!MerkleTree::verify_multiproof_strict(
+            &grown, &leaves, 5, 3, root
+        ));
+        assert!(!MerkleTree::verify_multiproof_strict(
+            &proof, &leaves, 5, 4, root
+        ));
+    }
+}
@@ -0,0 +1,258 @@
+use crate::{DomainSeparated, MerkleError, NodeHasher, Sha256Hasher, MAX_DEPTH};
+
+/// A merkle tree whose internal nodes have `arity` children.
+///
+/// Nodes hash their children in order with [`NodeHasher::hash_children`],
+/// missing children counting as empty subtrees, just as a
+/// [`MerkleTree`](crate::MerkleTree) pads to its depth. With `arity` 2 the
+/// two trees agree on every root. Wider trees have fewer
+/// levels, so proofs need fewer hash invocations, which helps circuits
+/// built on wide permutations such as Poseidon.
+#[derive(Debug, Clone)]
+pub struct NaryMerkleTree<H = DomainSeparated> {
+    arity: usize,
+    depth: usize,
+    /// Level 0 holds the leaves, the last level holds the root.
+    levels: Vec<Vec<[u8; 32]>>,
+    /// `zeros[level]` is the root of an empty subtree of height `level`.
+    zeros: Vec<[u8; 32]>,
+    hasher: H,
+}
+
+/// An inclusion proof in a [`NaryMerkleTree`]: for each level from the
+/// leaves up, the `arity - 1` other children of the node on the path, in
+/// order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+pub struct NaryProof {
+    pub index: usize,
+    pub size: usize,
+    pub arity: usize,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::levels"))]
+    pub siblings: Vec<Vec<[u8; 32]>>,
+}
+
+impl NaryProof {
+    pub fn verify(&self, leaf: [u8; 32], root: [u8; 32]) -> bool {
+        self.verify_with(&DomainSeparated(Sha256Hasher), leaf, root)
+    }
+
+    pub fn verify_with<H: NodeHasher>(&self, hasher: &H, leaf: [u8; 32], root: [u8; 32]) -> bool {
+        if self.arity < 2 || self.index >= self.size {
+            return false;
+        }
+        let mut node = leaf;
+        let mut index = self.index;
+        let mut children = Vec::with_capacity(self.arity);
+        for siblings in &self.siblings {
+            if siblings.len() != self.arity - 1 {
+                return false;
+            }
+            let position = index % self.arity;
+            children.clear();
+            children.extend_from_slice(&siblings[..position]);
+            children.push(node);
+            children.extend_from_slice(&siblings[position..]);
+            node = hasher.hash_children(&children);
+            index /= self.arity;
+        }
+        index == 0 && node == root
+    }
+}
+
+impl NaryMerkleTree {
+    /// Creates a domain-separated SHA-256 tree of `arity` holding up to
+    /// `arity^depth` leaves.
+    pub fn new(arity: usize, depth: usize) -> Result<Self, MerkleError> {
+        Self::with_hasher(arity, depth, DomainSeparated(Sha256Hasher))
+    }
+}
+
+impl<H: NodeHasher> NaryMerkleTree<H> {
+    /// Capacity is limited to the `2^MAX_DEPTH` leaves of a binary tree.
+    pub fn with_hasher(arity: usize, depth: usize, hasher: H) -> Result<Self, MerkleError> {
+        if arity < 2 {
+            return Err(MerkleError::InvalidEncoding("arity must be at least 2"));
+        }
+        let fits = |depth: usize| {
+            u32::try_from(depth)
+                .ok()
+                .and_then(|depth| (arity as u128).checked_pow(depth))
+                .is_some_and(|capacity| capacity <= 1 << MAX_DEPTH)
+        };
+        if !fits(depth) {
+            let max = (1..).take_while(|&depth| fits(depth)).count();
+            return Err(MerkleError::DepthTooLarge { depth, max });
+        }
+        let mut zeros = vec![[0u8; 32]];
+        for level in 0..depth {
+            zeros.push(hasher.hash_children(&vec![zeros[level]; arity]));
+        }
+        Ok(Self {
+            arity,
+            depth,
+            levels: vec![Vec::new()],
+            zeros,
+            hasher,
+        })
+    }
+
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn hasher(&self) -> &H {
+        &self.hasher
+    }
+
+    pub fn len(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Maximum number of leaves, `arity^depth`, or `usize::MAX` where that
+    /// does not fit.
+    pub fn capacity(&self) -> usize {
+        self.arity
+            .checked_pow(self.depth as u32)
+            .unwrap_or(usize::MAX)
+    }
+
+    pub fn leaves(&self) -> &[[u8; 32]] {
+        &self.levels[0]
+    }
+
+    /// The root at the tree's depth, the empty-subtree hash for an empty
+    /// tree.
+    pub fn root(&self) -> [u8; 32] {
+        let Some(&top) = self.levels[self.levels.len() - 1].first() else {
+            return self.zeros[self.depth];
+        };
+        (self.levels.len() - 1..self.depth).fold(top, |node, level| {
+            let mut children = vec![self.zeros[level]; self.arity];
+            children[0] = node;
+            self.hasher.hash_children(&children)
+        })
+    }
+
+    pub fn append_data(&mut self, data: &[u8]) -> Result<(), MerkleError> {
+        self.append_leaf(self.hasher.hash_leaf(data))
+    }
+
+    pub fn append_leaf(&mut self, leaf: [u8; 32]) -> Result<(), MerkleError> {
+        if self.len() >= self.capacity() {
+            return Err(MerkleError::TreeFull {
+                capacity: self.capacity(),
+            });
+        }
+        self.levels[0].push(leaf);
+        let mut index = self.len() - 1;
+        let mut level = 0;
+        while self.levels[level].len() > 1 {
+            let start = index - index % self.arity;
+            let nodes = &self.levels[level];
+            let mut children = nodes[start..nodes.len().min(start + self.arity)].to_vec();
+            children.resize(self.arity, self.zeros[level]);
+            let parent = self.hasher.hash_children(&children);
+
+            index /= self.arity;
+            level += 1;
+            if level == self.levels.len() {
+                self.levels.push(Vec::new());
+            }
+            let parents = &mut self.levels[level];
+            if index == parents.len() {
+                parents.push(parent);
+            } else {
+                parents[index] = parent;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get_proof(&self, index: usize) -> Result<NaryProof, MerkleError> {
+        if index >= self.len() {
+            return Err(MerkleError::IndexOutOfBounds {
+                index,
+                len: self.len(),
+            });
+        }
+        let mut position = index;
+        let siblings = (0..self.depth)
+            .map(|level| {
+                let nodes = self.levels.get(level).map_or(&[][..], Vec::as_slice);
+                let start = position - position % self.arity;
+                let siblings = (start..start + self.arity)
+                    .filter(|&at| at != position)
+                    .map(|at| nodes.get(at).copied().unwrap_or(self.zeros[level]))
+                    .collect();
+                position /= self.arity;
+                siblings
+            })
+            .collect();
+        Ok(NaryProof {
+            index,
+            size: self.len(),
+            arity: self.arity,
+            siblings,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MerkleTree;
+
+    #[test]
+    fn test_binary_matches_merkle_tree() {
+        let mut nary = NaryMerkleTree::new(2, 6).unwrap();
+        let mut binary = MerkleTree::new(6);
+        assert_eq!(nary.root(), binary.root());
+        for i in 0u8..11 {
+            nary.append_data(&[i]).unwrap();
+            binary.append_data(&[i]);
+            assert_eq!(nary.root(), binary.root());
+        }
+        assert_eq!(nary.get_proof(5).unwrap().siblings.len(), 6);
+    }
+
+    #[test]
+    fn test_wide_proofs() {
+        for arity in [3, 4, 16] {
+            let mut tree = NaryMerkleTree::new(arity, 4).unwrap();
+            for i in 0u8..37 {
+                tree.append_data(&[i]).unwrap();
+            }
+            for index in 0..37 {
+                let proof = tree.get_proof(index).unwrap();
+                assert!(proof.siblings.iter().all(|level| level.len() == arity - 1));
+                assert!(proof.verify(tree.leaves()[index], tree.root()));
+                assert!(!proof.verify(tree.leaves()[(index + 1) % 37], tree.root()));
+            }
+        }
+        let mut four = NaryMerkleTree::new(4, 3).unwrap();
+        for i in 0u8..64 {
+            four.append_data(&[i]).unwrap();
+        }
+        assert_eq!(four.get_proof(0).unwrap().siblings.len(), 3);
+        assert_eq!(
+            four.append_data(&[0]),
+            Err(MerkleError::TreeFull { capacity: 64 })
+        );
+        assert!(NaryMerkleTree::new(16, 15).is_ok());
+        assert!(NaryMerkleTree::new(16, 16).is_err());
+        assert!(NaryMerkleTree::new(1, 3).is_err());
+    }
+}
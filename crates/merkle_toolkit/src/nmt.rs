@@ -0,0 +1,447 @@
+use crate::{MerkleError, NodeHasher, Sha256Hasher, LEAF_PREFIX, NODE_PREFIX};
+
+/// Namespace size used by Celestia.
+pub const CELESTIA_NAMESPACE_LEN: usize = 29;
+
+/// A node of a [`NamespacedMerkleTree`]: a SHA-256 digest tagged with the
+/// smallest and largest namespace of the leaves below it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+pub struct NamespacedHash {
+    pub min: Vec<u8>,
+    pub max: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub digest: [u8; 32],
+}
+
+impl NamespacedHash {
+    /// Root of a tree without leaves: zero namespaces and the hash of
+    /// nothing.
+    pub fn empty(namespace_len: usize) -> Self {
+        Self {
+            min: vec![0; namespace_len],
+            max: vec![0; namespace_len],
+            digest: Sha256Hasher.hash(&[]),
+        }
+    }
+
+    /// Leaf hash of `data`, which starts with its namespace:
+    /// `ns || ns || sha256(0x00 || data)`.
+    pub fn leaf(namespace_len: usize, data: &[u8]) -> Result<Self, MerkleError> {
+        let namespace = data
+            .get(..namespace_len)
+            .ok_or(MerkleError::InvalidEncoding(
+                "leaf is shorter than its namespace",
+            ))?;
+        Ok(Self {
+            min: namespace.to_vec(),
+            max: namespace.to_vec(),
+            digest: Sha256Hasher.hash(&[&[LEAF_PREFIX], data]),
+        })
+    }
+
+    /// Parent of two siblings, or `None` if `right` has a namespace below
+    /// the largest one in `left`.
+    ///
+    /// With `ignore_max_namespace`, leaves in the all-`0xff` namespace (used
+    /// by Celestia for parity data) don't raise the parent's maximum.
+    pub fn parent(left: &Self, right: &Self, ignore_max_namespace: bool) -> Option<Self> {
+        if left.min > left.max || right.min > right.max || right.min < left.max {
+            return None;
+        }
+        let is_max = |namespace: &[u8]| namespace.iter().all(|&byte| byte == 0xff);
+        let max = if ignore_max_namespace && is_max(&left.min) {
+            left.min.clone()
+        } else if ignore_max_namespace && is_max(&right.min) {
+            left.max.clone()
+        } else {
+            right.max.clone().max(left.max.clone())
+        };
+        Some(Self {
+            min: left.min.clone(),
+            max,
+            digest: Sha256Hasher.hash(&[&[NODE_PREFIX], &left.to_bytes(), &right.to_bytes()]),
+        })
+    }
+
+    /// `min || max || digest`, the form hashed into parents.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        [&self.min[..], &self.max, &self.digest].concat()
+    }
+
+    pub fn from_bytes(namespace_len: usize, bytes: &[u8]) -> Result<Self, MerkleError> {
+        if bytes.len() != 2 * namespace_len + 32 {
+            return Err(MerkleError::InvalidEncoding(
+                "namespaced hash is not 2 * namespace + 32 bytes",
+            ));
+        }
+        Ok(Self {
+            min: bytes[..namespace_len].to_vec(),
+            max: bytes[namespace_len..2 * namespace_len].to_vec(),
+            digest: bytes[2 * namespace_len..].try_into().unwrap(),
+        })
+    }
+}
+
+/// A namespaced merkle tree as specified by Celestia.
+///
+/// Leaves are namespace-prefixed data pushed in non-decreasing namespace
+/// order, and every node records the namespace range below it, so a reader
+/// can be proven to have been given all of a namespace's leaves, or that a
+/// namespace has none. Unlike [`crate::MerkleTree`] the tree isn't padded:
+/// like RFC 6962, `n` leaves split into the largest power of two below `n`
+/// and the rest.
+#[derive(Debug, Clone)]
+pub struct NamespacedMerkleTree {
+    namespace_len: usize,
+    ignore_max_namespace: bool,
+    leaves: Vec<NamespacedHash>,
+}
+
+/// Proof for the leaves at `start..end` of a [`NamespacedMerkleTree`].
+///
+/// `nodes` are the roots of the subtrees left and right of the range, in
+/// order. For a namespace with no leaves, `leaf_hash` holds the leaf where it
+/// would have been; a proof with no range at all shows the namespace lies
+/// outside the root's range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+pub struct NamespaceProof {
+    pub start: usize,
+    pub end: usize,
+    pub nodes: Vec<NamespacedHash>,
+    pub leaf_hash: Option<NamespacedHash>,
+    pub ignore_max_namespace: bool,
+}
+
+impl NamespacedMerkleTree {
+    /// An empty tree over namespaces of `namespace_len` bytes, ignoring the
+    /// maximum namespace as Celestia does.
+    pub fn new(namespace_len: usize) -> Self {
+        Self {
+            namespace_len,
+            ignore_max_namespace: true,
+            leaves: Vec::new(),
+        }
+    }
+
+    pub fn celestia() -> Self {
+        Self::new(CELESTIA_NAMESPACE_LEN)
+    }
+
+    /// Counts leaves in the maximum namespace towards node maximums.
+    pub fn with_max_namespace(mut self) -> Self {
+        self.ignore_max_namespace = false;
+        self
+    }
+
+    pub fn namespace_len(&self) -> usize {
+        self.namespace_len
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    /// Appends namespace-prefixed `data`. Its namespace must not be smaller
+    /// than that of the previous leaf.
+    pub fn push(&mut self, data: &[u8]) -> Result<(), MerkleError> {
+        let leaf = NamespacedHash::leaf(self.namespace_len, data)?;
+        if self.leaves.last().is_some_and(|last| last.min > leaf.min) {
+            return Err(MerkleError::InvalidEncoding(
+                "leaves must be pushed in namespace order",
+            ));
+        }
+        self.leaves.push(leaf);
+        Ok(())
+    }
+
+    pub fn root(&self) -> NamespacedHash {
+        if self.leaves.is_empty() {
+            return NamespacedHash::empty(self.namespace_len);
+        }
+        self.subtree_root(0, self.len())
+    }
+
+    /// Proof for the leaves at `start..end`.
+    pub fn prove_range(&self, start: usize, end: usize) -> Result<NamespaceProof, MerkleError> {
+        if start >= end || end > self.len() {
+            return Err(MerkleError::IndexOutOfBounds {
+                index: end,
+                len: self.len(),
+            });
+        }
+        let mut nodes = Vec::new();
+        self.collect_range_nodes(0, self.len(), start, end, &mut nodes);
+        Ok(NamespaceProof {
+            start,
+            end,
+            nodes,
+            leaf_hash: None,
+            ignore_max_namespace: self.ignore_max_namespace,
+        })
+    }
+
+    /// Proof for every leaf in `namespace`, or of its absence.
+    pub fn prove_namespace(&self, namespace: &[u8]) -> Result<NamespaceProof, MerkleError> {
+        if namespace.len() != self.namespace_len {
+            return Err(MerkleError::InvalidEncoding(
+                "namespace has the wrong length",
+            ));
+        }
+        let root = self.root();
+        if self.is_empty() || namespace < &root.min[..] || namespace > &root.max[..] {
+            return Ok(NamespaceProof {
+                start: 0,
+                end: 0,
+                nodes: Vec::new(),
+                leaf_hash: None,
+                ignore_max_namespace: self.ignore_max_namespace,
+            });
+        }
+        let start = self
+            .leaves
+            .partition_point(|leaf| &leaf.min[..] < namespace);
+        let end = self
+            .leaves
+            .partition_point(|leaf| &leaf.min[..] <= namespace);
+        if start < end {
+            return self.prove_range(start, end);
+        }
+        let mut proof = self.prove_range(start, start + 1)?;
+        proof.leaf_hash = Some(self.leaves[start].clone());
+        Ok(proof)
+    }
+
+    fn subtree_root(&self, start: usize, end: usize) -> NamespacedHash {
+        if end - start == 1 {
+            return self.leaves[start].clone();
+        }
+        let split = start + split_point(end - start);
+        let left = self.subtree_root(start, split);
+        let right = self.subtree_root(split, end);
+        NamespacedHash::parent(&left, &right, self.ignore_max_namespace)
+            .expect("leaves are pushed in namespace order")
+    }
+
+    fn collect_range_nodes(
+        &self,
+        start: usize,
+        end: usize,
+        range_start: usize,
+        range_end: usize,
+        nodes: &mut Vec<NamespacedHash>,
+    ) {
+        if end <= range_start || start >= range_end {
+            nodes.push(self.subtree_root(start, end));
+        } else if range_start > start || range_end < end {
+            let split = start + split_point(end - start);
+            self.collect_range_nodes(start, split, range_start, range_end, nodes);
+            self.collect_range_nodes(split, end, range_start, range_end, nodes);
+        }
+    }
+}
+
+impl NamespaceProof {
+    /// A proof that its namespace is outside the root's range.
+    pub fn is_empty(&self) -> bool {
+        self.start == 0 && self.end == 0 && self.nodes.is_empty() && self.leaf_hash.is_none()
+    }
+
+    /// A proof that its namespace has no leaves.
+    pub fn is_absence(&self) -> bool {
+        self.leaf_hash.is_some()
+    }
+
+    /// Checks that `leaves` (namespace-prefixed data) are the leaves at
+    /// `start..end` under `root`.
+    pub fn verify_range(&self, leaves: &[impl AsRef<[u8]>], root: &NamespacedHash) -> bool {
+        let namespace_len = root.min.len();
+        let hashes: Result<Vec<_>, _> = leaves
+            .iter()
+            .map(|leaf| NamespacedHash::leaf(namespace_len, leaf.as_ref()))
+            .collect();
+        match hashes {
+            Ok(hashes) => self.compute_root(&hashes).as_ref() == Some(root),
+            Err(_) => false,
+        }
+    }
+
+    /// Checks that `leaves` are all the leaves in `namespace` under `root`:
+    /// every subtree left of the range ends below the namespace and every
+    /// subtree right of it starts above. For a proof of absence `leaves`
+    /// must be empty.
+    pub fn verify_namespace(
+        &self,
+        namespace: &[u8],
+        leaves: &[impl AsRef<[u8]>],
+        root: &NamespacedHash,
+    ) -> bool {
+        let namespace_len = root.min.len();
+        if namespace.len() != namespace_len {
+            return false;
+        }
+        if self.is_empty() {
+            return leaves.is_empty() && (namespace < &root.min[..] || namespace > &root.max[..]);
+        }
+        let hashes = match &self.leaf_hash {
+            Some(leaf_hash) => {
+                if !leaves.is_empty() || &leaf_hash.min[..] <= namespace {
+                    return false;
+                }
+                vec![leaf_hash.clone()]
+            }
+            None => {
+                let hashes: Result<Vec<_>, _> = leaves
+                    .iter()
+                    .map(|leaf| NamespacedHash::leaf(namespace_len, leaf.as_ref()))
+                    .collect();
+                match hashes {
+                    Ok(hashes) if hashes.iter().all(|hash| hash.min == namespace) => hashes,
+                    _ => return false,
+                }
+            }
+        };
+        let left_count = self.start.count_ones() as usize;
+        if left_count > self.nodes.len() {
+            return false;
+        }
+        let (left, right) = self.nodes.split_at(left_count);
+        left.iter().all(|node| &node.max[..] < namespace)
+            && right.iter().all(|node| &node.min[..] > namespace)
+            && self.compute_root(&hashes).as_ref() == Some(root)
+    }
+
+    /// Rebuilds the root from the leaf hashes of the range without knowing
+    /// the tree size: the subtree spanning `0..end` rounded up to a power of
+    /// two is rebuilt first and the remaining nodes are folded in on the
+    /// right.
+    fn compute_root(&self, leaves: &[NamespacedHash]) -> Option<NamespacedHash> {
+        if self.start >= self.end || leaves.len() != self.end - self.start {
+            return None;
+        }
+        let mut leaves = leaves.iter();
+        let mut nodes = self.nodes.iter();
+        let mut root = self.subtree(0, self.end.next_power_of_two(), &mut leaves, &mut nodes)??;
+        for node in nodes {
+            root = NamespacedHash::parent(&root, node, self.ignore_max_namespace)?;
+        }
+        Some(root)
+    }
+
+    /// Root of `start..end`, `Some(None)` if the subtree lies past the end of
+    /// the tree, or `None` if hashing failed.
+    fn subtree<'a>(
+        &self,
+        start: usize,
+        end: usize,
+        leaves: &mut impl Iterator<Item = &'a NamespacedHash>,
+        nodes: &mut impl Iterator<Item = &'a NamespacedHash>,
+    ) -> Option<Option<NamespacedHash>> {
+        if end - start == 1 && self.start <= start && start < self.end {
+            return Some(leaves.next().cloned());
+        }
+        if end - start == 1 || end <= self.start || start >= self.end {
+            return Some(nodes.next().cloned());
+        }
+        let split = start + split_point(end - start);
+        let left = self.subtree(start, split, leaves, nodes)?;
+        let right = self.subtree(split, end, leaves, nodes)?;
+        match (left, right) {
+            (Some(left), Some(right)) => {
+                NamespacedHash::parent(&left, &right, self.ignore_max_namespace).map(Some)
+            }
+            (left, None) => Some(left),
+            (None, Some(_)) => None,
+        }
+    }
+}
+
+/// Largest power of two smaller than `len`, which must be at least two.
+fn split_point(len: usize) -> usize {
+    1 << (usize::BITS - 1 - (len - 1).leading_zeros())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hex;
+
+    fn leaf(namespace: u8, data: &str) -> Vec<u8> {
+        [&[namespace][..], data.as_bytes()].concat()
+    }
+
+    fn tree(leaves: &[Vec<u8>]) -> NamespacedMerkleTree {
+        let mut tree = NamespacedMerkleTree::new(1);
+        for leaf in leaves {
+            tree.push(leaf).unwrap();
+        }
+        tree
+    }
+
+    #[test]
+    fn test_roots_and_range_proofs() {
+        assert_eq!(
+            hex::encode(&NamespacedMerkleTree::new(1).root().to_bytes()),
+            "0000e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        let leaves: Vec<_> = [1, 1, 2, 4, 4, 4, 7].map(|ns| leaf(ns, "data")).to_vec();
+        let tree = tree(&leaves);
+        let root = tree.root();
+        assert_eq!((root.min.clone(), root.max.clone()), (vec![1], vec![7]));
+        for start in 0..leaves.len() {
+            for end in start + 1..=leaves.len() {
+                let proof = tree.prove_range(start, end).unwrap();
+                assert!(proof.verify_range(&leaves[start..end], &root));
+                assert!(!proof.verify_range(&leaves[start..end - 1], &root));
+            }
+        }
+        let mut unordered = NamespacedMerkleTree::new(1);
+        unordered.push(&leaf(2, "a")).unwrap();
+        assert!(unordered.push(&leaf(1, "b")).is_err());
+        assert_eq!(split_point(2), 1);
+        assert_eq!(split_point(5), 4);
+        assert_eq!(split_point(8), 4);
+    }
+
+    #[test]
+    fn test_namespace_and_absence_proofs() {
+        let leaves: Vec<_> = [1, 1, 2, 4, 4, 4, 7, 0xff]
+            .map(|ns| leaf(ns, "data"))
+            .to_vec();
+        let tree = tree(&leaves);
+        let root = tree.root();
+        // The parity namespace doesn't count towards the root's maximum.
+        assert_eq!(root.max, vec![7]);
+
+        let proof = tree.prove_namespace(&[4]).unwrap();
+        assert_eq!((proof.start, proof.end), (3, 6));
+        assert!(proof.verify_namespace(&[4], &leaves[3..6], &root));
+        // Withholding a leaf of the namespace is caught.
+        let partial = tree.prove_range(3, 5).unwrap();
+        assert!(partial.verify_range(&leaves[3..5], &root));
+        assert!(!partial.verify_namespace(&[4], &leaves[3..5], &root));
+
+        let absent = tree.prove_namespace(&[3]).unwrap();
+        assert!(absent.is_absence());
+        assert!(absent.verify_namespace(&[3], &[] as &[Vec<u8>], &root));
+        assert!(!absent.verify_namespace(&[5], &[] as &[Vec<u8>], &root));
+
+        let outside = tree.prove_namespace(&[0]).unwrap();
+        assert!(outside.is_empty());
+        assert!(outside.verify_namespace(&[0], &[] as &[Vec<u8>], &root));
+        assert!(!outside.verify_namespace(&[2], &[] as &[Vec<u8>], &root));
+    }
+}
@@ -0,0 +1,145 @@
+use core::fmt;
+
+use crate::{
+    DomainSeparated, MemoryStore, MerkleError, MerkleTree, NodeHasher, NodeStore, TreeHead,
+};
+
+type AppendHook = Box<dyn FnMut(usize, [u8; 32]) + Send>;
+type RootHook = Box<dyn FnMut(TreeHead) + Send>;
+
+/// A [`MerkleTree`] that calls back registered observers as it changes, so
+/// new roots can be pushed to a chain or a message bus instead of polled.
+///
+/// Callbacks run synchronously on the thread making the change, in the
+/// order they were registered, and only once the change is part of the
+/// tree: an observer sees the tree it is told about and never a change that
+/// failed. Each operation first calls the [`on_append`] callbacks for its
+/// leaves in index order, then the [`on_new_root`] callbacks once with the
+/// resulting head, so a batch publishes one root. Heads therefore reach each
+/// observer in the order the tree took them on.
+///
+/// [`on_append`]: ObservedMerkleTree::on_append
+/// [`on_new_root`]: ObservedMerkleTree::on_new_root
+pub struct ObservedMerkleTree<H = DomainSeparated, S = MemoryStore> {
+    tree: MerkleTree<H, S>,
+    on_append: Vec<AppendHook>,
+    on_new_root: Vec<RootHook>,
+}
+
+impl ObservedMerkleTree {
+    pub fn new(depth: usize) -> Self {
+        Self::from_tree(MerkleTree::new(depth))
+    }
+}
+
+impl<H: NodeHasher, S: NodeStore> ObservedMerkleTree<H, S> {
+    pub fn from_tree(tree: MerkleTree<H, S>) -> Self {
+        Self {
+            tree,
+            on_append: Vec::new(),
+            on_new_root: Vec::new(),
+        }
+    }
+
+    pub fn tree(&self) -> &MerkleTree<H, S> {
+        &self.tree
+    }
+
+    /// Unwraps the tree, dropping the observers.
+    pub fn into_tree(self) -> MerkleTree<H, S> {
+        self.tree
+    }
+
+    /// Calls `hook` with the index and value of every appended leaf.
+    pub fn on_append(&mut self, hook: impl FnMut(usize, [u8; 32]) + Send + 'static) {
+        self.on_append.push(Box::new(hook));
+    }
+
+    /// Calls `hook` with the tree's head after every operation that changed
+    /// the root.
+    pub fn on_new_root(&mut self, hook: impl FnMut(TreeHead) + Send + 'static) {
+        self.on_new_root.push(Box::new(hook));
+    }
+
+    /// Appends `leaf`, returning its index.
+    pub fn append_leaf(&mut self, leaf: [u8; 32]) -> Result<usize, MerkleError> {
+        self.append_leaves(&[leaf])
+    }
+
+    pub fn append_data(&mut self, data: &[u8]) -> Result<usize, MerkleError> {
+        self.append_leaf(self.tree.hasher().hash_leaf(data))
+    }
+
+    /// Appends `leaves` with [`MerkleTree::append_leaves`], returning the
+    /// index of the first.
+    pub fn append_leaves(&mut self, leaves: &[[u8; 32]]) -> Result<usize, MerkleError> {
+        let start = self.tree.len();
+        self.tree.append_leaves(leaves)?;
+        for (index, &leaf) in (start..).zip(leaves) {
+            for hook in &mut self.on_append {
+                hook(index, leaf);
+            }
+        }
+        if !leaves.is_empty() {
+            self.publish()?;
+        }
+        Ok(start)
+    }
+
+    /// Replaces the leaf at `index`, which only calls the
+    /// [`ObservedMerkleTree::on_new_root`] callbacks.
+    pub fn set_leaf(&mut self, index: usize, leaf: [u8; 32]) -> Result<[u8; 32], MerkleError> {
+        let root = self.tree.set_leaf(index, leaf)?;
+        self.publish()?;
+        Ok(root)
+    }
+
+    fn publish(&mut self) -> Result<(), MerkleError> {
+        let head = self.tree.tree_head()?;
+        for hook in &mut self.on_new_root {
+            hook(head);
+        }
+        Ok(())
+    }
+}
+
+impl<H: fmt::Debug, S: fmt::Debug> fmt::Debug for ObservedMerkleTree<H, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ObservedMerkleTree")
+            .field("tree", &self.tree)
+            .field("on_append", &self.on_append.len())
+            .field("on_new_root", &self.on_new_root.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    #[test]
+    fn test_observers_see_committed_changes_in_order() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut tree = ObservedMerkleTree::new(2);
+        let log = Arc::clone(&events);
+        tree.on_append(move |index, leaf| {
+            log.lock()
+                .unwrap()
+                .push(format!("leaf {index} {}", leaf[0]))
+        });
+        let log = Arc::clone(&events);
+        tree.on_new_root(move |head| log.lock().unwrap().push(format!("root {}", head.size)));
+
+        tree.append_leaf([1; 32]).unwrap();
+        assert_eq!(tree.append_leaves(&[[2; 32], [3; 32]]).unwrap(), 1);
+        tree.set_leaf(0, [4; 32]).unwrap();
+        assert!(tree.append_leaves(&[[5; 32], [6; 32]]).is_err());
+        tree.append_leaves(&[]).unwrap();
+        assert_eq!(
+            *events.lock().unwrap(),
+            ["leaf 0 1", "root 1", "leaf 1 2", "leaf 2 3", "root 3", "root 3"]
+        );
+    }
+}
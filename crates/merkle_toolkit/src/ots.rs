@@ -0,0 +1,582 @@
+//! OpenTimestamps commitments for tree roots.
+//!
+//! An OpenTimestamps proof is a tree of operations rooted at the message
+//! being timestamped. Every path applies appends, prepends and hashes until
+//! it reaches a message a calendar or blockchain attests to. Submitting one
+//! tree root timestamps every leaf: [`leaf_timestamp`] prefixes the root's
+//! timestamp with the leaf's inclusion path, and [`Timestamp::merge`] folds
+//! in the upgraded timestamp a calendar hands out once its pending
+//! commitment has been anchored.
+//!
+//! Timestamps use the `.ots` binary encoding, so proofs produced here can be
+//! upgraded and verified by the standard clients.
+
+use crate::{
+    DomainSeparated, DoubleSha256Hasher, Keccak256Hasher, MerkleError, NodeHasher, Proof,
+    Sha256Hasher,
+};
+
+/// Magic bytes opening a detached timestamp file.
+pub const HEADER_MAGIC: &[u8; 31] =
+    b"\x00OpenTimestamps\x00\x00Proof\x00\xbf\x89\xe2\xe8\x84\xe8\x92\x94";
+
+/// Longest message an operation may produce or take as an argument.
+pub const MAX_MSG_LEN: usize = 4096;
+
+const MAX_RECURSION: usize = 256;
+const PENDING_TAG: [u8; 8] = [0x83, 0xdf, 0xe3, 0x0d, 0x2e, 0xf9, 0x0c, 0x8e];
+const BITCOIN_TAG: [u8; 8] = [0x05, 0x88, 0x96, 0x0d, 0x73, 0xd7, 0x19, 0x01];
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Op {
+    Sha256,
+    Keccak256,
+    Append(Vec<u8>),
+    Prepend(Vec<u8>),
+}
+
+impl Op {
+    fn tag(&self) -> u8 {
+        match self {
+            Op::Sha256 => 0x08,
+            Op::Keccak256 => 0x67,
+            Op::Append(_) => 0xf0,
+            Op::Prepend(_) => 0xf1,
+        }
+    }
+
+    /// The message this operation turns `msg` into.
+    pub fn apply(&self, msg: &[u8]) -> Vec<u8> {
+        match self {
+            Op::Sha256 => Sha256Hasher.hash(&[msg]).to_vec(),
+            Op::Keccak256 => Keccak256Hasher.hash(&[msg]).to_vec(),
+            Op::Append(suffix) => [msg, suffix].concat(),
+            Op::Prepend(prefix) => [prefix, msg].concat(),
+        }
+    }
+
+    fn sort_key(&self) -> (u8, &[u8]) {
+        match self {
+            Op::Sha256 | Op::Keccak256 => (self.tag(), &[]),
+            Op::Append(arg) | Op::Prepend(arg) => (self.tag(), arg),
+        }
+    }
+}
+
+/// A claim that a message existed at some point in time.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Attestation {
+    /// Submitted to the calendar at `uri`, not yet anchored.
+    Pending { uri: String },
+    /// The message is the merkle root of the Bitcoin block at `height`.
+    Bitcoin { height: u64 },
+    /// An attestation this crate doesn't interpret, kept for round trips.
+    Unknown { tag: [u8; 8], payload: Vec<u8> },
+}
+
+impl Attestation {
+    fn tag(&self) -> [u8; 8] {
+        match self {
+            Attestation::Pending { .. } => PENDING_TAG,
+            Attestation::Bitcoin { .. } => BITCOIN_TAG,
+            Attestation::Unknown { tag, .. } => *tag,
+        }
+    }
+
+    fn payload(&self) -> Vec<u8> {
+        let mut payload = Vec::new();
+        match self {
+            Attestation::Pending { uri } => write_bytes(&mut payload, uri.as_bytes()),
+            Attestation::Bitcoin { height } => write_varuint(&mut payload, *height),
+            Attestation::Unknown { payload: raw, .. } => payload.extend_from_slice(raw),
+        }
+        payload
+    }
+
+    fn sort_key(&self) -> ([u8; 8], Vec<u8>) {
+        match self {
+            Attestation::Pending { uri } => (PENDING_TAG, uri.as_bytes().to_vec()),
+            Attestation::Bitcoin { height } => (BITCOIN_TAG, height.to_be_bytes().to_vec()),
+            Attestation::Unknown { tag, payload } => (*tag, payload.clone()),
+        }
+    }
+}
+
+/// A message with the attestations on it and the operations leading on
+/// from it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Timestamp {
+    msg: Vec<u8>,
+    attestations: Vec<Attestation>,
+    ops: Vec<(Op, Timestamp)>,
+}
+
+impl Timestamp {
+    pub fn new(msg: impl Into<Vec<u8>>) -> Self {
+        Self {
+            msg: msg.into(),
+            attestations: Vec::new(),
+            ops: Vec::new(),
+        }
+    }
+
+    pub fn msg(&self) -> &[u8] {
+        &self.msg
+    }
+
+    /// Attestations made directly on this message.
+    pub fn own_attestations(&self) -> &[Attestation] {
+        &self.attestations
+    }
+
+    pub fn ops(&self) -> &[(Op, Timestamp)] {
+        &self.ops
+    }
+
+    pub fn add_attestation(&mut self, attestation: Attestation) {
+        if !self.attestations.contains(&attestation) {
+            self.attestations.push(attestation);
+        }
+    }
+
+    /// Applies `op` to this message and returns the timestamp of the result,
+    /// reusing the branch if `op` is already here.
+    pub fn add_op(&mut self, op: Op) -> &mut Timestamp {
+        let position = match self.ops.iter().position(|(existing, _)| *existing == op) {
+            Some(position) => position,
+            None => {
+                let msg = op.apply(&self.msg);
+                self.ops.push((op, Timestamp::new(msg)));
+                self.ops.len() - 1
+            }
+        };
+        &mut self.ops[position].1
+    }
+
+    /// Every attestation below this timestamp with the message it commits
+    /// to. A Bitcoin attestation holds if its message equals the merkle root
+    /// in that block's header.
+    pub fn attestations(&self) -> Vec<(&[u8], &Attestation)> {
+        let mut found: Vec<_> = self
+            .attestations
+            .iter()
+            .map(|attestation| (&self.msg[..], attestation))
+            .collect();
+        for (_, stamp) in &self.ops {
+            found.extend(stamp.attestations());
+        }
+        found
+    }
+
+    /// Whether any attestation below this timestamp is still pending.
+    pub fn is_pending(&self) -> bool {
+        self.attestations()
+            .iter()
+            .any(|(_, attestation)| matches!(attestation, Attestation::Pending { .. }))
+    }
+
+    /// The timestamp inside this one for `msg`, such as the commitment a
+    /// pending calendar attestation was made on.
+    pub fn find_mut(&mut self, msg: &[u8]) -> Option<&mut Timestamp> {
+        if self.msg == msg {
+            return Some(self);
+        }
+        self.ops
+            .iter_mut()
+            .find_map(|(_, stamp)| stamp.find_mut(msg))
+    }
+
+    /// Folds `other`, a timestamp of the same message, into this one. This
+    /// is how an upgraded timestamp fetched from a calendar is attached to
+    /// the commitment it completes; the pending attestation itself is kept,
+    /// as the reference client does.
+    pub fn merge(&mut self, other: &Timestamp) -> Result<(), MerkleError> {
+        if self.msg != other.msg {
+            return Err(MerkleError::InvalidProof);
+        }
+        for attestation in &other.attestations {
+            self.add_attestation(attestation.clone());
+        }
+        for (op, stamp) in &other.ops {
+            self.add_op(op.clone()).merge(stamp)?;
+        }
+        Ok(())
+    }
+
+    /// The `.ots` encoding of this timestamp, without the file header.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, MerkleError> {
+        let mut out = Vec::new();
+        self.write(&mut out)?;
+        Ok(out)
+    }
+
+    /// Reads a timestamp of `msg` from its `.ots` encoding.
+    pub fn from_bytes(msg: impl Into<Vec<u8>>, bytes: &[u8]) -> Result<Self, MerkleError> {
+        let mut reader = Reader { bytes, position: 0 };
+        let stamp = Self::read(&mut reader, msg.into(), 0)?;
+        reader.finish()?;
+        Ok(stamp)
+    }
+
+    fn write(&self, out: &mut Vec<u8>) -> Result<(), MerkleError> {
+        if self.attestations.is_empty() && self.ops.is_empty() {
+            return Err(MerkleError::InvalidEncoding(
+                "an empty timestamp can't be serialized",
+            ));
+        }
+        let mut attestations: Vec<_> = self.attestations.iter().collect();
+        attestations.sort_by_key(|attestation| attestation.sort_key());
+        let mut ops: Vec<_> = self.ops.iter().collect();
+        ops.sort_by(|(a, _), (b, _)| a.sort_key().cmp(&b.sort_key()));
+
+        // Every item but the last is marked with 0xff.
+        let items = attestations.len() + ops.len();
+        let mut written = 0;
+        for attestation in attestations {
+            written += 1;
+            if written < items {
+                out.push(0xff);
+            }
+            out.push(0x00);
+            out.extend_from_slice(&attestation.tag());
+            write_bytes(out, &attestation.payload());
+        }
+        for (op, stamp) in ops {
+            written += 1;
+            if written < items {
+                out.push(0xff);
+            }
+            out.push(op.tag());
+            if let Op::Append(arg) | Op::Prepend(arg) = op {
+                write_bytes(out, arg);
+            }
+            stamp.write(out)?;
+        }
+        Ok(())
+    }
+
+    fn read(reader: &mut Reader, msg: Vec<u8>, depth: usize) -> Result<Self, MerkleError> {
+        if depth > MAX_RECURSION {
+            return Err(MerkleError::InvalidEncoding("timestamp is nested too deep"));
+        }
+        let mut stamp = Self::new(msg);
+        loop {
+            let mut tag = reader.byte()?;
+            let more = tag == 0xff;
+            if more {
+                tag = reader.byte()?;
+            }
+            if tag == 0x00 {
+                let attestation_tag: [u8; 8] = reader.take(8)?.try_into().unwrap();
+                let payload = reader.bytes(8192)?;
+                stamp.add_attestation(read_attestation(attestation_tag, payload)?);
+            } else {
+                let op = match tag {
+                    0x08 => Op::Sha256,
+                    0x67 => Op::Keccak256,
+                    0xf0 => Op::Append(reader.bytes(MAX_MSG_LEN)?.to_vec()),
+                    0xf1 => Op::Prepend(reader.bytes(MAX_MSG_LEN)?.to_vec()),
+                    _ => return Err(MerkleError::InvalidEncoding("unsupported timestamp op")),
+                };
+                let next = op.apply(&stamp.msg);
+                if next.len() > MAX_MSG_LEN {
+                    return Err(MerkleError::InvalidEncoding("timestamp message too long"));
+                }
+                let child = Self::read(reader, next, depth + 1)?;
+                stamp.ops.push((op, child));
+            }
+            if !more {
+                return Ok(stamp);
+            }
+        }
+    }
+}
+
+/// Operations that turn a node into its parent, for hashers OpenTimestamps
+/// can express.
+pub trait OtsHasher: NodeHasher {
+    fn node_ops(&self, sibling: [u8; 32], sibling_is_left: bool) -> Vec<Op>;
+}
+
+fn concat(sibling: [u8; 32], sibling_is_left: bool) -> Op {
+    match sibling_is_left {
+        true => Op::Prepend(sibling.to_vec()),
+        false => Op::Append(sibling.to_vec()),
+    }
+}
+
+impl OtsHasher for Sha256Hasher {
+    fn node_ops(&self, sibling: [u8; 32], sibling_is_left: bool) -> Vec<Op> {
+        vec![concat(sibling, sibling_is_left), Op::Sha256]
+    }
+}
+
+impl OtsHasher for DoubleSha256Hasher {
+    fn node_ops(&self, sibling: [u8; 32], sibling_is_left: bool) -> Vec<Op> {
+        vec![concat(sibling, sibling_is_left), Op::Sha256, Op::Sha256]
+    }
+}
+
+impl OtsHasher for Keccak256Hasher {
+    fn node_ops(&self, sibling: [u8; 32], sibling_is_left: bool) -> Vec<Op> {
+        vec![concat(sibling, sibling_is_left), Op::Keccak256]
+    }
+}
+
+impl<H: OtsHasher> OtsHasher for DomainSeparated<H> {
+    fn node_ops(&self, sibling: [u8; 32], sibling_is_left: bool) -> Vec<Op> {
+        let mut ops = self.0.node_ops(sibling, sibling_is_left);
+        ops.insert(1, Op::Prepend(vec![crate::NODE_PREFIX]));
+        ops
+    }
+}
+
+/// The timestamp of `leaf`: its inclusion path up to the tree root, then
+/// `root`, the timestamp the root was given.
+pub fn leaf_timestamp<H: OtsHasher>(
+    hasher: &H,
+    leaf: [u8; 32],
+    proof: &Proof,
+    root: &Timestamp,
+) -> Result<Timestamp, MerkleError> {
+    let mut stamp = Timestamp::new(leaf.to_vec());
+    let mut current = &mut stamp;
+    for (level, &sibling) in proof.iter().enumerate() {
+        for op in hasher.node_ops(sibling, (proof.index >> level) & 1 == 1) {
+            current = current.add_op(op);
+        }
+    }
+    current.merge(root)?;
+    Ok(stamp)
+}
+
+/// A timestamp of a SHA-256 file digest, as stored in `.ots` files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DetachedTimestamp {
+    pub timestamp: Timestamp,
+}
+
+impl DetachedTimestamp {
+    /// Wraps a timestamp whose message is a SHA-256 digest.
+    pub fn new(timestamp: Timestamp) -> Result<Self, MerkleError> {
+        if timestamp.msg.len() != 32 {
+            return Err(MerkleError::InvalidEncoding(
+                "detached timestamps are of 32-byte digests",
+            ));
+        }
+        Ok(Self { timestamp })
+    }
+
+    pub fn digest(&self) -> [u8; 32] {
+        self.timestamp.msg[..].try_into().unwrap()
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, MerkleError> {
+        let mut out = HEADER_MAGIC.to_vec();
+        write_varuint(&mut out, 1);
+        out.push(Op::Sha256.tag());
+        out.extend_from_slice(&self.timestamp.msg);
+        self.timestamp.write(&mut out)?;
+        Ok(out)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MerkleError> {
+        let mut reader = Reader { bytes, position: 0 };
+        if reader.take(HEADER_MAGIC.len())? != HEADER_MAGIC {
+            return Err(MerkleError::InvalidEncoding("not a timestamp file"));
+        }
+        if reader.varuint()? != 1 {
+            return Err(MerkleError::InvalidEncoding(
+                "unsupported timestamp version",
+            ));
+        }
+        if reader.byte()? != Op::Sha256.tag() {
+            return Err(MerkleError::InvalidEncoding("unsupported file hash op"));
+        }
+        let digest = reader.take(32)?.to_vec();
+        let timestamp = Timestamp::read(&mut reader, digest, 0)?;
+        reader.finish()?;
+        Ok(Self { timestamp })
+    }
+}
+
+fn read_attestation(tag: [u8; 8], payload: &[u8]) -> Result<Attestation, MerkleError> {
+    let mut reader = Reader {
+        bytes: payload,
+        position: 0,
+    };
+    let attestation = match tag {
+        PENDING_TAG => {
+            let uri = reader.bytes(1000)?;
+            Attestation::Pending {
+                uri: String::from_utf8(uri.to_vec())
+                    .map_err(|_| MerkleError::InvalidEncoding("calendar uri is not utf-8"))?,
+            }
+        }
+        BITCOIN_TAG => Attestation::Bitcoin {
+            height: reader.varuint()?,
+        },
+        _ => {
+            return Ok(Attestation::Unknown {
+                tag,
+                payload: payload.to_vec(),
+            })
+        }
+    };
+    reader.finish()?;
+    Ok(attestation)
+}
+
+fn write_varuint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_varuint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], MerkleError> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + len)
+            .ok_or(MerkleError::InvalidEncoding("timestamp is truncated"))?;
+        self.position += len;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, MerkleError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn varuint(&mut self) -> Result<u64, MerkleError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(MerkleError::InvalidEncoding("varuint is too long"))
+    }
+
+    fn bytes(&mut self, max: usize) -> Result<&'a [u8], MerkleError> {
+        let len = self.varuint()?;
+        if len > max as u64 {
+            return Err(MerkleError::InvalidEncoding("timestamp field is too long"));
+        }
+        self.take(len as usize)
+    }
+
+    fn finish(&self) -> Result<(), MerkleError> {
+        match self.position == self.bytes.len() {
+            true => Ok(()),
+            false => Err(MerkleError::InvalidEncoding(
+                "trailing bytes after timestamp",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hex, MerkleTree};
+
+    #[test]
+    fn test_encoding() {
+        let mut stamp = Timestamp::new([0u8; 32]);
+        stamp.add_attestation(Attestation::Pending {
+            uri: "https://a.pool".into(),
+        });
+        stamp
+            .add_op(Op::Append(vec![0xab]))
+            .add_op(Op::Sha256)
+            .add_attestation(Attestation::Bitcoin { height: 300 });
+        let bytes = stamp.to_bytes().unwrap();
+        assert_eq!(
+            hex::encode(&bytes),
+            concat!(
+                "ff00",
+                "83dfe30d2ef90c8e",
+                "0f",
+                "0e",
+                "68747470733a2f2f612e706f6f6c",
+                "f0",
+                "01ab",
+                "08",
+                "00",
+                "0588960d73d71901",
+                "02",
+                "ac02",
+            )
+        );
+        assert_eq!(Timestamp::from_bytes([0u8; 32], &bytes).unwrap(), stamp);
+        assert!(Timestamp::from_bytes([0u8; 32], &bytes[..bytes.len() - 1]).is_err());
+
+        let file = DetachedTimestamp::new(stamp).unwrap();
+        let encoded = file.to_bytes().unwrap();
+        assert!(encoded.starts_with(HEADER_MAGIC));
+        assert_eq!(DetachedTimestamp::from_bytes(&encoded).unwrap(), file);
+    }
+
+    #[test]
+    fn test_leaf_timestamps_upgrade() {
+        let mut tree = MerkleTree::new(3);
+        for i in 0u8..6 {
+            tree.append_data(&[i]);
+        }
+        let mut root = Timestamp::new(tree.root());
+        root.add_attestation(Attestation::Pending {
+            uri: "https://alice.btc.calendar.opentimestamps.org".into(),
+        });
+
+        let mut stamps: Vec<_> = (0..6)
+            .map(|index| {
+                leaf_timestamp(
+                    tree.hasher(),
+                    tree.leaves()[index],
+                    &tree.get_proof(index),
+                    &root,
+                )
+                .unwrap()
+            })
+            .collect();
+        for stamp in &stamps {
+            assert_eq!(stamp.attestations()[0].0, tree.root());
+            assert!(stamp.is_pending());
+        }
+
+        // The calendar later returns the path from the root to a block.
+        let mut upgrade = Timestamp::new(tree.root());
+        upgrade
+            .add_op(Op::Prepend(vec![1; 8]))
+            .add_op(Op::Sha256)
+            .add_attestation(Attestation::Bitcoin { height: 800_000 });
+        let block_root = Sha256Hasher.hash(&[&[1; 8], &tree.root()]);
+        for stamp in &mut stamps {
+            stamp
+                .find_mut(&tree.root())
+                .unwrap()
+                .merge(&upgrade)
+                .unwrap();
+            let decoded =
+                Timestamp::from_bytes(stamp.msg().to_vec(), &stamp.to_bytes().unwrap()).unwrap();
+            assert!(decoded
+                .attestations()
+                .contains(&(&block_root[..], &Attestation::Bitcoin { height: 800_000 })));
+        }
+        assert!(stamps[0].merge(&upgrade).is_err());
+    }
+}
@@ -0,0 +1,371 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    height, zero_hashes_with, DomainSeparated, MerkleError, MerkleTree, Multiproof, NodeHasher,
+    Proof, Sha256Hasher,
+};
+
+/// The part of a tree a light client cares about: a subset of leaves, the
+/// nodes on their paths, and the boundary hashes next to those paths, all
+/// checked against a trusted root.
+///
+/// It starts empty with just the root, depth and tree size, and grows as
+/// proofs arrive; a proof that doesn't lead to the root is rejected without
+/// touching the tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartialMerkleTree<H = DomainSeparated> {
+    hasher: H,
+    depth: usize,
+    size: usize,
+    root: [u8; 32],
+    /// Indices of the leaves of interest.
+    tracked: BTreeSet<usize>,
+    /// Known nodes by `(level, index)`, leaves at level 0. Besides the
+    /// tracked leaves this holds their paths and the hashes beside them.
+    nodes: BTreeMap<(usize, usize), [u8; 32]>,
+    zeros: Vec<[u8; 32]>,
+}
+
+type Nodes = Vec<((usize, usize), [u8; 32])>;
+
+impl PartialMerkleTree {
+    pub fn new(depth: usize, size: usize, root: [u8; 32]) -> Self {
+        Self::with_hasher(DomainSeparated(Sha256Hasher), depth, size, root)
+    }
+
+    /// Decodes a tree written by [`PartialMerkleTree::to_bytes`] with the
+    /// default hasher.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MerkleError> {
+        Self::from_bytes_with(DomainSeparated(Sha256Hasher), bytes)
+    }
+}
+
+impl<H: NodeHasher> PartialMerkleTree<H> {
+    pub fn with_hasher(hasher: H, depth: usize, size: usize, root: [u8; 32]) -> Self {
+        Self {
+            depth,
+            size,
+            root,
+            tracked: BTreeSet::new(),
+            nodes: BTreeMap::new(),
+            zeros: zero_hashes_with(&hasher, depth),
+            hasher,
+        }
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        self.root
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Number of leaves in the full tree.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// The known leaves with their indices, in index order.
+    pub fn leaves(&self) -> impl Iterator<Item = (usize, [u8; 32])> + '_ {
+        self.tracked
+            .iter()
+            .map(|&index| (index, self.nodes[&(0, index)]))
+    }
+
+    pub fn leaf(&self, index: usize) -> Option<[u8; 32]> {
+        self.contains(index).then(|| self.nodes[&(0, index)])
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        self.tracked.contains(&index)
+    }
+
+    /// Whether `leaf` is the known leaf at `index`. Every stored leaf was
+    /// checked against the root when it was added.
+    pub fn verify_leaf(&self, index: usize, leaf: [u8; 32]) -> bool {
+        self.leaf(index) == Some(leaf)
+    }
+
+    /// Checks `proof` for `leaf` against the root and, if it holds, adds the
+    /// leaf and its path.
+    pub fn add_proof(&mut self, leaf: [u8; 32], proof: &Proof) -> Result<(), MerkleError> {
+        if proof.size != self.size || proof.index >= self.size || proof.siblings.len() != self.depth
+        {
+            return Err(MerkleError::InvalidProof);
+        }
+        let nodes = self.walk(vec![(proof.index, leaf)], |level, _| {
+            Some(proof.siblings[level])
+        })?;
+        self.tracked.insert(proof.index);
+        self.nodes.extend(nodes);
+        Ok(())
+    }
+
+    /// Checks `proof` for `leaves`, in the order of `proof.indices`, and adds
+    /// them if it holds.
+    pub fn add_multiproof(
+        &mut self,
+        proof: &Multiproof,
+        leaves: &[[u8; 32]],
+    ) -> Result<(), MerkleError> {
+        if proof.size != self.size
+            || proof.depth != self.depth
+            || proof.indices.is_empty()
+            || proof.indices.len() != leaves.len()
+            || proof.indices.windows(2).any(|pair| pair[0] >= pair[1])
+            || *proof.indices.last().unwrap() >= self.size
+        {
+            return Err(MerkleError::InvalidProof);
+        }
+        let mut siblings = proof.nodes.iter().copied();
+        let known = proof.indices.iter().copied().zip(leaves.iter().copied());
+        let nodes = self.walk(known.collect(), |_, _| siblings.next())?;
+        if siblings.next().is_some() {
+            return Err(MerkleError::InvalidProof);
+        }
+        self.tracked.extend(&proof.indices);
+        self.nodes.extend(nodes);
+        Ok(())
+    }
+
+    /// Proof for a known leaf, rebuilt from the stored nodes.
+    pub fn get_proof(&self, index: usize) -> Option<Proof> {
+        self.leaf(index)?;
+        let siblings = (0..self.depth)
+            .map(|level| {
+                let sibling = (index >> level) ^ 1;
+                if sibling < level_len(self.size, level) {
+                    self.nodes[&(level, sibling)]
+                } else {
+                    self.zeros[level]
+                }
+            })
+            .collect();
+        Some(Proof {
+            index,
+            size: self.size,
+            siblings,
+        })
+    }
+
+    /// A multiproof for every known leaf, holding just the boundary hashes.
+    pub fn to_multiproof(&self) -> Multiproof {
+        let indices: Vec<usize> = self.tracked.iter().copied().collect();
+        let mut nodes = Vec::new();
+        let mut known = indices.clone();
+        for level in 0..self.depth {
+            for (i, &index) in known.iter().enumerate() {
+                let sibling = index ^ 1;
+                let on_path = if index % 2 == 0 {
+                    known.get(i + 1) == Some(&sibling)
+                } else {
+                    i > 0 && known[i - 1] == sibling
+                };
+                if !on_path && sibling < level_len(self.size, level) {
+                    nodes.push(self.nodes[&(level, sibling)]);
+                }
+            }
+            known = known.into_iter().map(|index| index / 2).collect();
+            known.dedup();
+        }
+        Multiproof {
+            size: self.size,
+            depth: self.depth,
+            indices,
+            nodes,
+        }
+    }
+
+    /// Encodes the root, the leaf count (big-endian `u32`), the known leaves
+    /// and the compact form of [`PartialMerkleTree::to_multiproof`]. Path
+    /// nodes are left out and recomputed when decoding.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let leaves: Vec<_> = self.leaves().collect();
+        let mut bytes = self.root.to_vec();
+        bytes.extend_from_slice(&(leaves.len() as u32).to_be_bytes());
+        for (_, leaf) in leaves {
+            bytes.extend_from_slice(&leaf);
+        }
+        bytes.extend_from_slice(&self.to_multiproof().to_compact_bytes());
+        bytes
+    }
+
+    pub fn from_bytes_with(hasher: H, bytes: &[u8]) -> Result<Self, MerkleError> {
+        let invalid = MerkleError::InvalidEncoding("malformed partial merkle tree");
+        if bytes.len() < 36 {
+            return Err(invalid);
+        }
+        let root: [u8; 32] = bytes[..32].try_into().unwrap();
+        let count = u32::from_be_bytes(bytes[32..36].try_into().unwrap()) as usize;
+        let leaves_end = count
+            .checked_mul(32)
+            .and_then(|len| len.checked_add(36))
+            .filter(|&end| end <= bytes.len())
+            .ok_or(invalid)?;
+        let leaves: Vec<[u8; 32]> = bytes[36..leaves_end]
+            .chunks_exact(32)
+            .map(|leaf| leaf.try_into().unwrap())
+            .collect();
+        let proof = Multiproof::from_compact_bytes(&bytes[leaves_end..])?;
+        let mut tree = Self::with_hasher(hasher, proof.depth, proof.size, root);
+        if count > 0 {
+            tree.add_multiproof(&proof, &leaves)?;
+        } else if !proof.nodes.is_empty() {
+            return Err(MerkleError::InvalidProof);
+        }
+        Ok(tree)
+    }
+
+    /// Hashes `known` leaves up to the root, taking siblings off the paths
+    /// from `sibling(level, index)` and returning every node seen if the
+    /// result is the root.
+    fn walk(
+        &self,
+        mut known: Vec<(usize, [u8; 32])>,
+        mut sibling: impl FnMut(usize, usize) -> Option<[u8; 32]>,
+    ) -> Result<Nodes, MerkleError> {
+        if height(self.size) > self.depth {
+            return Err(MerkleError::InvalidProof);
+        }
+        let mut nodes = Vec::new();
+        for level in 0..self.depth {
+            let mut parents = Vec::with_capacity(known.len());
+            let mut i = 0;
+            while i < known.len() {
+                let (index, node) = known[i];
+                nodes.push(((level, index), node));
+                let (left, right) = match known.get(i + 1) {
+                    Some(&(next, right)) if index % 2 == 0 && next == index + 1 => {
+                        nodes.push(((level, next), right));
+                        i += 1;
+                        (node, right)
+                    }
+                    _ => {
+                        let sibling = if index ^ 1 < level_len(self.size, level) {
+                            let node =
+                                sibling(level, index ^ 1).ok_or(MerkleError::InvalidProof)?;
+                            nodes.push(((level, index ^ 1), node));
+                            node
+                        } else {
+                            self.zeros[level]
+                        };
+                        if index % 2 == 0 {
+                            (node, sibling)
+                        } else {
+                            (sibling, node)
+                        }
+                    }
+                };
+                parents.push((index / 2, self.hasher.hash_nodes(left, right)));
+                i += 1;
+            }
+            known = parents;
+        }
+        if known != [(0, self.root)] {
+            return Err(MerkleError::InvalidProof);
+        }
+        nodes.push(((self.depth, 0), self.root));
+        Ok(nodes)
+    }
+}
+
+impl<H: NodeHasher + Clone> MerkleTree<H> {
+    /// The partial tree holding the leaves at `indices`.
+    pub fn to_partial(&self, indices: &[usize]) -> Result<PartialMerkleTree<H>, MerkleError> {
+        let mut partial = PartialMerkleTree::with_hasher(
+            self.hasher.clone(),
+            self.depth,
+            self.len(),
+            self.root(),
+        );
+        if !indices.is_empty() {
+            let proof = self.get_multiproof(indices)?;
+            let leaves: Vec<_> = proof.indices.iter().map(|&i| self.leaves()[i]).collect();
+            partial.add_multiproof(&proof, &leaves)?;
+        }
+        Ok(partial)
+    }
+}
+
+fn level_len(size: usize, level: usize) -> usize {
+    ((size - 1) >> level) + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree(size: u8) -> MerkleTree {
+        let mut tree = MerkleTree::new(7);
+        for i in 0..size {
+            tree.append_data(&[i]);
+        }
+        tree
+    }
+
+    #[test]
+    fn test_grows_from_proofs() {
+        let tree = tree(21);
+        let mut partial = PartialMerkleTree::new(7, 21, tree.root());
+        for index in [3, 20, 2] {
+            partial
+                .add_proof(tree.leaves()[index], &tree.get_proof(index))
+                .unwrap();
+            // Leaf 2's hash is known as a boundary before it is tracked.
+            if index == 3 {
+                assert!(!partial.contains(2));
+            }
+        }
+        assert_eq!(
+            partial.leaves().map(|(index, _)| index).collect::<Vec<_>>(),
+            [2, 3, 20]
+        );
+        for index in [2, 3, 20] {
+            assert!(partial.verify_leaf(index, tree.leaves()[index]));
+            assert_eq!(partial.get_proof(index).unwrap(), tree.get_proof(index));
+        }
+        assert!(!partial.verify_leaf(4, tree.leaves()[4]));
+        assert_eq!(partial.get_proof(4), None);
+
+        // A bad proof is rejected and leaves the tree as it was.
+        let before = partial.clone();
+        assert_eq!(
+            partial.add_proof([9; 32], &tree.get_proof(7)),
+            Err(MerkleError::InvalidProof)
+        );
+        assert_eq!(partial, before);
+        let mut other = tree.get_multiproof(&[8, 9]).unwrap();
+        other.nodes[0][0] ^= 1;
+        assert!(partial
+            .add_multiproof(&other, &[tree.leaves()[8], tree.leaves()[9]])
+            .is_err());
+        assert_eq!(partial, before);
+    }
+
+    #[test]
+    fn test_compact_round_trip() {
+        let tree = tree(29);
+        let partial = tree.to_partial(&[0, 1, 2, 17, 28]).unwrap();
+        assert_eq!(
+            partial.to_multiproof(),
+            tree.get_multiproof(&[0, 1, 2, 17, 28]).unwrap()
+        );
+        let bytes = partial.to_bytes();
+        assert_eq!(PartialMerkleTree::from_bytes(&bytes).unwrap(), partial);
+        assert!(PartialMerkleTree::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+        // No leaves, then a multiproof header claiming 2^32 - 1 of them.
+        let mut lying = [0; 36].to_vec();
+        lying.extend_from_slice(&(1u64 << 40).to_be_bytes());
+        lying.extend_from_slice(&u32::MAX.to_be_bytes());
+        lying.push(40);
+        assert!(PartialMerkleTree::from_bytes(&lying).is_err());
+
+        let empty = tree.to_partial(&[]).unwrap();
+        assert_eq!(
+            PartialMerkleTree::from_bytes(&empty.to_bytes()).unwrap(),
+            empty
+        );
+    }
+}
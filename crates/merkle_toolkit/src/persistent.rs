@@ -0,0 +1,343 @@
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::tree::capacity;
+use crate::{
+    zero_hashes_with, DomainSeparated, MerkleError, MerkleTree, NodeHasher, Proof, RetentionPolicy,
+    Sha256Hasher, MAX_DEPTH,
+};
+
+/// A subtree, or `None` for an empty one, whose hash is the zero hash of its
+/// height.
+type Link = Option<Arc<Node>>;
+
+#[derive(Debug)]
+struct Node {
+    hash: [u8; 32],
+    /// Left and right subtrees; `None` for a leaf.
+    children: Option<(Link, Link)>,
+}
+
+/// An immutable merkle tree whose updates return a new tree, leaving the
+/// old one intact.
+///
+/// The new tree shares every subtree the update didn't touch with the old
+/// one through [`Arc`], so an update allocates only the `depth` nodes on the
+/// changed path, and a snapshot is a clone: `O(1)` and safe to send to other
+/// threads. Keeping old trees around gives MVCC-style reads, each reader
+/// querying the version it started with while writers move on.
+///
+/// Roots and proofs are those of a [`MerkleTree`] of the same depth,
+/// hasher and leaves.
+#[derive(Debug, Clone)]
+pub struct PersistentMerkleTree<H = DomainSeparated> {
+    depth: usize,
+    len: usize,
+    root: Link,
+    hasher: H,
+    zeros: Arc<[[u8; 32]]>,
+}
+
+impl PersistentMerkleTree {
+    pub fn new(depth: usize) -> Self {
+        Self::with_hasher(depth, DomainSeparated(Sha256Hasher))
+    }
+
+    pub fn try_new(depth: usize) -> Result<Self, MerkleError> {
+        Self::try_with_hasher(depth, DomainSeparated(Sha256Hasher))
+    }
+}
+
+impl<H: NodeHasher + Clone> PersistentMerkleTree<H> {
+    pub fn with_hasher(depth: usize, hasher: H) -> Self {
+        Self::try_with_hasher(depth, hasher).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    pub fn try_with_hasher(depth: usize, hasher: H) -> Result<Self, MerkleError> {
+        if depth > MAX_DEPTH {
+            return Err(MerkleError::DepthTooLarge {
+                depth,
+                max: MAX_DEPTH,
+            });
+        }
+        Ok(Self {
+            depth,
+            len: 0,
+            root: None,
+            zeros: zero_hashes_with(&hasher, depth).into(),
+            hasher,
+        })
+    }
+
+    /// A persistent copy of `tree`'s leaves.
+    pub fn from_tree(tree: &MerkleTree<H>) -> Self {
+        Self::with_hasher(tree.depth(), tree.hasher().clone())
+            .extend(tree.leaves().iter().copied())
+            .expect("leaves fit the tree they came from")
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        self.hash(&self.root, self.depth)
+    }
+
+    pub fn get(&self, index: usize) -> Option<[u8; 32]> {
+        if index >= self.len {
+            return None;
+        }
+        let mut link = &self.root;
+        for level in (0..self.depth).rev() {
+            let (left, right) = link.as_ref()?.children.as_ref()?;
+            link = if (index >> level) & 1 == 0 {
+                left
+            } else {
+                right
+            };
+        }
+        link.as_ref().map(|leaf| leaf.hash)
+    }
+
+    /// The tree with `leaf` appended.
+    pub fn push(&self, leaf: [u8; 32]) -> Result<Self, MerkleError> {
+        if self.len == capacity(self.depth) {
+            return Err(MerkleError::TreeFull {
+                capacity: capacity(self.depth),
+            });
+        }
+        Ok(self.with_leaf(self.len, leaf, self.len + 1))
+    }
+
+    /// The tree with every leaf of `leaves` appended.
+    pub fn extend(&self, leaves: impl IntoIterator<Item = [u8; 32]>) -> Result<Self, MerkleError> {
+        let mut tree = self.clone();
+        for leaf in leaves {
+            tree = tree.push(leaf)?;
+        }
+        Ok(tree)
+    }
+
+    /// The tree with the leaf at `index` replaced by `leaf`.
+    pub fn set(&self, index: usize, leaf: [u8; 32]) -> Result<Self, MerkleError> {
+        if index >= self.len {
+            return Err(MerkleError::IndexOutOfBounds {
+                index,
+                len: self.len,
+            });
+        }
+        Ok(self.with_leaf(index, leaf, self.len))
+    }
+
+    pub fn get_proof(&self, index: usize) -> Result<Proof, MerkleError> {
+        if index >= self.len {
+            return Err(MerkleError::IndexOutOfBounds {
+                index,
+                len: self.len,
+            });
+        }
+        let mut siblings = Vec::with_capacity(self.depth);
+        let mut link = &self.root;
+        for level in (0..self.depth).rev() {
+            let (left, right) = link
+                .as_ref()
+                .and_then(|node| node.children.as_ref())
+                .unwrap();
+            let (next, sibling) = match (index >> level) & 1 {
+                0 => (left, right),
+                _ => (right, left),
+            };
+            siblings.push(self.hash(sibling, level));
+            link = next;
+        }
+        siblings.reverse();
+        Ok(Proof {
+            index,
+            size: self.len,
+            siblings,
+        })
+    }
+
+    /// Whether `self` and `other` share their whole tree, as a snapshot and
+    /// the tree it was taken from do until either is updated.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        match (&self.root, &other.root) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+
+    fn with_leaf(&self, index: usize, leaf: [u8; 32], len: usize) -> Self {
+        Self {
+            root: Some(self.update(&self.root, self.depth, index, leaf)),
+            len,
+            ..self.clone()
+        }
+    }
+
+    /// Rebuilds the path from the root of `link`, `level` levels above the
+    /// leaves, down to the leaf at `index`.
+    fn update(&self, link: &Link, level: usize, index: usize, leaf: [u8; 32]) -> Arc<Node> {
+        if level == 0 {
+            return Arc::new(Node {
+                hash: leaf,
+                children: None,
+            });
+        }
+        let (mut left, mut right) = link
+            .as_ref()
+            .and_then(|node| node.children.clone())
+            .unwrap_or_default();
+        let child = match (index >> (level - 1)) & 1 {
+            0 => &mut left,
+            _ => &mut right,
+        };
+        *child = Some(self.update(child, level - 1, index, leaf));
+        Arc::new(Node {
+            hash: self
+                .hasher
+                .hash_nodes(self.hash(&left, level - 1), self.hash(&right, level - 1)),
+            children: Some((left, right)),
+        })
+    }
+
+    fn hash(&self, link: &Link, level: usize) -> [u8; 32] {
+        link.as_ref().map_or(self.zeros[level], |node| node.hash)
+    }
+}
+
+/// Numbered versions of a [`PersistentMerkleTree`], kept under a
+/// [`RetentionPolicy`].
+///
+/// Versions share their unchanged subtrees, so each costs only the nodes it
+/// changed. [`PersistentHistory::compact`] drops the versions the policy no
+/// longer keeps, freeing every node that no kept version, or tree handed out
+/// and still held elsewhere, shares. Version numbers stay the same after
+/// compaction.
+#[derive(Debug, Clone)]
+pub struct PersistentHistory<H = DomainSeparated> {
+    /// Number of the first kept version.
+    first_version: usize,
+    versions: Vec<PersistentMerkleTree<H>>,
+    recorded: Vec<SystemTime>,
+    retention: RetentionPolicy,
+}
+
+impl<H: NodeHasher + Clone> PersistentHistory<H> {
+    /// A history whose version 0 is `tree`.
+    pub fn new(tree: PersistentMerkleTree<H>) -> Self {
+        Self {
+            first_version: 0,
+            versions: vec![tree],
+            recorded: vec![SystemTime::now()],
+            retention: RetentionPolicy::KeepAll,
+        }
+    }
+
+    /// Sets the policy [`PersistentHistory::compact`] applies.
+    pub fn with_retention(mut self, retention: RetentionPolicy) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Records `tree` as the next version and returns its number.
+    pub fn commit(&mut self, tree: PersistentMerkleTree<H>) -> usize {
+        self.versions.push(tree);
+        self.recorded.push(SystemTime::now());
+        self.version()
+    }
+
+    /// Number of the latest version.
+    pub fn version(&self) -> usize {
+        self.first_version + self.versions.len() - 1
+    }
+
+    /// Number of the oldest version still kept.
+    pub fn oldest_version(&self) -> usize {
+        self.first_version
+    }
+
+    pub fn latest(&self) -> &PersistentMerkleTree<H> {
+        &self.versions[self.versions.len() - 1]
+    }
+
+    pub fn get(&self, version: usize) -> Option<&PersistentMerkleTree<H>> {
+        self.versions.get(version.checked_sub(self.first_version)?)
+    }
+
+    /// Drops the versions the retention policy no longer keeps, returning
+    /// how many were dropped.
+    pub fn compact(&mut self) -> usize {
+        let expired = self.retention.expired(&self.recorded, SystemTime::now());
+        self.versions.drain(..expired);
+        self.recorded.drain(..expired);
+        self.first_version += expired;
+        expired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_updates_leave_snapshots_intact() {
+        let mut tree = MerkleTree::new(4);
+        for i in 0u8..9 {
+            tree.append_data(&[i]);
+        }
+        let v1 = PersistentMerkleTree::from_tree(&tree);
+        assert_eq!(v1.root(), tree.root());
+        let snapshot = v1.clone();
+        assert!(snapshot.ptr_eq(&v1));
+
+        let v2 = v1.set(3, [7; 32]).unwrap().push([8; 32]).unwrap();
+        tree.set_leaf(3, [7; 32]).unwrap();
+        tree.append_leaf([8; 32]);
+        assert_eq!(v2.root(), tree.root());
+        assert_eq!(v2.get_proof(9).unwrap(), tree.get_proof(9));
+        assert_eq!(v2.get(3), Some([7; 32]));
+        assert_eq!((v1.len(), v2.len()), (9, 10));
+        assert_eq!(v1.root(), snapshot.root());
+        assert_ne!(v1.get(3), v2.get(3));
+        assert!(!v1.ptr_eq(&v2));
+
+        // An update on the right leaves the left half shared.
+        let halves = |tree: &PersistentMerkleTree| tree.root.as_ref().unwrap().children.clone();
+        let (v2_left, _) = halves(&v2).unwrap();
+        let (v3_left, _) = halves(&v2.set(9, [1; 32]).unwrap()).unwrap();
+        assert!(Arc::ptr_eq(&v2_left.unwrap(), &v3_left.unwrap()));
+        assert!(v1.set(9, [0; 32]).is_err());
+    }
+
+    #[test]
+    fn test_compact_frees_unshared_nodes() {
+        let base = PersistentMerkleTree::new(3)
+            .extend([[1; 32], [2; 32]])
+            .unwrap();
+        let mut history =
+            PersistentHistory::new(base.clone()).with_retention(RetentionPolicy::KeepLast(2));
+        let replaced = Arc::downgrade(base.root.as_ref().unwrap());
+        drop(base);
+        history.commit(history.latest().set(0, [3; 32]).unwrap());
+        let v2 = history.commit(history.latest().push([4; 32]).unwrap());
+        assert!(replaced.upgrade().is_some());
+
+        assert_eq!(history.compact(), 1);
+        assert!(replaced.upgrade().is_none());
+        assert_eq!((history.oldest_version(), history.version()), (1, v2));
+        assert!(history.get(0).is_none());
+        assert_eq!(history.get(1).unwrap().get(0), Some([3; 32]));
+        assert_eq!(history.latest().len(), 3);
+    }
+}
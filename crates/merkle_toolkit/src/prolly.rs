@@ -0,0 +1,435 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{NodeHasher, Sha256Hasher};
+
+/// Average number of entries per node of a [`ProllyTree`].
+pub const DEFAULT_CHUNK_SIZE: u32 = 32;
+
+type Entry = (Vec<u8>, Vec<u8>);
+
+/// A node of a [`ProllyTree`], addressed by the SHA-256 of its encoding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+pub enum ProllyNode {
+    /// Key-value pairs in key order.
+    Leaf(Vec<(Vec<u8>, Vec<u8>)>),
+    /// The first key below each child with the child's hash. Leaves are at
+    /// level 0, so `level` is at least 1.
+    Internal {
+        level: u8,
+        children: Vec<(Vec<u8>, [u8; 32])>,
+    },
+}
+
+impl ProllyNode {
+    pub fn level(&self) -> u8 {
+        match self {
+            Self::Leaf(_) => 0,
+            Self::Internal { level, .. } => *level,
+        }
+    }
+
+    /// Encodes the level byte, then every entry as length-prefixed (`u32`,
+    /// big-endian) key and value, or key and child hash.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![self.level()];
+        let mut push = |key: &[u8], value: &[u8], prefixed: bool| {
+            bytes.extend_from_slice(&(key.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(key);
+            if prefixed {
+                bytes.extend_from_slice(&(value.len() as u32).to_be_bytes());
+            }
+            bytes.extend_from_slice(value);
+        };
+        match self {
+            Self::Leaf(entries) => entries
+                .iter()
+                .for_each(|(key, value)| push(key, value, true)),
+            Self::Internal { children, .. } => children
+                .iter()
+                .for_each(|(key, hash)| push(key, hash, false)),
+        }
+        bytes
+    }
+
+    pub fn hash(&self) -> [u8; 32] {
+        Sha256Hasher.hash(&[&self.to_bytes()])
+    }
+
+    fn first_key(&self) -> &[u8] {
+        match self {
+            Self::Leaf(entries) => &entries[0].0,
+            Self::Internal { children, .. } => &children[0].0,
+        }
+    }
+}
+
+/// A change between two key-value sets, from the first to the second.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyDiff {
+    Added {
+        key: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Removed {
+        key: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Modified {
+        key: Vec<u8>,
+        old: Vec<u8>,
+        new: Vec<u8>,
+    },
+}
+
+impl KeyDiff {
+    pub fn key(&self) -> &[u8] {
+        match self {
+            Self::Added { key, .. } | Self::Removed { key, .. } | Self::Modified { key, .. } => key,
+        }
+    }
+}
+
+/// A probabilistic B-tree: a sorted key-value map cut into nodes at
+/// boundaries decided by hashing the keys themselves.
+///
+/// A key ends its node when the hash of the key and level falls below
+/// `u32::MAX / chunk_size`, so boundaries depend only on the content around
+/// them. The same entries always give the same tree whatever order they were
+/// written in, and an edit only changes the nodes on its path plus, rarely,
+/// a neighbour whose boundary moved. Replicas holding similar data therefore
+/// share most nodes, and [`ProllyTree::diff`] skips every shared subtree.
+///
+/// Edits rebuild the node set from the sorted entries; changing many keys
+/// at once through [`ProllyTree::apply`] pays that cost once.
+#[derive(Debug, Clone)]
+pub struct ProllyTree {
+    chunk_size: u32,
+    root: Option<[u8; 32]>,
+    nodes: HashMap<[u8; 32], ProllyNode>,
+}
+
+impl Default for ProllyTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProllyTree {
+    pub fn new() -> Self {
+        Self::with_chunk_size(DEFAULT_CHUNK_SIZE)
+    }
+
+    /// An empty tree averaging `chunk_size` entries per node. Trees only
+    /// share nodes with trees of the same chunk size.
+    pub fn with_chunk_size(chunk_size: u32) -> Self {
+        assert!(chunk_size > 1, "chunk size must be at least 2");
+        Self {
+            chunk_size,
+            root: None,
+            nodes: HashMap::new(),
+        }
+    }
+
+    pub fn from_entries(entries: impl IntoIterator<Item = (Vec<u8>, Vec<u8>)>) -> Self {
+        let mut tree = Self::new();
+        tree.apply(entries.into_iter().map(|(key, value)| (key, Some(value))));
+        tree
+    }
+
+    /// Root hash, or `None` for an empty tree.
+    pub fn root(&self) -> Option<[u8; 32]> {
+        self.root
+    }
+
+    pub fn node(&self, hash: &[u8; 32]) -> Option<&ProllyNode> {
+        self.nodes.get(hash)
+    }
+
+    /// Every node of the tree with its hash.
+    pub fn nodes(&self) -> impl Iterator<Item = (&[u8; 32], &ProllyNode)> {
+        self.nodes.iter()
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<&[u8]> {
+        let mut node = self.nodes.get(&self.root?)?;
+        loop {
+            match node {
+                ProllyNode::Leaf(entries) => {
+                    let index = entries
+                        .binary_search_by(|(entry, _)| entry.as_slice().cmp(key))
+                        .ok()?;
+                    return Some(&entries[index].1);
+                }
+                ProllyNode::Internal { children, .. } => {
+                    let index = children
+                        .partition_point(|(first, _)| first.as_slice() <= key)
+                        .checked_sub(1)?;
+                    node = &self.nodes[&children[index].1];
+                }
+            }
+        }
+    }
+
+    /// All entries in key order.
+    pub fn entries(&self) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let mut entries = Vec::new();
+        if let Some(root) = self.root {
+            self.collect(&root, &mut entries);
+        }
+        entries
+    }
+
+    pub fn insert(&mut self, key: Vec<u8>, value: Vec<u8>) {
+        self.apply([(key, Some(value))]);
+    }
+
+    pub fn remove(&mut self, key: &[u8]) {
+        self.apply([(key.to_vec(), None)]);
+    }
+
+    /// Sets (`Some`) or removes (`None`) each key, then rebuilds the tree.
+    pub fn apply(&mut self, edits: impl IntoIterator<Item = (Vec<u8>, Option<Vec<u8>>)>) {
+        let mut entries: BTreeMap<Vec<u8>, Vec<u8>> = self.entries().into_iter().collect();
+        for (key, value) in edits {
+            match value {
+                Some(value) => entries.insert(key, value),
+                None => entries.remove(&key),
+            };
+        }
+        self.build(entries.into_iter().collect());
+    }
+
+    /// The changes that turn `self` into `other`, in key order. Subtrees the
+    /// two trees share are skipped without being read.
+    pub fn diff(&self, other: &Self) -> Vec<KeyDiff> {
+        let mut diff = Vec::new();
+        let mut ours = Cursor::new(self);
+        let mut theirs = Cursor::new(other);
+        loop {
+            match (ours.stack.last(), theirs.stack.last()) {
+                (None, None) => break,
+                (Some(Item::Node(a)), Some(Item::Node(b))) if a == b => {
+                    ours.stack.pop();
+                    theirs.stack.pop();
+                }
+                (Some(Item::Entry(a)), Some(Item::Entry(b))) => match a.0.cmp(&b.0) {
+                    Ordering::Less => {
+                        let (key, value) = ours.pop_entry();
+                        diff.push(KeyDiff::Removed { key, value });
+                    }
+                    Ordering::Greater => {
+                        let (key, value) = theirs.pop_entry();
+                        diff.push(KeyDiff::Added { key, value });
+                    }
+                    Ordering::Equal => {
+                        let (key, old) = ours.pop_entry();
+                        let (_, new) = theirs.pop_entry();
+                        if old != new {
+                            diff.push(KeyDiff::Modified { key, old, new });
+                        }
+                    }
+                },
+                (Some(Item::Entry(_)), None) => {
+                    let (key, value) = ours.pop_entry();
+                    diff.push(KeyDiff::Removed { key, value });
+                }
+                (None, Some(Item::Entry(_))) => {
+                    let (key, value) = theirs.pop_entry();
+                    diff.push(KeyDiff::Added { key, value });
+                }
+                // Open the taller node first, so subtrees at the same level
+                // line up for the equality check above.
+                (a, b) => {
+                    if ours.level(a) >= theirs.level(b) {
+                        ours.expand();
+                    } else {
+                        theirs.expand();
+                    }
+                }
+            }
+        }
+        diff
+    }
+
+    fn collect(&self, hash: &[u8; 32], entries: &mut Vec<Entry>) {
+        match &self.nodes[hash] {
+            ProllyNode::Leaf(leaf) => entries.extend(leaf.iter().cloned()),
+            ProllyNode::Internal { children, .. } => children
+                .iter()
+                .for_each(|(_, child)| self.collect(child, entries)),
+        }
+    }
+
+    fn is_boundary(&self, level: u8, key: &[u8]) -> bool {
+        let hash = Sha256Hasher.hash(&[&[level], key]);
+        u32::from_be_bytes(hash[..4].try_into().unwrap()) < u32::MAX / self.chunk_size
+    }
+
+    fn build(&mut self, entries: Vec<Entry>) {
+        self.nodes.clear();
+        self.root = None;
+        if entries.is_empty() {
+            return;
+        }
+        let mut level = self.chunk(0, entries, ProllyNode::Leaf);
+        let mut height = 0;
+        while level.len() > 1 {
+            height += 1;
+            level = self.chunk(height, level, |children| ProllyNode::Internal {
+                level: height,
+                children,
+            });
+        }
+        self.root = Some(level[0].1);
+    }
+
+    /// Cuts `items` into nodes at content-defined boundaries and returns the
+    /// first key and hash of each.
+    fn chunk<T>(
+        &mut self,
+        level: u8,
+        items: Vec<(Vec<u8>, T)>,
+        node: impl Fn(Vec<(Vec<u8>, T)>) -> ProllyNode,
+    ) -> Vec<(Vec<u8>, [u8; 32])> {
+        let mut parents = Vec::new();
+        let mut current = Vec::new();
+        let last = items.len() - 1;
+        for (i, (key, item)) in items.into_iter().enumerate() {
+            let boundary = i == last || self.is_boundary(level, &key);
+            current.push((key, item));
+            if boundary {
+                let node = node(std::mem::take(&mut current));
+                let hash = node.hash();
+                parents.push((node.first_key().to_vec(), hash));
+                self.nodes.insert(hash, node);
+            }
+        }
+        parents
+    }
+}
+
+enum Item<'a> {
+    Node(&'a [u8; 32]),
+    Entry(&'a Entry),
+}
+
+/// The rest of a tree in key order, as a stack whose top comes first.
+struct Cursor<'a> {
+    tree: &'a ProllyTree,
+    stack: Vec<Item<'a>>,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(tree: &'a ProllyTree) -> Self {
+        Self {
+            tree,
+            stack: tree.root.iter().map(Item::Node).collect(),
+        }
+    }
+
+    fn level(&self, item: Option<&Item>) -> i16 {
+        match item {
+            Some(Item::Node(hash)) => i16::from(self.tree.nodes[*hash].level()),
+            _ => -1,
+        }
+    }
+
+    fn expand(&mut self) {
+        let Some(Item::Node(hash)) = self.stack.pop() else {
+            unreachable!("only nodes are expanded");
+        };
+        match &self.tree.nodes[hash] {
+            ProllyNode::Leaf(entries) => self.stack.extend(entries.iter().rev().map(Item::Entry)),
+            ProllyNode::Internal { children, .. } => self
+                .stack
+                .extend(children.iter().rev().map(|(_, child)| Item::Node(child))),
+        }
+    }
+
+    fn pop_entry(&mut self) -> Entry {
+        match self.stack.pop() {
+            Some(Item::Entry(entry)) => entry.clone(),
+            _ => unreachable!("top of the stack is an entry"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(range: std::ops::Range<u32>) -> Vec<Entry> {
+        range
+            .map(|i| (i.to_be_bytes().to_vec(), format!("value {i}").into_bytes()))
+            .collect()
+    }
+
+    #[test]
+    fn test_structure_is_history_independent() {
+        let all = entries(0..2000);
+        let tree = ProllyTree::from_entries(all.clone());
+        assert!(tree.nodes().any(|(_, node)| node.level() >= 1));
+        assert_eq!(tree.entries(), all);
+        assert_eq!(tree.get(&7u32.to_be_bytes()), Some(&b"value 7"[..]));
+        assert_eq!(tree.get(&5000u32.to_be_bytes()), None);
+
+        let mut incremental = ProllyTree::new();
+        for (key, value) in all.iter().rev().step_by(2) {
+            incremental.insert(key.clone(), value.clone());
+        }
+        incremental.apply(
+            all.iter()
+                .map(|(key, value)| (key.clone(), Some(value.clone()))),
+        );
+        assert_eq!(incremental.root(), tree.root());
+
+        for (key, _) in &all {
+            incremental.remove(key);
+        }
+        assert_eq!(incremental.root(), None);
+    }
+
+    #[test]
+    fn test_diff_skips_shared_nodes() {
+        let base = ProllyTree::from_entries(entries(0..5000));
+        let mut edited = base.clone();
+        edited.apply([
+            (10u32.to_be_bytes().to_vec(), None),
+            (2500u32.to_be_bytes().to_vec(), Some(b"changed".to_vec())),
+            (9000u32.to_be_bytes().to_vec(), Some(b"new".to_vec())),
+        ]);
+
+        let shared = edited
+            .nodes()
+            .filter(|(hash, _)| base.node(hash).is_some())
+            .count();
+        assert!(shared * 10 > edited.nodes().count() * 8);
+
+        assert_eq!(
+            base.diff(&edited),
+            [
+                KeyDiff::Removed {
+                    key: 10u32.to_be_bytes().to_vec(),
+                    value: b"value 10".to_vec(),
+                },
+                KeyDiff::Modified {
+                    key: 2500u32.to_be_bytes().to_vec(),
+                    old: b"value 2500".to_vec(),
+                    new: b"changed".to_vec(),
+                },
+                KeyDiff::Added {
+                    key: 9000u32.to_be_bytes().to_vec(),
+                    value: b"new".to_vec(),
+                },
+            ]
+        );
+        assert!(base.diff(&base).is_empty());
+        assert_eq!(ProllyTree::new().diff(&base).len(), 5000);
+    }
+}
@@ -0,0 +1,304 @@
+use std::ops::Deref;
+
+use crate::{hex, DomainSeparated, MerkleError, MerkleTree, NodeHasher, NodeStore, Sha256Hasher};
+
+/// An inclusion proof for a single leaf.
+///
+/// Besides the sibling path (leaf level first), the proof records which leaf
+/// it is for and the size of the tree it was generated from, so it can't be
+/// checked against the wrong index by accident. It dereferences to the
+/// sibling path.
+///
+/// The borsh encoding is `index: u64, size: u64, siblings: Vec<[u8; 32]>`,
+/// i.e. the layout of that struct deriving `BorshDeserialize` in a Solana
+/// program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+pub struct Proof {
+    pub index: usize,
+    pub size: usize,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::vec"))]
+    pub siblings: Vec<[u8; 32]>,
+}
+
+impl Proof {
+    /// Verifies the proof with the default [`MerkleTree`] hasher.
+    pub fn verify(&self, leaf: [u8; 32], root: [u8; 32]) -> bool {
+        self.verify_with(&DomainSeparated(Sha256Hasher), leaf, root)
+    }
+
+    pub fn verify_with<H: NodeHasher>(&self, hasher: &H, leaf: [u8; 32], root: [u8; 32]) -> bool {
+        span!(TRACE, "merkle.verify", index = self.index, size = self.size);
+        crate::telemetry::proof_verified(
+            self.index < self.size
+                && MerkleTree::verify_proof_with(hasher, leaf, &self.siblings, self.index, root),
+        )
+    }
+
+    /// Verifies the proof for the leaf of raw `data` with the default
+    /// [`MerkleTree`] hasher.
+    pub fn verify_data(&self, data: &[u8], root: [u8; 32]) -> bool {
+        self.verify_data_with(&DomainSeparated(Sha256Hasher), data, root)
+    }
+
+    /// Verifies the proof for `hasher.hash_leaf(data)`; see
+    /// [`crate::verify_data_proof`].
+    pub fn verify_data_with<H: NodeHasher>(&self, hasher: &H, data: &[u8], root: [u8; 32]) -> bool {
+        self.verify_with(hasher, hasher.hash_leaf(data), root)
+    }
+
+    /// Verifies the proof with the default [`MerkleTree`] hasher, also
+    /// requiring one sibling per level of a tree of `depth` that can hold
+    /// [`Proof::size`] leaves. See [`crate::verify_proof_strict`].
+    pub fn verify_strict(
+        &self,
+        leaf: [u8; 32],
+        root: [u8; 32],
+        depth: usize,
+    ) -> Result<(), MerkleError> {
+        self.verify_strict_with(&DomainSeparated(Sha256Hasher), leaf, root, depth)
+    }
+
+    pub fn verify_strict_with<H: NodeHasher>(
+        &self,
+        hasher: &H,
+        leaf: [u8; 32],
+        root: [u8; 32],
+        depth: usize,
+    ) -> Result<(), MerkleError> {
+        crate::verify_proof_strict(
+            hasher,
+            leaf,
+            &self.siblings,
+            self.index,
+            self.size,
+            depth,
+            root,
+        )
+    }
+
+    /// Encodes the index and size (big-endian `u64`) followed by the siblings.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(16 + self.siblings.len() * 32);
+        bytes.extend_from_slice(&(self.index as u64).to_be_bytes());
+        bytes.extend_from_slice(&(self.size as u64).to_be_bytes());
+        for sibling in &self.siblings {
+            bytes.extend_from_slice(sibling);
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MerkleError> {
+        if bytes.len() < 16 || (bytes.len() - 16) % 32 != 0 {
+            return Err(MerkleError::InvalidEncoding(
+                "proof length is not 16 + 32n bytes",
+            ));
+        }
+        let field = |range: std::ops::Range<usize>| {
+            usize::try_from(u64::from_be_bytes(bytes[range].try_into().unwrap()))
+                .map_err(|_| MerkleError::InvalidEncoding("proof field does not fit in usize"))
+        };
+        Ok(Self {
+            index: field(0..8)?,
+            size: field(8..16)?,
+            siblings: bytes[16..]
+                .chunks_exact(32)
+                .map(|sibling| sibling.try_into().unwrap())
+                .collect(),
+        })
+    }
+
+    /// Hex encoding of [`Proof::to_bytes`].
+    pub fn to_hex(&self) -> String {
+        hex::encode(&self.to_bytes())
+    }
+
+    pub fn from_hex(s: &str) -> Result<Self, MerkleError> {
+        Self::from_bytes(&hex::decode(s)?)
+    }
+}
+
+impl<H: NodeHasher, S: NodeStore> MerkleTree<H, S> {
+    /// Checks that `proof` leads from the leaf of raw `data`, hashed as this
+    /// tree hashes its leaves, to the current root.
+    pub fn verify_data(&self, data: &[u8], proof: &Proof) -> Result<bool, MerkleError> {
+        Ok(proof.verify_data_with(&self.hasher, data, self.try_root()?))
+    }
+}
+
+impl Deref for Proof {
+    type Target = [[u8; 32]];
+
+    fn deref(&self) -> &Self::Target {
+        &self.siblings
+    }
+}
+
+/// An inclusion proof that records, for each level, which side its sibling
+/// is on, instead of a leaf index.
+///
+/// Verification reads the hashing order from the flags, so there is no index
+/// for the caller to supply and get wrong; the leaf's index is derived from
+/// the flags with [`DirectionalProof::index`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+pub struct DirectionalProof {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::vec"))]
+    pub siblings: Vec<[u8; 32]>,
+    /// One flag per sibling, leaf level first: set when the sibling is the
+    /// left child, i.e. the path goes through a right child at that level.
+    pub sibling_left: Vec<bool>,
+}
+
+impl DirectionalProof {
+    /// Verifies the proof with the default [`MerkleTree`] hasher.
+    pub fn verify(&self, leaf: [u8; 32], root: [u8; 32]) -> bool {
+        self.verify_with(&DomainSeparated(Sha256Hasher), leaf, root)
+    }
+
+    /// Fails for proofs whose flags and siblings differ in number.
+    pub fn verify_with<H: NodeHasher>(&self, hasher: &H, leaf: [u8; 32], root: [u8; 32]) -> bool {
+        if self.siblings.len() != self.sibling_left.len() {
+            return false;
+        }
+        let computed =
+            self.siblings
+                .iter()
+                .zip(&self.sibling_left)
+                .fold(leaf, |node, (&sibling, &left)| {
+                    if left {
+                        hasher.hash_nodes(sibling, node)
+                    } else {
+                        hasher.hash_nodes(node, sibling)
+                    }
+                });
+        computed == root
+    }
+
+    /// The index of the proven leaf, or `None` if it does not fit in a
+    /// `usize`.
+    pub fn index(&self) -> Option<usize> {
+        self.sibling_left
+            .iter()
+            .enumerate()
+            .filter(|(_, &left)| left)
+            .try_fold(0usize, |index, (level, _)| {
+                1usize
+                    .checked_shl(u32::try_from(level).ok()?)
+                    .map(|bit| index | bit)
+            })
+    }
+}
+
+impl From<&Proof> for DirectionalProof {
+    fn from(proof: &Proof) -> Self {
+        Self {
+            siblings: proof.siblings.clone(),
+            sibling_left: (0..proof.siblings.len())
+                .map(|level| proof.index.checked_shr(level as u32).unwrap_or(0) & 1 == 1)
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proof_encoding_roundtrip() {
+        let mut tree = MerkleTree::new(3);
+        for i in 0u8..6 {
+            tree.append_data(&[i]);
+        }
+        let proof = tree.get_proof(4);
+        assert_eq!((proof.index, proof.size, proof.len()), (4, 6, 3));
+        assert_eq!(Proof::from_bytes(&proof.to_bytes()).unwrap(), proof);
+        assert_eq!(Proof::from_hex(&proof.to_hex()).unwrap(), proof);
+        assert!(Proof::from_bytes(&proof.to_bytes()[..20]).is_err());
+    }
+
+    #[cfg(feature = "borsh")]
+    #[test]
+    fn test_proof_borsh_layout() {
+        let proof = Proof {
+            index: 3,
+            size: 5,
+            siblings: vec![[7u8; 32]],
+        };
+        let bytes = borsh::to_vec(&proof).unwrap();
+        let mut expected = Vec::new();
+        expected.extend_from_slice(&3u64.to_le_bytes());
+        expected.extend_from_slice(&5u64.to_le_bytes());
+        expected.extend_from_slice(&1u32.to_le_bytes());
+        expected.extend_from_slice(&[7u8; 32]);
+        assert_eq!(bytes, expected);
+        assert_eq!(borsh::from_slice::<Proof>(&bytes).unwrap(), proof);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_proof_json_uses_hex() {
+        let proof = Proof {
+            index: 1,
+            size: 2,
+            siblings: vec![[0xab; 32]],
+        };
+        let json = serde_json::to_string(&proof).unwrap();
+        assert_eq!(
+            json,
+            format!(
+                r#"{{"index":1,"size":2,"siblings":["{}"]}}"#,
+                "ab".repeat(32)
+            )
+        );
+        assert_eq!(serde_json::from_str::<Proof>(&json).unwrap(), proof);
+    }
+
+    #[test]
+    fn test_proof_verifies_against_its_own_index() {
+        let mut tree = MerkleTree::new(2);
+        for i in 0u8..4 {
+            tree.append_data(&[i]);
+        }
+        let root = tree.root();
+        let proof = tree.get_proof(1);
+        assert!(proof.verify(tree.leaves()[1], root));
+        assert!(!proof.verify(tree.leaves()[2], root));
+
+        let mut out_of_range = proof.clone();
+        out_of_range.index += 4;
+        assert!(!out_of_range.verify(tree.leaves()[1], root));
+        assert_eq!(proof.verify_strict(tree.leaves()[1], root, 2), Ok(()));
+        assert!(proof.verify_data(&[1], root));
+        assert_eq!(tree.verify_data(&[1], &proof), Ok(true));
+        assert_eq!(tree.verify_data(&[2], &proof), Ok(false));
+        assert!(proof.verify_strict(tree.leaves()[1], root, 3).is_err());
+    }
+
+    #[test]
+    fn test_directional_proof_needs_no_index() {
+        let mut tree = MerkleTree::new(3);
+        for i in 0u8..6 {
+            tree.append_data(&[i]);
+        }
+        let root = tree.root();
+        for index in [0, 3, 5] {
+            let proof = DirectionalProof::from(&tree.get_proof(index));
+            assert_eq!(proof.index(), Some(index));
+            assert!(proof.verify(tree.leaves()[index], root));
+            assert!(!proof.verify(tree.leaves()[index ^ 1], root));
+        }
+        let mut truncated = DirectionalProof::from(&tree.get_proof(2));
+        truncated.sibling_left.pop();
+        assert!(!truncated.verify(tree.leaves()[2], root));
+    }
+}
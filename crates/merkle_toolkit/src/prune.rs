@@ -0,0 +1,171 @@
+use crate::tree::height;
+use crate::{zero_hashes_with, MerkleError, MerkleTree, NodeHasher, NodeStore};
+
+/// A [`NodeStore`] that keeps the leaves and the levels from `keep_from`
+/// up, and recomputes the interior nodes below on demand.
+///
+/// A node at pruned level `l` costs `2^l - 1` hashes to regenerate, so a
+/// proof or append costs about `2^keep_from` hashes instead of a few reads,
+/// while the stored interior nodes shrink by a factor of `2^keep_from`; for
+/// a full depth 24 tree with `keep_from = 8`, that is 512 MiB of leaves
+/// plus 4 MiB of interior nodes instead of another 512 MiB.
+#[derive(Debug, Clone)]
+pub struct PrunedStore<H> {
+    hasher: H,
+    /// `zeros[level]` for the pruned levels, to pad partial subtrees.
+    zeros: Vec<[u8; 32]>,
+    keep_from: usize,
+    leaves: Vec<[u8; 32]>,
+    /// Levels `keep_from` and up.
+    upper: Vec<Vec<[u8; 32]>>,
+}
+
+impl<H: NodeHasher> PrunedStore<H> {
+    /// Levels below this one, other than the leaves, are recomputed.
+    pub fn keep_from(&self) -> usize {
+        self.keep_from
+    }
+
+    /// Number of nodes held, the leaves included.
+    pub fn stored_nodes(&self) -> usize {
+        self.leaves.len() + self.upper.iter().map(Vec::len).sum::<usize>()
+    }
+
+    fn is_pruned(&self, level: usize) -> bool {
+        level > 0 && level < self.keep_from
+    }
+
+    /// Hashes the leaves under `(level, index)` up to it.
+    fn regenerate(&self, level: usize, index: usize) -> [u8; 32] {
+        let start = index << level;
+        let end = self.leaves.len().min(start + (1 << level));
+        let mut nodes = self.leaves[start..end].to_vec();
+        for zero in &self.zeros[..level] {
+            if nodes.len() % 2 == 1 {
+                nodes.push(*zero);
+            }
+            let mut parents = vec![[0u8; 32]; nodes.len() / 2];
+            self.hasher.hash_pairs(&nodes, &mut parents);
+            nodes = parents;
+        }
+        nodes[0]
+    }
+}
+
+impl<H: NodeHasher> NodeStore for PrunedStore<H> {
+    fn levels(&self) -> usize {
+        height(self.leaves.len()) + 1
+    }
+
+    fn level_len(&self, level: usize) -> usize {
+        match level {
+            0 => self.leaves.len(),
+            _ if level < self.levels() => ((self.leaves.len() - 1) >> level) + 1,
+            _ => 0,
+        }
+    }
+
+    fn get(&self, level: usize, index: usize) -> Result<Option<[u8; 32]>, MerkleError> {
+        if level == 0 {
+            return Ok(self.leaves.get(index).copied());
+        }
+        if self.is_pruned(level) {
+            let present = index < self.level_len(level);
+            return Ok(present.then(|| self.regenerate(level, index)));
+        }
+        Ok(self
+            .upper
+            .as_slice()
+            .get(level - self.keep_from)
+            .and_then(|nodes| nodes.get(index))
+            .copied())
+    }
+
+    fn put(&mut self, level: usize, index: usize, node: [u8; 32]) -> Result<(), MerkleError> {
+        let nodes = match level {
+            0 => &mut self.leaves,
+            _ if self.is_pruned(level) => return Ok(()),
+            _ => {
+                let level = level - self.keep_from;
+                if level == self.upper.len() {
+                    self.upper.push(Vec::new());
+                }
+                &mut self.upper[level]
+            }
+        };
+        if index == nodes.len() {
+            nodes.push(node);
+        } else {
+            nodes[index] = node;
+        }
+        Ok(())
+    }
+}
+
+impl<H: NodeHasher + Clone> MerkleTree<H> {
+    /// Drops the cached interior nodes below level `keep_from`, which are
+    /// then recomputed from the leaves whenever a proof or update needs
+    /// them. See [`PrunedStore`] for the trade-off.
+    pub fn prune(self, keep_from: usize) -> MerkleTree<H, PrunedStore<H>> {
+        let keep_from = keep_from.max(1);
+        let mut levels = self.levels.into_iter();
+        let leaves = levels.next().unwrap_or_default();
+        let upper = levels.skip(keep_from - 1).collect();
+        MerkleTree {
+            depth: self.depth,
+            levels: PrunedStore {
+                zeros: zero_hashes_with(&self.hasher, keep_from.min(self.depth)),
+                hasher: self.hasher.clone(),
+                keep_from,
+                leaves,
+                upper,
+            },
+            hasher: self.hasher,
+            zeros: self.zeros,
+            leaf_index: self.leaf_index,
+            checkpoints: self.checkpoints,
+        }
+    }
+}
+
+impl<H: NodeHasher> MerkleTree<H, PrunedStore<H>> {
+    /// Rebuilds every level, returning to a fully cached tree.
+    pub fn unprune(self) -> MerkleTree<H> {
+        let mut tree = MerkleTree::from_leaves(self.depth, self.hasher, self.levels.leaves)
+            .expect("leaves fit the depth they were appended at");
+        tree.leaf_index = self.leaf_index;
+        tree.checkpoints = self.checkpoints;
+        tree
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pruned_tree_matches_full() {
+        let mut full = MerkleTree::new(6);
+        for i in 0u8..37 {
+            full.append_data(&[i]);
+        }
+        let mut pruned = full.clone().prune(3);
+        assert_eq!(pruned.store().stored_nodes(), 37 + 5 + 3 + 2 + 1);
+        assert_eq!(pruned.root(), full.root());
+        for index in [0, 17, 36] {
+            assert_eq!(pruned.get_proof(index), full.get_proof(index));
+        }
+
+        for i in 37u8..45 {
+            full.append_data(&[i]);
+            pruned.append_data(&[i]);
+        }
+        let leaf = [7; 32];
+        assert_eq!(pruned.set_leaf(9, leaf), full.set_leaf(9, leaf));
+        assert_eq!(pruned.get_proof(44), full.get_proof(44));
+        pruned.check_invariants().unwrap();
+
+        let restored = pruned.unprune();
+        assert_eq!(restored.levels, full.levels);
+    }
+}
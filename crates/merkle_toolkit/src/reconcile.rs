@@ -0,0 +1,185 @@
+//! Finding the leaves two replicas disagree on by comparing node hashes.
+//!
+//! The local side walks the tree top down; each round it asks the remote
+//! side for the nodes under every pair of nodes that differed in the last
+//! round, one level further down. Identical subtrees are never opened, so
+//! replicas that mostly agree finish in `height` rounds while exchanging
+//! hashes proportional to the number of differing leaves.
+//!
+//! How messages travel is up to the caller: implement [`Transport`] over a
+//! socket or RPC client, and answer incoming requests with [`respond`].
+
+use crate::{height, MerkleError, MerkleTree, NodeHasher, NodeStore};
+
+/// A question from the reconciling side.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+pub enum Request {
+    /// The number of leaves.
+    Size,
+    /// The nodes at `level` with the given indices.
+    Nodes { level: usize, indices: Vec<usize> },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+pub enum Response {
+    Size(usize),
+    /// One entry per requested index, `None` where the tree has no node.
+    Nodes(Vec<Option<[u8; 32]>>),
+}
+
+/// Carries requests to the remote replica and brings back its answers.
+pub trait Transport {
+    fn request(&mut self, request: Request) -> Result<Response, MerkleError>;
+}
+
+/// Answers `request` from `tree`.
+pub fn respond<H: NodeHasher, S: NodeStore>(
+    tree: &MerkleTree<H, S>,
+    request: &Request,
+) -> Result<Response, MerkleError> {
+    Ok(match request {
+        Request::Size => Response::Size(tree.len()),
+        Request::Nodes { level, indices } => Response::Nodes(
+            indices
+                .iter()
+                .map(|&index| tree.levels.get(*level, index))
+                .collect::<Result<_, _>>()?,
+        ),
+    })
+}
+
+/// A replica in the same process, for tests and local syncing.
+impl<H: NodeHasher, S: NodeStore> Transport for &MerkleTree<H, S> {
+    fn request(&mut self, request: Request) -> Result<Response, MerkleError> {
+        respond(self, &request)
+    }
+}
+
+/// The outcome of [`reconcile`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Reconciliation {
+    /// Indices of the leaves that differ or exist on one side only, sorted.
+    pub differing: Vec<usize>,
+    /// Round trips made, including the size exchange.
+    pub rounds: usize,
+    /// Node hashes received from the remote side.
+    pub nodes_received: usize,
+}
+
+/// Compares `local` against the replica behind `remote` and reports which
+/// leaves differ.
+///
+/// Leaves past the shorter tree's end always differ. Below that the trees
+/// are compared node by node, so both sides must use the same hasher.
+pub fn reconcile<H: NodeHasher, S: NodeStore>(
+    local: &MerkleTree<H, S>,
+    remote: &mut impl Transport,
+) -> Result<Reconciliation, MerkleError> {
+    let unexpected = || MerkleError::InvalidEncoding("unexpected reconciliation response");
+    let remote_len = match remote.request(Request::Size)? {
+        Response::Size(len) => len,
+        Response::Nodes(_) => return Err(unexpected()),
+    };
+    let mut outcome = Reconciliation {
+        rounds: 1,
+        ..Default::default()
+    };
+    let shared = local.len().min(remote_len);
+    if shared > 0 {
+        let mut level = height(local.len()).max(height(remote_len));
+        let mut pending = vec![0];
+        loop {
+            let nodes = match remote.request(Request::Nodes {
+                level,
+                indices: pending.clone(),
+            })? {
+                Response::Nodes(nodes) if nodes.len() == pending.len() => nodes,
+                _ => return Err(unexpected()),
+            };
+            outcome.rounds += 1;
+            outcome.nodes_received += nodes.iter().flatten().count();
+            let mut differing = Vec::new();
+            for (index, theirs) in pending.into_iter().zip(nodes) {
+                if local.levels.get(level, index)? != theirs {
+                    differing.push(index);
+                }
+            }
+            if level == 0 {
+                outcome.differing = differing;
+                break;
+            }
+            level -= 1;
+            // Only children holding leaves both sides have are worth asking
+            // about; the rest differ by size alone.
+            pending = differing
+                .into_iter()
+                .flat_map(|index| [2 * index, 2 * index + 1])
+                .filter(|&child| child << level < shared)
+                .collect();
+            if pending.is_empty() {
+                break;
+            }
+        }
+    }
+    outcome
+        .differing
+        .extend(shared..local.len().max(remote_len));
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree(size: u16) -> MerkleTree {
+        let mut tree = MerkleTree::new(12);
+        for i in 0..size {
+            tree.append_data(&i.to_be_bytes());
+        }
+        tree
+    }
+
+    #[test]
+    fn test_finds_changed_leaves() {
+        let ours = tree(1000);
+        let mut theirs = tree(1000);
+        for index in [0, 511, 512, 999] {
+            theirs.set_leaf(index, [7; 32]).unwrap();
+        }
+        let outcome = reconcile(&ours, &mut &theirs).unwrap();
+        assert_eq!(outcome.differing, [0, 511, 512, 999]);
+        assert_eq!(outcome.rounds, 1 + 11);
+        assert!(outcome.nodes_received < 4 * 2 * 11);
+
+        let same = reconcile(&ours, &mut &tree(1000)).unwrap();
+        assert_eq!((same.differing.len(), same.rounds), (0, 2));
+    }
+
+    #[test]
+    fn test_different_sizes() {
+        let mut longer = tree(19);
+        longer.set_leaf(4, [1; 32]).unwrap();
+        assert_eq!(
+            reconcile(&tree(12), &mut &longer).unwrap().differing,
+            [4, 12, 13, 14, 15, 16, 17, 18]
+        );
+        assert_eq!(
+            reconcile(&longer, &mut &tree(12)).unwrap().differing,
+            [4, 12, 13, 14, 15, 16, 17, 18]
+        );
+        assert_eq!(
+            reconcile(&tree(0), &mut &tree(3)).unwrap().differing,
+            [0, 1, 2]
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use crate::{
+    zero_hashes_with, DomainSeparated, MerkleError, MerkleTree, NodeHasher, Proof, Sha256Hasher,
+};
+
+/// Default leaf written by [`MerkleTree::remove_leaf`].
+///
+/// It is distinct from the all-zero leaf that empty subtrees are built from,
+/// so a removed slot can never be confused with one that was never filled.
+pub const TOMBSTONE: [u8; 32] = [0xff; 32];
+
+impl<H: NodeHasher> MerkleTree<H> {
+    /// Replaces the leaf at `index` with [`TOMBSTONE`] and returns the new
+    /// root. The slot keeps its index, so later leaves keep theirs.
+    pub fn remove_leaf(&mut self, index: usize) -> Result<[u8; 32], MerkleError> {
+        self.remove_leaf_with(index, TOMBSTONE)
+    }
+
+    /// Like [`MerkleTree::remove_leaf`] with a caller-chosen tombstone.
+    pub fn remove_leaf_with(
+        &mut self,
+        index: usize,
+        tombstone: [u8; 32],
+    ) -> Result<[u8; 32], MerkleError> {
+        self.set_leaf(index, tombstone)
+    }
+
+    /// Proof that no leaf was ever appended at `index`: the proof of the last
+    /// leaf, whose path has nothing to its right.
+    pub fn get_absence_proof(&self, index: usize) -> Result<Proof, MerkleError> {
+        if index < self.len() {
+            return Err(MerkleError::InvalidProof);
+        }
+        self.try_get_proof(self.len().checked_sub(1).ok_or(MerkleError::InvalidProof)?)
+    }
+}
+
+impl Proof {
+    /// Checks that the slot this proof is for holds [`TOMBSTONE`].
+    pub fn verify_removed(&self, root: [u8; 32]) -> bool {
+        self.verify(TOMBSTONE, root)
+    }
+
+    /// Checks a proof from [`MerkleTree::get_absence_proof`] with the default
+    /// [`MerkleTree`] hasher.
+    pub fn verify_absence(&self, index: usize, last_leaf: [u8; 32], root: [u8; 32]) -> bool {
+        self.verify_absence_with(&DomainSeparated(Sha256Hasher), index, last_leaf, root)
+    }
+
+    /// Checks that `last_leaf` is the last leaf of the tree and `index` lies
+    /// past it: every sibling to the right of its path is an empty subtree.
+    pub fn verify_absence_with<H: NodeHasher>(
+        &self,
+        hasher: &H,
+        index: usize,
+        last_leaf: [u8; 32],
+        root: [u8; 32],
+    ) -> bool {
+        let last = self.index;
+        let zeros = zero_hashes_with(hasher, self.len());
+        last + 1 == self.size
+            && index > last
+            && self.iter().enumerate().all(|(level, sibling)| {
+                last.checked_shr(level as u32).unwrap_or(0) & 1 == 1 || *sibling == zeros[level]
+            })
+            && self.verify_with(hasher, last_leaf, root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_removed_and_absent_leaves_are_distinct() {
+        let mut tree = MerkleTree::new(3);
+        for i in 0u8..5 {
+            tree.append_data(&[i]);
+        }
+        let root = tree.remove_leaf(1).unwrap();
+        assert_eq!(tree.leaves()[1], TOMBSTONE);
+        assert_eq!(tree.len(), 5);
+        assert!(tree.get_proof(1).verify_removed(root));
+        assert!(!tree.get_proof(2).verify_removed(root));
+
+        let last_leaf = tree.leaves()[4];
+        let absence = tree.get_absence_proof(6).unwrap();
+        assert!(absence.verify_absence(6, last_leaf, root));
+        assert!(!absence.verify_absence(3, last_leaf, root));
+        assert!(tree.get_absence_proof(1).is_err());
+
+        // A proof of a leaf with something to its right proves nothing.
+        let proof = tree.get_proof(3);
+        let mut forged = proof.clone();
+        forged.size = 4;
+        assert!(!forged.verify_absence(6, tree.leaves()[3], root));
+    }
+}
@@ -0,0 +1,32 @@
+use std::time::{Duration, SystemTime};
+
+/// Which past versions a [`VersionedMerkleTree`](crate::VersionedMerkleTree)
+/// or [`PersistentHistory`](crate::PersistentHistory) keeps when compacted.
+///
+/// The latest version is always kept, whatever the policy.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RetentionPolicy {
+    /// Never discard a version.
+    #[default]
+    KeepAll,
+    /// Keep the latest `n` versions.
+    KeepLast(usize),
+    /// Keep the versions recorded less than this long ago.
+    NewerThan(Duration),
+}
+
+impl RetentionPolicy {
+    /// Number of versions to discard from the front of a history recorded
+    /// at `recorded`, oldest first, as of `now`.
+    pub(crate) fn expired(&self, recorded: &[SystemTime], now: SystemTime) -> usize {
+        let expired = match *self {
+            Self::KeepAll => 0,
+            Self::KeepLast(n) => recorded.len().saturating_sub(n),
+            Self::NewerThan(age) => recorded
+                .iter()
+                .take_while(|&&at| now.duration_since(at).is_ok_and(|elapsed| elapsed >= age))
+                .count(),
+        };
+        expired.min(recorded.len().saturating_sub(1))
+    }
+}
@@ -0,0 +1,168 @@
+use std::path::Path;
+
+use rocksdb::{WriteBatch, DB};
+
+use crate::store::{len_key, node_key};
+use crate::{MerkleError, NodeStore};
+
+/// Number of top levels [`RocksStore::open`] keeps in memory.
+pub const DEFAULT_CACHE_LEVELS: usize = 16;
+
+/// A [`NodeStore`] persisted in a RocksDB database.
+///
+/// Every node is written through to disk, so a tree reopened over the same
+/// path with [`MerkleTree::with_store`](crate::MerkleTree::with_store) picks
+/// up where it left off without rehashing. The top levels, which every proof
+/// and append touches, are also cached in memory; deeper nodes are read from
+/// disk on demand.
+pub struct RocksStore {
+    db: DB,
+    /// Number of nodes at each level.
+    lens: Vec<usize>,
+    cache_levels: usize,
+    /// Nodes of the cached levels, indexed by level. Levels below the cached
+    /// window are left empty.
+    cache: Vec<Vec<[u8; 32]>>,
+}
+
+impl RocksStore {
+    /// Opens or creates the database at `path`, caching the top
+    /// [`DEFAULT_CACHE_LEVELS`] levels.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, MerkleError> {
+        Self::open_with_cache(path, DEFAULT_CACHE_LEVELS)
+    }
+
+    /// Like [`RocksStore::open`], caching the top `cache_levels` levels. The
+    /// cache holds at most `2^(cache_levels + 1)` nodes.
+    pub fn open_with_cache(
+        path: impl AsRef<Path>,
+        cache_levels: usize,
+    ) -> Result<Self, MerkleError> {
+        let db = DB::open_default(path).map_err(storage_error)?;
+        let mut lens = Vec::new();
+        while let Some(len) = db.get(len_key(lens.len())).map_err(storage_error)? {
+            let len: [u8; 8] = len
+                .try_into()
+                .map_err(|_| MerkleError::InvalidEncoding("level length"))?;
+            lens.push(u64::from_le_bytes(len) as usize);
+        }
+        let mut store = Self {
+            db,
+            cache: vec![Vec::new(); lens.len()],
+            lens,
+            cache_levels,
+        };
+        for level in 0..store.lens.len() {
+            if store.is_cached(level) {
+                store.cache[level] = (0..store.lens[level])
+                    .map(|index| store.read(level, index))
+                    .collect::<Result<_, _>>()?;
+            }
+        }
+        Ok(store)
+    }
+
+    /// Flushes the memtables to disk.
+    pub fn flush(&self) -> Result<(), MerkleError> {
+        self.db.flush().map_err(storage_error)
+    }
+
+    fn is_cached(&self, level: usize) -> bool {
+        level + self.cache_levels >= self.lens.len()
+    }
+
+    fn read(&self, level: usize, index: usize) -> Result<[u8; 32], MerkleError> {
+        let node = self
+            .db
+            .get(node_key(level, index))
+            .map_err(storage_error)?
+            .ok_or(MerkleError::InvalidEncoding("missing node"))?;
+        node.try_into()
+            .map_err(|_| MerkleError::InvalidEncoding("node length"))
+    }
+}
+
+impl NodeStore for RocksStore {
+    fn levels(&self) -> usize {
+        self.lens.len().max(1)
+    }
+
+    fn level_len(&self, level: usize) -> usize {
+        self.lens.get(level).copied().unwrap_or(0)
+    }
+
+    fn get(&self, level: usize, index: usize) -> Result<Option<[u8; 32]>, MerkleError> {
+        if index >= self.level_len(level) {
+            return Ok(None);
+        }
+        if self.is_cached(level) {
+            return Ok(Some(self.cache[level][index]));
+        }
+        self.read(level, index).map(Some)
+    }
+
+    fn put(&mut self, level: usize, index: usize, node: [u8; 32]) -> Result<(), MerkleError> {
+        if level == self.lens.len() {
+            self.lens.push(0);
+            self.cache.push(Vec::new());
+            // The window moved up a level; drop the one that fell out.
+            if let Some(evicted) = self.lens.len().checked_sub(self.cache_levels + 1) {
+                self.cache[evicted] = Vec::new();
+            }
+        }
+        let mut batch = WriteBatch::default();
+        batch.put(node_key(level, index), node);
+        if index == self.lens[level] {
+            self.lens[level] += 1;
+            batch.put(len_key(level), (self.lens[level] as u64).to_le_bytes());
+        }
+        self.db.write(batch).map_err(storage_error)?;
+
+        if self.is_cached(level) {
+            let nodes = &mut self.cache[level];
+            if index == nodes.len() {
+                nodes.push(node);
+            } else {
+                nodes[index] = node;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn storage_error(error: rocksdb::Error) -> MerkleError {
+    MerkleError::Storage(error.into_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DomainSeparated, MerkleTree, Sha256Hasher};
+
+    #[test]
+    fn test_reopen_keeps_tree() {
+        let path = std::env::temp_dir().join(format!("merkle-rocks-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        let hasher = DomainSeparated(Sha256Hasher);
+        let mut memory = MerkleTree::new(10);
+        {
+            let store = RocksStore::open_with_cache(&path, 2).unwrap();
+            let mut tree = MerkleTree::with_store(10, hasher, store).unwrap();
+            for i in 0u16..300 {
+                tree.append_data(&i.to_le_bytes());
+                memory.append_data(&i.to_le_bytes());
+            }
+            assert_eq!(tree.root(), memory.root());
+        }
+        let store = RocksStore::open_with_cache(&path, 2).unwrap();
+        let mut tree = MerkleTree::with_store(10, hasher, store).unwrap();
+        assert_eq!(tree.len(), 300);
+        assert_eq!(tree.root(), memory.root());
+        assert_eq!(tree.get_proof(7), memory.get_proof(7));
+        tree.append_data(b"more");
+        memory.append_data(b"more");
+        assert_eq!(tree.root(), memory.root());
+        drop(tree);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+}
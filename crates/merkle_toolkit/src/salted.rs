@@ -0,0 +1,167 @@
+use std::fmt;
+
+use crate::{DomainSeparated, MerkleError, MerkleTree, NodeHasher, Proof, Sha256Hasher};
+
+/// The leaf of `data` blinded by `salt`: `hash_leaf(salt || data)`.
+///
+/// Without a salt, a sibling leaf in a proof is the bare hash of its data,
+/// so low-entropy values such as salaries or birth dates can be recovered by
+/// hashing every candidate. With a secret 32 byte salt per leaf the sibling
+/// hashes reveal nothing about the data they commit to.
+pub fn salted_leaf<H: NodeHasher>(hasher: &H, salt: &[u8; 32], data: &[u8]) -> [u8; 32] {
+    hasher.hash_leaf(&[salt.as_slice(), data].concat())
+}
+
+/// An inclusion proof for salted leaf data, carrying the salt the verifier
+/// needs to rebuild the leaf.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+pub struct SaltedProof {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub salt: [u8; 32],
+    pub proof: Proof,
+}
+
+impl SaltedProof {
+    /// Verifies the proof with the default [`MerkleTree`] hasher.
+    pub fn verify(&self, data: &[u8], root: [u8; 32]) -> bool {
+        self.verify_with(&DomainSeparated(Sha256Hasher), data, root)
+    }
+
+    pub fn verify_with<H: NodeHasher>(&self, hasher: &H, data: &[u8], root: [u8; 32]) -> bool {
+        self.proof
+            .verify_with(hasher, salted_leaf(hasher, &self.salt, data), root)
+    }
+}
+
+/// A tree whose leaves are [`salted_leaf`]s, keeping each leaf's salt so
+/// proofs can disclose it.
+///
+/// Salts are either supplied per leaf or derived from a secret seed and the
+/// leaf index, `hash(seed || index)`, so the seed alone is enough to recover
+/// them. The seed must be drawn from a secure random source and never shared:
+/// anyone holding it can derive every salt.
+#[derive(Clone)]
+pub struct SaltedMerkleTree<H = DomainSeparated> {
+    tree: MerkleTree<H>,
+    seed: [u8; 32],
+    salts: Vec<[u8; 32]>,
+}
+
+impl SaltedMerkleTree {
+    pub fn new(depth: usize, seed: [u8; 32]) -> Self {
+        Self::with_hasher(depth, DomainSeparated(Sha256Hasher), seed)
+    }
+}
+
+impl<H: NodeHasher> SaltedMerkleTree<H> {
+    pub fn with_hasher(depth: usize, hasher: H, seed: [u8; 32]) -> Self {
+        Self::try_with_hasher(depth, hasher, seed).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    pub fn try_with_hasher(depth: usize, hasher: H, seed: [u8; 32]) -> Result<Self, MerkleError> {
+        Ok(Self {
+            tree: MerkleTree::try_with_hasher(depth, hasher)?,
+            seed,
+            salts: Vec::new(),
+        })
+    }
+
+    pub fn tree(&self) -> &MerkleTree<H> {
+        &self.tree
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        self.tree.root()
+    }
+
+    /// The salt the seed yields for the leaf at `index`.
+    pub fn derive_salt(&self, index: usize) -> [u8; 32] {
+        self.tree
+            .hasher()
+            .hash(&[&self.seed, &(index as u64).to_be_bytes()])
+    }
+
+    /// Appends `data` under the salt derived for its index, returning the
+    /// index.
+    pub fn push(&mut self, data: &[u8]) -> Result<usize, MerkleError> {
+        self.push_with_salt(data, self.derive_salt(self.len()))
+    }
+
+    /// Appends `data` under a caller-chosen `salt`, returning the index.
+    pub fn push_with_salt(&mut self, data: &[u8], salt: [u8; 32]) -> Result<usize, MerkleError> {
+        let index = self.len();
+        self.tree
+            .try_append_leaf(salted_leaf(self.tree.hasher(), &salt, data))?;
+        self.salts.push(salt);
+        Ok(index)
+    }
+
+    pub fn salt(&self, index: usize) -> Option<[u8; 32]> {
+        self.salts.get(index).copied()
+    }
+
+    pub fn get_proof(&self, index: usize) -> Result<SaltedProof, MerkleError> {
+        Ok(SaltedProof {
+            proof: self.tree.try_get_proof(index)?,
+            salt: self.salts[index],
+        })
+    }
+}
+
+impl<H> fmt::Debug for SaltedMerkleTree<H> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SaltedMerkleTree")
+            .field("depth", &self.tree.depth)
+            .field("len", &self.salts.len())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_salted_proofs() {
+        let mut tree = SaltedMerkleTree::new(3, [7; 32]);
+        for salary in ["52000", "61000", "58000"] {
+            tree.push(salary.as_bytes()).unwrap();
+        }
+        tree.push_with_salt(b"70000", [1; 32]).unwrap();
+        assert_eq!(tree.salt(3), Some([1; 32]));
+        assert_ne!(tree.salt(0), tree.salt(1));
+        assert_eq!(tree.salt(2), Some(tree.derive_salt(2)));
+
+        let proof = tree.get_proof(1).unwrap();
+        assert!(proof.verify(b"61000", tree.root()));
+        assert!(!proof.verify(b"61001", tree.root()));
+        // A sibling's unsalted hash no longer matches its data.
+        let hasher = DomainSeparated(Sha256Hasher);
+        assert_ne!(proof.proof.siblings[0], hasher.hash_leaf(b"52000"));
+        let wrong_salt = SaltedProof {
+            salt: [0; 32],
+            ..proof
+        };
+        assert!(!wrong_salt.verify(b"61000", tree.root()));
+        assert!(tree.get_proof(4).is_err());
+
+        // Neither the seed nor the salts show up in debug output.
+        assert_eq!(
+            format!("{tree:?}"),
+            "SaltedMerkleTree { depth: 3, len: 4, .. }"
+        );
+    }
+}
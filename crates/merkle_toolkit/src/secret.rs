@@ -0,0 +1,166 @@
+use core::fmt;
+
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::{
+    DomainSeparated, HmacSha256Hasher, MerkleError, MerkleTree, NodeHasher, Proof, Sha256Hasher,
+};
+
+/// Wipes the leaves and cached levels, leaving an empty tree.
+///
+/// The leaf index, if enabled, is dropped without being wiped: a hash map
+/// gives no way to overwrite its keys in place.
+impl<H> Zeroize for MerkleTree<H> {
+    fn zeroize(&mut self) {
+        self.levels.zeroize();
+        self.levels.push(Vec::new());
+        self.leaf_index = None;
+    }
+}
+
+impl Zeroize for HmacSha256Hasher {
+    fn zeroize(&mut self) {
+        self.inner_pad.zeroize();
+        self.outer_pad.zeroize();
+    }
+}
+
+impl Drop for HmacSha256Hasher {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for HmacSha256Hasher {}
+
+/// A tree over secret material, such as seed shares or credentials, that
+/// wipes its nodes when dropped.
+///
+/// Room for `capacity` leaves is reserved up front and the tree refuses to
+/// grow past it, so no level is ever reallocated and left behind unwiped.
+/// Leaves can't be read back, the tree can't be cloned, and `Debug` prints
+/// only its shape; roots and proofs are the only way out.
+pub struct SecretMerkleTree<H = DomainSeparated> {
+    tree: MerkleTree<H>,
+    capacity: usize,
+}
+
+impl SecretMerkleTree {
+    pub fn new(depth: usize, capacity: usize) -> Result<Self, MerkleError> {
+        Self::with_hasher(depth, capacity, DomainSeparated(Sha256Hasher))
+    }
+}
+
+impl<H: NodeHasher> SecretMerkleTree<H> {
+    /// A tree of up to `capacity` leaves, at most `2^depth`.
+    pub fn with_hasher(depth: usize, capacity: usize, hasher: H) -> Result<Self, MerkleError> {
+        let mut tree = MerkleTree::try_with_hasher(depth, hasher)?;
+        let capacity = capacity.min(tree.capacity());
+        tree.levels.reserve_exact(depth + 1);
+        tree.levels[0].reserve_exact(capacity);
+        Ok(Self { tree, capacity })
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        self.tree.root()
+    }
+
+    /// Appends the leaf of `data`, returning its index. The caller remains
+    /// responsible for wiping `data` itself.
+    pub fn push_secret(&mut self, data: &[u8]) -> Result<usize, MerkleError> {
+        let mut leaf = self.tree.hasher().hash_leaf(data);
+        let index = self.push_leaf(leaf);
+        leaf.zeroize();
+        index
+    }
+
+    /// Appends an already hashed leaf, returning its index.
+    pub fn push_leaf(&mut self, leaf: [u8; 32]) -> Result<usize, MerkleError> {
+        let index = self.len();
+        if index == self.capacity {
+            return Err(MerkleError::TreeFull {
+                capacity: self.capacity,
+            });
+        }
+        let levels = self.tree.levels.len();
+        self.tree.try_append_leaf(leaf)?;
+        // A level the append created is moved into a buffer sized for a
+        // full tree, and the one it started in is wiped.
+        for level in levels..self.tree.levels.len() {
+            let mut nodes = Vec::with_capacity(((self.capacity - 1) >> level) + 1);
+            nodes.extend_from_slice(&self.tree.levels[level]);
+            core::mem::replace(&mut self.tree.levels[level], nodes).zeroize();
+        }
+        Ok(index)
+    }
+
+    pub fn get_proof(&self, index: usize) -> Result<Proof, MerkleError> {
+        self.tree.try_get_proof(index)
+    }
+}
+
+impl<H> fmt::Debug for SecretMerkleTree<H> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecretMerkleTree")
+            .field("depth", &self.tree.depth)
+            .field("capacity", &self.capacity)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<H> Drop for SecretMerkleTree<H> {
+    fn drop(&mut self) {
+        self.tree.zeroize();
+    }
+}
+
+impl<H> ZeroizeOnDrop for SecretMerkleTree<H> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_tree_matches_plain_and_is_bounded() {
+        let mut secret = SecretMerkleTree::new(4, 3).unwrap();
+        let mut plain = MerkleTree::new(4);
+        for share in [b"share-1", b"share-2", b"share-3"] {
+            secret.push_secret(share).unwrap();
+            plain.append_data(share);
+        }
+        assert_eq!(secret.root(), plain.root());
+        assert_eq!(secret.get_proof(2).unwrap(), plain.get_proof(2));
+        assert_eq!(
+            secret.push_secret(b"share-4"),
+            Err(MerkleError::TreeFull { capacity: 3 })
+        );
+        assert!(!format!("{secret:?}").contains("levels"));
+        // Every level sits in a buffer it filled without reallocating.
+        assert!(secret
+            .tree
+            .levels
+            .iter()
+            .all(|level| level.len() == level.capacity()));
+    }
+
+    #[test]
+    fn test_zeroize_empties_tree() {
+        let mut tree = MerkleTree::new(3).with_leaf_index();
+        tree.append_data(b"secret");
+        tree.zeroize();
+        assert!(tree.is_empty() && !tree.has_leaf_index());
+        assert_eq!(tree.root(), MerkleTree::new(3).root());
+    }
+}
@@ -0,0 +1,111 @@
+//! Serde helpers for 32 byte hashes.
+//!
+//! Human-readable formats such as JSON get lowercase hex strings, binary
+//! formats get the raw bytes. Use with `#[serde(with = "merkle_toolkit::serde_hex")]`
+//! on a `[u8; 32]` field, `serde_hex::vec` on a `Vec<[u8; 32]>`,
+//! `serde_hex::levels` on a `Vec<Vec<[u8; 32]>>`, or `serde_hex::bytes` on
+//! arbitrary `Vec<u8>` data such as signatures.
+
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::hex;
+
+pub fn serialize<S: Serializer>(hash: &[u8; 32], serializer: S) -> Result<S::Ok, S::Error> {
+    if serializer.is_human_readable() {
+        serializer.serialize_str(&hex::encode(hash))
+    } else {
+        hash.serialize(serializer)
+    }
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; 32], D::Error> {
+    if deserializer.is_human_readable() {
+        let s = String::deserialize(deserializer)?;
+        hex::decode_hash(&s).map_err(D::Error::custom)
+    } else {
+        <[u8; 32]>::deserialize(deserializer)
+    }
+}
+
+struct Hash([u8; 32]);
+
+impl Serialize for Hash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize(&self.0, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Hash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize(deserializer).map(Hash)
+    }
+}
+
+pub mod vec {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(hashes: &[[u8; 32]], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(hashes.iter().map(|hash| Hash(*hash)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<[u8; 32]>, D::Error> {
+        let hashes = Vec::<Hash>::deserialize(deserializer)?;
+        Ok(hashes.into_iter().map(|hash| hash.0).collect())
+    }
+}
+
+pub mod levels {
+    use super::*;
+
+    struct Level(Vec<[u8; 32]>);
+
+    impl Serialize for Level {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            vec::serialize(&self.0, serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Level {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            vec::deserialize(deserializer).map(Level)
+        }
+    }
+
+    pub fn serialize<S: Serializer>(
+        levels: &[Vec<[u8; 32]>],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(levels.iter().map(|level| Level(level.clone())))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<Vec<[u8; 32]>>, D::Error> {
+        let levels = Vec::<Level>::deserialize(deserializer)?;
+        Ok(levels.into_iter().map(|level| level.0).collect())
+    }
+}
+
+pub mod bytes {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&hex::encode(bytes))
+        } else {
+            bytes.serialize(serializer)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        if deserializer.is_human_readable() {
+            let s = String::deserialize(deserializer)?;
+            hex::decode(&s).map_err(D::Error::custom)
+        } else {
+            Vec::<u8>::deserialize(deserializer)
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+
+use crate::{DomainSeparated, MerkleError, MerkleTree, NodeHasher, Proof, TreeMetadata};
+
+/// A [`MerkleTree`] shared between threads.
+///
+/// Clones are handles to the same tree. Reads such as proofs and roots run
+/// in parallel; an append holds the write lock only while it rehashes one
+/// path, so readers are never held up for longer than that.
+///
+/// Because the tree is append-only, a [`TreeMetadata`] from
+/// [`SharedMerkleTree::snapshot`] names a state that stays provable: proofs
+/// from [`SharedMerkleTree::get_proof_at`] verify against the snapshot's
+/// root however many leaves were appended in between.
+#[derive(Debug)]
+pub struct SharedMerkleTree<H = DomainSeparated> {
+    tree: Arc<RwLock<MerkleTree<H>>>,
+}
+
+impl<H> Clone for SharedMerkleTree<H> {
+    fn clone(&self) -> Self {
+        Self {
+            tree: Arc::clone(&self.tree),
+        }
+    }
+}
+
+impl<H: NodeHasher> From<MerkleTree<H>> for SharedMerkleTree<H> {
+    fn from(tree: MerkleTree<H>) -> Self {
+        Self::new(tree)
+    }
+}
+
+impl<H: NodeHasher> SharedMerkleTree<H> {
+    pub fn new(tree: MerkleTree<H>) -> Self {
+        Self {
+            tree: Arc::new(RwLock::new(tree)),
+        }
+    }
+
+    /// Read access to the whole tree, for several reads from one state.
+    /// Appends wait until the guard is dropped.
+    pub fn read(&self) -> RwLockReadGuard<'_, MerkleTree<H>> {
+        self.tree.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    pub fn root(&self) -> Result<[u8; 32], MerkleError> {
+        self.read().try_root()
+    }
+
+    /// The current size and root, read together.
+    pub fn snapshot(&self) -> Result<TreeMetadata, MerkleError> {
+        let tree = self.read();
+        Ok(TreeMetadata {
+            depth: tree.depth(),
+            size: tree.len(),
+            root: tree.try_root()?,
+        })
+    }
+
+    /// Appends `leaf` and returns its index.
+    pub fn append_leaf(&self, leaf: [u8; 32]) -> Result<usize, MerkleError> {
+        let mut tree = self.tree.write().unwrap_or_else(|e| e.into_inner());
+        tree.try_append_leaf(leaf)?;
+        Ok(tree.len() - 1)
+    }
+
+    /// Appends the leaf derived from `data` and returns its index.
+    pub fn append_data(&self, data: &[u8]) -> Result<usize, MerkleError> {
+        let leaf = self.read().hasher().hash_leaf(data);
+        self.append_leaf(leaf)
+    }
+
+    /// Proof against the root at the moment of the call, which is also the
+    /// root of the tree at `proof.size` leaves.
+    pub fn get_proof(&self, index: usize) -> Result<Proof, MerkleError> {
+        self.read().try_get_proof(index)
+    }
+
+    /// Proof against `snapshot.root`, unaffected by later appends.
+    pub fn get_proof_at(
+        &self,
+        snapshot: &TreeMetadata,
+        index: usize,
+    ) -> Result<Proof, MerkleError> {
+        self.read().get_proof_at_size(index, snapshot.size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_proofs_during_appends() {
+        let shared = SharedMerkleTree::new(MerkleTree::new(12));
+        for i in 0u16..64 {
+            shared.append_data(&i.to_be_bytes()).unwrap();
+        }
+        let snapshot = shared.snapshot().unwrap();
+        let leaves = shared.read().leaves()[..64].to_vec();
+
+        let writer = {
+            let shared = shared.clone();
+            thread::spawn(move || {
+                for i in 64u16..1064 {
+                    shared.append_data(&i.to_be_bytes()).unwrap();
+                }
+            })
+        };
+        let readers: Vec<_> = (0..4)
+            .map(|reader| {
+                let (shared, leaves) = (shared.clone(), leaves.clone());
+                thread::spawn(move || {
+                    for index in (reader..64).step_by(4) {
+                        let proof = shared.get_proof_at(&snapshot, index).unwrap();
+                        assert!(proof.verify(leaves[index], snapshot.root));
+
+                        let current = shared.get_proof(index).unwrap();
+                        let root = shared.read().root_at_size(current.size).unwrap();
+                        assert!(current.verify(leaves[index], root));
+                    }
+                })
+            })
+            .collect();
+        writer.join().unwrap();
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(shared.len(), 1064);
+    }
+}
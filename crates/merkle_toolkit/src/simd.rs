@@ -0,0 +1,207 @@
+//! Multi-buffer SHA-256 for hashing many sibling pairs at once.
+//!
+//! Every pair in a level is independent, so eight of them can be compressed
+//! side by side in the 32-bit lanes of AVX2 registers. On CPUs with the SHA
+//! extensions the single-buffer path of the `sha2` crate is faster, so the
+//! backend is chosen at runtime: SHA-NI, then AVX2, then portable code.
+
+use crate::{NodeHasher, Sha256Hasher};
+
+/// Hashes `prefix || left || right` for every consecutive pair of `children`.
+pub(crate) fn sha256_pairs(prefix: &[u8], children: &[[u8; 32]], parents: &mut [[u8; 32]]) {
+    #[cfg(target_arch = "x86_64")]
+    if prefix.len() <= avx2::MAX_PREFIX
+        && !std::is_x86_feature_detected!("sha")
+        && std::is_x86_feature_detected!("avx2")
+    {
+        let lanes = parents.len() / 8 * 8;
+        for (pairs, out) in children[..lanes * 2]
+            .chunks_exact(16)
+            .zip(parents[..lanes].chunks_exact_mut(8))
+        {
+            // SAFETY: AVX2 support was checked above.
+            unsafe { avx2::hash_pairs_x8(prefix, pairs, out) };
+        }
+        return scalar_pairs(prefix, &children[lanes * 2..], &mut parents[lanes..]);
+    }
+    scalar_pairs(prefix, children, parents)
+}
+
+fn scalar_pairs(prefix: &[u8], children: &[[u8; 32]], parents: &mut [[u8; 32]]) {
+    for (pair, parent) in children.chunks_exact(2).zip(parents) {
+        *parent = Sha256Hasher.hash(&[prefix, &pair[0], &pair[1]]);
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use std::arch::x86_64::*;
+
+    /// Longest prefix for which `prefix || left || right` and its padding fit
+    /// in two blocks.
+    pub(super) const MAX_PREFIX: usize = 128 - 64 - 9;
+
+    const K: [u32; 64] = [
+        0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4,
+        0xab1c5ed5, 0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe,
+        0x9bdc06a7, 0xc19bf174, 0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f,
+        0x4a7484aa, 0x5cb0a9dc, 0x76f988da, 0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7,
+        0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967, 0x27b70a85, 0x2e1b2138, 0x4d2c6dfc,
+        0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85, 0xa2bfe8a1, 0xa81a664b,
+        0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070, 0x19a4c116,
+        0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+        0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7,
+        0xc67178f2,
+    ];
+
+    const H0: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+        0x5be0cd19,
+    ];
+
+    /// Hashes eight pairs from `pairs` (16 nodes) into `out`.
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn hash_pairs_x8(prefix: &[u8], pairs: &[[u8; 32]], out: &mut [[u8; 32]]) {
+        let len = prefix.len() + 64;
+        let blocks = (len + 9).div_ceil(64);
+        let mut messages = [[0u8; 128]; 8];
+        for (lane, message) in messages.iter_mut().enumerate() {
+            message[..prefix.len()].copy_from_slice(prefix);
+            message[prefix.len()..len - 32].copy_from_slice(&pairs[2 * lane]);
+            message[len - 32..len].copy_from_slice(&pairs[2 * lane + 1]);
+            message[len] = 0x80;
+            message[blocks * 64 - 8..blocks * 64].copy_from_slice(&(len as u64 * 8).to_be_bytes());
+        }
+
+        let mut state = H0.map(|h| _mm256_set1_epi32(h as i32));
+        for block in 0..blocks {
+            let mut w = [_mm256_setzero_si256(); 16];
+            for (i, word) in w.iter_mut().enumerate() {
+                let at = block * 64 + i * 4;
+                let lane =
+                    |l: usize| i32::from_be_bytes(messages[l][at..at + 4].try_into().unwrap());
+                *word = _mm256_setr_epi32(
+                    lane(0),
+                    lane(1),
+                    lane(2),
+                    lane(3),
+                    lane(4),
+                    lane(5),
+                    lane(6),
+                    lane(7),
+                );
+            }
+            compress(&mut state, w);
+        }
+
+        let mut words = [[0u32; 8]; 8];
+        for (word, lanes) in state.iter().zip(words.iter_mut()) {
+            _mm256_storeu_si256(lanes.as_mut_ptr().cast(), *word);
+        }
+        for (lane, digest) in out.iter_mut().enumerate() {
+            for (i, lanes) in words.iter().enumerate() {
+                digest[i * 4..i * 4 + 4].copy_from_slice(&lanes[lane].to_be_bytes());
+            }
+        }
+    }
+
+    #[target_feature(enable = "avx2")]
+    unsafe fn compress(state: &mut [__m256i; 8], block: [__m256i; 16]) {
+        let mut w = [_mm256_setzero_si256(); 64];
+        w[..16].copy_from_slice(&block);
+        for t in 16..64 {
+            let s0 = xor3(
+                rotr::<7, 25>(w[t - 15]),
+                rotr::<18, 14>(w[t - 15]),
+                _mm256_srli_epi32::<3>(w[t - 15]),
+            );
+            let s1 = xor3(
+                rotr::<17, 15>(w[t - 2]),
+                rotr::<19, 13>(w[t - 2]),
+                _mm256_srli_epi32::<10>(w[t - 2]),
+            );
+            w[t] = add(add(w[t - 16], s0), add(w[t - 7], s1));
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+        for t in 0..64 {
+            let s1 = xor3(rotr::<6, 26>(e), rotr::<11, 21>(e), rotr::<25, 7>(e));
+            let ch = _mm256_xor_si256(_mm256_and_si256(e, f), _mm256_andnot_si256(e, g));
+            let k = _mm256_set1_epi32(K[t] as i32);
+            let t1 = add(add(h, s1), add(ch, add(k, w[t])));
+            let s0 = xor3(rotr::<2, 30>(a), rotr::<13, 19>(a), rotr::<22, 10>(a));
+            let maj = _mm256_xor_si256(
+                _mm256_xor_si256(_mm256_and_si256(a, b), _mm256_and_si256(a, c)),
+                _mm256_and_si256(b, c),
+            );
+            let t2 = add(s0, maj);
+            h = g;
+            g = f;
+            f = e;
+            e = add(d, t1);
+            d = c;
+            c = b;
+            b = a;
+            a = add(t1, t2);
+        }
+        for (word, x) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *word = add(*word, x);
+        }
+    }
+
+    /// Rotates every lane right by `R`; `L` must be `32 - R`.
+    #[inline(always)]
+    unsafe fn rotr<const R: i32, const L: i32>(x: __m256i) -> __m256i {
+        _mm256_or_si256(_mm256_srli_epi32::<R>(x), _mm256_slli_epi32::<L>(x))
+    }
+
+    #[inline(always)]
+    unsafe fn xor3(a: __m256i, b: __m256i, c: __m256i) -> __m256i {
+        _mm256_xor_si256(_mm256_xor_si256(a, b), c)
+    }
+
+    #[inline(always)]
+    unsafe fn add(a: __m256i, b: __m256i) -> __m256i {
+        _mm256_add_epi32(a, b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn children(n: usize) -> Vec<[u8; 32]> {
+        (0..n)
+            .map(|i| Sha256Hasher.hash(&[&(i as u64).to_le_bytes()]))
+            .collect()
+    }
+
+    #[test]
+    fn test_pairs_match_scalar() {
+        let children = children(2 * 19);
+        for prefix in [&[][..], &[0x01], &[0xaa; 55]] {
+            let mut expected = vec![[0u8; 32]; 19];
+            scalar_pairs(prefix, &children, &mut expected);
+            let mut parents = vec![[0u8; 32]; 19];
+            sha256_pairs(prefix, &children, &mut parents);
+            assert_eq!(parents, expected);
+        }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_avx2_kernel_matches_scalar() {
+        if !std::is_x86_feature_detected!("avx2") {
+            return;
+        }
+        let children = children(16);
+        for prefix in [&[][..], &[0x01], &[0xaa; 55]] {
+            let mut expected = [[0u8; 32]; 8];
+            scalar_pairs(prefix, &children, &mut expected);
+            let mut parents = [[0u8; 32]; 8];
+            // SAFETY: AVX2 support was checked above.
+            unsafe { avx2::hash_pairs_x8(prefix, &children, &mut parents) };
+            assert_eq!(parents, expected);
+        }
+    }
+}
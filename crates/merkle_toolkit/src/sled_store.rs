@@ -0,0 +1,114 @@
+use std::path::Path;
+
+use crate::store::{len_key, node_key};
+use crate::{MerkleError, NodeStore};
+
+/// A [`NodeStore`] persisted in a sled database, for builds that can't link
+/// RocksDB.
+///
+/// As with [`RocksStore`](crate::RocksStore) every node is written through,
+/// so reopening the same path resumes the tree. Caching is left to sled's
+/// own page cache.
+pub struct SledStore {
+    db: sled::Db,
+    /// Number of nodes at each level.
+    lens: Vec<usize>,
+}
+
+impl SledStore {
+    /// Opens or creates the database at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, MerkleError> {
+        Self::from_db(sled::open(path).map_err(storage_error)?)
+    }
+
+    /// Uses an already open database, such as a temporary one.
+    pub fn from_db(db: sled::Db) -> Result<Self, MerkleError> {
+        let mut lens = Vec::new();
+        while let Some(len) = db.get(len_key(lens.len())).map_err(storage_error)? {
+            let len: [u8; 8] = len
+                .as_ref()
+                .try_into()
+                .map_err(|_| MerkleError::InvalidEncoding("level length"))?;
+            lens.push(u64::from_le_bytes(len) as usize);
+        }
+        Ok(Self { db, lens })
+    }
+
+    /// Flushes dirty pages to disk.
+    pub fn flush(&self) -> Result<(), MerkleError> {
+        self.db.flush().map(drop).map_err(storage_error)
+    }
+}
+
+impl NodeStore for SledStore {
+    fn levels(&self) -> usize {
+        self.lens.len().max(1)
+    }
+
+    fn level_len(&self, level: usize) -> usize {
+        self.lens.get(level).copied().unwrap_or(0)
+    }
+
+    fn get(&self, level: usize, index: usize) -> Result<Option<[u8; 32]>, MerkleError> {
+        if index >= self.level_len(level) {
+            return Ok(None);
+        }
+        let node = self
+            .db
+            .get(node_key(level, index))
+            .map_err(storage_error)?
+            .ok_or(MerkleError::InvalidEncoding("missing node"))?;
+        node.as_ref()
+            .try_into()
+            .map(Some)
+            .map_err(|_| MerkleError::InvalidEncoding("node length"))
+    }
+
+    fn put(&mut self, level: usize, index: usize, node: [u8; 32]) -> Result<(), MerkleError> {
+        if level == self.lens.len() {
+            self.lens.push(0);
+        }
+        let mut batch = sled::Batch::default();
+        batch.insert(&node_key(level, index), &node);
+        if index == self.lens[level] {
+            self.lens[level] += 1;
+            batch.insert(&len_key(level), &(self.lens[level] as u64).to_le_bytes());
+        }
+        self.db.apply_batch(batch).map_err(storage_error)
+    }
+}
+
+fn storage_error(error: sled::Error) -> MerkleError {
+    MerkleError::Storage(error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DomainSeparated, MerkleTree, Sha256Hasher};
+
+    #[test]
+    fn test_reopen_keeps_tree() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let hasher = DomainSeparated(Sha256Hasher);
+        let mut memory = MerkleTree::new(10);
+        {
+            let store = SledStore::from_db(db.clone()).unwrap();
+            let mut tree = MerkleTree::with_store(10, hasher, store).unwrap();
+            for i in 0u16..300 {
+                tree.append_data(&i.to_le_bytes());
+                memory.append_data(&i.to_le_bytes());
+            }
+            assert_eq!(tree.root(), memory.root());
+        }
+        let store = SledStore::from_db(db).unwrap();
+        let mut tree = MerkleTree::with_store(10, hasher, store).unwrap();
+        assert_eq!(tree.len(), 300);
+        assert_eq!(tree.root(), memory.root());
+        assert_eq!(tree.get_proof(7), memory.get_proof(7));
+        tree.append_data(b"more");
+        memory.append_data(b"more");
+        assert_eq!(tree.root(), memory.root());
+        tree.store().flush().unwrap();
+    }
+}
@@ -0,0 +1,172 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::{DomainSeparated, MerkleError, MerkleTree, NodeHasher, Sha256Hasher, MAX_DEPTH};
+
+/// First bytes of every snapshot file.
+pub const SNAPSHOT_MAGIC: [u8; 4] = *b"MKLT";
+
+/// Format version written by [`MerkleTree::write_snapshot`].
+pub const SNAPSHOT_VERSION: u8 = 1;
+
+/// Set in the flags byte when the levels above the leaves follow them.
+const FLAG_LEVELS: u8 = 1;
+
+// A snapshot is laid out as:
+//
+//   magic (4) | version (1) | flags (1) | depth (1) | reserved (1)
+//   hasher fingerprint (32) | leaf count (u64, big-endian)
+//   leaves (32 each)
+//   if FLAG_LEVELS: every level above the leaves, bottom up (32 each)
+//
+// The fingerprint is the hash of two zero nodes, so loading with a different
+// hasher than the one that saved the tree fails instead of producing a tree
+// whose cached levels disagree with its leaves.
+
+impl MerkleTree {
+    /// Loads a snapshot written by [`MerkleTree::save`] with the default
+    /// hasher.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::load_with(path, DomainSeparated(Sha256Hasher))
+    }
+}
+
+impl<H: NodeHasher> MerkleTree<H> {
+    /// Writes the tree, including every cached level, to `path`. Loading it
+    /// back reads the levels instead of rehashing them.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_snapshot(&mut writer, true)?;
+        writer.flush()
+    }
+
+    pub fn load_with(path: impl AsRef<Path>, hasher: H) -> io::Result<Self> {
+        Self::read_snapshot(BufReader::new(File::open(path)?), hasher)
+    }
+
+    /// Writes a snapshot to `writer`. Without `with_levels` only the leaves
+    /// are written, halving the size, and loading rebuilds the levels.
+    pub fn write_snapshot(&self, mut writer: impl Write, with_levels: bool) -> io::Result<()> {
+        let flags = if with_levels { FLAG_LEVELS } else { 0 };
+        writer.write_all(&SNAPSHOT_MAGIC)?;
+        writer.write_all(&[SNAPSHOT_VERSION, flags, self.depth as u8, 0])?;
+        writer.write_all(&fingerprint(&self.hasher))?;
+        writer.write_all(&(self.len() as u64).to_be_bytes())?;
+        let levels = if with_levels {
+            &self.levels[..]
+        } else {
+            &self.levels[..1]
+        };
+        for node in levels.iter().flatten() {
+            writer.write_all(node)?;
+        }
+        Ok(())
+    }
+
+    pub fn read_snapshot(mut reader: impl Read, hasher: H) -> io::Result<Self> {
+        let mut header = [0u8; 48];
+        reader.read_exact(&mut header)?;
+        if header[..4] != SNAPSHOT_MAGIC {
+            return Err(invalid("not a merkle tree snapshot"));
+        }
+        if header[4] != SNAPSHOT_VERSION {
+            return Err(invalid("unsupported snapshot version"));
+        }
+        let flags = header[5];
+        let depth = header[6] as usize;
+        if depth > MAX_DEPTH {
+            return Err(invalid_data(MerkleError::DepthTooLarge {
+                depth,
+                max: MAX_DEPTH,
+            }));
+        }
+        if header[8..40] != fingerprint(&hasher) {
+            return Err(invalid("snapshot was written with a different hasher"));
+        }
+        let len = u64::from_be_bytes(header[40..].try_into().unwrap());
+        if len > 1 << depth {
+            return Err(invalid_data(MerkleError::TreeFull {
+                capacity: 1 << depth,
+            }));
+        }
+        let len = usize::try_from(len)
+            .map_err(|_| invalid("snapshot leaf count does not fit in usize"))?;
+        let leaves = read_nodes(&mut reader, len)?;
+        if flags & FLAG_LEVELS == 0 {
+            return Self::from_leaves(depth, hasher, leaves).map_err(invalid_data);
+        }
+
+        let mut tree = Self::try_with_hasher(depth, hasher).map_err(invalid_data)?;
+        tree.levels[0] = leaves;
+        while tree.levels.last().unwrap().len() > 1 {
+            let parents = tree.levels.last().unwrap().len().div_ceil(2);
+            tree.levels.push(read_nodes(&mut reader, parents)?);
+        }
+        Ok(tree)
+    }
+}
+
+pub(crate) fn fingerprint(hasher: &impl NodeHasher) -> [u8; 32] {
+    hasher.hash_nodes([0u8; 32], [0u8; 32])
+}
+
+/// Reads `count` nodes. The count comes from the header, so the nodes are
+/// only allocated as they are read, and a short stream fails with
+/// [`io::ErrorKind::UnexpectedEof`] instead of reserving the whole tree.
+fn read_nodes(reader: &mut impl Read, count: usize) -> io::Result<Vec<[u8; 32]>> {
+    let mut nodes = Vec::with_capacity(count.min(1 << 16));
+    for _ in 0..count {
+        let mut node = [0u8; 32];
+        reader.read_exact(&mut node)?;
+        nodes.push(node);
+    }
+    Ok(nodes)
+}
+
+pub(crate) fn invalid(reason: &'static str) -> io::Error {
+    invalid_data(MerkleError::InvalidEncoding(reason))
+}
+
+pub(crate) fn invalid_data(error: MerkleError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let mut tree = MerkleTree::new(5);
+        for i in 0u8..21 {
+            tree.append_data(&[i]);
+        }
+        let hasher = *tree.hasher();
+        for with_levels in [true, false] {
+            let mut bytes = Vec::new();
+            tree.write_snapshot(&mut bytes, with_levels).unwrap();
+            let loaded = MerkleTree::read_snapshot(&bytes[..], hasher).unwrap();
+            assert_eq!(loaded.levels, tree.levels);
+            assert_eq!(loaded.depth(), 5);
+
+            let truncated = &bytes[..bytes.len() - 1];
+            assert!(MerkleTree::read_snapshot(truncated, hasher).is_err());
+            assert!(MerkleTree::read_snapshot(&bytes[..], Sha256Hasher).is_err());
+        }
+
+        // A header claiming 2^45 leaves of a depth 48 tree, and no leaves.
+        let mut lying = Vec::new();
+        MerkleTree::new(48)
+            .write_snapshot(&mut lying, true)
+            .unwrap();
+        lying[40..48].copy_from_slice(&(1u64 << 45).to_be_bytes());
+        let error = MerkleTree::read_snapshot(&lying[..], hasher).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+
+        let path = std::env::temp_dir().join(format!("merkle-snapshot-{}", std::process::id()));
+        tree.save(&path).unwrap();
+        assert_eq!(MerkleTree::load(&path).unwrap().root(), tree.root());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,214 @@
+use crate::{
+    zero_hashes_with, DomainSeparated, MerkleError, MerkleTree, NodeHasher, Proof, Sha256Hasher,
+    MAX_DEPTH,
+};
+
+/// A tree over leaves kept in ascending byte order without duplicates, so a
+/// value that is not a leaf can be shown absent by its two neighbours.
+#[derive(Debug, Clone)]
+pub struct SortedMerkleTree<H = DomainSeparated> {
+    tree: MerkleTree<H>,
+}
+
+/// A leaf next to the value a [`NonMembershipProof`] is for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+pub struct BoundingLeaf {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub leaf: [u8; 32],
+    pub proof: Proof,
+}
+
+/// Proof that a value is not a leaf of a [`SortedMerkleTree`]: the adjacent
+/// leaves on either side of where it would sort. `left` is missing when the
+/// value sorts first and `right` when it sorts last.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+pub struct NonMembershipProof {
+    pub size: usize,
+    pub depth: usize,
+    pub left: Option<BoundingLeaf>,
+    pub right: Option<BoundingLeaf>,
+}
+
+impl SortedMerkleTree {
+    pub fn new(depth: usize, leaves: Vec<[u8; 32]>) -> Result<Self, MerkleError> {
+        Self::with_hasher(depth, DomainSeparated(Sha256Hasher), leaves)
+    }
+}
+
+impl<H: NodeHasher> SortedMerkleTree<H> {
+    /// Sorts and deduplicates `leaves` and builds the tree over them.
+    pub fn with_hasher(
+        depth: usize,
+        hasher: H,
+        leaves: Vec<[u8; 32]>,
+    ) -> Result<Self, MerkleError> {
+        let (tree, _) = MerkleTree::from_leaves_sorted(depth, hasher, leaves)?;
+        Ok(Self { tree })
+    }
+
+    pub fn tree(&self) -> &MerkleTree<H> {
+        &self.tree
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        self.tree.root()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Index of `leaf`, found by binary search.
+    pub fn index_of(&self, leaf: &[u8; 32]) -> Option<usize> {
+        self.tree.leaves().binary_search(leaf).ok()
+    }
+
+    /// Inclusion proof for `leaf`, if it is in the tree.
+    pub fn prove(&self, leaf: &[u8; 32]) -> Option<Proof> {
+        self.index_of(leaf).map(|index| self.tree.get_proof(index))
+    }
+
+    /// Proof that `value` is not a leaf, or `None` if it is one.
+    pub fn prove_absence(&self, value: &[u8; 32]) -> Option<NonMembershipProof> {
+        let position = self.tree.leaves().binary_search(value).err()?;
+        let bound = |index: usize| BoundingLeaf {
+            leaf: self.tree.leaves()[index],
+            proof: self.tree.get_proof(index),
+        };
+        Some(NonMembershipProof {
+            size: self.len(),
+            depth: self.tree.depth(),
+            left: position.checked_sub(1).map(bound),
+            right: (position < self.len()).then(|| bound(position)),
+        })
+    }
+}
+
+impl NonMembershipProof {
+    /// Verifies the proof with the default [`MerkleTree`] hasher.
+    pub fn verify(&self, value: &[u8; 32], root: [u8; 32]) -> bool {
+        self.verify_with(&DomainSeparated(Sha256Hasher), value, root)
+    }
+
+    pub fn verify_with<H: NodeHasher>(&self, hasher: &H, value: &[u8; 32], root: [u8; 32]) -> bool {
+        let included = |bound: &BoundingLeaf| {
+            bound.proof.size == self.size && bound.proof.verify_with(hasher, bound.leaf, root)
+        };
+        match (&self.left, &self.right) {
+            (None, None) => {
+                self.size == 0
+                    && self.depth <= MAX_DEPTH
+                    && root == zero_hashes_with(hasher, self.depth)[self.depth]
+            }
+            (None, Some(right)) => right.proof.index == 0 && *value < right.leaf && included(right),
+            // Nothing may follow the left leaf, so every sibling to the right
+            // of its path must be an empty subtree.
+            (Some(left), None) => {
+                let last = left.proof.index;
+                let zeros = zero_hashes_with(hasher, left.proof.len());
+                last + 1 == self.size
+                    && left.leaf < *value
+                    && left.proof.iter().enumerate().all(|(level, sibling)| {
+                        last.checked_shr(level as u32).unwrap_or(0) & 1 == 1
+                            || *sibling == zeros[level]
+                    })
+                    && included(left)
+            }
+            (Some(left), Some(right)) => {
+                left.proof.index + 1 == right.proof.index
+                    && left.leaf < *value
+                    && *value < right.leaf
+                    && included(left)
+                    && included(right)
+            }
+        }
+    }
+}
+
+impl<H: NodeHasher> MerkleTree<H> {
+    /// [`MerkleTree::from_leaves`] over `leaves` in ascending byte order
+    /// without duplicates, as merkletreejs `sortLeaves` builds them, so the
+    /// root does not depend on the input order.
+    ///
+    /// Also returns where each input leaf ended up: the tree index of
+    /// `leaves[i]` is `indices[i]`, shared by all copies of a duplicate.
+    pub fn from_leaves_sorted(
+        depth: usize,
+        hasher: H,
+        leaves: Vec<[u8; 32]>,
+    ) -> Result<(Self, Vec<usize>), MerkleError> {
+        let mut order: Vec<usize> = (0..leaves.len()).collect();
+        order.sort_unstable_by_key(|&i| leaves[i]);
+        let mut sorted: Vec<[u8; 32]> = Vec::with_capacity(leaves.len());
+        let mut indices = vec![0; leaves.len()];
+        for i in order {
+            if sorted.last() != Some(&leaves[i]) {
+                sorted.push(leaves[i]);
+            }
+            indices[i] = sorted.len() - 1;
+        }
+        Ok((Self::from_leaves(depth, hasher, sorted)?, indices))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_absence_between_neighbours() {
+        let values: Vec<[u8; 32]> = [50u8, 10, 30, 10, 40, 20].map(|v| [v; 32]).to_vec();
+        let tree = SortedMerkleTree::new(3, values).unwrap();
+        assert_eq!(tree.len(), 5);
+        assert_eq!(tree.index_of(&[30; 32]), Some(2));
+        assert!(tree.prove(&[30; 32]).unwrap().verify([30; 32], tree.root()));
+        assert!(tree.prove_absence(&[30; 32]).is_none());
+
+        let root = tree.root();
+        for value in [0u8, 15, 35, 49, 60] {
+            let proof = tree.prove_absence(&[value; 32]).unwrap();
+            assert!(proof.verify(&[value; 32], root));
+            assert!(!proof.verify(&[30; 32], root));
+        }
+
+        // Skipping a leaf between the bounds must not verify.
+        let wide = NonMembershipProof {
+            right: tree.prove_absence(&[35; 32]).unwrap().right,
+            ..tree.prove_absence(&[25; 32]).unwrap()
+        };
+        assert!(!wide.verify(&[30; 32], root));
+        // Nor may a leaf with others after it pass as the last.
+        let mut early = tree.prove_absence(&[35; 32]).unwrap();
+        early.right = None;
+        early.size = 3;
+        assert!(!early.verify(&[35; 32], root));
+
+        let (built, indices) = MerkleTree::from_leaves_sorted(
+            3,
+            DomainSeparated(Sha256Hasher),
+            [50u8, 10, 30, 10, 40, 20].map(|v| [v; 32]).to_vec(),
+        )
+        .unwrap();
+        assert_eq!(built.root(), root);
+        assert_eq!(indices, [4, 0, 2, 0, 3, 1]);
+
+        let empty = SortedMerkleTree::new(3, Vec::new()).unwrap();
+        let proof = empty.prove_absence(&[1; 32]).unwrap();
+        assert!(proof.verify(&[1; 32], empty.root()));
+        assert!(!proof.verify(&[1; 32], [0; 32]));
+    }
+}
@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+
+use crate::{NodeHasher, Sha256Hasher};
+
+/// Number of levels below the root of a [`SparseMerkleTree`], one per key bit.
+pub const SPARSE_DEPTH: usize = 256;
+
+/// A merkle tree over the full `2^256` key space.
+///
+/// Absent keys are represented by all-zero leaves, so the tree only stores
+/// nodes whose subtree contains at least one present key. A present key is
+/// committed to as `hash(key || value)`, which keeps a stored value of all
+/// zeroes distinguishable from an absent key.
+#[derive(Debug, Clone)]
+pub struct SparseMerkleTree<H = Sha256Hasher> {
+    values: HashMap<[u8; 32], [u8; 32]>,
+    /// Non-empty nodes keyed by `(height, key prefix)`, where the prefix is
+    /// the key with its lowest `height` bits cleared.
+    nodes: HashMap<(usize, [u8; 32]), [u8; 32]>,
+    /// `zeros[height]` is the root of an empty subtree of that height.
+    zeros: Vec<[u8; 32]>,
+    hasher: H,
+}
+
+/// Sibling path from a key's leaf up to the root, leaf level first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+pub struct SparseMerkleProof {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::vec"))]
+    pub siblings: Vec<[u8; 32]>,
+}
+
+impl SparseMerkleTree {
+    pub fn new() -> Self {
+        Self::with_hasher(Sha256Hasher)
+    }
+
+    pub fn verify_membership(
+        key: [u8; 32],
+        value: [u8; 32],
+        proof: &SparseMerkleProof,
+        root: [u8; 32],
+    ) -> bool {
+        Self::verify_membership_with(&Sha256Hasher, key, value, proof, root)
+    }
+
+    pub fn verify_non_membership(key: [u8; 32], proof: &SparseMerkleProof, root: [u8; 32]) -> bool {
+        Self::verify_non_membership_with(&Sha256Hasher, key, proof, root)
+    }
+}
+
+impl Default for SparseMerkleTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<H: NodeHasher> SparseMerkleTree<H> {
+    pub fn with_hasher(hasher: H) -> Self {
+        let mut zeros = Vec::with_capacity(SPARSE_DEPTH + 1);
+        zeros.push([0u8; 32]);
+        for height in 0..SPARSE_DEPTH {
+            zeros.push(hasher.hash_nodes(zeros[height], zeros[height]));
+        }
+        Self {
+            values: HashMap::new(),
+            nodes: HashMap::new(),
+            zeros,
+            hasher,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        self.node(SPARSE_DEPTH, &[0u8; 32])
+    }
+
+    pub fn get(&self, key: &[u8; 32]) -> Option<&[u8; 32]> {
+        self.values.get(key)
+    }
+
+    /// Inserts or updates `key`, returning the previous value.
+    pub fn insert(&mut self, key: [u8; 32], value: [u8; 32]) -> Option<[u8; 32]> {
+        let leaf = self.hasher.hash(&[&key, &value]);
+        self.update_path(key, leaf);
+        self.values.insert(key, value)
+    }
+
+    /// Removes `key`, returning its value if it was present.
+    pub fn remove(&mut self, key: &[u8; 32]) -> Option<[u8; 32]> {
+        let old = self.values.remove(key)?;
+        self.update_path(*key, self.zeros[0]);
+        Some(old)
+    }
+
+    /// Proves the current state of `key`: a membership proof if it is present,
+    /// otherwise a non-membership proof.
+    pub fn prove(&self, key: &[u8; 32]) -> SparseMerkleProof {
+        let siblings = (0..SPARSE_DEPTH)
+            .map(|height| self.node(height, &prefix(&flip_bit(key, height), height)))
+            .collect();
+        SparseMerkleProof { siblings }
+    }
+
+    pub fn verify_membership_with(
+        hasher: &H,
+        key: [u8; 32],
+        value: [u8; 32],
+        proof: &SparseMerkleProof,
+        root: [u8; 32],
+    ) -> bool {
+        Self::compute_root(hasher, &key, hasher.hash(&[&key, &value]), proof) == Some(root)
+    }
+
+    pub fn verify_non_membership_with(
+        hasher: &H,
+        key: [u8; 32],
+        proof: &SparseMerkleProof,
+        root: [u8; 32],
+    ) -> bool {
+        Self::compute_root(hasher, &key, [0u8; 32], proof) == Some(root)
+    }
+
+    fn compute_root(
+        hasher: &H,
+        key: &[u8; 32],
+        leaf: [u8; 32],
+        proof: &SparseMerkleProof,
+    ) -> Option<[u8; 32]> {
+        if proof.siblings.len() != SPARSE_DEPTH {
+            return None;
+        }
+        let root = proof
+            .siblings
+            .iter()
+            .enumerate()
+            .fold(leaf, |node, (height, sibling)| {
+                if bit(key, height) {
+                    hasher.hash_nodes(*sibling, node)
+                } else {
+                    hasher.hash_nodes(node, *sibling)
+                }
+            });
+        Some(root)
+    }
+
+    fn node(&self, height: usize, prefix: &[u8; 32]) -> [u8; 32] {
+        self.nodes
+            .get(&(height, *prefix))
+            .copied()
+            .unwrap_or(self.zeros[height])
+    }
+
+    fn set_node(&mut self, height: usize, prefix: [u8; 32], node: [u8; 32]) {
+        if node == self.zeros[height] {
+            self.nodes.remove(&(height, prefix));
+        } else {
+            self.nodes.insert((height, prefix), node);
+        }
+    }
+
+    fn update_path(&mut self, key: [u8; 32], leaf: [u8; 32]) {
+        let mut node = leaf;
+        self.set_node(0, key, node);
+        for height in 0..SPARSE_DEPTH {
+            let sibling = self.node(height, &prefix(&flip_bit(&key, height), height));
+            node = if bit(&key, height) {
+                self.hasher.hash_nodes(sibling, node)
+            } else {
+                self.hasher.hash_nodes(node, sibling)
+            };
+            self.set_node(height + 1, prefix(&key, height + 1), node);
+        }
+    }
+}
+
+/// Bit `n` of `key` counting from the least significant bit of the
+/// big-endian integer, i.e. the branch taken just above height `n`.
+fn bit(key: &[u8; 32], n: usize) -> bool {
+    key[31 - n / 8] >> (n % 8) & 1 == 1
+}
+
+fn flip_bit(key: &[u8; 32], n: usize) -> [u8; 32] {
+    let mut key = *key;
+    key[31 - n / 8] ^= 1 << (n % 8);
+    key
+}
+
+/// `key` with its lowest `height` bits cleared.
+fn prefix(key: &[u8; 32], height: usize) -> [u8; 32] {
+    let mut key = *key;
+    for (i, byte) in key.iter_mut().rev().enumerate() {
+        let low = height.saturating_sub(i * 8).min(8);
+        *byte &= !((1u16 << low) - 1) as u8;
+    }
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_update_delete() {
+        let mut tree = SparseMerkleTree::new();
+        let empty = tree.root();
+        let (a, b) = ([1u8; 32], [2u8; 32]);
+
+        assert_eq!(tree.insert(a, [10u8; 32]), None);
+        let with_a = tree.root();
+        assert_eq!(tree.insert(b, [20u8; 32]), None);
+        assert_eq!(tree.insert(b, [21u8; 32]), Some([20u8; 32]));
+        assert_eq!(tree.get(&b), Some(&[21u8; 32]));
+        assert_ne!(tree.root(), with_a);
+
+        assert_eq!(tree.remove(&b), Some([21u8; 32]));
+        assert_eq!(tree.root(), with_a);
+        assert_eq!(tree.remove(&a), Some([10u8; 32]));
+        assert_eq!(tree.root(), empty);
+        assert!(tree.nodes.is_empty());
+    }
+
+    #[test]
+    fn test_membership_and_non_membership_proofs() {
+        let mut tree = SparseMerkleTree::new();
+        let present = [7u8; 32];
+        let mut absent = present;
+        absent[31] ^= 1;
+        tree.insert(present, [0u8; 32]);
+        tree.insert([9u8; 32], [1u8; 32]);
+        let root = tree.root();
+
+        let proof = tree.prove(&present);
+        assert!(SparseMerkleTree::verify_membership(
+            present, [0u8; 32], &proof, root
+        ));
+        assert!(!SparseMerkleTree::verify_non_membership(
+            present, &proof, root
+        ));
+
+        let proof = tree.prove(&absent);
+        assert!(SparseMerkleTree::verify_non_membership(
+            absent, &proof, root
+        ));
+        assert!(!SparseMerkleTree::verify_membership(
+            absent, [0u8; 32], &proof, root
+        ));
+    }
+}
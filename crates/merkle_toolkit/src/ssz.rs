@@ -0,0 +1,273 @@
+//! SSZ merkleization as specified for the Ethereum consensus layer.
+//!
+//! Objects are reduced to 32 byte chunks and merkleized with plain SHA-256,
+//! padding with zero subtrees up to the next power of two of the chunk
+//! limit. Nodes are addressed by generalized index: the root is 1 and the
+//! children of `g` are `2g` and `2g + 1`.
+
+use crate::{MerkleError, NodeHasher, Sha256Hasher};
+
+/// The SSZ `hash_tree_root` of a value.
+pub trait HashTreeRoot {
+    fn hash_tree_root(&self) -> [u8; 32];
+}
+
+macro_rules! impl_uint {
+    ($($ty:ty),*) => {$(
+        impl HashTreeRoot for $ty {
+            fn hash_tree_root(&self) -> [u8; 32] {
+                let mut chunk = [0u8; 32];
+                let bytes = self.to_le_bytes();
+                chunk[..bytes.len()].copy_from_slice(&bytes);
+                chunk
+            }
+        }
+    )*};
+}
+
+impl_uint!(u8, u16, u32, u64, u128);
+
+impl HashTreeRoot for bool {
+    fn hash_tree_root(&self) -> [u8; 32] {
+        u8::from(*self).hash_tree_root()
+    }
+}
+
+/// `Bytes32` and `Root` are a single chunk.
+impl HashTreeRoot for [u8; 32] {
+    fn hash_tree_root(&self) -> [u8; 32] {
+        *self
+    }
+}
+
+/// Splits serialized basic values into chunks, zero padding the last.
+pub fn pack(bytes: &[u8]) -> Vec<[u8; 32]> {
+    bytes
+        .chunks(32)
+        .map(|part| {
+            let mut chunk = [0u8; 32];
+            chunk[..part.len()].copy_from_slice(part);
+            chunk
+        })
+        .collect()
+}
+
+/// Root of `chunks` padded to the next power of two of `limit`, or of the
+/// chunk count when there is no limit.
+pub fn merkleize(chunks: &[[u8; 32]], limit: Option<usize>) -> Result<[u8; 32], MerkleError> {
+    Ok(SszTree::new(chunks.to_vec(), limit)?.root())
+}
+
+/// Mixes a list length into the root of its elements.
+pub fn mix_in_length(root: [u8; 32], length: usize) -> [u8; 32] {
+    Sha256Hasher.hash_nodes(root, (length as u64).hash_tree_root())
+}
+
+/// Generalized index of a node reached by following `indices` from the root,
+/// each relative to the subtree selected by the previous ones.
+pub fn concat_generalized_indices(indices: &[u64]) -> u64 {
+    indices.iter().fold(1, |acc, &index| {
+        let depth = index.ilog2();
+        acc << depth | (index ^ 1 << depth)
+    })
+}
+
+/// A merkleized chunk list that can prove any node by generalized index.
+#[derive(Debug, Clone)]
+pub struct SszTree {
+    /// `levels[0]` holds the chunks; levels stop short of the zero padding.
+    levels: Vec<Vec<[u8; 32]>>,
+    zeros: Vec<[u8; 32]>,
+    /// Set for lists, whose root mixes in the element count.
+    length: Option<usize>,
+}
+
+impl SszTree {
+    /// Merkleizes `chunks` as an SSZ vector or container.
+    pub fn new(chunks: Vec<[u8; 32]>, limit: Option<usize>) -> Result<Self, MerkleError> {
+        let limit = limit.unwrap_or(chunks.len());
+        if chunks.len() > limit {
+            return Err(MerkleError::TreeFull { capacity: limit });
+        }
+        let depth = limit.next_power_of_two().trailing_zeros() as usize;
+        let mut zeros = vec![[0u8; 32]];
+        for level in 0..depth {
+            zeros.push(Sha256Hasher.hash_nodes(zeros[level], zeros[level]));
+        }
+        let mut levels = vec![chunks];
+        for level in 0..depth {
+            let mut children = levels[level].clone();
+            if children.len() % 2 == 1 {
+                children.push(zeros[level]);
+            }
+            let mut parents = vec![[0u8; 32]; children.len() / 2];
+            Sha256Hasher.hash_pairs(&children, &mut parents);
+            levels.push(parents);
+        }
+        Ok(Self {
+            levels,
+            zeros,
+            length: None,
+        })
+    }
+
+    /// Merkleizes `chunks` as an SSZ list of `length` elements, mixing the
+    /// length into the root. Element chunks sit under generalized index 2.
+    pub fn list(chunks: Vec<[u8; 32]>, limit: usize, length: usize) -> Result<Self, MerkleError> {
+        let mut tree = Self::new(chunks, Some(limit))?;
+        tree.length = Some(length);
+        Ok(tree)
+    }
+
+    /// Height of the chunk tree, excluding the length mix-in.
+    pub fn depth(&self) -> usize {
+        self.levels.len() - 1
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        let data_root = self.data_node(self.depth(), 0);
+        match self.length {
+            Some(length) => mix_in_length(data_root, length),
+            None => data_root,
+        }
+    }
+
+    /// Generalized index of the chunk at `index`.
+    pub fn chunk_gindex(&self, index: usize) -> u64 {
+        let chunk = (1u64 << self.depth()) | index as u64;
+        match self.length {
+            Some(_) => concat_generalized_indices(&[2, chunk]),
+            None => chunk,
+        }
+    }
+
+    /// The node at generalized index `gindex`.
+    pub fn node(&self, gindex: u64) -> Result<[u8; 32], MerkleError> {
+        let out_of_bounds = MerkleError::IndexOutOfBounds {
+            index: gindex as usize,
+            len: self.node_count(),
+        };
+        if gindex == 0 {
+            return Err(out_of_bounds);
+        }
+        let gindex = match self.length {
+            None => gindex,
+            Some(_) if gindex == 1 => return Ok(self.root()),
+            Some(length) if gindex == 3 => return Ok((length as u64).hash_tree_root()),
+            Some(_) => {
+                // Re-root the subtree under 2 at 1 by dropping the bit below
+                // the leading one.
+                let depth = gindex.ilog2();
+                if gindex >> (depth - 1) != 2 {
+                    return Err(out_of_bounds);
+                }
+                gindex ^ 1 << depth | 1 << (depth - 1)
+            }
+        };
+        let depth = gindex.ilog2() as usize;
+        if depth > self.depth() {
+            return Err(out_of_bounds);
+        }
+        let index = (gindex ^ 1 << depth) as usize;
+        Ok(self.data_node(self.depth() - depth, index))
+    }
+
+    /// Sibling nodes from `gindex` up to the root.
+    pub fn prove(&self, gindex: u64) -> Result<Vec<[u8; 32]>, MerkleError> {
+        self.node(gindex)?;
+        let mut branch = Vec::new();
+        let mut current = gindex;
+        while current > 1 {
+            branch.push(self.node(current ^ 1)?);
+            current /= 2;
+        }
+        Ok(branch)
+    }
+
+    fn data_node(&self, level: usize, index: usize) -> [u8; 32] {
+        self.levels[level]
+            .get(index)
+            .copied()
+            .unwrap_or(self.zeros[level])
+    }
+
+    /// Upper bound on valid generalized indices, for error reporting.
+    fn node_count(&self) -> usize {
+        let nodes = 2usize.saturating_pow(self.depth() as u32 + 1);
+        match self.length {
+            Some(_) => nodes.saturating_mul(2),
+            None => nodes,
+        }
+    }
+}
+
+/// Checks that `branch` leads from `leaf` at generalized index `gindex` to
+/// `root`, as the consensus spec's `is_valid_merkle_branch` does.
+pub fn verify_gindex_proof(
+    leaf: [u8; 32],
+    branch: &[[u8; 32]],
+    gindex: u64,
+    root: [u8; 32],
+) -> bool {
+    if gindex == 0 || branch.len() != gindex.ilog2() as usize {
+        return false;
+    }
+    let index = (gindex ^ 1 << branch.len()) as usize;
+    crate::verify_proof(&Sha256Hasher, leaf, branch, index, root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hex;
+
+    #[test]
+    fn test_basic_roots() {
+        let mut one = [0u8; 32];
+        one[0] = 1;
+        assert_eq!(1u64.hash_tree_root(), one);
+        assert_eq!(true.hash_tree_root(), one);
+        assert_eq!(
+            pack(&[1, 0, 0, 0, 2]),
+            vec![{
+                let mut chunk = [0u8; 32];
+                chunk[0] = 1;
+                chunk[4] = 2;
+                chunk
+            }]
+        );
+
+        // `Checkpoint { epoch: 0, root: 0x00.. }`
+        let checkpoint = merkleize(&[0u64.hash_tree_root(), [0u8; 32]], None).unwrap();
+        assert_eq!(
+            hex::encode(&checkpoint),
+            "f5a5fd42d16a20302798ef6ed309979b43003d2320d9f0e8ea9831a92759fb4b"
+        );
+        assert!(merkleize(&[[0u8; 32]; 3], Some(2)).is_err());
+    }
+
+    #[test]
+    fn test_list_proofs_by_generalized_index() {
+        let chunks: Vec<[u8; 32]> = (0u64..5).map(|i| i.hash_tree_root()).collect();
+        let tree = SszTree::list(chunks.clone(), 8, 5).unwrap();
+        let data_root = merkleize(&chunks, Some(8)).unwrap();
+        assert_eq!(tree.root(), mix_in_length(data_root, 5));
+        assert_eq!(tree.node(2).unwrap(), data_root);
+
+        for (index, chunk) in chunks.iter().enumerate() {
+            let gindex = tree.chunk_gindex(index);
+            assert_eq!(gindex, 16 + index as u64);
+            let branch = tree.prove(gindex).unwrap();
+            assert!(verify_gindex_proof(*chunk, &branch, gindex, tree.root()));
+        }
+        let length_branch = tree.prove(3).unwrap();
+        assert!(verify_gindex_proof(
+            5u64.hash_tree_root(),
+            &length_branch,
+            3,
+            tree.root()
+        ));
+        assert!(tree.prove(32).is_err());
+        assert!(tree.prove(6).is_err());
+    }
+}
@@ -0,0 +1,60 @@
+use std::mem::size_of;
+
+use crate::{MerkleTree, NodeHasher};
+
+/// Sizes of an in-memory tree, from [`MerkleTree::stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TreeStats {
+    pub leaves: usize,
+    /// Levels holding at least one node, the leaves included; the levels
+    /// above them up to the depth are padded with zero hashes on the fly.
+    pub levels: usize,
+    /// Nodes stored across all levels, the leaves included.
+    pub cached_nodes: usize,
+    /// Estimated heap use: allocated level buffers, the zero hashes and the
+    /// leaf index if enabled. Allocator overhead is not counted.
+    pub heap_bytes: usize,
+}
+
+impl<H: NodeHasher> MerkleTree<H> {
+    pub fn stats(&self) -> TreeStats {
+        let node = size_of::<[u8; 32]>();
+        let levels_bytes = self.levels.capacity() * size_of::<Vec<[u8; 32]>>()
+            + self
+                .levels
+                .iter()
+                .map(|level| level.capacity() * node)
+                .sum::<usize>();
+        // hashbrown stores each entry plus one control byte per bucket.
+        let index_bytes = self.leaf_index.as_ref().map_or(0, |map| {
+            map.capacity() * (size_of::<([u8; 32], usize)>() + 1)
+        });
+        TreeStats {
+            leaves: self.len(),
+            levels: self.levels.len(),
+            cached_nodes: self.levels.iter().map(Vec::len).sum(),
+            heap_bytes: levels_bytes + self.zeros.capacity() * node + index_bytes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats_count_nodes() {
+        let mut tree = MerkleTree::new(10);
+        for i in 0u8..5 {
+            tree.append_leaf([i; 32]);
+        }
+        let stats = tree.stats();
+        assert_eq!((stats.leaves, stats.levels), (5, 4));
+        assert_eq!(stats.cached_nodes, 5 + 3 + 2 + 1);
+        assert!(stats.heap_bytes >= (11 + 11) * 32);
+
+        let indexed = tree.with_leaf_index().stats();
+        assert!(indexed.heap_bytes >= stats.heap_bytes + 5 * 40);
+    }
+}
@@ -0,0 +1,179 @@
+use crate::{MerkleError, MerkleTree, NodeHasher, NodeStore};
+
+/// Produces signatures over tree heads. Implemented for ed25519 signing
+/// keys with the `ed25519` feature; HSM or KMS backed signers implement it
+/// themselves.
+pub trait TreeHeadSigner {
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, MerkleError>;
+}
+
+/// Checks signatures made by a [`TreeHeadSigner`].
+pub trait TreeHeadVerifier {
+    fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), MerkleError>;
+}
+
+/// A log operator's signed commitment to the tree at `size` leaves.
+///
+/// The signature covers the RFC 6962 `TreeHeadSignature` structure, so a
+/// client holding two heads from the same key can demand a consistency
+/// proof between them, and a head contradicting another of the same size
+/// is evidence of misbehaviour. Heads from
+/// [`MerkleTree::signed_tree_head`] sign the RFC 6962 Merkle Tree Hash
+/// ([`MerkleTree::ct_root`]), which CT verifiers accept; a head signed over
+/// the depth-padded [`MerkleTree::root`] is only meaningful to this crate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+pub struct SignedTreeHead {
+    pub size: u64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub root: [u8; 32],
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::bytes"))]
+    pub signature: Vec<u8>,
+}
+
+impl SignedTreeHead {
+    pub fn sign(
+        size: u64,
+        root: [u8; 32],
+        timestamp: u64,
+        signer: &impl TreeHeadSigner,
+    ) -> Result<Self, MerkleError> {
+        let signature = signer.sign(&signed_data(size, root, timestamp))?;
+        Ok(Self {
+            size,
+            root,
+            timestamp,
+            signature,
+        })
+    }
+
+    /// The bytes the signature covers: version `v1`, signature type
+    /// `tree_hash`, then timestamp, size and root.
+    pub fn signed_data(&self) -> [u8; 50] {
+        signed_data(self.size, self.root, self.timestamp)
+    }
+
+    pub fn verify(&self, verifier: &impl TreeHeadVerifier) -> Result<(), MerkleError> {
+        verifier.verify(&self.signed_data(), &self.signature)
+    }
+}
+
+fn signed_data(size: u64, root: [u8; 32], timestamp: u64) -> [u8; 50] {
+    let mut data = [0u8; 50];
+    data[1] = 1;
+    data[2..10].copy_from_slice(&timestamp.to_be_bytes());
+    data[10..18].copy_from_slice(&size.to_be_bytes());
+    data[18..].copy_from_slice(&root);
+    data
+}
+
+impl<H: NodeHasher, S: NodeStore> MerkleTree<H, S> {
+    /// Signs the size and the RFC 6962 Merkle Tree Hash of the leaves, as a
+    /// CT log does.
+    pub fn signed_tree_head(
+        &self,
+        timestamp: u64,
+        signer: &impl TreeHeadSigner,
+    ) -> Result<SignedTreeHead, MerkleError> {
+        SignedTreeHead::sign(
+            self.len() as u64,
+            self.ct_root(self.len())?,
+            timestamp,
+            signer,
+        )
+    }
+}
+
+#[cfg(feature = "ed25519")]
+impl TreeHeadSigner for ed25519_dalek::SigningKey {
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, MerkleError> {
+        use ed25519_dalek::Signer;
+        Ok(self
+            .try_sign(message)
+            .map_err(|_| MerkleError::InvalidSignature)?
+            .to_bytes()
+            .to_vec())
+    }
+}
+
+#[cfg(feature = "ed25519")]
+impl TreeHeadVerifier for ed25519_dalek::VerifyingKey {
+    fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), MerkleError> {
+        let signature = ed25519_dalek::Signature::from_slice(signature)
+            .map_err(|_| MerkleError::InvalidSignature)?;
+        self.verify_strict(message, &signature)
+            .map_err(|_| MerkleError::InvalidSignature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Signs by hashing the message with a secret, standing in for a real
+    /// signature scheme.
+    struct KeyedHash([u8; 32]);
+
+    impl TreeHeadSigner for KeyedHash {
+        fn sign(&self, message: &[u8]) -> Result<Vec<u8>, MerkleError> {
+            Ok(crate::Sha256Hasher.hash(&[&self.0, message]).to_vec())
+        }
+    }
+
+    impl TreeHeadVerifier for KeyedHash {
+        fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), MerkleError> {
+            match self.sign(message)? == signature {
+                true => Ok(()),
+                false => Err(MerkleError::InvalidSignature),
+            }
+        }
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let mut tree = MerkleTree::new(4);
+        tree.append_data(b"a");
+        tree.append_data(b"b");
+        tree.append_data(b"c");
+        let key = KeyedHash([9; 32]);
+        let head = tree.signed_tree_head(1_700_000_000_000, &key).unwrap();
+        assert_eq!((head.size, head.root), (3, tree.ct_root(3).unwrap()));
+        assert_ne!(head.root, tree.root());
+        assert_eq!(
+            &head.signed_data()[..10],
+            &[0, 1, 0, 0, 1, 0x8b, 0xcf, 0xe5, 0x68, 0]
+        );
+        assert_eq!(&head.signed_data()[10..18], &3u64.to_be_bytes());
+        head.verify(&key).unwrap();
+
+        let mut forged = head.clone();
+        forged.size = 4;
+        assert_eq!(forged.verify(&key), Err(MerkleError::InvalidSignature));
+        assert_eq!(
+            head.verify(&KeyedHash([8; 32])),
+            Err(MerkleError::InvalidSignature)
+        );
+    }
+
+    #[cfg(feature = "ed25519")]
+    #[test]
+    fn test_ed25519() {
+        let key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+        let head = SignedTreeHead::sign(5, [1; 32], 42, &key).unwrap();
+        assert_eq!(head.signature.len(), 64);
+        head.verify(&key.verifying_key()).unwrap();
+
+        let mut tampered = head.clone();
+        tampered.root[0] ^= 1;
+        assert_eq!(
+            tampered.verify(&key.verifying_key()),
+            Err(MerkleError::InvalidSignature)
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use crate::MerkleError;
+
+/// The default node storage: one `Vec` per level, leaves first.
+pub type MemoryStore = Vec<Vec<[u8; 32]>>;
+
+/// Storage for the nodes of a [`MerkleTree`](crate::MerkleTree), addressed by
+/// `(level, index)` with the leaves at level 0.
+///
+/// Levels are dense: a level holds every node from index 0 up to its length,
+/// and the tree only ever writes to an existing index or one past the end.
+pub trait NodeStore {
+    /// Number of levels holding at least one node, or one for an empty tree.
+    fn levels(&self) -> usize;
+
+    /// Number of nodes at `level`, zero past the top.
+    fn level_len(&self, level: usize) -> usize;
+
+    /// The node at `(level, index)`, or `None` if it has not been written.
+    fn get(&self, level: usize, index: usize) -> Result<Option<[u8; 32]>, MerkleError>;
+
+    /// Writes the node at `(level, index)`, growing the level or adding a
+    /// new top level if needed.
+    fn put(&mut self, level: usize, index: usize, node: [u8; 32]) -> Result<(), MerkleError>;
+}
+
+impl NodeStore for MemoryStore {
+    fn levels(&self) -> usize {
+        self.len()
+    }
+
+    fn level_len(&self, level: usize) -> usize {
+        self.as_slice().get(level).map_or(0, Vec::len)
+    }
+
+    fn get(&self, level: usize, index: usize) -> Result<Option<[u8; 32]>, MerkleError> {
+        Ok(self
+            .as_slice()
+            .get(level)
+            .and_then(|nodes| nodes.get(index))
+            .copied())
+    }
+
+    fn put(&mut self, level: usize, index: usize, node: [u8; 32]) -> Result<(), MerkleError> {
+        if level == self.len() {
+            self.push(Vec::new());
+        }
+        let nodes = &mut self[level];
+        if index == nodes.len() {
+            nodes.push(node);
+        } else {
+            nodes[index] = node;
+        }
+        Ok(())
+    }
+}
+
+/// Key of the node at `(level, index)` in key-value backed stores. Indices
+/// are big-endian so a level's nodes sort in order.
+#[cfg(any(feature = "rocksdb", feature = "sled"))]
+pub(crate) fn node_key(level: usize, index: usize) -> [u8; 10] {
+    let mut key = [0u8; 10];
+    key[0] = b'n';
+    key[1] = level as u8;
+    key[2..].copy_from_slice(&(index as u64).to_be_bytes());
+    key
+}
+
+/// Key of the node count of `level` in key-value backed stores.
+#[cfg(any(feature = "rocksdb", feature = "sled"))]
+pub(crate) fn len_key(level: usize) -> [u8; 2] {
+    [b'l', level as u8]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DomainSeparated, MerkleTree, NodeHasher, Sha256Hasher};
+    use std::collections::HashMap;
+
+    /// A sparse store keyed by position, standing in for a KV database.
+    #[derive(Default)]
+    struct MapStore {
+        nodes: HashMap<(usize, usize), [u8; 32]>,
+        lens: Vec<usize>,
+    }
+
+    impl NodeStore for MapStore {
+        fn levels(&self) -> usize {
+            self.lens.len().max(1)
+        }
+
+        fn level_len(&self, level: usize) -> usize {
+            self.lens.get(level).copied().unwrap_or(0)
+        }
+
+        fn get(&self, level: usize, index: usize) -> Result<Option<[u8; 32]>, MerkleError> {
+            Ok(self.nodes.get(&(level, index)).copied())
+        }
+
+        fn put(&mut self, level: usize, index: usize, node: [u8; 32]) -> Result<(), MerkleError> {
+            if level == self.lens.len() {
+                self.lens.push(0);
+            }
+            self.lens[level] = self.lens[level].max(index + 1);
+            self.nodes.insert((level, index), node);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_custom_store_matches_memory() {
+        let hasher = DomainSeparated(Sha256Hasher);
+        let mut memory = MerkleTree::new(4);
+        let mut mapped = MerkleTree::with_store(4, hasher, MapStore::default()).unwrap();
+        assert_eq!(mapped.root(), memory.root());
+        for i in 0u8..11 {
+            memory.append_data(&[i]);
+            mapped.append_data(&[i]);
+            assert_eq!(mapped.root(), memory.root());
+        }
+        assert_eq!(mapped.len(), 11);
+        for index in 0..11 {
+            assert_eq!(mapped.get_proof(index), memory.get_proof(index));
+        }
+        let leaf = hasher.hash_leaf(b"new");
+        assert_eq!(mapped.set_leaf(3, leaf), memory.set_leaf(3, leaf));
+        assert!(mapped.try_get_proof(11).is_err());
+    }
+}
@@ -0,0 +1,148 @@
+use crate::{height, pad_root, zero_hashes_with, DomainSeparated, NodeHasher, Sha256Hasher};
+
+/// Computes the root of a [`crate::MerkleTree`] from leaves pushed one at a
+/// time, without storing them.
+///
+/// Only the roots of the completed subtrees on the left edge are kept, one
+/// per level, so memory is `O(log n)` for `n` leaves.
+#[derive(Debug, Clone)]
+pub struct RootBuilder<H = DomainSeparated> {
+    hasher: H,
+    len: usize,
+    /// `frontier[level]` is meaningful when bit `level` of `len` is set: it is
+    /// the root of the last complete subtree of `2^level` leaves.
+    frontier: Vec<[u8; 32]>,
+}
+
+/// Root of the smallest default [`crate::MerkleTree`] holding `leaves`.
+pub fn root_from_iter(leaves: impl IntoIterator<Item = [u8; 32]>) -> [u8; 32] {
+    root_from_iter_with(DomainSeparated(Sha256Hasher), leaves)
+}
+
+pub fn root_from_iter_with<H: NodeHasher>(
+    hasher: H,
+    leaves: impl IntoIterator<Item = [u8; 32]>,
+) -> [u8; 32] {
+    let mut builder = RootBuilder::with_hasher(hasher);
+    builder.extend(leaves);
+    builder.root()
+}
+
+impl RootBuilder {
+    pub fn new() -> Self {
+        Self::with_hasher(DomainSeparated(Sha256Hasher))
+    }
+}
+
+impl Default for RootBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<H: NodeHasher> RootBuilder<H> {
+    pub fn with_hasher(hasher: H) -> Self {
+        Self {
+            hasher,
+            len: 0,
+            frontier: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn push(&mut self, leaf: [u8; 32]) {
+        let mut node = leaf;
+        let mut level = 0;
+        while self.len >> level & 1 == 1 {
+            node = self.hasher.hash_nodes(self.frontier[level], node);
+            level += 1;
+        }
+        if level == self.frontier.len() {
+            self.frontier.push(node);
+        } else {
+            self.frontier[level] = node;
+        }
+        self.len += 1;
+    }
+
+    /// Root of a tree of `depth`, which must be deep enough for
+    /// [`RootBuilder::len`] leaves.
+    pub fn root_at_depth(&self, depth: usize) -> [u8; 32] {
+        assert!(height(self.len) <= depth, "depth {depth} is too small");
+        let zeros = zero_hashes_with(&self.hasher, depth);
+        match self.len {
+            0 => zeros[depth],
+            _ => pad_root(&self.hasher, &zeros, self.root(), height(self.len), depth),
+        }
+    }
+
+    /// Root of the smallest tree holding the leaves, of depth
+    /// `ceil(log2(len))`.
+    pub fn root(&self) -> [u8; 32] {
+        if self.len == 0 {
+            return [0u8; 32];
+        }
+        let zeros = zero_hashes_with(&self.hasher, height(self.len));
+        // Walk up the right edge of the tree. `edge` holds the rightmost node
+        // of the current level while it is incomplete; once the rightmost
+        // node is a complete subtree it is found in the frontier instead.
+        let mut edge: Option<[u8; 32]> = None;
+        let mut level = 0;
+        loop {
+            let count = (self.len - 1) / (1 << level) + 1;
+            let last_is_right = (count - 1) % 2 == 1;
+            if count == 1 {
+                return edge.unwrap_or_else(|| self.frontier[level]);
+            }
+            edge = match edge {
+                None if last_is_right => None,
+                None => Some(self.hasher.hash_nodes(self.frontier[level], zeros[level])),
+                Some(node) if last_is_right => {
+                    Some(self.hasher.hash_nodes(self.frontier[level], node))
+                }
+                Some(node) => Some(self.hasher.hash_nodes(node, zeros[level])),
+            };
+            level += 1;
+        }
+    }
+}
+
+impl<H: NodeHasher> Extend<[u8; 32]> for RootBuilder<H> {
+    fn extend<I: IntoIterator<Item = [u8; 32]>>(&mut self, leaves: I) {
+        for leaf in leaves {
+            self.push(leaf);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MerkleTree;
+
+    #[test]
+    fn test_streaming_root_matches_tree() {
+        let mut tree = MerkleTree::new(6);
+        let mut builder = RootBuilder::new();
+        assert_eq!(builder.root_at_depth(6), tree.root());
+        for i in 0u8..40 {
+            let leaf = [i; 32];
+            tree.append_leaf(leaf);
+            builder.push(leaf);
+            assert_eq!(
+                builder.root_at_depth(6),
+                tree.root(),
+                "after {} leaves",
+                i + 1
+            );
+        }
+        assert_eq!(root_from_iter(tree.leaves().iter().copied()), tree.root());
+    }
+}
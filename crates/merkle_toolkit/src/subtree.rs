@@ -0,0 +1,127 @@
+use crate::{
+    capacity, DomainSeparated, MerkleError, MerkleTree, NodeHasher, NodeStore, Sha256Hasher,
+    MAX_DEPTH,
+};
+
+/// Proof that a subtree root is the node at `(level, index)` of a tree,
+/// where level 0 holds the leaves: the siblings of its ancestors from
+/// `level` up to the root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+pub struct SubtreeProof {
+    pub level: usize,
+    pub index: usize,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::vec"))]
+    pub siblings: Vec<[u8; 32]>,
+}
+
+impl SubtreeProof {
+    /// Verifies the proof with the default [`MerkleTree`] hasher.
+    pub fn verify(&self, subtree_root: [u8; 32], root: [u8; 32]) -> bool {
+        self.verify_with(&DomainSeparated(Sha256Hasher), subtree_root, root)
+    }
+
+    /// Checks that `subtree_root` hashes up to `root` through the siblings,
+    /// which must reach exactly the top of the tree.
+    pub fn verify_with<H: NodeHasher>(
+        &self,
+        hasher: &H,
+        subtree_root: [u8; 32],
+        root: [u8; 32],
+    ) -> bool {
+        self.level + self.siblings.len() <= MAX_DEPTH
+            && self.index >> self.siblings.len() == 0
+            && MerkleTree::verify_proof_with(hasher, subtree_root, &self.siblings, self.index, root)
+    }
+}
+
+impl<H: NodeHasher, S: NodeStore> MerkleTree<H, S> {
+    /// The root of the subtree at `(level, index)`, where level 0 holds the
+    /// leaves and level `depth` the root. Subtrees without leaves have the
+    /// empty-subtree hash.
+    pub fn subtree_root(&self, level: usize, index: usize) -> Result<[u8; 32], MerkleError> {
+        self.check_subtree(level, index)?;
+        self.node(level, index)
+    }
+
+    /// Proves [`MerkleTree::subtree_root`] for `(level, index)` up to the
+    /// current root.
+    pub fn prove_subtree(&self, level: usize, index: usize) -> Result<SubtreeProof, MerkleError> {
+        self.check_subtree(level, index)?;
+        let siblings = (level..self.depth)
+            .map(|at| self.node(at, (index >> (at - level)) ^ 1))
+            .collect::<Result<_, _>>()?;
+        Ok(SubtreeProof {
+            level,
+            index,
+            siblings,
+        })
+    }
+
+    fn check_subtree(&self, level: usize, index: usize) -> Result<(), MerkleError> {
+        if level > self.depth {
+            return Err(MerkleError::DepthTooLarge {
+                depth: level,
+                max: self.depth,
+            });
+        }
+        let len = capacity(self.depth - level);
+        if index >= len {
+            return Err(MerkleError::IndexOutOfBounds { index, len });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subtree_roots_prove_to_root() {
+        let mut tree = MerkleTree::new(5);
+        for i in 0u8..11 {
+            tree.append_data(&[i]);
+        }
+        let root = tree.root();
+        assert_eq!(tree.subtree_root(0, 7).unwrap(), tree.leaves()[7]);
+        assert_eq!(tree.subtree_root(5, 0).unwrap(), root);
+        for level in 0..=5 {
+            for index in 0..1usize << (5 - level) {
+                let node = tree.subtree_root(level, index).unwrap();
+                let proof = tree.prove_subtree(level, index).unwrap();
+                assert_eq!(proof.siblings.len(), 5 - level);
+                assert!(proof.verify(node, root));
+                assert!(!proof.verify([9; 32], root));
+            }
+        }
+
+        let shard = tree.subtree_root(2, 1).unwrap();
+        let mut rebuilt = MerkleTree::new(2);
+        for &leaf in &tree.leaves()[4..8] {
+            rebuilt.append_leaf(leaf);
+        }
+        assert_eq!(shard, rebuilt.root());
+        assert_eq!(tree.subtree_root(3, 3).unwrap(), crate::zero_hashes(3)[3]);
+    }
+
+    #[test]
+    fn test_subtree_bounds() {
+        let tree = MerkleTree::new(4);
+        assert_eq!(
+            tree.subtree_root(5, 0),
+            Err(MerkleError::DepthTooLarge { depth: 5, max: 4 })
+        );
+        assert_eq!(
+            tree.prove_subtree(2, 4),
+            Err(MerkleError::IndexOutOfBounds { index: 4, len: 4 })
+        );
+        let mut proof = tree.prove_subtree(2, 3).unwrap();
+        proof.index = 4;
+        assert!(!proof.verify(tree.subtree_root(2, 3).unwrap(), tree.root()));
+    }
+}
@@ -0,0 +1,183 @@
+//! Metrics for proof-serving services (feature `metrics`).
+//!
+//! The crate reports through the [`metrics`](https://docs.rs/metrics) facade,
+//! so any installed recorder picks them up, such as
+//! `metrics-exporter-prometheus` for a Prometheus scrape endpoint. Nothing is
+//! recorded until a recorder is installed, and without the feature the hooks
+//! compile away.
+//!
+//! | name | kind | counts |
+//! |---|---|---|
+//! | [`LEAVES_APPENDED`] | counter | leaves added to [`MerkleTree`](crate::MerkleTree)s |
+//! | [`PROOFS_GENERATED`] | counter | inclusion proofs generated |
+//! | [`PROOFS_VERIFIED`] | counter | inclusion proofs checked, labelled `result` = `valid` or `invalid` |
+//! | [`PROOF_GENERATION_SECONDS`] | histogram | time to generate one proof |
+
+pub const LEAVES_APPENDED: &str = "merkle_leaves_appended_total";
+pub const PROOFS_GENERATED: &str = "merkle_proofs_generated_total";
+pub const PROOFS_VERIFIED: &str = "merkle_proofs_verified_total";
+pub const PROOF_GENERATION_SECONDS: &str = "merkle_proof_generation_seconds";
+
+/// Registers units and help text for the metrics with the installed
+/// recorder, for exporters that publish them. Call once after installing it.
+#[cfg(feature = "metrics")]
+pub fn describe_metrics() {
+    use metrics::{describe_counter, describe_histogram, Unit};
+
+    describe_counter!(
+        LEAVES_APPENDED,
+        Unit::Count,
+        "Leaves appended to merkle trees"
+    );
+    describe_counter!(
+        PROOFS_GENERATED,
+        Unit::Count,
+        "Merkle inclusion proofs generated"
+    );
+    describe_counter!(
+        PROOFS_VERIFIED,
+        Unit::Count,
+        "Merkle inclusion proofs verified, by result"
+    );
+    describe_histogram!(
+        PROOF_GENERATION_SECONDS,
+        Unit::Seconds,
+        "Time taken to generate a merkle inclusion proof"
+    );
+}
+
+pub(crate) fn leaves_appended(count: usize) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!(LEAVES_APPENDED).increment(count as u64);
+    #[cfg(not(feature = "metrics"))]
+    let _ = count;
+}
+
+/// Counts proofs generated in bulk, which are not timed one by one.
+pub(crate) fn proofs_generated(count: usize) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!(PROOFS_GENERATED).increment(count as u64);
+    #[cfg(not(feature = "metrics"))]
+    let _ = count;
+}
+
+pub(crate) fn proof_verified(valid: bool) -> bool {
+    #[cfg(feature = "metrics")]
+    metrics::counter!(PROOFS_VERIFIED, "result" => if valid { "valid" } else { "invalid" })
+        .increment(1);
+    valid
+}
+
+/// Times the generation of one proof, counting it when finished.
+pub(crate) struct ProofTimer {
+    #[cfg(feature = "metrics")]
+    start: std::time::Instant,
+}
+
+impl ProofTimer {
+    pub(crate) fn start() -> Self {
+        Self {
+            #[cfg(feature = "metrics")]
+            start: std::time::Instant::now(),
+        }
+    }
+
+    pub(crate) fn finish(self) {
+        #[cfg(feature = "metrics")]
+        {
+            metrics::counter!(PROOFS_GENERATED).increment(1);
+            metrics::histogram!(PROOF_GENERATION_SECONDS).record(self.start.elapsed());
+        }
+    }
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use metrics::{
+        Counter, Gauge, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder, SharedString,
+        Unit,
+    };
+
+    use crate::MerkleTree;
+
+    struct Samples(Mutex<Vec<f64>>);
+
+    impl HistogramFn for Samples {
+        fn record(&self, value: f64) {
+            self.0.lock().unwrap().push(value);
+        }
+    }
+
+    /// Keeps every counter and histogram by its key.
+    #[derive(Default)]
+    struct TestRecorder {
+        counters: Mutex<HashMap<Key, Arc<AtomicU64>>>,
+        histograms: Mutex<HashMap<Key, Arc<Samples>>>,
+    }
+
+    impl TestRecorder {
+        fn counter(&self, name: &'static str, labels: &[(&'static str, &'static str)]) -> u64 {
+            let labels: Vec<metrics::Label> = labels.iter().map(metrics::Label::from).collect();
+            self.counters
+                .lock()
+                .unwrap()
+                .get(&Key::from_parts(name, labels))
+                .map_or(0, |value| value.load(Ordering::Relaxed))
+        }
+    }
+
+    impl Recorder for TestRecorder {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+            let mut counters = self.counters.lock().unwrap();
+            Counter::from_arc(counters.entry(key.clone()).or_default().clone())
+        }
+
+        fn register_gauge(&self, _: &Key, _: &Metadata<'_>) -> Gauge {
+            Gauge::noop()
+        }
+
+        fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+            let mut histograms = self.histograms.lock().unwrap();
+            let samples = histograms
+                .entry(key.clone())
+                .or_insert_with(|| Arc::new(Samples(Mutex::new(Vec::new()))));
+            Histogram::from_arc(samples.clone())
+        }
+    }
+
+    #[test]
+    fn test_operations_are_counted() {
+        let recorder = TestRecorder::default();
+        metrics::with_local_recorder(&recorder, || {
+            super::describe_metrics();
+            let mut tree = MerkleTree::new(3);
+            for i in 0u8..3 {
+                tree.append_leaf([i; 32]);
+            }
+            let proof = tree.get_proof(2);
+            assert!(proof.verify([2; 32], tree.root()));
+            assert!(!proof.verify([3; 32], tree.root()));
+        });
+        assert_eq!(recorder.counter(super::LEAVES_APPENDED, &[]), 3);
+        assert_eq!(recorder.counter(super::PROOFS_GENERATED, &[]), 1);
+        assert_eq!(
+            recorder.counter(super::PROOFS_VERIFIED, &[("result", "valid")]),
+            1
+        );
+        assert_eq!(
+            recorder.counter(super::PROOFS_VERIFIED, &[("result", "invalid")]),
+            1
+        );
+        let histograms = recorder.histograms.lock().unwrap();
+        let samples = &histograms[&Key::from_name(super::PROOF_GENERATION_SECONDS)];
+        assert_eq!(samples.0.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,95 @@
+//! proptest strategies for property-testing code built on this crate
+//! (feature `test-util`).
+//!
+//! [`MerkleTree`] and [`Proof`] implement [`Arbitrary`], so they can be
+//! drawn with `any::<MerkleTree>()`. Generated trees are always valid, use
+//! the default hasher and are at most [`MAX_GENERATED_DEPTH`] deep; proofs
+//! are random and almost never verify, which suits decoders and verifiers.
+//!
+//! ```
+//! use merkle_toolkit::test_util::tree_and_index;
+//! use proptest::prelude::*;
+//!
+//! proptest!(|((tree, index) in tree_and_index())| {
+//!     let proof = tree.get_proof(index);
+//!     prop_assert!(proof.verify(tree.leaves()[index], tree.root()));
+//! });
+//! ```
+
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+use crate::{MerkleTree, Proof};
+
+/// Deepest tree drawn by [`trees`] and the [`Arbitrary`] impls.
+pub const MAX_GENERATED_DEPTH: usize = 8;
+
+/// Trees of depth up to `max_depth`, each filled to a random size.
+pub fn trees(max_depth: usize) -> impl Strategy<Value = MerkleTree> {
+    (0..=max_depth)
+        .prop_flat_map(|depth| (Just(depth), vec(any::<[u8; 32]>(), 0..=1 << depth)))
+        .prop_map(|(depth, leaves)| {
+            let mut tree = MerkleTree::new(depth);
+            for leaf in leaves {
+                tree.append_leaf(leaf);
+            }
+            tree
+        })
+}
+
+/// A non-empty tree and the index of one of its leaves.
+pub fn tree_and_index() -> impl Strategy<Value = (MerkleTree, usize)> {
+    trees(MAX_GENERATED_DEPTH)
+        .prop_filter("tree has no leaves", |tree| !tree.is_empty())
+        .prop_flat_map(|tree| {
+            let len = tree.len();
+            (Just(tree), 0..len)
+        })
+}
+
+impl Arbitrary for MerkleTree {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        trees(MAX_GENERATED_DEPTH).boxed()
+    }
+}
+
+impl Arbitrary for Proof {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        (
+            any::<u32>(),
+            any::<u32>(),
+            vec(any::<[u8; 32]>(), 0..=MAX_GENERATED_DEPTH),
+        )
+            .prop_map(|(index, size, siblings)| Proof {
+                index: index as usize,
+                size: size as usize,
+                siblings,
+            })
+            .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn test_generated_trees_are_consistent((tree, index) in tree_and_index()) {
+            prop_assert!(tree.check_invariants().is_ok());
+            let proof = tree.get_proof(index);
+            prop_assert!(proof.verify(tree.leaves()[index], tree.root()));
+        }
+
+        #[test]
+        fn test_random_proofs_roundtrip(proof in any::<Proof>()) {
+            prop_assert_eq!(Proof::from_bytes(&proof.to_bytes()), Ok(proof));
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::{btree_map, BTreeMap};
+
+use crate::{height, pad_root, MerkleError, MerkleTree, NodeHasher, NodeStore};
+
+/// A batch of appends and leaf updates staged against a tree, opened with
+/// [`MerkleTree::begin`].
+///
+/// Nothing touches the tree until [`TreeTransaction::commit`]; until then
+/// [`TreeTransaction::dry_run_root`] gives the root the batch would produce by
+/// rehashing only the paths above staged leaves, over the tree's own nodes.
+/// Dropping the transaction, or calling [`TreeTransaction::rollback`],
+/// discards the batch.
+#[derive(Debug)]
+pub struct TreeTransaction<'a, H, S> {
+    tree: &'a mut MerkleTree<H, S>,
+    appends: Vec<[u8; 32]>,
+    updates: BTreeMap<usize, [u8; 32]>,
+}
+
+impl<H: NodeHasher, S: NodeStore> MerkleTree<H, S> {
+    /// Opens a transaction; see [`TreeTransaction`].
+    pub fn begin(&mut self) -> TreeTransaction<'_, H, S> {
+        TreeTransaction {
+            tree: self,
+            appends: Vec::new(),
+            updates: BTreeMap::new(),
+        }
+    }
+}
+
+impl<H: NodeHasher, S: NodeStore> TreeTransaction<'_, H, S> {
+    /// The tree as it was when the transaction began.
+    pub fn tree(&self) -> &MerkleTree<H, S> {
+        self.tree
+    }
+
+    /// Number of leaves the tree will have once the batch is committed.
+    pub fn len(&self) -> usize {
+        self.tree.len() + self.appends.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Stages an append, returning the index the leaf will have.
+    pub fn append_leaf(&mut self, leaf: [u8; 32]) -> Result<usize, MerkleError> {
+        let index = self.len();
+        if index >= self.tree.capacity() {
+            return Err(MerkleError::TreeFull {
+                capacity: self.tree.capacity(),
+            });
+        }
+        self.appends.push(leaf);
+        Ok(index)
+    }
+
+    /// Stages an append of the leaf derived from `data`.
+    pub fn append_data(&mut self, data: &[u8]) -> Result<usize, MerkleError> {
+        self.append_leaf(self.tree.hasher.hash_leaf(data))
+    }
+
+    /// Stages replacing the leaf at `index`, which may be one appended
+    /// earlier in the transaction.
+    pub fn set_leaf(&mut self, index: usize, leaf: [u8; 32]) -> Result<(), MerkleError> {
+        match index.checked_sub(self.tree.len()) {
+            _ if index >= self.len() => {
+                return Err(MerkleError::IndexOutOfBounds {
+                    index,
+                    len: self.len(),
+                })
+            }
+            Some(staged) => self.appends[staged] = leaf,
+            None => {
+                self.updates.insert(index, leaf);
+            }
+        }
+        Ok(())
+    }
+
+    /// The root the tree will have once the batch is committed.
+    pub fn dry_run_root(&self) -> Result<[u8; 32], MerkleError> {
+        let tree = &*self.tree;
+        let first_append = tree.len();
+        let mut dirty = self.updates.clone();
+        dirty.extend((first_append..).zip(self.appends.iter().copied()));
+        if dirty.is_empty() {
+            return tree.try_root();
+        }
+        let top = height(self.len());
+        for level in 0..top {
+            let node = |index: usize| match dirty.get(&index) {
+                Some(&node) => Ok(node),
+                None => tree.node(level, index),
+            };
+            let mut parents = BTreeMap::new();
+            for parent in dirty.keys().map(|index| index >> 1) {
+                if let btree_map::Entry::Vacant(entry) = parents.entry(parent) {
+                    let (left, right) = (node(parent << 1)?, node((parent << 1) | 1)?);
+                    entry.insert(tree.hasher.hash_nodes(left, right));
+                }
+            }
+            dirty = parents;
+        }
+        Ok(pad_root(
+            &tree.hasher,
+            &tree.zeros,
+            dirty[&0],
+            top,
+            tree.depth,
+        ))
+    }
+
+    /// Applies the batch, updates first, and returns the new root.
+    ///
+    /// Staging already checked every index and the tree's capacity, so with
+    /// an in-memory store this can't fail; a [`MerkleError::Storage`] error
+    /// from another store may leave part of the batch applied.
+    pub fn commit(self) -> Result<[u8; 32], MerkleError> {
+        for (&index, &leaf) in &self.updates {
+            self.tree.set_leaf(index, leaf)?;
+        }
+        for &leaf in &self.appends {
+            self.tree.try_append_leaf(leaf)?;
+        }
+        self.tree.try_root()
+    }
+
+    /// Discards the batch, leaving the tree as it was.
+    pub fn rollback(self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dry_run_root_matches_commit() {
+        let mut tree = MerkleTree::new(4);
+        for i in 0u8..5 {
+            tree.append_data(&[i]);
+        }
+        let root = tree.root();
+        let mut expected = tree.clone();
+
+        let mut tx = tree.begin();
+        assert_eq!(tx.dry_run_root().unwrap(), root);
+        tx.set_leaf(1, [1; 32]).unwrap();
+        assert_eq!(tx.append_leaf([2; 32]).unwrap(), 5);
+        tx.append_data(b"six").unwrap();
+        tx.set_leaf(5, [3; 32]).unwrap();
+        assert!(tx.set_leaf(7, [4; 32]).is_err());
+        let dry_run = tx.dry_run_root().unwrap();
+        tx.rollback();
+        assert_eq!(tree.root(), root);
+
+        expected.set_leaf(1, [1; 32]).unwrap();
+        expected.append_leaf([3; 32]);
+        expected.append_data(b"six");
+        assert_eq!(dry_run, expected.root());
+
+        let mut tx = tree.begin();
+        tx.set_leaf(1, [1; 32]).unwrap();
+        tx.append_leaf([3; 32]).unwrap();
+        tx.append_data(b"six").unwrap();
+        assert_eq!(tx.commit().unwrap(), expected.root());
+        assert_eq!(tree.leaves(), expected.leaves());
+    }
+
+    #[test]
+    fn test_staged_appends_respect_capacity() {
+        let mut tree = MerkleTree::new(1);
+        let mut tx = tree.begin();
+        tx.append_leaf([1; 32]).unwrap();
+        tx.append_leaf([2; 32]).unwrap();
+        assert_eq!(
+            tx.append_leaf([3; 32]),
+            Err(MerkleError::TreeFull { capacity: 2 })
+        );
+        assert_eq!(
+            tx.commit().unwrap(),
+            MerkleTree::new(1).hasher().hash_nodes([1; 32], [2; 32])
+        );
+    }
+}
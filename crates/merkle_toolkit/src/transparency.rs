@@ -0,0 +1,455 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::ct::{
+    self, ConsistencyProofDataV2, InclusionProofDataV2, SignedTreeHeadDataV2, TreeHeadDataV2,
+};
+use crate::{
+    ConsistencyProof, DomainSeparated, MemoryStore, MerkleError, MerkleTree, NodeHasher, NodeStore,
+    Proof, Sha256Hasher, SignedTreeHead, TreeHeadSigner, TreeHeadVerifier, MAX_DEPTH,
+};
+
+/// An append-only log following RFC 6962 Certificate Transparency.
+///
+/// Entries are only ever appended, and every state of the log is committed
+/// to with a [`SignedTreeHead`] over the RFC 6962 Merkle Tree Hash.
+/// Inclusion and consistency proofs are the audit paths and consistency
+/// lists of RFC 6962 section 2.1, made against a particular tree size so
+/// they can be checked against any earlier head; consistency proofs link
+/// heads so clients can detect a log that rewrites its history. Heads and
+/// proofs can be checked by any CT client, or by [`LogClient`].
+///
+/// The store is an ordinary [`MerkleTree`], whose complete subtrees are the
+/// Merkle Tree Hash's, so proofs cost `O(log n)` reads. The `padded_*`
+/// methods expose the tree's own depth-padded root and proofs instead,
+/// which no CT client accepts.
+#[derive(Debug)]
+pub struct TransparencyLog<K, H = DomainSeparated, S = MemoryStore> {
+    tree: MerkleTree<H, S>,
+    signer: K,
+}
+
+impl<K: TreeHeadSigner> TransparencyLog<K> {
+    /// An in-memory log holding up to `2^MAX_DEPTH` entries.
+    pub fn new(signer: K) -> Self {
+        Self {
+            tree: MerkleTree::with_hasher(MAX_DEPTH, DomainSeparated(Sha256Hasher)),
+            signer,
+        }
+    }
+}
+
+impl<K: TreeHeadSigner, H: NodeHasher, S: NodeStore> TransparencyLog<K, H, S> {
+    /// A log over `tree`, which may already hold entries from an earlier run.
+    pub fn with_tree(tree: MerkleTree<H, S>, signer: K) -> Self {
+        Self { tree, signer }
+    }
+
+    pub fn tree(&self) -> &MerkleTree<H, S> {
+        &self.tree
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Appends an entry, hashed with [`NodeHasher::hash_leaf`], and returns
+    /// its index.
+    pub fn append(&mut self, entry: &[u8]) -> Result<usize, MerkleError> {
+        self.append_leaf(self.tree.hasher().hash_leaf(entry))
+    }
+
+    /// Appends an already hashed entry and returns its index.
+    pub fn append_leaf(&mut self, leaf: [u8; 32]) -> Result<usize, MerkleError> {
+        self.tree.try_append_leaf(leaf)?;
+        Ok(self.tree.len() - 1)
+    }
+
+    /// Signs the current state of the log, timestamped now.
+    pub fn tree_head(&self) -> Result<SignedTreeHead, MerkleError> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as u64);
+        self.tree_head_at(now)
+    }
+
+    /// Signs the current state of the log with an explicit timestamp, in
+    /// milliseconds since the Unix epoch.
+    pub fn tree_head_at(&self, timestamp: u64) -> Result<SignedTreeHead, MerkleError> {
+        self.tree.signed_tree_head(timestamp, &self.signer)
+    }
+
+    /// Audit path of entry `index` in the log as it was at `tree_size`
+    /// entries, leaf level first.
+    pub fn inclusion_proof(
+        &self,
+        index: usize,
+        tree_size: usize,
+    ) -> Result<Vec<[u8; 32]>, MerkleError> {
+        self.tree.ct_inclusion_path(index, tree_size)
+    }
+
+    /// Consistency proof that the log at `new_size` entries extends the
+    /// log at `old_size`.
+    pub fn consistency_proof(
+        &self,
+        old_size: usize,
+        new_size: usize,
+    ) -> Result<Vec<[u8; 32]>, MerkleError> {
+        self.tree.ct_consistency_path(old_size, new_size)
+    }
+
+    /// Signs the tree's depth-padded [`MerkleTree::root`] instead of the
+    /// Merkle Tree Hash. Not a CT tree head.
+    pub fn padded_tree_head_at(&self, timestamp: u64) -> Result<SignedTreeHead, MerkleError> {
+        SignedTreeHead::sign(
+            self.len() as u64,
+            self.tree.try_root()?,
+            timestamp,
+            &self.signer,
+        )
+    }
+
+    /// Depth-padded proof of entry `index` at `tree_size` entries, checked
+    /// against [`MerkleTree::root_at_size`]. Not a CT audit path.
+    pub fn padded_inclusion_proof(
+        &self,
+        index: usize,
+        tree_size: usize,
+    ) -> Result<Proof, MerkleError> {
+        self.tree.get_proof_at_size(index, tree_size)
+    }
+
+    /// Depth-padded consistency proof, see [`ConsistencyProof`]. Not a CT
+    /// consistency proof.
+    pub fn padded_consistency_proof(
+        &self,
+        old_size: usize,
+        new_size: usize,
+    ) -> Result<ConsistencyProof, MerkleError> {
+        self.tree.consistency_proof(old_size, new_size)
+    }
+
+    /// Signs the RFC 9162 tree head of the current state of the log, for a
+    /// log identified to CT clients by `log_id`. See [`crate::ct`].
+    pub fn ct_tree_head_at(
+        &self,
+        log_id: &[u8],
+        timestamp: u64,
+    ) -> Result<SignedTreeHeadDataV2, MerkleError> {
+        let tree_head = TreeHeadDataV2 {
+            timestamp,
+            tree_size: self.len() as u64,
+            root_hash: self.tree.ct_root(self.len())?,
+            extensions: Vec::new(),
+        };
+        SignedTreeHeadDataV2::sign(log_id.to_vec(), tree_head, &self.signer)
+    }
+
+    /// RFC 9162 proof that entry `index` is in the log at `tree_size`
+    /// entries.
+    pub fn ct_inclusion_proof(
+        &self,
+        log_id: &[u8],
+        index: usize,
+        tree_size: usize,
+    ) -> Result<InclusionProofDataV2, MerkleError> {
+        Ok(InclusionProofDataV2 {
+            log_id: log_id.to_vec(),
+            tree_size: tree_size as u64,
+            leaf_index: index as u64,
+            inclusion_path: self.tree.ct_inclusion_path(index, tree_size)?,
+        })
+    }
+
+    /// RFC 9162 proof that the log at `new_size` entries extends the log at
+    /// `old_size`.
+    pub fn ct_consistency_proof(
+        &self,
+        log_id: &[u8],
+        old_size: usize,
+        new_size: usize,
+    ) -> Result<ConsistencyProofDataV2, MerkleError> {
+        Ok(ConsistencyProofDataV2 {
+            log_id: log_id.to_vec(),
+            tree_size_1: old_size as u64,
+            tree_size_2: new_size as u64,
+            consistency_path: self.tree.ct_consistency_path(old_size, new_size)?,
+        })
+    }
+}
+
+/// A client's view of an RFC 6962 log such as [`TransparencyLog`]: the
+/// latest tree head it has verified, which every newer head must
+/// consistently extend.
+#[derive(Debug, Clone)]
+pub struct LogClient<V, H = DomainSeparated> {
+    verifier: V,
+    hasher: H,
+    head: Option<SignedTreeHead>,
+}
+
+impl<V: TreeHeadVerifier> LogClient<V> {
+    pub fn new(verifier: V) -> Self {
+        Self::with_hasher(verifier, DomainSeparated(Sha256Hasher))
+    }
+}
+
+impl<V: TreeHeadVerifier, H: NodeHasher> LogClient<V, H> {
+    pub fn with_hasher(verifier: V, hasher: H) -> Self {
+        Self {
+            verifier,
+            hasher,
+            head: None,
+        }
+    }
+
+    /// The latest verified tree head.
+    pub fn head(&self) -> Option<&SignedTreeHead> {
+        self.head.as_ref()
+    }
+
+    /// Accepts `head` if it is signed by the log and the consistency proof
+    /// `proof` shows it extends the current head. The first head is trusted
+    /// on its signature alone.
+    ///
+    /// A head of the same size with a different root, a smaller head, or a
+    /// failed consistency proof is rejected and leaves the client unchanged;
+    /// the conflicting heads are evidence the log misbehaved.
+    pub fn update(&mut self, head: SignedTreeHead, proof: &[[u8; 32]]) -> Result<(), MerkleError> {
+        head.verify(&self.verifier)?;
+        if let Some(current) = &self.head {
+            if head.size < current.size {
+                return Err(MerkleError::InvalidConsistencyRange {
+                    old_size: current.size as usize,
+                    new_size: head.size as usize,
+                });
+            }
+            if !ct::verify_consistency(
+                &self.hasher,
+                current.size,
+                head.size,
+                proof,
+                current.root,
+                head.root,
+            ) {
+                return Err(MerkleError::InvalidProof);
+            }
+        }
+        self.head = Some(head);
+        Ok(())
+    }
+
+    /// Checks that `leaf` is entry `index` of the log at the current head,
+    /// given its audit path.
+    pub fn verify_inclusion(
+        &self,
+        leaf: [u8; 32],
+        index: u64,
+        path: &[[u8; 32]],
+    ) -> Result<(), MerkleError> {
+        let head = self.head.as_ref().ok_or(MerkleError::InvalidProof)?;
+        match ct::verify_inclusion(&self.hasher, leaf, index, head.size, path, head.root) {
+            true => Ok(()),
+            false => Err(MerkleError::InvalidProof),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hex;
+
+    /// An unkeyed stand-in for a real signature scheme.
+    #[derive(Debug, Clone)]
+    struct Checksum;
+
+    impl TreeHeadSigner for Checksum {
+        fn sign(&self, message: &[u8]) -> Result<Vec<u8>, MerkleError> {
+            Ok(Sha256Hasher.hash(&[message]).to_vec())
+        }
+    }
+
+    impl TreeHeadVerifier for Checksum {
+        fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), MerkleError> {
+            match Sha256Hasher.hash(&[message]) == signature {
+                true => Ok(()),
+                false => Err(MerkleError::InvalidSignature),
+            }
+        }
+    }
+
+    fn hashes(hex: &[&str]) -> Vec<[u8; 32]> {
+        hex.iter()
+            .map(|node| hex::decode_hash(node).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_rfc6962_vectors() {
+        // Leaves, roots and proofs from the certificate-transparency suite.
+        let inputs: [&[u8]; 8] = [
+            b"",
+            b"\x00",
+            b"\x10",
+            b"\x20\x21",
+            b"\x30\x31",
+            b"\x40\x41\x42\x43",
+            b"\x50\x51\x52\x53\x54\x55\x56\x57",
+            b"\x60\x61\x62\x63\x64\x65\x66\x67\x68\x69\x6a\x6b\x6c\x6d\x6e\x6f",
+        ];
+        let mut log = TransparencyLog::new(Checksum);
+        let mut client = LogClient::new(Checksum);
+        for input in &inputs[..6] {
+            log.append(input).unwrap();
+        }
+        client.update(log.tree_head_at(1).unwrap(), &[]).unwrap();
+        for input in &inputs[6..] {
+            log.append(input).unwrap();
+        }
+        let head = log.tree_head_at(2).unwrap();
+        assert_eq!(
+            hex::encode(&head.root),
+            "5dc9da79a70659a9ad559cb701ded9a2ab9d823aad2f4960cfe370eff4604328"
+        );
+
+        let consistency = hashes(&[
+            "0ebc5d3437fbe2db158b9f126a1d118e308181031d0a949f8dededebc558ef6a",
+            "ca854ea128ed050b41b35ffc1b87b8eb2bde461e9e3b5596ece6b9d5975a0ae0",
+            "d37ee418976dd95753c1c73862b9398fa2a2cf9b4ff0fdfe8b30cd95209614b7",
+        ]);
+        assert_eq!(log.consistency_proof(6, 8).unwrap(), consistency);
+        client.update(head, &consistency).unwrap();
+        assert_eq!(
+            log.consistency_proof(2, 5).unwrap(),
+            hashes(&[
+                "5f083f0a1a33ca076a95279832580db3e0ef4584bdff1f54c8a360f50de3031e",
+                "bc1a0643b12e4d2d7c77918f44e0f4f79a838b6cf9ec5b5c283e1f4d88599e6b",
+            ])
+        );
+
+        let audit_path = hashes(&[
+            "bc1a0643b12e4d2d7c77918f44e0f4f79a838b6cf9ec5b5c283e1f4d88599e6b",
+            "ca854ea128ed050b41b35ffc1b87b8eb2bde461e9e3b5596ece6b9d5975a0ae0",
+            "d37ee418976dd95753c1c73862b9398fa2a2cf9b4ff0fdfe8b30cd95209614b7",
+        ]);
+        assert_eq!(log.inclusion_proof(5, 8).unwrap(), audit_path);
+        let leaf = log.tree().leaves()[5];
+        client.verify_inclusion(leaf, 5, &audit_path).unwrap();
+        assert!(client.verify_inclusion(leaf, 4, &audit_path).is_err());
+        assert_eq!(
+            log.inclusion_proof(2, 3).unwrap(),
+            hashes(&["fac54203e7cc696cf0dfcb42c92a1d9dbaf70ad9e621f4bd8d98662f00e3c125"])
+        );
+    }
+
+    #[test]
+    fn test_log_and_client() {
+        let mut log = TransparencyLog::new(Checksum);
+        let mut client = LogClient::new(Checksum);
+        for i in 0u8..3 {
+            log.append(&[i]).unwrap();
+        }
+        client.update(log.tree_head_at(1).unwrap(), &[]).unwrap();
+
+        for i in 3u8..11 {
+            assert_eq!(log.append(&[i]).unwrap(), i as usize);
+            let head = log.tree_head_at(i as u64).unwrap();
+            let old_size = client.head().unwrap().size as usize;
+            let proof = log.consistency_proof(old_size, log.len()).unwrap();
+            client.update(head, &proof).unwrap();
+        }
+
+        // Entries logged before the head are provable at its size.
+        let size = log.len();
+        log.append(b"later").unwrap();
+        for index in 0..size {
+            let leaf = log.tree().leaves()[index];
+            let path = log.inclusion_proof(index, size).unwrap();
+            client.verify_inclusion(leaf, index as u64, &path).unwrap();
+        }
+        let stale = log.inclusion_proof(0, size + 1).unwrap();
+        assert!(client
+            .verify_inclusion(log.tree().leaves()[0], 0, &stale)
+            .is_err());
+
+        // The padded mode commits to the tree's own root and proofs.
+        let padded = log.padded_tree_head_at(3).unwrap();
+        assert_eq!(padded.root, log.tree().root());
+        assert_ne!(padded.root, log.tree_head_at(3).unwrap().root);
+        let proof = log.padded_inclusion_proof(4, log.len()).unwrap();
+        assert!(proof.verify(log.tree().leaves()[4], padded.root));
+        let proof = log.padded_consistency_proof(size, log.len()).unwrap();
+        let old_root = log.tree().root_at_size(size).unwrap();
+        assert!(MerkleTree::verify_consistency(
+            &proof,
+            MAX_DEPTH,
+            size,
+            log.len(),
+            old_root,
+            padded.root
+        ));
+    }
+
+    #[test]
+    fn test_client_rejects_forks() {
+        let mut log = TransparencyLog::new(Checksum);
+        let mut client = LogClient::new(Checksum);
+        for i in 0u8..4 {
+            log.append(&[i]).unwrap();
+        }
+        client.update(log.tree_head_at(0).unwrap(), &[]).unwrap();
+
+        // A log that rewrote entry 1 can't prove it extends the trusted head.
+        let mut forked = TransparencyLog::new(Checksum);
+        for i in [0u8, 9, 2, 3, 4] {
+            forked.append(&[i]).unwrap();
+        }
+        let proof = forked.consistency_proof(4, 5).unwrap();
+        assert_eq!(
+            client.update(forked.tree_head_at(1).unwrap(), &proof),
+            Err(MerkleError::InvalidProof)
+        );
+
+        let mut forged = log.tree_head_at(1).unwrap();
+        forged.root = [7; 32];
+        assert_eq!(
+            client.update(forged, &log.consistency_proof(4, 4).unwrap()),
+            Err(MerkleError::InvalidSignature)
+        );
+        assert_eq!(client.head().unwrap().root, log.tree().ct_root(4).unwrap());
+    }
+
+    #[test]
+    fn test_ct_v2_items() {
+        let mut log = TransparencyLog::new(Checksum);
+        let log_id = [0x2b, 0x06, 0x01, 0x04];
+        for i in 0u8..5 {
+            log.append(&[i]).unwrap();
+        }
+        let old = log.ct_tree_head_at(&log_id, 1).unwrap();
+        for i in 5u8..13 {
+            log.append(&[i]).unwrap();
+        }
+        let new = log.ct_tree_head_at(&log_id, 2).unwrap();
+        new.verify(&Checksum).unwrap();
+        assert_eq!(new.tree_head.tree_size, 13);
+        assert_eq!(new.tree_head.root_hash, log.tree_head_at(2).unwrap().root);
+
+        // A monitor checks the new head against the old one, and entries
+        // against the new head.
+        let proof = log.ct_consistency_proof(&log_id, 5, 13).unwrap();
+        assert!(proof.verify(old.tree_head.root_hash, new.tree_head.root_hash));
+        assert!(!proof.verify(new.tree_head.root_hash, new.tree_head.root_hash));
+        let proof = log.ct_inclusion_proof(&log_id, 7, 13).unwrap();
+        assert!(proof.verify(log.tree().leaves()[7], new.tree_head.root_hash));
+
+        let mut forged = new;
+        forged.tree_head.tree_size = 14;
+        assert_eq!(forged.verify(&Checksum), Err(MerkleError::InvalidSignature));
+    }
+}
@@ -0,0 +1,766 @@
+use std::collections::HashMap;
+
+use crate::checkpoint::Checkpoints;
+#[cfg(feature = "serde")]
+use crate::serde_hex;
+use crate::{
+    DomainSeparated, HmacSha256Hasher, MemoryStore, MerkleError, NodeHasher, NodeStore, Proof,
+    Sha256Hasher,
+};
+
+/// Largest depth accepted by [`MerkleTree::new`], so that leaf indices and
+/// generalized indices fit in a `u64`.
+pub const MAX_DEPTH: usize = 63;
+
+/// A binary merkle tree over 32 byte leaves.
+///
+/// Trees built with [`MerkleTree::new`] hash leaves and internal nodes under
+/// the RFC 6962 domain separation prefixes (see [`DomainSeparated`]). Use
+/// [`MerkleTree::legacy`] for the original undifferentiated SHA-256 scheme.
+///
+/// The tree has a fixed `depth` and holds at most `2^depth` leaves. Its root
+/// and proofs are those of the full depth, with every missing subtree taken
+/// from [`zero_hashes_with`], so they match fixed-depth incremental trees
+/// such as Tornado Cash's or Semaphore's whatever the number of leaves.
+///
+/// Every level up to the smallest subtree holding all the leaves is cached
+/// and kept up to date as leaves are appended, so an append only rehashes
+/// the path from the new leaf to the top of that subtree. Levels live in
+/// memory unless another [`NodeStore`] is given to
+/// [`MerkleTree::with_store`].
+///
+/// The depth costs nothing until leaves reach it: the cache holds about
+/// `2n` nodes (64 bytes per leaf) for `n` leaves whatever the depth, plus
+/// the `depth + 1` zero hashes. [`MerkleTree::root`] and proofs hash through
+/// the empty levels above the cache, `depth - ceil(log2(n))` extra hashes,
+/// so a tree of depth 40 or more is as cheap to hold as a shallow one.
+#[derive(Debug, Clone)]
+pub struct MerkleTree<H = DomainSeparated, S = MemoryStore> {
+    pub(crate) depth: usize,
+    /// Level 0 holds the leaves, the last level holds the root.
+    pub(crate) levels: S,
+    pub(crate) hasher: H,
+    /// `zeros[level]` is the root of an empty subtree of height `level`.
+    pub(crate) zeros: Vec<[u8; 32]>,
+    /// First index of each leaf, kept once enabled with
+    /// [`MerkleTree::with_leaf_index`].
+    pub(crate) leaf_index: Option<HashMap<[u8; 32], usize>>,
+    /// Saved by [`MerkleTree::checkpoint`].
+    pub(crate) checkpoints: Checkpoints,
+}
+
+/// Summary of a tree that is enough to check proofs against it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+pub struct TreeMetadata {
+    pub depth: usize,
+    pub size: usize,
+    #[cfg_attr(feature = "serde", serde(with = "serde_hex"))]
+    pub root: [u8; 32],
+}
+
+impl MerkleTree {
+    pub fn new(depth: usize) -> Self {
+        Self::with_hasher(depth, DomainSeparated(Sha256Hasher))
+    }
+
+    pub fn try_new(depth: usize) -> Result<Self, MerkleError> {
+        Self::try_with_hasher(depth, DomainSeparated(Sha256Hasher))
+    }
+
+    pub fn verify_proof(leaf: [u8; 32], proof: &[[u8; 32]], index: usize, root: [u8; 32]) -> bool {
+        Self::verify_proof_with(&DomainSeparated(Sha256Hasher), leaf, proof, index, root)
+    }
+}
+
+impl MerkleTree<Sha256Hasher> {
+    /// A tree using plain SHA-256 for both leaves and nodes, compatible with
+    /// roots produced before domain separation became the default.
+    pub fn legacy(depth: usize) -> Self {
+        Self::with_hasher(depth, Sha256Hasher)
+    }
+}
+
+impl MerkleTree<DomainSeparated<HmacSha256Hasher>> {
+    /// A tree hashing leaves and nodes with domain-separated HMAC-SHA256
+    /// under `key`. Proofs verify only with a hasher built from the same key,
+    /// through [`Proof::verify_with`].
+    pub fn keyed(depth: usize, key: &[u8]) -> Self {
+        Self::with_hasher(depth, DomainSeparated(HmacSha256Hasher::new(key)))
+    }
+}
+
+impl<H: NodeHasher> MerkleTree<H> {
+    pub fn with_hasher(depth: usize, hasher: H) -> Self {
+        Self::try_with_hasher(depth, hasher).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    pub fn try_with_hasher(depth: usize, hasher: H) -> Result<Self, MerkleError> {
+        if depth > MAX_DEPTH {
+            return Err(MerkleError::DepthTooLarge {
+                depth,
+                max: MAX_DEPTH,
+            });
+        }
+        Ok(Self {
+            depth,
+            levels: vec![Vec::new()],
+            zeros: zero_hashes_with(&hasher, depth),
+            leaf_index: None,
+            checkpoints: Checkpoints::default(),
+            hasher,
+        })
+    }
+
+    /// Builds a tree over `leaves` in one pass, hashing each level with
+    /// [`NodeHasher::hash_pairs`].
+    pub fn from_leaves(
+        depth: usize,
+        hasher: H,
+        leaves: Vec<[u8; 32]>,
+    ) -> Result<Self, MerkleError> {
+        span!(DEBUG, "merkle.build", depth, leaves = leaves.len());
+        let mut tree = Self::try_with_hasher(depth, hasher)?;
+        if leaves.len() > tree.capacity() {
+            return Err(MerkleError::TreeFull {
+                capacity: tree.capacity(),
+            });
+        }
+        crate::telemetry::leaves_appended(leaves.len());
+        tree.levels[0] = leaves;
+        while tree.levels.last().unwrap().len() > 1 {
+            let zero = tree.zeros[tree.levels.len() - 1];
+            let mut children = tree.levels.last().unwrap().clone();
+            if children.len() % 2 == 1 {
+                children.push(zero);
+            }
+            let mut parents = vec![[0u8; 32]; children.len() / 2];
+            tree.hasher.hash_pairs(&children, &mut parents);
+            tree.levels.push(parents);
+        }
+        Ok(tree)
+    }
+
+    pub fn leaves(&self) -> &[[u8; 32]] {
+        &self.levels[0]
+    }
+
+    /// Proofs for every leaf, in leaf order, read straight from the cached
+    /// levels in `O(n log n)` total.
+    pub fn all_proofs(&self) -> Vec<Proof> {
+        span!(DEBUG, "merkle.all_proofs", leaves = self.len());
+        crate::telemetry::proofs_generated(self.len());
+        let top = self.levels.len() - 1;
+        let size = self.len();
+        (0..size)
+            .map(|index| {
+                let mut siblings = Vec::with_capacity(self.depth);
+                siblings.extend((0..top).map(|level| {
+                    let sibling = (index >> level) ^ 1;
+                    self.levels[level]
+                        .get(sibling)
+                        .copied()
+                        .unwrap_or(self.zeros[level])
+                }));
+                siblings.extend_from_slice(&self.zeros[top..self.depth]);
+                Proof {
+                    index,
+                    size,
+                    siblings,
+                }
+            })
+            .collect()
+    }
+
+    pub fn verify_proof_with(
+        hasher: &H,
+        leaf: [u8; 32],
+        proof: &[[u8; 32]],
+        index: usize,
+        root: [u8; 32],
+    ) -> bool {
+        crate::verify_proof(hasher, leaf, proof, index, root)
+    }
+}
+
+impl<H: NodeHasher, S: NodeStore> MerkleTree<H, S> {
+    /// Creates a tree over `store`, which may already hold the nodes of a
+    /// tree built with the same depth and hasher.
+    pub fn with_store(depth: usize, hasher: H, store: S) -> Result<Self, MerkleError> {
+        if depth > MAX_DEPTH {
+            return Err(MerkleError::DepthTooLarge {
+                depth,
+                max: MAX_DEPTH,
+            });
+        }
+        Ok(Self {
+            depth,
+            levels: store,
+            zeros: zero_hashes_with(&hasher, depth),
+            leaf_index: None,
+            checkpoints: Checkpoints::default(),
+            hasher,
+        })
+    }
+
+    pub fn hasher(&self) -> &H {
+        &self.hasher
+    }
+
+    pub fn store(&self) -> &S {
+        &self.levels
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn len(&self) -> usize {
+        self.levels.level_len(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Maximum number of leaves the tree can hold, `2^depth`, or
+    /// `usize::MAX` when that does not fit.
+    pub fn capacity(&self) -> usize {
+        capacity(self.depth)
+    }
+
+    pub fn metadata(&self) -> TreeMetadata {
+        TreeMetadata {
+            depth: self.depth,
+            size: self.len(),
+            root: self.root(),
+        }
+    }
+
+    /// Panics if the tree is full; see [`MerkleTree::try_append_leaf`].
+    pub fn append_leaf(&mut self, leaf: [u8; 32]) {
+        self.try_append_leaf(leaf).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Appends the leaf derived from `data` with [`NodeHasher::hash_leaf`].
+    pub fn append_data(&mut self, data: &[u8]) {
+        self.append_leaf(self.hasher.hash_leaf(data));
+    }
+
+    pub fn try_append_leaf(&mut self, leaf: [u8; 32]) -> Result<(), MerkleError> {
+        if self.len() >= self.capacity() {
+            return Err(MerkleError::TreeFull {
+                capacity: self.capacity(),
+            });
+        }
+        self.push_leaf(leaf)
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        self.try_root().unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// The root at the tree's depth; an empty tree has the empty-subtree
+    /// hash `zero_hashes_with(hasher, depth)[depth]`.
+    pub fn try_root(&self) -> Result<[u8; 32], MerkleError> {
+        self.node(self.depth, 0)
+    }
+
+    /// The node at `(level, index)` of the full-depth tree, an empty-subtree
+    /// hash when no leaves are below it. Nodes above the cached levels are
+    /// hashed on demand.
+    pub(crate) fn node(&self, level: usize, index: usize) -> Result<[u8; 32], MerkleError> {
+        let top = self.levels.levels() - 1;
+        if level <= top {
+            return Ok(self.levels.get(level, index)?.unwrap_or(self.zeros[level]));
+        }
+        if index > 0 || self.is_empty() {
+            return Ok(self.zeros[level]);
+        }
+        let node = self.levels.get(top, 0)?.unwrap_or(self.zeros[top]);
+        Ok(pad_root(&self.hasher, &self.zeros, node, top, level))
+    }
+
+    pub fn get_proof(&self, index: usize) -> Proof {
+        self.try_get_proof(index).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    pub fn try_get_proof(&self, index: usize) -> Result<Proof, MerkleError> {
+        span!(TRACE, "merkle.prove", index, leaves = self.len());
+        let timer = crate::telemetry::ProofTimer::start();
+        self.check_index(index)?;
+        let top = self.levels.levels() - 1;
+        let mut siblings = (0..top)
+            .map(|level| self.node(level, (index >> level) ^ 1))
+            .collect::<Result<Vec<_>, _>>()?;
+        siblings.extend_from_slice(&self.zeros[top..self.depth]);
+        timer.finish();
+        Ok(Proof {
+            index,
+            size: self.len(),
+            siblings,
+        })
+    }
+
+    /// Equivalent to [`MerkleTree::get_proof`]; both read from the level cache.
+    pub fn get_proof_optimized(&self, index: usize) -> Proof {
+        self.get_proof(index)
+    }
+
+    pub(crate) fn check_index(&self, index: usize) -> Result<(), MerkleError> {
+        if index >= self.len() {
+            return Err(MerkleError::IndexOutOfBounds {
+                index,
+                len: self.len(),
+            });
+        }
+        Ok(())
+    }
+
+    fn push_leaf(&mut self, leaf: [u8; 32]) -> Result<(), MerkleError> {
+        let index = self.len();
+        self.levels.put(0, index, leaf)?;
+        crate::telemetry::leaves_appended(1);
+        self.index_leaf(index, leaf);
+        self.update_path(index)
+    }
+
+    /// Rehashes every ancestor of the leaf at `index`.
+    pub(crate) fn update_path(&mut self, mut index: usize) -> Result<(), MerkleError> {
+        let mut level = 0;
+        while self.levels.level_len(level) > 1 {
+            let left = self
+                .levels
+                .get(level, index & !1)?
+                .unwrap_or(self.zeros[level]);
+            let right = self
+                .levels
+                .get(level, index | 1)?
+                .unwrap_or(self.zeros[level]);
+            let parent = self.hasher.hash_nodes(left, right);
+
+            index /= 2;
+            level += 1;
+            self.levels.put(level, index, parent)?;
+        }
+        Ok(())
+    }
+}
+
+/// Trees serialize as their depth and leaves; the level cache is rebuilt on
+/// deserialization.
+#[cfg(feature = "serde")]
+impl<H> serde::Serialize for MerkleTree<H> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        struct Leaves<'a>(&'a [[u8; 32]]);
+
+        impl serde::Serialize for Leaves<'_> {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serde_hex::vec::serialize(self.0, serializer)
+            }
+        }
+
+        let mut state = serializer.serialize_struct("MerkleTree", 2)?;
+        state.serialize_field("depth", &self.depth)?;
+        state.serialize_field("leaves", &Leaves(&self.levels[0]))?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, H: NodeHasher + Default> serde::Deserialize<'de> for MerkleTree<H> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        #[derive(serde::Deserialize)]
+        struct Repr {
+            depth: usize,
+            #[serde(with = "serde_hex::vec")]
+            leaves: Vec<[u8; 32]>,
+        }
+
+        let repr = Repr::deserialize(deserializer)?;
+        let mut tree = Self::try_with_hasher(repr.depth, H::default()).map_err(D::Error::custom)?;
+        for leaf in repr.leaves {
+            tree.try_append_leaf(leaf).map_err(D::Error::custom)?;
+        }
+        Ok(tree)
+    }
+}
+
+/// `2^depth`, saturating at `usize::MAX`.
+pub(crate) fn capacity(depth: usize) -> usize {
+    u32::try_from(depth)
+        .ok()
+        .and_then(|depth| 1usize.checked_shl(depth))
+        .unwrap_or(usize::MAX)
+}
+
+/// Number of levels above the leaves in a tree of `size` leaves.
+pub(crate) fn height(size: usize) -> usize {
+    size.next_power_of_two().trailing_zeros() as usize
+}
+
+/// Hashes `node`, the root of a subtree `height` levels tall, up to `depth`
+/// with the empty subtree from `zeros` as its right sibling at each level.
+pub(crate) fn pad_root<H: NodeHasher>(
+    hasher: &H,
+    zeros: &[[u8; 32]],
+    mut node: [u8; 32],
+    height: usize,
+    depth: usize,
+) -> [u8; 32] {
+    for zero in &zeros[height..depth] {
+        node = hasher.hash_nodes(node, *zero);
+    }
+    node
+}
+
+/// Roots of empty subtrees of height `0..=depth` in a default
+/// [`MerkleTree`]; see [`zero_hashes_with`].
+pub fn zero_hashes(depth: usize) -> Vec<[u8; 32]> {
+    zero_hashes_with(&DomainSeparated(Sha256Hasher), depth)
+}
+
+/// Roots of empty subtrees of height `0..=depth`, starting from an all-zero
+/// leaf: `zeros[0]` is `[0; 32]` and `zeros[level + 1]` hashes two copies of
+/// `zeros[level]`.
+pub fn zero_hashes_with<H: NodeHasher>(hasher: &H, depth: usize) -> Vec<[u8; 32]> {
+    let mut zeros = Vec::with_capacity(depth + 1);
+    zeros.push([0u8; 32]);
+    for level in 0..depth {
+        zeros.push(hasher.hash_nodes(zeros[level], zeros[level]));
+    }
+    zeros
+}
+
+/// Hashes two nodes with plain SHA-256, as done by [`MerkleTree::legacy`].
+pub fn hash_nodes(left: [u8; 32], right: [u8; 32]) -> [u8; 32] {
+    Sha256Hasher.hash_nodes(left, right)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hex, Keccak256Hasher, SortedPair};
+    use sha2::{Digest, Sha256};
+
+    #[test]
+    fn test_merkle_root_and_proof() {
+        let mut tree = MerkleTree::new(3);
+
+        let leaf1 = Sha256::digest(b"leaf1").into();
+        let leaf2 = Sha256::digest(b"leaf2").into();
+        let leaf3 = Sha256::digest(b"leaf3").into();
+
+        tree.append_leaf(leaf1);
+        tree.append_leaf(leaf2);
+        tree.append_leaf(leaf3);
+
+        let root = tree.root();
+
+        for i in 0..tree.leaves().len() {
+            let leaf = tree.leaves()[i];
+            let proof_1 = tree.get_proof(i);
+            assert!(MerkleTree::verify_proof(leaf, &proof_1, i, root));
+            let proof_2 = tree.get_proof_optimized(i);
+            assert!(MerkleTree::verify_proof(leaf, &proof_2, i, root));
+            assert_eq!(proof_1, proof_2);
+        }
+    }
+
+    #[test]
+    fn test_invalid_proof_fails() {
+        let mut tree = MerkleTree::new(3);
+
+        tree.append_leaf(Sha256::digest(b"a").into());
+        tree.append_leaf(Sha256::digest(b"b").into());
+
+        let bad_leaf = Sha256::digest(b"c").into();
+        let proof = tree.get_proof(0);
+        let root = tree.root();
+
+        assert!(!MerkleTree::verify_proof(bad_leaf, &proof, 0, root));
+    }
+
+    #[test]
+    fn test_keccak_tree_roundtrip() {
+        let mut tree = MerkleTree::with_hasher(2, Keccak256Hasher);
+        let mut sha_tree = MerkleTree::new(2);
+        for i in 0u8..3 {
+            tree.append_leaf([i; 32]);
+            sha_tree.append_leaf([i; 32]);
+        }
+
+        let root = tree.root();
+        assert_ne!(root, sha_tree.root());
+        for i in 0..tree.leaves().len() {
+            let proof = tree.get_proof(i);
+            assert!(MerkleTree::verify_proof_with(
+                &Keccak256Hasher,
+                tree.leaves()[i],
+                &proof,
+                i,
+                root
+            ));
+            assert!(!MerkleTree::verify_proof(tree.leaves()[i], &proof, i, root));
+        }
+    }
+
+    #[test]
+    fn test_fallible_api() {
+        assert_eq!(
+            MerkleTree::try_new(MAX_DEPTH + 1).unwrap_err(),
+            MerkleError::DepthTooLarge {
+                depth: MAX_DEPTH + 1,
+                max: MAX_DEPTH
+            }
+        );
+
+        let mut tree = MerkleTree::try_new(1).unwrap();
+        assert_eq!(
+            tree.try_get_proof(0).unwrap_err(),
+            MerkleError::IndexOutOfBounds { index: 0, len: 0 }
+        );
+        tree.try_append_leaf([1u8; 32]).unwrap();
+        tree.try_append_leaf([2u8; 32]).unwrap();
+        assert_eq!(
+            tree.try_append_leaf([3u8; 32]).unwrap_err(),
+            MerkleError::TreeFull { capacity: 2 }
+        );
+        assert_eq!(tree.try_get_proof(1).unwrap(), tree.get_proof(1));
+    }
+
+    /// Recomputes the root the naive way, padding the leaves with zero
+    /// leaves up to `2^depth` and hashing level by level.
+    fn rebuild_root(leaves: &[[u8; 32]], depth: usize) -> [u8; 32] {
+        let mut level = leaves.to_vec();
+        level.resize(1 << depth, [0u8; 32]);
+        while level.len() > 1 {
+            level = level
+                .chunks(2)
+                .map(|pair| hash_nodes(pair[0], pair[1]))
+                .collect();
+        }
+        level[0]
+    }
+
+    #[test]
+    fn test_cached_root_matches_rebuild() {
+        let mut tree = MerkleTree::legacy(4);
+        assert_eq!(tree.root(), rebuild_root(&[], 4));
+        for i in 0u8..16 {
+            tree.append_leaf(Sha256::digest([i]).into());
+            assert_eq!(tree.root(), rebuild_root(tree.leaves(), 4));
+        }
+    }
+
+    #[test]
+    fn test_root_and_proofs_span_depth() {
+        let mut tree = MerkleTree::legacy(10);
+        for i in 0u8..3 {
+            tree.append_leaf([i; 32]);
+        }
+        assert_eq!(tree.root(), rebuild_root(tree.leaves(), 10));
+        let proof = tree.get_proof(2);
+        assert_eq!(proof.siblings.len(), 10);
+        assert!(proof.verify_with(&Sha256Hasher, [2; 32], tree.root()));
+        assert_eq!(tree.root_at_size(3).unwrap(), tree.root());
+        assert_eq!(tree.get_proof_at_size(2, 3).unwrap(), proof);
+    }
+
+    #[test]
+    fn test_large_depths() {
+        for depth in [40, MAX_DEPTH] {
+            let mut tree = MerkleTree::legacy(depth);
+            let mut frontier = crate::IncrementalMerkleTree::new(depth);
+            for i in 0u8..5 {
+                tree.append_leaf([i; 32]);
+                frontier.append([i; 32]).unwrap();
+            }
+            assert_eq!(tree.root(), frontier.root());
+            assert_eq!(tree.capacity(), 1 << depth);
+            let proof = tree.get_proof(4);
+            assert_eq!(proof.siblings.len(), depth);
+            assert!(proof.verify_with(&Sha256Hasher, [4; 32], tree.root()));
+            assert_eq!(tree.root_at_size(3).unwrap(), {
+                let mut old = MerkleTree::legacy(depth);
+                (0u8..3).for_each(|i| old.append_leaf([i; 32]));
+                old.root()
+            });
+            let consistency = tree.consistency_proof(3, 5).unwrap();
+            assert!(MerkleTree::verify_consistency_with(
+                &Sha256Hasher,
+                &consistency,
+                depth,
+                3,
+                5,
+                tree.root_at_size(3).unwrap(),
+                tree.root()
+            ));
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_tracing_spans() {
+        use std::sync::{Arc, Mutex};
+        use tracing::{span, Event, Metadata, Subscriber};
+
+        /// Records the name of every span created.
+        #[derive(Clone, Default)]
+        struct Names(Arc<Mutex<Vec<&'static str>>>);
+
+        impl Subscriber for Names {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+
+            fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+                let mut names = self.0.lock().unwrap();
+                names.push(span.metadata().name());
+                span::Id::from_u64(names.len() as u64)
+            }
+
+            fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+            fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+            fn event(&self, _: &Event<'_>) {}
+            fn enter(&self, _: &span::Id) {}
+            fn exit(&self, _: &span::Id) {}
+        }
+
+        let names = Names::default();
+        tracing::subscriber::with_default(names.clone(), || {
+            let leaves = vec![[1; 32], [2; 32]];
+            let tree = MerkleTree::from_leaves(2, DomainSeparated(Sha256Hasher), leaves).unwrap();
+            assert!(tree.get_proof(1).verify([2; 32], tree.root()));
+        });
+        assert_eq!(
+            *names.0.lock().unwrap(),
+            ["merkle.build", "merkle.prove", "merkle.verify"]
+        );
+    }
+
+    #[test]
+    fn test_keyed_tree() {
+        let mut tree = MerkleTree::keyed(3, b"secret");
+        let mut other = MerkleTree::keyed(3, b"other");
+        for data in [b"a", b"b", b"c"] {
+            tree.append_data(data);
+            other.append_data(data);
+        }
+        assert_ne!(tree.root(), other.root());
+        let leaf = tree.hasher().hash_leaf(b"b");
+        let proof = tree.get_proof(1);
+        assert!(proof.verify_with(tree.hasher(), leaf, tree.root()));
+        let guess = DomainSeparated(HmacSha256Hasher::new(b"guess"));
+        assert!(!proof.verify_with(&guess, leaf, tree.root()));
+    }
+
+    #[test]
+    #[should_panic(expected = "full")]
+    fn test_append_past_capacity_panics() {
+        let mut tree = MerkleTree::new(2);
+        for i in 0u8..5 {
+            tree.append_leaf([i; 32]);
+        }
+    }
+
+    #[test]
+    fn test_from_leaves_matches_appends() {
+        let leaves: Vec<[u8; 32]> = (0u8..37).map(|i| [i; 32]).collect();
+        let mut appended = MerkleTree::new(6);
+        for leaf in &leaves {
+            appended.append_leaf(*leaf);
+        }
+        let built = MerkleTree::from_leaves(6, DomainSeparated(Sha256Hasher), leaves).unwrap();
+        assert_eq!(built.root(), appended.root());
+        assert_eq!(built.get_proof(36), appended.get_proof(36));
+        let proofs = built.all_proofs();
+        assert_eq!(proofs.len(), 37);
+        assert!(proofs
+            .iter()
+            .enumerate()
+            .all(|(index, proof)| *proof == built.get_proof(index)));
+        assert!(MerkleTree::new(3).all_proofs().is_empty());
+        assert!(MerkleTree::from_leaves(1, Sha256Hasher, vec![[0u8; 32]; 3]).is_err());
+    }
+
+    #[test]
+    fn test_rfc6962_test_vectors() {
+        // Leaf inputs and roots from the certificate-transparency test suite.
+        let inputs: [&[u8]; 8] = [
+            b"",
+            b"\x00",
+            b"\x10",
+            b"\x20\x21",
+            b"\x30\x31",
+            b"\x40\x41\x42\x43",
+            b"\x50\x51\x52\x53\x54\x55\x56\x57",
+            b"\x60\x61\x62\x63\x64\x65\x66\x67\x68\x69\x6a\x6b\x6c\x6d\x6e\x6f",
+        ];
+        let roots = [
+            (
+                1,
+                "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d",
+            ),
+            (
+                2,
+                "fac54203e7cc696cf0dfcb42c92a1d9dbaf70ad9e621f4bd8d98662f00e3c125",
+            ),
+            (
+                4,
+                "d37ee418976dd95753c1c73862b9398fa2a2cf9b4ff0fdfe8b30cd95209614b7",
+            ),
+            (
+                8,
+                "5dc9da79a70659a9ad559cb701ded9a2ab9d823aad2f4960cfe370eff4604328",
+            ),
+        ];
+
+        // The vectors are for trees of exactly `size` leaves, so each tree
+        // is only as deep as its size requires.
+        for (size, expected) in roots {
+            let mut tree = MerkleTree::new(height(size));
+            for input in &inputs[..size] {
+                tree.append_data(input);
+            }
+            assert_eq!(hex::encode(&tree.root()), expected);
+        }
+    }
+
+    #[test]
+    fn test_sorted_pair_proofs_verify_without_index() {
+        let leaves: Vec<[u8; 32]> = (0u8..5).map(|i| Keccak256Hasher.hash(&[&[i]])).collect();
+        let mut tree = MerkleTree::with_hasher(3, SortedPair(Keccak256Hasher));
+        for leaf in &leaves {
+            tree.append_leaf(*leaf);
+        }
+        let rebuilt = MerkleTree::from_leaves(3, SortedPair(Keccak256Hasher), leaves.clone());
+        assert_eq!(rebuilt.unwrap().root(), tree.root());
+        for (index, leaf) in leaves.iter().enumerate() {
+            let proof = tree.get_proof(index);
+            assert!(crate::verify_sorted_proof(*leaf, &proof, tree.root()));
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_tree_serde_roundtrip() {
+        let mut tree = MerkleTree::new(2);
+        tree.append_data(b"a");
+        tree.append_data(b"b");
+
+        let json = serde_json::to_string(&tree).unwrap();
+        assert!(json.starts_with(r#"{"depth":2,"leaves":[""#));
+        let decoded: MerkleTree = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.root(), tree.root());
+        assert_eq!(decoded.leaves(), tree.leaves());
+    }
+}
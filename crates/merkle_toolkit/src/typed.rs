@@ -0,0 +1,211 @@
+use crate::{DomainSeparated, MerkleError, MerkleTree, NodeHasher, Proof, Sha256Hasher};
+
+/// A value that can be turned into a tree leaf.
+///
+/// Implementations should derive the leaf with [`NodeHasher::hash_leaf`] so
+/// the hasher's leaf prefix, if any, keeps leaves apart from internal nodes.
+pub trait LeafHash {
+    fn leaf_hash<H: NodeHasher>(&self, hasher: &H) -> [u8; 32];
+}
+
+impl LeafHash for [u8] {
+    fn leaf_hash<H: NodeHasher>(&self, hasher: &H) -> [u8; 32] {
+        hasher.hash_leaf(self)
+    }
+}
+
+impl<const N: usize> LeafHash for [u8; N] {
+    fn leaf_hash<H: NodeHasher>(&self, hasher: &H) -> [u8; 32] {
+        hasher.hash_leaf(self)
+    }
+}
+
+impl LeafHash for Vec<u8> {
+    fn leaf_hash<H: NodeHasher>(&self, hasher: &H) -> [u8; 32] {
+        hasher.hash_leaf(self)
+    }
+}
+
+impl LeafHash for str {
+    fn leaf_hash<H: NodeHasher>(&self, hasher: &H) -> [u8; 32] {
+        hasher.hash_leaf(self.as_bytes())
+    }
+}
+
+impl LeafHash for String {
+    fn leaf_hash<H: NodeHasher>(&self, hasher: &H) -> [u8; 32] {
+        hasher.hash_leaf(self.as_bytes())
+    }
+}
+
+/// Integers hash their big-endian bytes.
+macro_rules! impl_leaf_hash_for_int {
+    ($($int:ty),*) => {
+        $(impl LeafHash for $int {
+            fn leaf_hash<H: NodeHasher>(&self, hasher: &H) -> [u8; 32] {
+                hasher.hash_leaf(&self.to_be_bytes())
+            }
+        })*
+    };
+}
+
+impl_leaf_hash_for_int!(u16, u32, u64, u128, i16, i32, i64, i128);
+
+impl<T: LeafHash + ?Sized> LeafHash for &T {
+    fn leaf_hash<H: NodeHasher>(&self, hasher: &H) -> [u8; 32] {
+        (**self).leaf_hash(hasher)
+    }
+}
+
+/// A tree over values of `T`, each leaf derived with [`LeafHash`].
+///
+/// The values are kept alongside the tree so they can be read back by index,
+/// unless the tree is built [`TypedMerkleTree::without_values`].
+#[derive(Debug, Clone)]
+pub struct TypedMerkleTree<T, H = DomainSeparated> {
+    tree: MerkleTree<H>,
+    values: Option<Vec<T>>,
+}
+
+impl<T: LeafHash> TypedMerkleTree<T> {
+    pub fn new(depth: usize) -> Self {
+        Self::with_hasher(depth, DomainSeparated(Sha256Hasher))
+    }
+
+    pub fn try_new(depth: usize) -> Result<Self, MerkleError> {
+        Self::try_with_hasher(depth, DomainSeparated(Sha256Hasher))
+    }
+
+    pub fn verify(value: &T, proof: &Proof, root: [u8; 32]) -> bool {
+        Self::verify_with(&DomainSeparated(Sha256Hasher), value, proof, root)
+    }
+}
+
+impl<T: LeafHash, H: NodeHasher> TypedMerkleTree<T, H> {
+    pub fn with_hasher(depth: usize, hasher: H) -> Self {
+        Self::try_with_hasher(depth, hasher).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    pub fn try_with_hasher(depth: usize, hasher: H) -> Result<Self, MerkleError> {
+        Ok(Self {
+            tree: MerkleTree::try_with_hasher(depth, hasher)?,
+            values: Some(Vec::new()),
+        })
+    }
+
+    /// Drops the stored values and stops keeping new ones, leaving only the
+    /// leaves.
+    pub fn without_values(mut self) -> Self {
+        self.values = None;
+        self
+    }
+
+    pub fn verify_with(hasher: &H, value: &T, proof: &Proof, root: [u8; 32]) -> bool {
+        proof.verify_with(hasher, value.leaf_hash(hasher), root)
+    }
+
+    pub fn tree(&self) -> &MerkleTree<H> {
+        &self.tree
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        self.tree.root()
+    }
+
+    /// Appends `value`, returning its index.
+    pub fn push(&mut self, value: T) -> Result<usize, MerkleError> {
+        let index = self.tree.len();
+        self.tree
+            .try_append_leaf(value.leaf_hash(self.tree.hasher()))?;
+        if let Some(values) = &mut self.values {
+            values.push(value);
+        }
+        Ok(index)
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.values.as_ref()?.get(index)
+    }
+
+    pub fn values(&self) -> Option<&[T]> {
+        self.values.as_deref()
+    }
+
+    pub fn get_proof(&self, index: usize) -> Result<Proof, MerkleError> {
+        self.tree.try_get_proof(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Payment {
+        to: String,
+        amount: u64,
+    }
+
+    impl LeafHash for Payment {
+        fn leaf_hash<H: NodeHasher>(&self, hasher: &H) -> [u8; 32] {
+            let mut bytes = self.to.as_bytes().to_vec();
+            bytes.extend_from_slice(&self.amount.to_be_bytes());
+            hasher.hash_leaf(&bytes)
+        }
+    }
+
+    #[test]
+    fn test_values_hash_like_data() {
+        let mut typed = TypedMerkleTree::new(3);
+        let mut plain = MerkleTree::new(3);
+        for word in ["a", "bb", "ccc"] {
+            typed.push(word.to_string()).unwrap();
+            plain.append_data(word.as_bytes());
+        }
+        assert_eq!(typed.root(), plain.root());
+        assert_eq!(typed.get(1).map(String::as_str), Some("bb"));
+        let proof = typed.get_proof(2).unwrap();
+        assert!(TypedMerkleTree::verify(
+            &"ccc".to_string(),
+            &proof,
+            typed.root()
+        ));
+        assert!(!TypedMerkleTree::verify(
+            &"cc".to_string(),
+            &proof,
+            typed.root()
+        ));
+    }
+
+    #[test]
+    fn test_without_values() {
+        let mut tree = TypedMerkleTree::new(2).without_values();
+        for amount in [5, 7] {
+            tree.push(Payment {
+                to: "alice".into(),
+                amount,
+            })
+            .unwrap();
+        }
+        assert!(tree.get(0).is_none() && tree.values().is_none());
+        let payment = Payment {
+            to: "alice".into(),
+            amount: 7,
+        };
+        let proof = tree.get_proof(1).unwrap();
+        assert!(TypedMerkleTree::verify(&payment, &proof, tree.root()));
+
+        let hasher = DomainSeparated(Sha256Hasher);
+        assert_eq!(
+            42u64.leaf_hash(&hasher),
+            hasher.hash_leaf(&42u64.to_be_bytes())
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use std::collections::BTreeSet;
+
+use crate::{DomainSeparated, MerkleError, MerkleTree, NodeHasher, NodeStore, Proof, Sha256Hasher};
+
+/// Evidence that replacing one leaf took a tree from one root to another.
+///
+/// The siblings of a leaf don't change when the leaf does, so one path
+/// authenticates both the old leaf under the old root and the new leaf under
+/// the new root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+pub struct LeafUpdate {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub old_leaf: [u8; 32],
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub new_leaf: [u8; 32],
+    pub proof: Proof,
+}
+
+impl LeafUpdate {
+    /// Verifies the update with the default [`MerkleTree`] hasher.
+    pub fn verify(&self, old_root: [u8; 32], new_root: [u8; 32]) -> bool {
+        self.verify_with(&DomainSeparated(Sha256Hasher), old_root, new_root)
+    }
+
+    pub fn verify_with<H: NodeHasher>(
+        &self,
+        hasher: &H,
+        old_root: [u8; 32],
+        new_root: [u8; 32],
+    ) -> bool {
+        self.proof.verify_with(hasher, self.old_leaf, old_root)
+            && self.proof.verify_with(hasher, self.new_leaf, new_root)
+    }
+}
+
+impl<H: NodeHasher, S: NodeStore> MerkleTree<H, S> {
+    /// Replaces the leaf at `index`, rehashing only its path, and returns the
+    /// new root.
+    pub fn set_leaf(&mut self, index: usize, leaf: [u8; 32]) -> Result<[u8; 32], MerkleError> {
+        self.check_index(index)?;
+        let old_leaf = self.levels.get(0, index)?;
+        self.levels.put(0, index, leaf)?;
+        self.reindex_leaf(index, old_leaf, leaf)?;
+        self.update_path(index)?;
+        self.try_root()
+    }
+
+    /// Replaces many leaves, given as `(index, leaf)` with later entries for
+    /// an index winning, and rehashes each node above them once. Returns the
+    /// old and the new root. Nothing is replaced if any index is out of
+    /// bounds.
+    pub fn set_leaves(
+        &mut self,
+        leaves: &[(usize, [u8; 32])],
+    ) -> Result<([u8; 32], [u8; 32]), MerkleError> {
+        for &(index, _) in leaves {
+            self.check_index(index)?;
+        }
+        let old_root = self.try_root()?;
+        let mut dirty = BTreeSet::new();
+        for &(index, leaf) in leaves {
+            let old_leaf = self.levels.get(0, index)?;
+            self.levels.put(0, index, leaf)?;
+            self.reindex_leaf(index, old_leaf, leaf)?;
+            dirty.insert(index / 2);
+        }
+
+        let mut level = 0;
+        while self.levels.level_len(level) > 1 {
+            for &parent in &dirty {
+                let left = self
+                    .levels
+                    .get(level, 2 * parent)?
+                    .unwrap_or(self.zeros[level]);
+                let right = self
+                    .levels
+                    .get(level, 2 * parent + 1)?
+                    .unwrap_or(self.zeros[level]);
+                self.levels
+                    .put(level + 1, parent, self.hasher.hash_nodes(left, right))?;
+            }
+            dirty = dirty.iter().map(|parent| parent / 2).collect();
+            level += 1;
+        }
+        Ok((old_root, self.try_root()?))
+    }
+
+    /// Like [`MerkleTree::set_leaf`], returning a proof of the update.
+    pub fn set_leaf_with_proof(
+        &mut self,
+        index: usize,
+        leaf: [u8; 32],
+    ) -> Result<LeafUpdate, MerkleError> {
+        let proof = self.try_get_proof(index)?;
+        let old_leaf = self.levels.get(0, index)?.unwrap_or([0u8; 32]);
+        self.set_leaf(index, leaf)?;
+        Ok(LeafUpdate {
+            old_leaf,
+            new_leaf: leaf,
+            proof,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_leaf_matches_rebuild() {
+        let mut tree = MerkleTree::new(3);
+        for i in 0u8..6 {
+            tree.append_data(&[i]);
+        }
+        let old_root = tree.root();
+        let leaf = tree.hasher().hash_leaf(b"new");
+        let update = tree.set_leaf_with_proof(4, leaf).unwrap();
+
+        let mut leaves = tree.leaves().to_vec();
+        assert_eq!(leaves[4], leaf);
+        let rebuilt = MerkleTree::from_leaves(3, *tree.hasher(), std::mem::take(&mut leaves));
+        assert_eq!(rebuilt.unwrap().root(), tree.root());
+
+        assert!(update.verify(old_root, tree.root()));
+        assert!(!update.verify(tree.root(), old_root));
+        assert!(tree.set_leaf(6, leaf).is_err());
+    }
+
+    #[test]
+    fn test_set_leaves_matches_single_updates() {
+        let mut tree = MerkleTree::new(5).with_leaf_index();
+        for i in 0u8..21 {
+            tree.append_data(&[i]);
+        }
+        let mut single = tree.clone();
+        let updates = [(3, [1; 32]), (20, [2; 32]), (4, [3; 32]), (3, [4; 32])];
+        let old_root = tree.root();
+        let (old, new) = tree.set_leaves(&updates).unwrap();
+        for (index, leaf) in updates {
+            single.set_leaf(index, leaf).unwrap();
+        }
+        assert_eq!((old, new), (old_root, single.root()));
+        assert_eq!(tree.store(), single.store());
+        assert_eq!(tree.index_of(&[4; 32]), Some(3));
+        assert!(tree.index_of(&[1; 32]).is_none());
+
+        assert!(tree.set_leaves(&[(0, [9; 32]), (21, [9; 32])]).is_err());
+        assert_eq!(tree.root(), new);
+    }
+}
@@ -0,0 +1,170 @@
+use crate::{Keccak256Hasher, MerkleError, NodeHasher};
+
+/// Checks that `proof` (siblings from the leaf level up) leads from `leaf` at
+/// `index` to `root`.
+///
+/// This is the `no_std` entry point for verification: it works on borrowed
+/// slices and never allocates.
+pub fn verify_proof<H: NodeHasher>(
+    hasher: &H,
+    leaf: [u8; 32],
+    proof: &[[u8; 32]],
+    index: usize,
+    root: [u8; 32],
+) -> bool {
+    let mut computed_hash = leaf;
+    let mut idx = index;
+    for sibling in proof {
+        computed_hash = if idx % 2 == 0 {
+            hasher.hash_nodes(computed_hash, *sibling)
+        } else {
+            hasher.hash_nodes(*sibling, computed_hash)
+        };
+        idx /= 2;
+    }
+    computed_hash == root
+}
+
+/// Like [`verify_proof`] for the leaf of raw `data`, hashed with
+/// [`NodeHasher::hash_leaf`] first.
+///
+/// The leaf is derived exactly as a tree built with `hasher` derives it,
+/// prefix and hash function included, so callers holding the data never
+/// hash it themselves with settings that may not match the tree's.
+pub fn verify_data_proof<H: NodeHasher>(
+    hasher: &H,
+    data: &[u8],
+    proof: &[[u8; 32]],
+    index: usize,
+    root: [u8; 32],
+) -> bool {
+    verify_proof(hasher, hasher.hash_leaf(data), proof, index, root)
+}
+
+/// Like [`verify_proof`], but first checks the proof's shape against a tree
+/// of `size` leaves and `depth` levels, so a truncated or padded path, or an
+/// index past the last leaf, is rejected even where it would still hash to
+/// `root`.
+///
+/// [`verify_proof`] accepts any path length: a proof for a leaf of a
+/// subtree, or an internal node presented as a leaf with a shortened path,
+/// verifies just as well.
+pub fn verify_proof_strict<H: NodeHasher>(
+    hasher: &H,
+    leaf: [u8; 32],
+    proof: &[[u8; 32]],
+    index: usize,
+    size: usize,
+    depth: usize,
+    root: [u8; 32],
+) -> Result<(), MerkleError> {
+    let capacity = u32::try_from(depth)
+        .ok()
+        .and_then(|depth| 1u128.checked_shl(depth))
+        .unwrap_or(u128::MAX);
+    if size as u128 > capacity {
+        return Err(MerkleError::SizeOutOfBounds {
+            size,
+            len: usize::try_from(capacity).unwrap_or(usize::MAX),
+        });
+    }
+    if index >= size {
+        return Err(MerkleError::IndexOutOfBounds { index, len: size });
+    }
+    if proof.len() != depth {
+        return Err(MerkleError::InvalidProofLength {
+            expected: depth,
+            actual: proof.len(),
+        });
+    }
+    if !verify_proof(hasher, leaf, proof, index, root) {
+        return Err(MerkleError::InvalidProof);
+    }
+    Ok(())
+}
+
+/// Checks an OpenZeppelin style proof: plain Keccak-256 over sorted pairs,
+/// with no leaf index.
+pub fn verify_sorted_proof(leaf: [u8; 32], proof: &[[u8; 32]], root: [u8; 32]) -> bool {
+    verify_sorted_proof_with(&Keccak256Hasher, leaf, proof, root)
+}
+
+/// Checks a proof for a tree hashed with [`SortedPair`](crate::SortedPair)`(hasher)`.
+pub fn verify_sorted_proof_with<H: NodeHasher>(
+    hasher: &H,
+    leaf: [u8; 32],
+    proof: &[[u8; 32]],
+    root: [u8; 32],
+) -> bool {
+    let mut computed_hash = leaf;
+    for sibling in proof {
+        computed_hash = if computed_hash <= *sibling {
+            hasher.hash_nodes(computed_hash, *sibling)
+        } else {
+            hasher.hash_nodes(*sibling, computed_hash)
+        };
+    }
+    computed_hash == root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DomainSeparated, Sha256Hasher};
+
+    #[test]
+    fn test_verify_two_leaf_tree() {
+        let hasher = DomainSeparated(Sha256Hasher);
+        let (a, b) = (hasher.hash_leaf(b"a"), hasher.hash_leaf(b"b"));
+        let root = hasher.hash_nodes(a, b);
+        assert!(verify_proof(&hasher, a, &[b], 0, root));
+        assert!(verify_proof(&hasher, b, &[a], 1, root));
+        assert!(!verify_proof(&hasher, a, &[b], 1, root));
+        assert!(verify_data_proof(&hasher, b"b", &[a], 1, root));
+        assert!(!verify_data_proof(&Sha256Hasher, b"b", &[a], 1, root));
+    }
+
+    #[test]
+    fn test_strict_rejects_malformed_shapes() {
+        let hasher = DomainSeparated(Sha256Hasher);
+        let (a, b) = (hasher.hash_leaf(b"a"), hasher.hash_leaf(b"b"));
+        let ab = hasher.hash_nodes(a, b);
+        let zero = hasher.hash_nodes([0; 32], [0; 32]);
+        let root = hasher.hash_nodes(ab, zero);
+        assert_eq!(
+            verify_proof_strict(&hasher, a, &[b, zero], 0, 2, 2, root),
+            Ok(())
+        );
+        // The left subtree root passes plain verification as a leaf.
+        assert!(verify_proof(&hasher, ab, &[zero], 0, root));
+        assert_eq!(
+            verify_proof_strict(&hasher, ab, &[zero], 0, 2, 2, root),
+            Err(MerkleError::InvalidProofLength {
+                expected: 2,
+                actual: 1
+            })
+        );
+        assert_eq!(
+            verify_proof_strict(&hasher, a, &[b, zero], 2, 2, 2, root),
+            Err(MerkleError::IndexOutOfBounds { index: 2, len: 2 })
+        );
+        assert!(matches!(
+            verify_proof_strict(&hasher, a, &[b, zero], 0, 5, 2, root),
+            Err(MerkleError::SizeOutOfBounds { size: 5, len: 4 })
+        ));
+        assert_eq!(
+            verify_proof_strict(&hasher, b, &[b, zero], 0, 2, 2, root),
+            Err(MerkleError::InvalidProof)
+        );
+    }
+
+    #[test]
+    fn test_verify_sorted_proof_ignores_position() {
+        let (a, b, c) = ([3u8; 32], [1u8; 32], [2u8; 32]);
+        let ab = Keccak256Hasher.hash_nodes(b, a);
+        let root = Keccak256Hasher.hash_nodes(c, ab);
+        assert!(verify_sorted_proof(a, &[b, c], root));
+        assert!(verify_sorted_proof(b, &[a, c], root));
+        assert!(!verify_sorted_proof(c, &[a, b], root));
+    }
+}
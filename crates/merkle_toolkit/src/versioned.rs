@@ -0,0 +1,223 @@
+use std::time::SystemTime;
+
+use crate::{
+    DomainSeparated, MerkleError, MerkleTree, NodeHasher, Proof, RetentionPolicy, TreeMetadata,
+};
+
+/// An append-only [`MerkleTree`] that remembers the root of every version it
+/// has published, so inclusion can be shown against a root handed out in
+/// the past.
+///
+/// Version 0 is the tree as it was wrapped; each [`checkpoint`] that follows
+/// an append records the next one. With
+/// [`VersionedMerkleTree::checkpoint_every_append`], every append is a
+/// version of its own.
+///
+/// Versions are kept until [`VersionedMerkleTree::compact`] discards those
+/// its [`RetentionPolicy`] no longer covers. Version numbers stay the same
+/// after compaction; discarded versions just have no root.
+///
+/// [`checkpoint`]: VersionedMerkleTree::checkpoint
+#[derive(Debug, Clone)]
+pub struct VersionedMerkleTree<H = DomainSeparated> {
+    tree: MerkleTree<H>,
+    /// Number of the first kept version.
+    first_version: usize,
+    versions: Vec<TreeMetadata>,
+    recorded: Vec<SystemTime>,
+    retention: RetentionPolicy,
+    every_append: bool,
+}
+
+impl VersionedMerkleTree {
+    pub fn new(depth: usize) -> Self {
+        Self::from_tree(MerkleTree::new(depth))
+    }
+}
+
+impl<H: NodeHasher> VersionedMerkleTree<H> {
+    /// Wraps `tree`, recording its current state as version 0. Later
+    /// versions are only recorded by [`VersionedMerkleTree::checkpoint`].
+    pub fn from_tree(tree: MerkleTree<H>) -> Self {
+        Self {
+            first_version: 0,
+            versions: vec![tree.metadata()],
+            recorded: vec![SystemTime::now()],
+            tree,
+            retention: RetentionPolicy::KeepAll,
+            every_append: false,
+        }
+    }
+
+    /// Sets the policy [`VersionedMerkleTree::compact`] applies.
+    pub fn with_retention(mut self, retention: RetentionPolicy) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Records a version after every append instead of at checkpoints.
+    pub fn checkpoint_every_append(mut self) -> Self {
+        self.every_append = true;
+        self
+    }
+
+    pub fn tree(&self) -> &MerkleTree<H> {
+        &self.tree
+    }
+
+    pub fn into_tree(self) -> MerkleTree<H> {
+        self.tree
+    }
+
+    pub fn append_leaf(&mut self, leaf: [u8; 32]) -> Result<(), MerkleError> {
+        self.tree.try_append_leaf(leaf)?;
+        if self.every_append {
+            self.checkpoint();
+        }
+        Ok(())
+    }
+
+    pub fn append_data(&mut self, data: &[u8]) -> Result<(), MerkleError> {
+        self.append_leaf(self.tree.hasher().hash_leaf(data))
+    }
+
+    /// Records the current root as a new version and returns its number.
+    /// Nothing is recorded if no leaf was appended since the last version.
+    pub fn checkpoint(&mut self) -> usize {
+        if self.tree.len() != self.versions[self.versions.len() - 1].size {
+            self.versions.push(self.tree.metadata());
+            self.recorded.push(SystemTime::now());
+        }
+        self.version()
+    }
+
+    /// Number of the latest recorded version.
+    pub fn version(&self) -> usize {
+        self.first_version + self.versions.len() - 1
+    }
+
+    /// Number of the oldest version still kept.
+    pub fn oldest_version(&self) -> usize {
+        self.first_version
+    }
+
+    /// Discards the versions the retention policy no longer keeps, returning
+    /// how many were discarded.
+    pub fn compact(&mut self) -> usize {
+        self.compact_at(SystemTime::now())
+    }
+
+    fn compact_at(&mut self, now: SystemTime) -> usize {
+        let expired = self.retention.expired(&self.recorded, now);
+        self.versions.drain(..expired);
+        self.recorded.drain(..expired);
+        self.first_version += expired;
+        expired
+    }
+
+    pub fn root_at(&self, version: usize) -> Option<[u8; 32]> {
+        self.metadata_at(version).map(|version| version.root)
+    }
+
+    fn metadata_at(&self, version: usize) -> Option<&TreeMetadata> {
+        self.versions.get(version.checked_sub(self.first_version)?)
+    }
+
+    /// Proof for the leaf at `index` against [`VersionedMerkleTree::root_at`]
+    /// `version`.
+    ///
+    /// The tree only grows, so every past version is a prefix of the current
+    /// leaves; its nodes are read from the live tree and only the right edge
+    /// of the old tree is rehashed.
+    pub fn get_proof_at(&self, index: usize, version: usize) -> Result<Proof, MerkleError> {
+        let size = self
+            .metadata_at(version)
+            .ok_or(MerkleError::IndexOutOfBounds {
+                index: version,
+                len: self.version() + 1,
+            })?
+            .size;
+        self.tree.get_proof_at_size(index, size)
+    }
+
+    /// Depth, size and root of every kept version, oldest first.
+    pub fn versions(&self) -> &[TreeMetadata] {
+        &self.versions
+    }
+
+    /// Root of every kept version, oldest first.
+    pub fn roots(&self) -> impl Iterator<Item = [u8; 32]> + '_ {
+        self.versions.iter().map(|version| version.root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_checkpoints_record_roots() {
+        let mut tree = VersionedMerkleTree::new(4);
+        let mut every = VersionedMerkleTree::new(4).checkpoint_every_append();
+        let mut expected = vec![tree.tree().root()];
+        for i in 0u8..6 {
+            tree.append_data(&[i]).unwrap();
+            every.append_data(&[i]).unwrap();
+            expected.push(every.tree().root());
+            if i % 2 == 1 {
+                assert_eq!(tree.checkpoint(), usize::from(i / 2 + 1));
+            }
+        }
+        assert_eq!(tree.checkpoint(), 3);
+        assert_eq!(every.version(), 6);
+        assert_eq!(every.roots().collect::<Vec<_>>(), expected);
+        assert_eq!(tree.root_at(2), every.root_at(4));
+        assert_eq!(tree.versions()[3].size, 6);
+        assert_eq!(tree.root_at(4), None);
+    }
+
+    #[test]
+    fn test_proofs_against_past_versions() {
+        let mut tree = VersionedMerkleTree::new(4).checkpoint_every_append();
+        for i in 0u8..11 {
+            tree.append_data(&[i]).unwrap();
+        }
+        for version in 1..=11 {
+            let root = tree.root_at(version).unwrap();
+            for index in 0..version {
+                let proof = tree.get_proof_at(index, version).unwrap();
+                assert!(proof.verify(tree.tree().leaves()[index], root));
+            }
+            assert!(tree.get_proof_at(version, version).is_err());
+        }
+        assert_eq!(tree.get_proof_at(3, 11).unwrap(), tree.tree().get_proof(3));
+        assert!(tree.get_proof_at(0, 12).is_err());
+    }
+
+    #[test]
+    fn test_compact_keeps_version_numbers() {
+        let mut tree = VersionedMerkleTree::new(4)
+            .checkpoint_every_append()
+            .with_retention(RetentionPolicy::KeepLast(3));
+        for i in 0u8..6 {
+            tree.append_data(&[i]).unwrap();
+        }
+        let root = tree.root_at(4).unwrap();
+        assert_eq!(tree.compact(), 4);
+        assert_eq!((tree.oldest_version(), tree.version()), (4, 6));
+        assert_eq!(tree.root_at(3), None);
+        assert_eq!(tree.root_at(4), Some(root));
+        let proof = tree.get_proof_at(2, 4).unwrap();
+        assert!(proof.verify(tree.tree().leaves()[2], root));
+        assert!(tree.get_proof_at(2, 3).is_err());
+        assert_eq!(tree.checkpoint(), 6);
+
+        let mut tree = tree.with_retention(RetentionPolicy::NewerThan(Duration::from_secs(60)));
+        assert_eq!(tree.compact(), 0);
+        let later = SystemTime::now() + Duration::from_secs(61);
+        assert_eq!(tree.compact_at(later), 2);
+        assert_eq!(tree.versions().len(), 1);
+    }
+}
@@ -0,0 +1,219 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use crate::snapshot::{fingerprint, invalid, invalid_data};
+use crate::{DomainSeparated, MerkleError, MerkleTree, NodeHasher, Proof, Sha256Hasher, MAX_DEPTH};
+
+/// First bytes of every write-ahead log.
+pub const WAL_MAGIC: [u8; 4] = *b"MKLW";
+
+/// Format version written by [`WalMerkleTree`].
+pub const WAL_VERSION: u8 = 1;
+
+const HEADER_LEN: usize = 40;
+const RECORD_LEN: usize = 36;
+
+// A log is laid out as:
+//
+//   magic (4) | version (1) | depth (1) | reserved (2)
+//   hasher fingerprint (32)
+//   records: leaf (32) | checksum (4)
+//
+// The checksum is the first four bytes of the SHA-256 of the leaf. A crash
+// mid-append can only tear the last record, which replay drops; a bad record
+// anywhere else is corruption and fails the open.
+
+/// An append-only [`MerkleTree`] that logs every leaf to a file, and syncs
+/// it, before the append returns, so a tree reopened after a crash holds
+/// every leaf that was acknowledged.
+///
+/// Opening an existing log replays it. Each call to
+/// [`WalMerkleTree::append_leaves`] costs one write and one sync however
+/// many leaves it carries, so batching appends amortizes the sync.
+#[derive(Debug)]
+pub struct WalMerkleTree<H = DomainSeparated> {
+    tree: MerkleTree<H>,
+    log: File,
+    log_len: u64,
+}
+
+impl WalMerkleTree {
+    /// Opens or creates the log at `path` with the default hasher.
+    pub fn open(path: impl AsRef<Path>, depth: usize) -> io::Result<Self> {
+        Self::open_with(path, depth, DomainSeparated(Sha256Hasher))
+    }
+}
+
+impl<H: NodeHasher> WalMerkleTree<H> {
+    /// Opens the log at `path`, replaying it, or creates an empty one. An
+    /// existing log must have been written with the same depth and hasher.
+    pub fn open_with(path: impl AsRef<Path>, depth: usize, hasher: H) -> io::Result<Self> {
+        if depth > MAX_DEPTH {
+            return Err(invalid_data(MerkleError::DepthTooLarge {
+                depth,
+                max: MAX_DEPTH,
+            }));
+        }
+        let mut log = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        let mut bytes = Vec::new();
+        log.read_to_end(&mut bytes)?;
+
+        let fingerprint = fingerprint(&hasher);
+        let mut tree = MerkleTree::try_with_hasher(depth, hasher).map_err(invalid_data)?;
+        if bytes.is_empty() {
+            let mut header = [0u8; HEADER_LEN];
+            header[..4].copy_from_slice(&WAL_MAGIC);
+            header[4] = WAL_VERSION;
+            header[5] = depth as u8;
+            header[8..].copy_from_slice(&fingerprint);
+            log.write_all(&header)?;
+            log.sync_all()?;
+            return Ok(Self {
+                tree,
+                log,
+                log_len: HEADER_LEN as u64,
+            });
+        }
+
+        if bytes.len() < HEADER_LEN || bytes[..4] != WAL_MAGIC {
+            return Err(invalid("not a merkle tree write-ahead log"));
+        }
+        if bytes[4] != WAL_VERSION {
+            return Err(invalid("unsupported write-ahead log version"));
+        }
+        if bytes[5] as usize != depth {
+            return Err(invalid("write-ahead log was written for a different depth"));
+        }
+        if bytes[8..HEADER_LEN] != fingerprint {
+            return Err(invalid(
+                "write-ahead log was written with a different hasher",
+            ));
+        }
+        let records = &bytes[HEADER_LEN..];
+        let mut leaves = Vec::with_capacity(records.len() / RECORD_LEN);
+        for (n, record) in records.chunks(RECORD_LEN).enumerate() {
+            let leaf = record
+                .get(..32)
+                .and_then(|leaf| <[u8; 32]>::try_from(leaf).ok());
+            match leaf {
+                Some(leaf) if record.len() == RECORD_LEN && record[32..] == checksum(&leaf) => {
+                    leaves.push(leaf)
+                }
+                _ if (n + 1) * RECORD_LEN >= records.len() => break,
+                _ => return Err(invalid("corrupt record in write-ahead log")),
+            }
+        }
+        let log_len = (HEADER_LEN + leaves.len() * RECORD_LEN) as u64;
+        if log_len < bytes.len() as u64 {
+            log.set_len(log_len)?;
+            log.sync_all()?;
+        }
+        tree.append_leaves(&leaves).map_err(invalid_data)?;
+        Ok(Self { tree, log, log_len })
+    }
+
+    pub fn tree(&self) -> &MerkleTree<H> {
+        &self.tree
+    }
+
+    pub fn into_tree(self) -> MerkleTree<H> {
+        self.tree
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        self.tree.root()
+    }
+
+    pub fn get_proof(&self, index: usize) -> Result<Proof, MerkleError> {
+        self.tree.try_get_proof(index)
+    }
+
+    /// Logs and appends `leaf`, returning its index.
+    pub fn append_leaf(&mut self, leaf: [u8; 32]) -> io::Result<usize> {
+        self.append_leaves(&[leaf])
+    }
+
+    pub fn append_data(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.append_leaf(self.tree.hasher().hash_leaf(data))
+    }
+
+    /// Logs `leaves` and syncs the log, then appends them, returning the
+    /// index of the first. If logging fails, the log is cut back to where it
+    /// was and nothing is appended.
+    pub fn append_leaves(&mut self, leaves: &[[u8; 32]]) -> io::Result<usize> {
+        let start = self.tree.len();
+        if leaves.len() > self.tree.capacity() - start {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                MerkleError::TreeFull {
+                    capacity: self.tree.capacity(),
+                },
+            ));
+        }
+        let mut records = Vec::with_capacity(leaves.len() * RECORD_LEN);
+        for leaf in leaves {
+            records.extend_from_slice(leaf);
+            records.extend_from_slice(&checksum(leaf));
+        }
+        if let Err(e) = self
+            .log
+            .write_all(&records)
+            .and_then(|()| self.log.sync_data())
+        {
+            let _ = self.log.set_len(self.log_len);
+            return Err(e);
+        }
+        self.log_len += records.len() as u64;
+        self.tree.append_leaves(leaves).map_err(invalid_data)?;
+        Ok(start)
+    }
+}
+
+fn checksum(leaf: &[u8; 32]) -> [u8; 4] {
+    Sha256Hasher.hash(&[leaf])[..4].try_into().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replay_after_torn_append() {
+        let path = std::env::temp_dir().join(format!("merkle-wal-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut wal = WalMerkleTree::open(&path, 4).unwrap();
+        wal.append_data(b"a").unwrap();
+        assert_eq!(wal.append_leaves(&[[1; 32], [2; 32]]).unwrap(), 1);
+        let root = wal.root();
+        drop(wal);
+
+        // A crash halfway through writing a record leaves part of it behind.
+        let mut log = OpenOptions::new().append(true).open(&path).unwrap();
+        log.write_all(&[3; 20]).unwrap();
+        let mut wal = WalMerkleTree::open(&path, 4).unwrap();
+        assert_eq!((wal.len(), wal.root()), (3, root));
+        wal.append_leaf([4; 32]).unwrap();
+        let root = wal.root();
+        assert_eq!(WalMerkleTree::open(&path, 4).unwrap().root(), root);
+        assert!(WalMerkleTree::open(&path, 5).is_err());
+
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[HEADER_LEN] ^= 1;
+        std::fs::write(&path, bytes).unwrap();
+        assert!(WalMerkleTree::open(&path, 4).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,106 @@
+use crate::{MerkleError, MerkleTree, NodeHasher, NodeStore, Proof};
+
+/// What a holder of a proof from a tree of `old_size` leaves needs to bring
+/// it up to date with the tree after it grew to `new_size`.
+///
+/// Appending only changes nodes to the right of leaf `old_size - 1`, so an
+/// old proof can only have stale siblings where its path meets that leaf's
+/// path. At each level the witness holds the pair of nodes where the paths
+/// meet, leaf level first: `2 * depth` hashes, however many leaves were
+/// appended, shared by every old proof.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+pub struct AppendWitness {
+    pub old_size: usize,
+    pub new_size: usize,
+    /// The left and right node of the pair at each level.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::vec"))]
+    pub nodes: Vec<[u8; 32]>,
+}
+
+impl<H: NodeHasher, S: NodeStore> MerkleTree<H, S> {
+    /// The witness updating proofs made when the tree had `old_size` leaves
+    /// to the current tree.
+    pub fn append_witness(&self, old_size: usize) -> Result<AppendWitness, MerkleError> {
+        if old_size > self.len() {
+            return Err(MerkleError::SizeOutOfBounds {
+                size: old_size,
+                len: self.len(),
+            });
+        }
+        let last = old_size.saturating_sub(1);
+        let mut nodes = Vec::with_capacity(2 * self.depth);
+        for level in 0..self.depth {
+            let pair = (last >> level) & !1;
+            nodes.push(self.node(level, pair)?);
+            nodes.push(self.node(level, pair | 1)?);
+        }
+        Ok(AppendWitness {
+            old_size,
+            new_size: self.len(),
+            nodes,
+        })
+    }
+}
+
+impl Proof {
+    /// Brings a proof made for a tree of [`AppendWitness::old_size`] leaves
+    /// up to date with the tree of [`AppendWitness::new_size`], replacing
+    /// only the siblings the appends changed.
+    pub fn update(&mut self, witness: &AppendWitness) -> Result<(), MerkleError> {
+        if self.size != witness.old_size {
+            return Err(MerkleError::InvalidProof);
+        }
+        if self.index >= self.size {
+            return Err(MerkleError::IndexOutOfBounds {
+                index: self.index,
+                len: self.size,
+            });
+        }
+        if 2 * self.siblings.len() != witness.nodes.len() {
+            return Err(MerkleError::InvalidProofLength {
+                expected: witness.nodes.len() / 2,
+                actual: self.siblings.len(),
+            });
+        }
+        let last = witness.old_size - 1;
+        for (level, sibling) in self.siblings.iter_mut().enumerate() {
+            let node = self.index >> level;
+            if node >> 1 == (last >> level) >> 1 {
+                *sibling = witness.nodes[2 * level + ((node & 1) ^ 1)];
+            }
+        }
+        self.size = witness.new_size;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_updated_proofs_match_fresh_ones() {
+        let mut tree = MerkleTree::new(5);
+        for i in 0u8..11 {
+            tree.append_data(&[i]);
+        }
+        let mut proofs: Vec<Proof> = (0..11).map(|index| tree.get_proof(index)).collect();
+        for i in 11u8..20 {
+            tree.append_data(&[i]);
+        }
+        let witness = tree.append_witness(11).unwrap();
+        assert_eq!(witness.nodes.len(), 10);
+        for (index, proof) in proofs.iter_mut().enumerate() {
+            proof.update(&witness).unwrap();
+            assert_eq!(*proof, tree.get_proof(index));
+            assert!(proof.verify(tree.leaves()[index], tree.root()));
+        }
+        assert_eq!(proofs[0].update(&witness), Err(MerkleError::InvalidProof));
+        assert!(tree.append_witness(21).is_err());
+    }
+}
@@ -0,0 +1,172 @@
+//! Python bindings for `merkle_toolkit`, built with maturin:
+//!
+//! ```text
+//! maturin develop -m crates/merkle_toolkit_py/Cargo.toml
+//! ```
+//!
+//! ```python
+//! from merkle_toolkit_py import MerkleTree, hash_leaf, verify_proof
+//!
+//! tree = MerkleTree.from_data(20, [b"alice", b"bob"])
+//! proof = tree.get_proof(1)
+//! assert verify_proof(hash_leaf(b"bob"), proof, tree.root())
+//! ```
+//!
+//! Hashes are `bytes` of length 32 and proofs use the [`Proof::to_bytes`]
+//! encoding, so they can be handed to any other binding or the Rust crate.
+
+use merkle_toolkit::{HashAlgorithm, MerkleError, NodeHasher, Proof, RuntimeHasher};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+/// A merkle tree over 32 byte leaves.
+#[pyclass(name = "MerkleTree", module = "merkle_toolkit_py")]
+struct PyMerkleTree {
+    inner: merkle_toolkit::MerkleTree<RuntimeHasher>,
+}
+
+#[pymethods]
+impl PyMerkleTree {
+    #[new]
+    #[pyo3(signature = (depth, hash = "sha256", domain_separation = true, sorted_pairs = false))]
+    fn new(
+        depth: usize,
+        hash: &str,
+        domain_separation: bool,
+        sorted_pairs: bool,
+    ) -> PyResult<Self> {
+        let hasher = runtime_hasher(hash, domain_separation, sorted_pairs)?;
+        let inner = merkle_toolkit::MerkleTree::try_with_hasher(depth, hasher).map_err(py_error)?;
+        Ok(Self { inner })
+    }
+
+    /// Builds a tree over already hashed leaves in one pass.
+    #[staticmethod]
+    #[pyo3(signature = (depth, leaves, hash = "sha256", domain_separation = true, sorted_pairs = false))]
+    fn from_leaves(
+        depth: usize,
+        leaves: Vec<Vec<u8>>,
+        hash: &str,
+        domain_separation: bool,
+        sorted_pairs: bool,
+    ) -> PyResult<Self> {
+        let hasher = runtime_hasher(hash, domain_separation, sorted_pairs)?;
+        let leaves = leaves
+            .iter()
+            .map(|leaf| hash_from_slice(leaf))
+            .collect::<PyResult<_>>()?;
+        let inner =
+            merkle_toolkit::MerkleTree::from_leaves(depth, hasher, leaves).map_err(py_error)?;
+        Ok(Self { inner })
+    }
+
+    /// Hashes every item of `data` into a leaf and builds a tree over them.
+    #[staticmethod]
+    #[pyo3(signature = (depth, data, hash = "sha256", domain_separation = true, sorted_pairs = false))]
+    fn from_data(
+        py: Python<'_>,
+        depth: usize,
+        data: Vec<Vec<u8>>,
+        hash: &str,
+        domain_separation: bool,
+        sorted_pairs: bool,
+    ) -> PyResult<Self> {
+        let hasher = runtime_hasher(hash, domain_separation, sorted_pairs)?;
+        let inner = py.allow_threads(|| {
+            let leaves = data.iter().map(|item| hasher.hash_leaf(item)).collect();
+            merkle_toolkit::MerkleTree::from_leaves(depth, hasher, leaves)
+        });
+        Ok(Self {
+            inner: inner.map_err(py_error)?,
+        })
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.len()
+    }
+
+    #[getter]
+    fn depth(&self) -> usize {
+        self.inner.depth()
+    }
+
+    fn append_leaf(&mut self, leaf: &[u8]) -> PyResult<()> {
+        let leaf = hash_from_slice(leaf)?;
+        self.inner.try_append_leaf(leaf).map_err(py_error)
+    }
+
+    fn append_data(&mut self, data: &[u8]) -> PyResult<()> {
+        let leaf = self.inner.hasher().hash_leaf(data);
+        self.inner.try_append_leaf(leaf).map_err(py_error)
+    }
+
+    fn root<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.inner.root())
+    }
+
+    fn get_proof<'py>(&self, py: Python<'py>, index: usize) -> PyResult<Bound<'py, PyBytes>> {
+        let proof = self.inner.try_get_proof(index).map_err(py_error)?;
+        Ok(PyBytes::new(py, &proof.to_bytes()))
+    }
+}
+
+/// Hashes `data` into a leaf the way `MerkleTree.append_data` does.
+#[pyfunction]
+#[pyo3(signature = (data, hash = "sha256", domain_separation = true))]
+fn hash_leaf<'py>(
+    py: Python<'py>,
+    data: &[u8],
+    hash: &str,
+    domain_separation: bool,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let hasher = runtime_hasher(hash, domain_separation, false)?;
+    Ok(PyBytes::new(py, &hasher.hash_leaf(data)))
+}
+
+/// Checks an encoded proof for a 32 byte `leaf` against `root`.
+#[pyfunction]
+#[pyo3(signature = (leaf, proof, root, hash = "sha256", domain_separation = true, sorted_pairs = false))]
+fn verify_proof(
+    leaf: &[u8],
+    proof: &[u8],
+    root: &[u8],
+    hash: &str,
+    domain_separation: bool,
+    sorted_pairs: bool,
+) -> PyResult<bool> {
+    let hasher = runtime_hasher(hash, domain_separation, sorted_pairs)?;
+    let proof = Proof::from_bytes(proof).map_err(py_error)?;
+    Ok(proof.verify_with(&hasher, hash_from_slice(leaf)?, hash_from_slice(root)?))
+}
+
+#[pymodule]
+fn merkle_toolkit_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyMerkleTree>()?;
+    m.add_function(wrap_pyfunction!(hash_leaf, m)?)?;
+    m.add_function(wrap_pyfunction!(verify_proof, m)?)?;
+    Ok(())
+}
+
+fn runtime_hasher(
+    hash: &str,
+    domain_separation: bool,
+    sorted_pairs: bool,
+) -> PyResult<RuntimeHasher> {
+    let algorithm: HashAlgorithm = hash.parse().map_err(py_error)?;
+    Ok(RuntimeHasher {
+        algorithm,
+        domain_separation,
+        sorted_pairs,
+    })
+}
+
+fn hash_from_slice(bytes: &[u8]) -> PyResult<[u8; 32]> {
+    bytes
+        .try_into()
+        .map_err(|_| PyValueError::new_err("expected 32 bytes"))
+}
+
+fn py_error(error: MerkleError) -> PyErr {
+    PyValueError::new_err(error.to_string())
+}
@@ -0,0 +1,160 @@
+//! JavaScript bindings for `merkle_toolkit`.
+//!
+//! Build with `wasm-pack build crates/merkle_wasm`; wasm-bindgen emits the
+//! TypeScript definitions alongside the module. Hashes and proofs cross the
+//! boundary as `Uint8Array`s, proofs in the [`Proof::to_bytes`] encoding.
+
+use merkle_toolkit::{HashAlgorithm, MerkleError, MerkleTree, NodeHasher, Proof, RuntimeHasher};
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen(typescript_custom_section)]
+const TS_TYPES: &str = r#"
+/** A 32 byte hash. */
+export type Hash = Uint8Array;
+/** A proof as produced by `MerkleTree.getProof`: index and tree size as
+ * big-endian u64, followed by the 32 byte siblings. */
+export type ProofBytes = Uint8Array;
+"#;
+
+#[wasm_bindgen]
+#[derive(Clone, Copy)]
+pub enum HashFunction {
+    Sha256 = "sha256",
+    Keccak256 = "keccak256",
+}
+
+/// Hasher options shared by tree construction and verification.
+#[wasm_bindgen]
+#[derive(Clone, Copy)]
+pub struct HasherOptions {
+    pub function: HashFunction,
+    #[wasm_bindgen(js_name = domainSeparation)]
+    pub domain_separation: bool,
+    /// Hash children in ascending order, as OpenZeppelin's `MerkleProof` does.
+    #[wasm_bindgen(js_name = sortedPairs)]
+    pub sorted_pairs: bool,
+}
+
+#[wasm_bindgen]
+impl HasherOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new(function: HashFunction, domain_separation: bool) -> Self {
+        Self {
+            function,
+            domain_separation,
+            sorted_pairs: false,
+        }
+    }
+}
+
+impl From<HasherOptions> for RuntimeHasher {
+    fn from(options: HasherOptions) -> Self {
+        Self {
+            algorithm: match options.function {
+                HashFunction::Keccak256 => HashAlgorithm::Keccak256,
+                _ => HashAlgorithm::Sha256,
+            },
+            domain_separation: options.domain_separation,
+            sorted_pairs: options.sorted_pairs,
+        }
+    }
+}
+
+#[wasm_bindgen(js_name = MerkleTree)]
+pub struct WasmMerkleTree {
+    inner: MerkleTree<RuntimeHasher>,
+}
+
+#[wasm_bindgen(js_class = MerkleTree)]
+impl WasmMerkleTree {
+    /// Creates an empty tree; hashing defaults to domain-separated SHA-256.
+    #[wasm_bindgen(constructor)]
+    pub fn new(depth: usize, options: Option<HasherOptions>) -> Result<WasmMerkleTree, JsError> {
+        let hasher = options.map(RuntimeHasher::from).unwrap_or_default();
+        Ok(Self {
+            inner: MerkleTree::try_with_hasher(depth, hasher).map_err(js_error)?,
+        })
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Appends a 32 byte leaf.
+    #[wasm_bindgen(js_name = appendLeaf)]
+    pub fn append_leaf(&mut self, leaf: &[u8]) -> Result<(), JsError> {
+        let leaf = hash_from_slice(leaf)?;
+        self.inner.try_append_leaf(leaf).map_err(js_error)
+    }
+
+    /// Hashes `data` into a leaf and appends it.
+    #[wasm_bindgen(js_name = appendData)]
+    pub fn append_data(&mut self, data: &[u8]) -> Result<(), JsError> {
+        let leaf = self.inner.hasher().hash_leaf(data);
+        self.inner.try_append_leaf(leaf).map_err(js_error)
+    }
+
+    pub fn root(&self) -> Vec<u8> {
+        self.inner.root().to_vec()
+    }
+
+    #[wasm_bindgen(js_name = getProof)]
+    pub fn get_proof(&self, index: usize) -> Result<Vec<u8>, JsError> {
+        Ok(self
+            .inner
+            .try_get_proof(index)
+            .map_err(js_error)?
+            .to_bytes())
+    }
+}
+
+/// Checks an encoded proof for a 32 byte `leaf` against `root`.
+#[wasm_bindgen(js_name = verifyProof)]
+pub fn verify_proof(
+    leaf: &[u8],
+    proof: &[u8],
+    root: &[u8],
+    options: Option<HasherOptions>,
+) -> Result<bool, JsError> {
+    let hasher = options.map(RuntimeHasher::from).unwrap_or_default();
+    let proof = Proof::from_bytes(proof).map_err(js_error)?;
+    Ok(proof.verify_with(&hasher, hash_from_slice(leaf)?, hash_from_slice(root)?))
+}
+
+/// Hashes `data` into a leaf the way `MerkleTree.appendData` does.
+#[wasm_bindgen(js_name = hashLeaf)]
+pub fn hash_leaf(data: &[u8], options: Option<HasherOptions>) -> Vec<u8> {
+    let hasher = options.map(RuntimeHasher::from).unwrap_or_default();
+    hasher.hash_leaf(data).to_vec()
+}
+
+fn hash_from_slice(bytes: &[u8]) -> Result<[u8; 32], JsError> {
+    bytes
+        .try_into()
+        .map_err(|_| JsError::new("expected a 32 byte Uint8Array"))
+}
+
+fn js_error(error: MerkleError) -> JsError {
+    JsError::new(&error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_prove_verify() {
+        let mut tree = WasmMerkleTree::new(2, None).unwrap();
+        for data in [&b"a"[..], b"b", b"c"] {
+            tree.append_data(data).unwrap();
+        }
+        let root = tree.root();
+        let proof = tree.get_proof(1).unwrap();
+        let leaf = hash_leaf(b"b", None);
+        assert!(verify_proof(&leaf, &proof, &root, None).unwrap());
+
+        let keccak = HasherOptions::new(HashFunction::Keccak256, false);
+        assert!(!verify_proof(&leaf, &proof, &root, Some(keccak)).unwrap());
+    }
+}